@@ -0,0 +1,1055 @@
+//! Single pass compiler turning the scanned tokens into bytecode directly
+//! without building a syntax tree first.
+
+use std::{mem::discriminant, rc::Rc};
+
+use lox_frontend::{Scanner, Token, TokenType as TT};
+
+use crate::{
+    chunk::{Chunk, OpCode},
+    errors::CompileError,
+    object::{Function, Object},
+    value::Value,
+};
+
+const U8_COUNT: usize = u8::MAX as usize + 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    None,
+    Assignment, // =
+    Or,         // or
+    And,        // and
+    Equality,   // == !=
+    Comparison, // < > >= <=
+    Term,       // + -
+    Factor,     // * /
+    Unary,      // ! -
+    Call,       // . ()
+    Primary,
+}
+
+impl Precedence {
+    /// Returns the precedence one level higher than this one.
+    fn next(self) -> Self {
+        match self {
+            Precedence::None => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+type ParseFn = fn(&mut Compiler, bool);
+
+struct ParseRule {
+    prefix: Option<ParseFn>,
+    infix: Option<ParseFn>,
+    precedence: Precedence,
+}
+
+impl ParseRule {
+    fn new(prefix: Option<ParseFn>, infix: Option<ParseFn>, precedence: Precedence) -> Self {
+        Self {
+            prefix,
+            infix,
+            precedence,
+        }
+    }
+}
+
+fn get_rule(typ: &TT) -> ParseRule {
+    use Precedence as P;
+
+    match typ {
+        TT::LeftParen => ParseRule::new(Some(Compiler::grouping), Some(Compiler::call), P::Call),
+        TT::Dot => ParseRule::new(None, Some(Compiler::dot), P::Call),
+        TT::Minus => ParseRule::new(Some(Compiler::unary), Some(Compiler::binary), P::Term),
+        TT::Plus => ParseRule::new(None, Some(Compiler::binary), P::Term),
+        TT::Slash | TT::Star => ParseRule::new(None, Some(Compiler::binary), P::Factor),
+        TT::Bang => ParseRule::new(Some(Compiler::unary), None, P::None),
+        TT::BangEqual | TT::EqualEqual => ParseRule::new(None, Some(Compiler::binary), P::Equality),
+        TT::Greater | TT::GreaterEqual | TT::Less | TT::LessEqual => {
+            ParseRule::new(None, Some(Compiler::binary), P::Comparison)
+        }
+        TT::Identifier(_) => ParseRule::new(Some(Compiler::variable), None, P::None),
+        TT::String(_) => ParseRule::new(Some(Compiler::string), None, P::None),
+        TT::Number(_) => ParseRule::new(Some(Compiler::number), None, P::None),
+        TT::And => ParseRule::new(None, Some(Compiler::and), P::And),
+        TT::Or => ParseRule::new(None, Some(Compiler::or), P::Or),
+        TT::False | TT::Nil | TT::True => ParseRule::new(Some(Compiler::literal), None, P::None),
+        TT::Super => ParseRule::new(Some(Compiler::super_), None, P::None),
+        TT::This => ParseRule::new(Some(Compiler::this), None, P::None),
+        _ => ParseRule::new(None, None, P::None),
+    }
+}
+
+#[derive(Debug)]
+struct Local {
+    name: Rc<str>,
+    /// Depth of the scope declaring the variable, which is missing while
+    /// the variable is still being initialized.
+    depth: Option<usize>,
+    is_captured: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct UpvalueInfo {
+    index: u8,
+    is_local: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionType {
+    Function,
+    Initializer,
+    Method,
+    Script,
+}
+
+/// State of the function being currently compiled.
+#[derive(Debug)]
+struct FunctionCompiler {
+    function: Function,
+    typ: FunctionType,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueInfo>,
+    scope_depth: usize,
+}
+
+/// State of the class being currently compiled.
+#[derive(Debug)]
+struct ClassCompiler {
+    has_superclass: bool,
+}
+
+struct Compiler {
+    tokens: std::vec::IntoIter<Token>,
+    current: Token,
+    previous: Token,
+    errors: Vec<CompileError>,
+    panic_mode: bool,
+    /// Compilers of the nested functions with the innermost one at the end.
+    functions: Vec<FunctionCompiler>,
+    /// Compilers of the nested classes with the innermost one at the end.
+    classes: Vec<ClassCompiler>,
+}
+
+/// Compiles the source code into the function of the top level script.
+pub fn compile(source: String) -> Result<Function, Vec<CompileError>> {
+    let scan_res = Scanner::new(source).scan_tokens();
+    if !scan_res.errors.is_empty() {
+        return Err(scan_res
+            .errors
+            .into_iter()
+            .map(CompileError::Scan)
+            .collect());
+    }
+
+    let mut compiler = Compiler::new(scan_res.tokens);
+    compiler.init_compiler(FunctionType::Script);
+
+    compiler.advance();
+    while !compiler.match_then_advance(TT::Eof) {
+        compiler.declaration();
+    }
+
+    let (function, _) = compiler.end_compiler();
+
+    if compiler.errors.is_empty() {
+        Ok(function)
+    } else {
+        Err(compiler.errors)
+    }
+}
+
+impl Compiler {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self {
+            tokens: tokens.into_iter(),
+            current: Token::new(TT::Eof, "", 0),
+            previous: Token::new(TT::Eof, "", 0),
+            errors: Vec::new(),
+            panic_mode: false,
+            functions: Vec::new(),
+            classes: Vec::new(),
+        }
+    }
+
+    fn current_function(&mut self) -> &mut FunctionCompiler {
+        self.functions
+            .last_mut()
+            .expect("There is always a function while compiling")
+    }
+
+    fn current_chunk(&mut self) -> &mut Chunk {
+        &mut self.current_function().function.chunk
+    }
+
+    fn error_at(&mut self, token: Token, message: &str) {
+        if self.panic_mode {
+            return;
+        }
+        self.panic_mode = true;
+
+        let position = match token.typ {
+            TT::Eof => String::from(" at end"),
+            _ => format!(" at '{}'", token.lexeme),
+        };
+
+        self.errors.push(CompileError::Parse {
+            line: token.line,
+            position,
+            message: message.into(),
+        });
+    }
+
+    /// Reports the error at the previous token, which is the one reported
+    /// most of the time.
+    fn error(&mut self, message: &str) {
+        self.error_at(self.previous.clone(), message);
+    }
+
+    fn error_at_current(&mut self, message: &str) {
+        self.error_at(self.current.clone(), message);
+    }
+
+    fn advance(&mut self) {
+        // The scanner always ends the tokens with `Eof` which is kept as the
+        // current token once reached.
+        let next = self.tokens.next().unwrap_or_else(|| self.current.clone());
+        self.previous = std::mem::replace(&mut self.current, next);
+    }
+
+    /// Checks if the current token has the given type, ignoring the values
+    /// of literals.
+    fn check(&self, typ: &TT) -> bool {
+        discriminant(&self.current.typ) == discriminant(typ)
+    }
+
+    fn consume(&mut self, typ: TT, message: &str) {
+        if self.check(&typ) {
+            self.advance();
+            return;
+        }
+
+        self.error_at_current(message);
+    }
+
+    fn consume_identifier(&mut self, message: &str) {
+        self.consume(TT::Identifier("".into()), message);
+    }
+
+    /// Checks if the current token has the given type. Only then it will
+    /// consume it.
+    fn match_then_advance(&mut self, typ: TT) -> bool {
+        if !self.check(&typ) {
+            return false;
+        }
+
+        self.advance();
+        true
+    }
+
+    fn emit_byte(&mut self, byte: impl Into<u8>) {
+        let line = self.previous.line;
+        self.current_chunk().write(byte, line);
+    }
+
+    fn emit_bytes(&mut self, byte1: impl Into<u8>, byte2: impl Into<u8>) {
+        self.emit_byte(byte1);
+        self.emit_byte(byte2);
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_byte(OpCode::Loop);
+
+        // +2 to jump over the operand of the loop instruction itself.
+        let offset = self.current_chunk().code.len() - loop_start + 2;
+        if offset > u16::MAX as usize {
+            self.error("Loop body too large.");
+        }
+
+        self.emit_bytes((offset >> 8) as u8, offset as u8);
+    }
+
+    /// Emits the jump instruction with a placeholder for its offset, returning
+    /// the position of the placeholder to be patched later.
+    fn emit_jump(&mut self, instruction: OpCode) -> usize {
+        self.emit_byte(instruction);
+        self.emit_bytes(0xff, 0xff);
+
+        self.current_chunk().code.len() - 2
+    }
+
+    fn emit_return(&mut self) {
+        if self.current_function().typ == FunctionType::Initializer {
+            // Initializers return the instance in slot zero instead of nil.
+            self.emit_bytes(OpCode::GetLocal, 0);
+        } else {
+            self.emit_byte(OpCode::Nil);
+        }
+        self.emit_byte(OpCode::Return);
+    }
+
+    fn make_constant(&mut self, value: Value) -> u8 {
+        let constant = self.current_chunk().add_constant(value);
+
+        match u8::try_from(constant) {
+            Ok(constant) => constant,
+            Err(_) => {
+                self.error("Too many constants in one chunk.");
+                0
+            }
+        }
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let constant = self.make_constant(value);
+        self.emit_bytes(OpCode::Constant, constant);
+    }
+
+    fn patch_jump(&mut self, offset: usize) {
+        // -2 to adjust for the operand of the jump instruction itself.
+        let jump = self.current_chunk().code.len() - offset - 2;
+        if jump > u16::MAX as usize {
+            self.error("Too much code to jump over.");
+        }
+
+        let code = &mut self.current_chunk().code;
+        code[offset] = (jump >> 8) as u8;
+        code[offset + 1] = jump as u8;
+    }
+
+    fn init_compiler(&mut self, typ: FunctionType) {
+        let name = (typ != FunctionType::Script).then(|| self.previous.lexeme.clone());
+
+        // Slot zero is used by the VM for the called function or the instance
+        // in methods, therefore it can't be accessed by name in functions.
+        let slot_name = if typ == FunctionType::Function {
+            ""
+        } else {
+            "this"
+        };
+
+        self.functions.push(FunctionCompiler {
+            function: Function {
+                name,
+                ..Default::default()
+            },
+            typ,
+            locals: vec![Local {
+                name: slot_name.into(),
+                depth: Some(0),
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+        });
+    }
+
+    /// Finishes the current function returning it with the variables it
+    /// captures from the enclosing functions.
+    fn end_compiler(&mut self) -> (Function, Vec<UpvalueInfo>) {
+        self.emit_return();
+        let compiler = self
+            .functions
+            .pop()
+            .expect("There is always a function while compiling");
+
+        (compiler.function, compiler.upvalues)
+    }
+
+    fn begin_scope(&mut self) {
+        self.current_function().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        let compiler = self.current_function();
+        compiler.scope_depth -= 1;
+        let depth = compiler.scope_depth;
+
+        while let Some(local) = self.current_function().locals.last()
+            && local.depth.is_some_and(|local_depth| local_depth > depth)
+        {
+            if local.is_captured {
+                self.emit_byte(OpCode::CloseUpvalue);
+            } else {
+                self.emit_byte(OpCode::Pop);
+            }
+            self.current_function().locals.pop();
+        }
+    }
+
+    fn identifier_constant(&mut self, name: Rc<str>) -> u8 {
+        self.make_constant(Value::Obj(Object::String(name)))
+    }
+
+    /// Resolves the local variable in the function with the given index
+    /// returning its slot.
+    fn resolve_local(&mut self, function_idx: usize, name: &str) -> Option<u8> {
+        let (slot, local) = self.functions[function_idx]
+            .locals
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, local)| &*local.name == name)?;
+
+        if local.depth.is_none() {
+            self.error("Can't read local variable in its own initializer.");
+        }
+
+        Some(slot as u8)
+    }
+
+    fn add_upvalue(&mut self, function_idx: usize, index: u8, is_local: bool) -> u8 {
+        let upvalue = UpvalueInfo { index, is_local };
+        let compiler = &mut self.functions[function_idx];
+
+        if let Some(existing) = compiler.upvalues.iter().position(|up| *up == upvalue) {
+            return existing as u8;
+        }
+
+        if compiler.upvalues.len() == U8_COUNT {
+            self.error("Too many closure variables in function.");
+            return 0;
+        }
+
+        compiler.upvalues.push(upvalue);
+        compiler.function.upvalue_count += 1;
+
+        (compiler.upvalues.len() - 1) as u8
+    }
+
+    /// Resolves the variable captured from the functions enclosing the one
+    /// with the given index returning the index of its upvalue.
+    fn resolve_upvalue(&mut self, function_idx: usize, name: &str) -> Option<u8> {
+        let enclosing = function_idx.checked_sub(1)?;
+
+        if let Some(local) = self.resolve_local(enclosing, name) {
+            self.functions[enclosing].locals[local as usize].is_captured = true;
+            return Some(self.add_upvalue(function_idx, local, true));
+        }
+
+        let upvalue = self.resolve_upvalue(enclosing, name)?;
+        Some(self.add_upvalue(function_idx, upvalue, false))
+    }
+
+    fn add_local(&mut self, name: Rc<str>) {
+        if self.current_function().locals.len() == U8_COUNT {
+            self.error("Too many local variables in function.");
+            return;
+        }
+
+        self.current_function().locals.push(Local {
+            name,
+            // Variables are uninitialized until their initializers are
+            // compiled to handle cases like `var a = a;`.
+            depth: None,
+            is_captured: false,
+        });
+    }
+
+    fn declare_variable(&mut self) {
+        let name = self.previous.lexeme.clone();
+        let compiler = self.current_function();
+        if compiler.scope_depth == 0 {
+            return;
+        }
+
+        let scope_depth = compiler.scope_depth;
+        let already_declared = compiler
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth.is_none_or(|depth| depth >= scope_depth))
+            .any(|local| local.name == name);
+
+        if already_declared {
+            self.error("Already a variable with this name in this scope.");
+        }
+
+        self.add_local(name);
+    }
+
+    fn parse_variable(&mut self, message: &str) -> u8 {
+        self.consume_identifier(message);
+
+        self.declare_variable();
+        if self.current_function().scope_depth > 0 {
+            return 0;
+        }
+
+        self.identifier_constant(self.previous.lexeme.clone())
+    }
+
+    fn mark_initialized(&mut self) {
+        let compiler = self.current_function();
+        if compiler.scope_depth == 0 {
+            return;
+        }
+
+        let depth = compiler.scope_depth;
+        if let Some(local) = compiler.locals.last_mut() {
+            local.depth = Some(depth);
+        }
+    }
+
+    fn define_variable(&mut self, global: u8) {
+        if self.current_function().scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+
+        self.emit_bytes(OpCode::DefineGlobal, global);
+    }
+
+    fn argument_list(&mut self) -> u8 {
+        let mut arg_count: usize = 0;
+
+        if !self.check(&TT::RightParen) {
+            loop {
+                self.expression();
+                if arg_count == 255 {
+                    self.error("Can't have more than 255 arguments.");
+                }
+                arg_count += 1;
+
+                if !self.match_then_advance(TT::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TT::RightParen, "Expect ')' after arguments.");
+        arg_count as u8
+    }
+
+    fn expression(&mut self) {
+        self.parse_precedence(Precedence::Assignment);
+    }
+
+    /// block → "{" declaration* "}" ;
+    fn block(&mut self) {
+        while !self.check(&TT::RightBrace) && !self.check(&TT::Eof) {
+            self.declaration();
+        }
+
+        self.consume(TT::RightBrace, "Expect '}' after block.");
+    }
+
+    fn function(&mut self, typ: FunctionType) {
+        self.init_compiler(typ);
+
+        // This scope doesn't need to be ended since the whole compiler of the
+        // function is ended once its body is done.
+        self.begin_scope();
+
+        self.consume(TT::LeftParen, "Expect '(' after function name.");
+        if !self.check(&TT::RightParen) {
+            loop {
+                let function = &mut self.current_function().function;
+                function.arity += 1;
+                if function.arity > 255 {
+                    self.error_at_current("Can't have more than 255 parameters.");
+                }
+
+                let constant = self.parse_variable("Expect parameter name.");
+                self.define_variable(constant);
+
+                if !self.match_then_advance(TT::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TT::RightParen, "Expect ')' after parameters.");
+        self.consume(TT::LeftBrace, "Expect '{' before function body.");
+        self.block();
+
+        let (function, upvalues) = self.end_compiler();
+        let constant = self.make_constant(Value::Obj(Object::Function(Rc::new(function))));
+        self.emit_bytes(OpCode::Closure, constant);
+
+        for upvalue in upvalues {
+            self.emit_bytes(upvalue.is_local as u8, upvalue.index);
+        }
+    }
+
+    fn method(&mut self) {
+        self.consume_identifier("Expect method name.");
+        let constant = self.identifier_constant(self.previous.lexeme.clone());
+
+        let typ = if &*self.previous.lexeme == "init" {
+            FunctionType::Initializer
+        } else {
+            FunctionType::Method
+        };
+
+        self.function(typ);
+        self.emit_bytes(OpCode::Method, constant);
+    }
+
+    fn class_declaration(&mut self) {
+        self.consume_identifier("Expect class name.");
+        let class_name = self.previous.lexeme.clone();
+        let name_constant = self.identifier_constant(class_name.clone());
+        self.declare_variable();
+
+        self.emit_bytes(OpCode::Class, name_constant);
+        self.define_variable(name_constant);
+
+        self.classes.push(ClassCompiler {
+            has_superclass: false,
+        });
+
+        if self.match_then_advance(TT::Less) {
+            self.consume_identifier("Expect superclass name.");
+            self.variable(false);
+
+            if class_name == self.previous.lexeme {
+                self.error("A class can't inherit from itself.");
+            }
+
+            // The superclass is stored in a local scope so each class has its
+            // own `super` captured by its methods.
+            self.begin_scope();
+            self.add_local("super".into());
+            self.define_variable(0);
+
+            self.named_variable(class_name.clone(), false);
+            self.emit_byte(OpCode::Inherit);
+            if let Some(class) = self.classes.last_mut() {
+                class.has_superclass = true;
+            }
+        }
+
+        self.named_variable(class_name, false);
+        self.consume(TT::LeftBrace, "Expect '{' before class body.");
+        while !self.check(&TT::RightBrace) && !self.check(&TT::Eof) {
+            self.method();
+        }
+        self.consume(TT::RightBrace, "Expect '}' after class body.");
+        // The class itself
+        self.emit_byte(OpCode::Pop);
+
+        let class = self.classes.pop().expect("Class has been pushed above");
+        if class.has_superclass {
+            self.end_scope();
+        }
+    }
+
+    fn fun_declaration(&mut self) {
+        let global = self.parse_variable("Expect function name.");
+        // Functions can refer to themselves in their bodies.
+        self.mark_initialized();
+        self.function(FunctionType::Function);
+        self.define_variable(global);
+    }
+
+    fn var_declaration(&mut self) {
+        let global = self.parse_variable("Expect variable name.");
+
+        if self.match_then_advance(TT::Equal) {
+            self.expression();
+        } else {
+            self.emit_byte(OpCode::Nil);
+        }
+
+        self.consume(TT::SemiColon, "Expect ';' after variable declaration.");
+        self.define_variable(global);
+    }
+
+    fn expression_statement(&mut self) {
+        self.expression();
+        self.consume(TT::SemiColon, "Expect ';' after expression.");
+        self.emit_byte(OpCode::Pop);
+    }
+
+    fn for_statement(&mut self) {
+        self.begin_scope();
+
+        self.consume(TT::LeftParen, "Expect '(' after 'for'.");
+        if self.match_then_advance(TT::SemiColon) {
+            // No initializer.
+        } else if self.match_then_advance(TT::Var) {
+            self.var_declaration();
+        } else {
+            self.expression_statement();
+        }
+
+        let mut loop_start = self.current_chunk().code.len();
+
+        let mut exit_jump = None;
+        if !self.match_then_advance(TT::SemiColon) {
+            self.expression();
+            self.consume(TT::SemiColon, "Expect ';' after loop condition.");
+
+            // Jump out of the loop if the condition is false.
+            exit_jump = Some(self.emit_jump(OpCode::JumpIfFalse));
+            // Condition
+            self.emit_byte(OpCode::Pop);
+        }
+
+        if !self.match_then_advance(TT::RightParen) {
+            // The increment is executed after the body, so the body is jumped
+            // to first before looping back to the increment.
+            let body_jump = self.emit_jump(OpCode::Jump);
+            let increment_start = self.current_chunk().code.len();
+            self.expression();
+            self.emit_byte(OpCode::Pop);
+            self.consume(TT::RightParen, "Expect ')' after for clauses.");
+
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+
+        self.statement();
+        self.emit_loop(loop_start);
+
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump);
+            // Condition
+            self.emit_byte(OpCode::Pop);
+        }
+
+        self.end_scope();
+    }
+
+    fn if_statement(&mut self) {
+        self.consume(TT::LeftParen, "Expect '(' after 'if'.");
+        self.expression();
+        self.consume(TT::RightParen, "Expect ')' after condition.");
+
+        // The condition is popped in both of the branches.
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_byte(OpCode::Pop);
+        self.statement();
+
+        let else_jump = self.emit_jump(OpCode::Jump);
+
+        self.patch_jump(then_jump);
+        self.emit_byte(OpCode::Pop);
+
+        if self.match_then_advance(TT::Else) {
+            self.statement();
+        }
+
+        self.patch_jump(else_jump);
+    }
+
+    fn print_statement(&mut self) {
+        self.expression();
+        self.consume(TT::SemiColon, "Expect ';' after value.");
+        self.emit_byte(OpCode::Print);
+    }
+
+    fn return_statement(&mut self) {
+        let typ = self.current_function().typ;
+        if typ == FunctionType::Script {
+            self.error("Can't return from top-level code.");
+        }
+
+        if self.match_then_advance(TT::SemiColon) {
+            self.emit_return();
+        } else {
+            if typ == FunctionType::Initializer {
+                self.error("Can't return a value from an initializer.");
+            }
+
+            self.expression();
+            self.consume(TT::SemiColon, "Expect ';' after return value.");
+            self.emit_byte(OpCode::Return);
+        }
+    }
+
+    fn while_statement(&mut self) {
+        let loop_start = self.current_chunk().code.len();
+
+        self.consume(TT::LeftParen, "Expect '(' after 'while'.");
+        self.expression();
+        self.consume(TT::RightParen, "Expect ')' after condition.");
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_byte(OpCode::Pop);
+        self.statement();
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_byte(OpCode::Pop);
+    }
+
+    /// Skips tokens until reaching the next statement after an error.
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while self.current.typ != TT::Eof {
+            if self.previous.typ == TT::SemiColon {
+                return;
+            }
+
+            match self.current.typ {
+                TT::Class
+                | TT::Fun
+                | TT::Var
+                | TT::For
+                | TT::If
+                | TT::While
+                | TT::Print
+                | TT::Return => return,
+                _ => {}
+            }
+
+            self.advance();
+        }
+    }
+
+    /// declaration → classDecl
+    ///             | funDecl
+    ///             | varDecl
+    ///             | statement ;
+    fn declaration(&mut self) {
+        if self.match_then_advance(TT::Class) {
+            self.class_declaration();
+        } else if self.match_then_advance(TT::Fun) {
+            self.fun_declaration();
+        } else if self.match_then_advance(TT::Var) {
+            self.var_declaration();
+        } else {
+            self.statement();
+        }
+
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+
+    /// statement → exprStmt
+    ///           | forStmt
+    ///           | ifStmt
+    ///           | printStmt
+    ///           | returnStmt
+    ///           | whileStmt
+    ///           | block ;
+    fn statement(&mut self) {
+        if self.match_then_advance(TT::Print) {
+            self.print_statement();
+        } else if self.match_then_advance(TT::For) {
+            self.for_statement();
+        } else if self.match_then_advance(TT::If) {
+            self.if_statement();
+        } else if self.match_then_advance(TT::Return) {
+            self.return_statement();
+        } else if self.match_then_advance(TT::While) {
+            self.while_statement();
+        } else if self.match_then_advance(TT::LeftBrace) {
+            self.begin_scope();
+            self.block();
+            self.end_scope();
+        } else {
+            self.expression_statement();
+        }
+    }
+
+    fn parse_precedence(&mut self, precedence: Precedence) {
+        self.advance();
+        let Some(prefix_rule) = get_rule(&self.previous.typ).prefix else {
+            self.error("Expect expression.");
+            return;
+        };
+
+        let can_assign = precedence <= Precedence::Assignment;
+        prefix_rule(self, can_assign);
+
+        while precedence <= get_rule(&self.current.typ).precedence {
+            self.advance();
+            let infix_rule = get_rule(&self.previous.typ)
+                .infix
+                .expect("Tokens with precedence must have infix rules");
+            infix_rule(self, can_assign);
+        }
+
+        // Targets which can't be assigned leave the equal sign unconsumed.
+        if can_assign && self.match_then_advance(TT::Equal) {
+            self.error("Invalid assignment target.");
+        }
+    }
+
+    fn named_variable(&mut self, name: Rc<str>, can_assign: bool) {
+        let function_idx = self.functions.len() - 1;
+        let (get_op, set_op, arg) = if let Some(slot) = self.resolve_local(function_idx, &name) {
+            (OpCode::GetLocal, OpCode::SetLocal, slot)
+        } else if let Some(upvalue) = self.resolve_upvalue(function_idx, &name) {
+            (OpCode::GetUpvalue, OpCode::SetUpvalue, upvalue)
+        } else {
+            let constant = self.identifier_constant(name);
+            (OpCode::GetGlobal, OpCode::SetGlobal, constant)
+        };
+
+        if can_assign && self.match_then_advance(TT::Equal) {
+            self.expression();
+            self.emit_bytes(set_op, arg);
+        } else {
+            self.emit_bytes(get_op, arg);
+        }
+    }
+
+    // Parse functions of the rules.
+
+    fn grouping(&mut self, _can_assign: bool) {
+        self.expression();
+        self.consume(TT::RightParen, "Expect ')' after expression.");
+    }
+
+    fn call(&mut self, _can_assign: bool) {
+        let arg_count = self.argument_list();
+        self.emit_bytes(OpCode::Call, arg_count);
+    }
+
+    fn dot(&mut self, can_assign: bool) {
+        self.consume_identifier("Expect property name after '.'.");
+        let name = self.identifier_constant(self.previous.lexeme.clone());
+
+        if can_assign && self.match_then_advance(TT::Equal) {
+            self.expression();
+            self.emit_bytes(OpCode::SetProperty, name);
+        } else if self.match_then_advance(TT::LeftParen) {
+            let arg_count = self.argument_list();
+            self.emit_bytes(OpCode::Invoke, name);
+            self.emit_byte(arg_count);
+        } else {
+            self.emit_bytes(OpCode::GetProperty, name);
+        }
+    }
+
+    fn unary(&mut self, _can_assign: bool) {
+        let operator = self.previous.typ.clone();
+
+        // Compile the operand.
+        self.parse_precedence(Precedence::Unary);
+
+        match operator {
+            TT::Bang => self.emit_byte(OpCode::Not),
+            TT::Minus => self.emit_byte(OpCode::Negate),
+            _ => unreachable!("Unary rule is only used for unary operators"),
+        }
+    }
+
+    fn binary(&mut self, _can_assign: bool) {
+        let operator = self.previous.typ.clone();
+        let rule = get_rule(&operator);
+        self.parse_precedence(rule.precedence.next());
+
+        match operator {
+            // `a != b` is the same as `!(a == b)`
+            TT::BangEqual => self.emit_bytes(OpCode::Equal, OpCode::Not),
+            TT::EqualEqual => self.emit_byte(OpCode::Equal),
+            TT::Greater => self.emit_byte(OpCode::Greater),
+            // `a >= b` is the same as `!(a < b)`
+            TT::GreaterEqual => self.emit_bytes(OpCode::Less, OpCode::Not),
+            TT::Less => self.emit_byte(OpCode::Less),
+            // `a <= b` is the same as `!(a > b)`
+            TT::LessEqual => self.emit_bytes(OpCode::Greater, OpCode::Not),
+            TT::Plus => self.emit_byte(OpCode::Add),
+            TT::Minus => self.emit_byte(OpCode::Subtract),
+            TT::Star => self.emit_byte(OpCode::Multiply),
+            TT::Slash => self.emit_byte(OpCode::Divide),
+            _ => unreachable!("Binary rule is only used for binary operators"),
+        }
+    }
+
+    fn variable(&mut self, can_assign: bool) {
+        self.named_variable(self.previous.lexeme.clone(), can_assign);
+    }
+
+    fn string(&mut self, _can_assign: bool) {
+        let TT::String(text) = &self.previous.typ else {
+            unreachable!("String rule is only used for string tokens");
+        };
+
+        self.emit_constant(Value::Obj(Object::String(text.clone())));
+    }
+
+    fn number(&mut self, _can_assign: bool) {
+        let TT::Number(num) = self.previous.typ else {
+            unreachable!("Number rule is only used for number tokens");
+        };
+
+        self.emit_constant(Value::Number(num));
+    }
+
+    /// Skips the right operand if the left one is falsey, leaving it as the
+    /// result of the whole expression.
+    fn and(&mut self, _can_assign: bool) {
+        let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+
+        self.emit_byte(OpCode::Pop);
+        self.parse_precedence(Precedence::And);
+
+        self.patch_jump(end_jump);
+    }
+
+    /// Skips the right operand if the left one is truthy, leaving it as the
+    /// result of the whole expression.
+    fn or(&mut self, _can_assign: bool) {
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+        let end_jump = self.emit_jump(OpCode::Jump);
+
+        self.patch_jump(else_jump);
+        self.emit_byte(OpCode::Pop);
+
+        self.parse_precedence(Precedence::Or);
+        self.patch_jump(end_jump);
+    }
+
+    fn literal(&mut self, _can_assign: bool) {
+        match self.previous.typ {
+            TT::False => self.emit_byte(OpCode::False),
+            TT::Nil => self.emit_byte(OpCode::Nil),
+            TT::True => self.emit_byte(OpCode::True),
+            _ => unreachable!("Literal rule is only used for literal tokens"),
+        }
+    }
+
+    fn super_(&mut self, _can_assign: bool) {
+        match self.classes.last() {
+            None => self.error("Can't use 'super' outside of a class."),
+            Some(class) if !class.has_superclass => {
+                self.error("Can't use 'super' in a class with no superclass.");
+            }
+            Some(_) => {}
+        }
+
+        self.consume(TT::Dot, "Expect '.' after 'super'.");
+        self.consume_identifier("Expect superclass method name.");
+        let name = self.identifier_constant(self.previous.lexeme.clone());
+
+        self.named_variable("this".into(), false);
+        if self.match_then_advance(TT::LeftParen) {
+            let arg_count = self.argument_list();
+            self.named_variable("super".into(), false);
+            self.emit_bytes(OpCode::SuperInvoke, name);
+            self.emit_byte(arg_count);
+        } else {
+            self.named_variable("super".into(), false);
+            self.emit_bytes(OpCode::GetSuper, name);
+        }
+    }
+
+    fn this(&mut self, _can_assign: bool) {
+        if self.classes.is_empty() {
+            self.error("Can't use 'this' outside of a class.");
+            return;
+        }
+
+        self.variable(false);
+    }
+}
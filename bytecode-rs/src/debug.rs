@@ -0,0 +1,217 @@
+//! Disassembler printing the bytecode in a human readable form.
+
+use std::fmt::{Result, Write};
+
+use crate::{
+    chunk::{Chunk, OpCode},
+    object::{Function, Object},
+    value::Value,
+};
+
+/// Offset of the next instruction after writing the current one.
+type OffsetResult = std::result::Result<usize, std::fmt::Error>;
+
+/// Writes the chunk of the function followed by the chunks of the functions
+/// declared inside it.
+pub fn disassemble_function(f: &mut impl Write, function: &Function) -> Result {
+    disassemble_chunk(f, &function.chunk, &function.to_string())?;
+
+    for constant in &function.chunk.constants {
+        if let Value::Obj(Object::Function(inner)) = constant {
+            writeln!(f)?;
+            disassemble_function(f, inner)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn disassemble_chunk(f: &mut impl Write, chunk: &Chunk, name: &str) -> Result {
+    writeln!(f, "== {name} ==")?;
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(f, chunk, offset)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the instruction at the given offset returning the offset of the
+/// next instruction.
+pub fn disassemble_instruction(f: &mut impl Write, chunk: &Chunk, offset: usize) -> OffsetResult {
+    write!(f, "{offset:04} ")?;
+
+    if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
+        write!(f, "   | ")?;
+    } else {
+        write!(f, "{:4} ", chunk.lines[offset])?;
+    }
+
+    let instruction = chunk.code[offset];
+    let Some(op) = OpCode::from_byte(instruction) else {
+        writeln!(f, "Unknown opcode: {instruction}")?;
+        return Ok(offset + 1);
+    };
+
+    let name = op_name(op);
+    match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method => constant_instruction(f, name, chunk, offset),
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => byte_instruction(f, name, chunk, offset),
+        OpCode::Jump | OpCode::JumpIfFalse => jump_instruction(f, name, true, chunk, offset),
+        OpCode::Loop => jump_instruction(f, name, false, chunk, offset),
+        OpCode::Invoke | OpCode::SuperInvoke => invoke_instruction(f, name, chunk, offset),
+        OpCode::Closure => closure_instruction(f, chunk, offset),
+        OpCode::Nil
+        | OpCode::True
+        | OpCode::False
+        | OpCode::Pop
+        | OpCode::Equal
+        | OpCode::Greater
+        | OpCode::Less
+        | OpCode::Add
+        | OpCode::Subtract
+        | OpCode::Multiply
+        | OpCode::Divide
+        | OpCode::Not
+        | OpCode::Negate
+        | OpCode::Print
+        | OpCode::CloseUpvalue
+        | OpCode::Return
+        | OpCode::Inherit => {
+            writeln!(f, "{name}")?;
+            Ok(offset + 1)
+        }
+    }
+}
+
+fn op_name(op: OpCode) -> &'static str {
+    match op {
+        OpCode::Constant => "OP_CONSTANT",
+        OpCode::Nil => "OP_NIL",
+        OpCode::True => "OP_TRUE",
+        OpCode::False => "OP_FALSE",
+        OpCode::Pop => "OP_POP",
+        OpCode::GetLocal => "OP_GET_LOCAL",
+        OpCode::SetLocal => "OP_SET_LOCAL",
+        OpCode::GetGlobal => "OP_GET_GLOBAL",
+        OpCode::DefineGlobal => "OP_DEFINE_GLOBAL",
+        OpCode::SetGlobal => "OP_SET_GLOBAL",
+        OpCode::GetUpvalue => "OP_GET_UPVALUE",
+        OpCode::SetUpvalue => "OP_SET_UPVALUE",
+        OpCode::GetProperty => "OP_GET_PROPERTY",
+        OpCode::SetProperty => "OP_SET_PROPERTY",
+        OpCode::GetSuper => "OP_GET_SUPER",
+        OpCode::Equal => "OP_EQUAL",
+        OpCode::Greater => "OP_GREATER",
+        OpCode::Less => "OP_LESS",
+        OpCode::Add => "OP_ADD",
+        OpCode::Subtract => "OP_SUBTRACT",
+        OpCode::Multiply => "OP_MULTIPLY",
+        OpCode::Divide => "OP_DIVIDE",
+        OpCode::Not => "OP_NOT",
+        OpCode::Negate => "OP_NEGATE",
+        OpCode::Print => "OP_PRINT",
+        OpCode::Jump => "OP_JUMP",
+        OpCode::JumpIfFalse => "OP_JUMP_IF_FALSE",
+        OpCode::Loop => "OP_LOOP",
+        OpCode::Call => "OP_CALL",
+        OpCode::Invoke => "OP_INVOKE",
+        OpCode::SuperInvoke => "OP_SUPER_INVOKE",
+        OpCode::Closure => "OP_CLOSURE",
+        OpCode::CloseUpvalue => "OP_CLOSE_UPVALUE",
+        OpCode::Return => "OP_RETURN",
+        OpCode::Class => "OP_CLASS",
+        OpCode::Inherit => "OP_INHERIT",
+        OpCode::Method => "OP_METHOD",
+    }
+}
+
+fn byte_instruction(f: &mut impl Write, name: &str, chunk: &Chunk, offset: usize) -> OffsetResult {
+    let slot = chunk.code[offset + 1];
+    writeln!(f, "{name:<16} {slot:4}")?;
+
+    Ok(offset + 2)
+}
+
+fn jump_instruction(
+    f: &mut impl Write,
+    name: &str,
+    forward: bool,
+    chunk: &Chunk,
+    offset: usize,
+) -> OffsetResult {
+    let jump = ((chunk.code[offset + 1] as usize) << 8) | chunk.code[offset + 2] as usize;
+    let next = offset + 3;
+    let target = if forward {
+        next + jump
+    } else {
+        next.saturating_sub(jump)
+    };
+    writeln!(f, "{name:<16} {offset:4} -> {target}")?;
+
+    Ok(next)
+}
+
+fn constant_instruction(
+    f: &mut impl Write,
+    name: &str,
+    chunk: &Chunk,
+    offset: usize,
+) -> OffsetResult {
+    let constant = chunk.code[offset + 1];
+    let value = &chunk.constants[constant as usize];
+    writeln!(f, "{name:<16} {constant:4} '{value}'")?;
+
+    Ok(offset + 2)
+}
+
+fn invoke_instruction(
+    f: &mut impl Write,
+    name: &str,
+    chunk: &Chunk,
+    offset: usize,
+) -> OffsetResult {
+    let constant = chunk.code[offset + 1];
+    let arg_count = chunk.code[offset + 2];
+    let value = &chunk.constants[constant as usize];
+    writeln!(f, "{name:<16} ({arg_count} args) {constant:4} '{value}'")?;
+
+    Ok(offset + 3)
+}
+
+/// Writes the closure instruction followed by its operands describing the
+/// captured variables.
+fn closure_instruction(f: &mut impl Write, chunk: &Chunk, offset: usize) -> OffsetResult {
+    let constant = chunk.code[offset + 1];
+    let value = &chunk.constants[constant as usize];
+    writeln!(f, "{:<16} {constant:4} {value}", "OP_CLOSURE")?;
+
+    let upvalue_count = match value {
+        Value::Obj(Object::Function(function)) => function.upvalue_count,
+        _ => 0,
+    };
+
+    let mut offset = offset + 2;
+    for _ in 0..upvalue_count {
+        let is_local = chunk.code[offset] == 1;
+        let index = chunk.code[offset + 1];
+        let kind = if is_local { "local" } else { "upvalue" };
+        writeln!(f, "{offset:04}      |                     {kind} {index}")?;
+        offset += 2;
+    }
+
+    Ok(offset)
+}
@@ -0,0 +1,42 @@
+use lox_frontend::engine::{Diagnostics, Engine, Output};
+
+use crate::{InterpretError, Vm};
+
+/// Engine running the programs on the bytecode virtual machine.
+#[derive(Debug, Default)]
+pub struct VmEngine {
+    vm: Vm,
+}
+
+impl VmEngine {
+    pub fn new(vm: Vm) -> Self {
+        Self { vm }
+    }
+}
+
+impl Engine for VmEngine {
+    fn name(&self) -> &'static str {
+        "vm"
+    }
+
+    fn run(&mut self, source: &str) -> Result<Output, Diagnostics> {
+        self.vm.capture_output();
+        let result = self.vm.interpret(source.to_owned());
+        let output = Output {
+            stdout: self
+                .vm
+                .take_output()
+                .expect("Output is captured while running"),
+        };
+
+        let errors = match result {
+            Ok(()) => return Ok(output),
+            Err(InterpretError::Compile(errors)) => {
+                errors.iter().map(ToString::to_string).collect()
+            }
+            Err(err) => vec![err.to_string()],
+        };
+
+        Err(Diagnostics { output, errors })
+    }
+}
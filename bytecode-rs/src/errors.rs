@@ -0,0 +1,90 @@
+use std::fmt::{Display, Formatter, Result};
+
+use lox_frontend::ScanError;
+use thiserror::Error;
+
+use crate::serialize::FORMAT_VERSION;
+
+#[derive(Error, Debug)]
+/// General error for interpreting source code in the virtual machine.
+pub enum InterpretError {
+    #[error("{}", join_errors(.0))]
+    Compile(Vec<CompileError>),
+    #[error("{0}")]
+    Runtime(RuntimeError),
+    #[error("{0}")]
+    Load(#[from] LoadError),
+}
+
+fn join_errors(errors: &[CompileError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Debug)]
+pub enum CompileError {
+    Scan(ScanError),
+    Parse {
+        line: usize,
+        /// Description of the token the error is reported at.
+        position: String,
+        message: String,
+    },
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            CompileError::Scan(err) => write!(f, "{err}"),
+            CompileError::Parse {
+                line,
+                position,
+                message,
+            } => write!(f, "[line {line}] Error{position}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub message: String,
+    /// Lines of the stack trace starting from the innermost call.
+    pub trace: Vec<String>,
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.message)?;
+        for line in &self.trace {
+            write!(f, "\n{line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Error while loading compiled bytecode.
+#[derive(Error, Debug)]
+pub enum LoadError {
+    #[error("File doesn't contain compiled Lox bytecode")]
+    InvalidMagic,
+    #[error("Unsupported version {0} of compiled bytecode. Expected version: {FORMAT_VERSION}")]
+    UnsupportedVersion(u16),
+    #[error("Compiled bytecode ended unexpectedly")]
+    UnexpectedEnd,
+    #[error("Compiled bytecode has unexpected bytes after its end")]
+    TrailingBytes,
+    #[error("Compiled bytecode has invalid UTF-8 string")]
+    InvalidString,
+    #[error("Compiled bytecode has constant with invalid tag {0}")]
+    InvalidConstant(u8),
+    #[error("Compiled bytecode has invalid instruction at offset {0}")]
+    InvalidBytecode(usize),
+}
@@ -0,0 +1,269 @@
+//! Mark-and-sweep garbage collector freeing the objects which reference each
+//! other in cycles.
+//!
+//! Objects are still shared via reference counting, which frees most of them
+//! as soon as they aren't used anymore. Objects in cycles keep each other
+//! alive, so the collector marks all objects reachable from the roots of the
+//! virtual machine, then clears the references inside the unreachable ones.
+//! This breaks their cycles letting reference counting free them.
+//!
+//! Only objects which can be changed after their creation are tracked, since
+//! a cycle can only be created by storing an object into an existing one.
+
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    object::{Class, ClassRef, Instance, InstanceRef, Object, Upvalue, UpvalueRef},
+    value::Value,
+};
+
+/// Minimal number of tracked objects before running a collection.
+const INITIAL_GC_THRESHOLD: usize = 1024;
+/// Factor to grow the threshold by, relative to the objects surviving the
+/// last collection.
+const GC_HEAP_GROW_FACTOR: usize = 2;
+
+/// Options to debug the garbage collector.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GcOptions {
+    /// Runs a collection after each allocation of a tracked object.
+    pub stress: bool,
+    /// Prints the progress of each collection to stderr.
+    pub log: bool,
+}
+
+#[derive(Debug)]
+enum Tracked {
+    Upvalue(Weak<RefCell<Upvalue>>),
+    Class(Weak<RefCell<Class>>),
+    Instance(Weak<RefCell<Instance>>),
+}
+
+/// Tracks the allocated objects and decides when to collect them.
+#[derive(Debug)]
+pub struct Heap {
+    objects: Vec<Tracked>,
+    /// Number of tracked objects which triggers the next collection.
+    next_gc: usize,
+    options: GcOptions,
+}
+
+impl Heap {
+    pub fn new(options: GcOptions) -> Self {
+        Self {
+            objects: Vec::new(),
+            next_gc: if options.stress {
+                0
+            } else {
+                INITIAL_GC_THRESHOLD
+            },
+            options,
+        }
+    }
+
+    pub fn alloc_upvalue(&mut self, upvalue: Upvalue) -> UpvalueRef {
+        let upvalue = Rc::new(RefCell::new(upvalue));
+        self.objects.push(Tracked::Upvalue(Rc::downgrade(&upvalue)));
+        upvalue
+    }
+
+    pub fn alloc_class(&mut self, class: Class) -> ClassRef {
+        let class = Rc::new(RefCell::new(class));
+        self.objects.push(Tracked::Class(Rc::downgrade(&class)));
+        class
+    }
+
+    pub fn alloc_instance(&mut self, instance: Instance) -> InstanceRef {
+        let instance = Rc::new(RefCell::new(instance));
+        self.objects
+            .push(Tracked::Instance(Rc::downgrade(&instance)));
+        instance
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.objects.len() > self.next_gc
+    }
+
+    /// Frees the tracked objects which can't be reached from the given roots.
+    ///
+    /// All objects still in use must be reachable from the roots, since the
+    /// unreachable ones get their references cleared.
+    pub fn collect<'a>(
+        &mut self,
+        values: impl IntoIterator<Item = Value>,
+        upvalues: impl IntoIterator<Item = &'a UpvalueRef>,
+    ) {
+        let before = self.objects.len();
+        if self.options.log {
+            eprintln!("-- gc begin");
+        }
+
+        let mut marker = Marker::default();
+        values
+            .into_iter()
+            .for_each(|value| marker.gray.push(Gray::Value(value)));
+        upvalues
+            .into_iter()
+            .for_each(|upvalue| marker.gray.push(Gray::Upvalue(Rc::clone(upvalue))));
+        marker.trace_references();
+
+        let freed = self.sweep(&marker.marked);
+
+        self.next_gc = if self.options.stress {
+            self.objects.len()
+        } else {
+            (self.objects.len() * GC_HEAP_GROW_FACTOR).max(INITIAL_GC_THRESHOLD)
+        };
+
+        if self.options.log {
+            eprintln!("-- gc end");
+            eprintln!(
+                "   collected {freed} objects (from {before} to {}) next at {}",
+                self.objects.len(),
+                self.next_gc
+            );
+        }
+    }
+
+    /// Clears the references inside the unmarked objects, returning their
+    /// count. Objects already freed by reference counting are dropped from
+    /// the tracked ones as well.
+    fn sweep(&mut self, marked: &HashSet<*const ()>) -> usize {
+        let log = self.options.log;
+        let mut freed = 0;
+        self.objects.retain(|object| {
+            let alive = match object {
+                Tracked::Upvalue(upvalue) => upvalue.upgrade().map(|upvalue| {
+                    let reachable = marked.contains(&address(&upvalue));
+                    if !reachable {
+                        let closed = upvalue.replace(Upvalue::Closed(Value::Nil));
+                        log_free(log, "upvalue");
+                        drop(closed);
+                    }
+                    reachable
+                }),
+                Tracked::Class(class) => class.upgrade().map(|class| {
+                    let reachable = marked.contains(&address(&class));
+                    if !reachable {
+                        let methods = std::mem::take(&mut class.borrow_mut().methods);
+                        log_free(log, &class.borrow().name);
+                        drop(methods);
+                    }
+                    reachable
+                }),
+                Tracked::Instance(instance) => instance.upgrade().map(|instance| {
+                    let reachable = marked.contains(&address(&instance));
+                    if !reachable {
+                        let fields = std::mem::take(&mut instance.borrow_mut().fields);
+                        log_free(
+                            log,
+                            &format!("{} instance", instance.borrow().class.borrow().name),
+                        );
+                        drop(fields);
+                    }
+                    reachable
+                }),
+            };
+
+            match alive {
+                Some(true) => true,
+                Some(false) => {
+                    freed += 1;
+                    false
+                }
+                None => false,
+            }
+        });
+
+        freed
+    }
+}
+
+fn log_free(log: bool, object: &str) {
+    if log {
+        eprintln!("   free {object}");
+    }
+}
+
+fn address<T>(object: &Rc<T>) -> *const () {
+    Rc::as_ptr(object) as *const ()
+}
+
+/// Object waiting for its references to be traced.
+enum Gray {
+    Value(Value),
+    Upvalue(UpvalueRef),
+}
+
+#[derive(Default)]
+struct Marker {
+    /// Addresses of the tracked objects reached so far.
+    marked: HashSet<*const ()>,
+    gray: Vec<Gray>,
+}
+
+impl Marker {
+    /// Marks the object, returning if it was marked for the first time.
+    fn mark<T>(&mut self, object: &Rc<T>) -> bool {
+        self.marked.insert(address(object))
+    }
+
+    fn trace_references(&mut self) {
+        while let Some(gray) = self.gray.pop() {
+            match gray {
+                Gray::Value(value) => self.blacken(value),
+                Gray::Upvalue(upvalue) => {
+                    if self.mark(&upvalue)
+                        && let Upvalue::Closed(value) = &*upvalue.borrow()
+                    {
+                        self.gray.push(Gray::Value(value.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    fn blacken(&mut self, value: Value) {
+        let Value::Obj(object) = value else {
+            return;
+        };
+
+        match object {
+            // Strings and functions can't reference mutable objects.
+            Object::String(_) | Object::Function(_) | Object::Native(_) => {}
+            Object::Closure(closure) => {
+                let upvalues = closure.upvalues.iter().cloned().map(Gray::Upvalue);
+                self.gray.extend(upvalues);
+            }
+            Object::Class(class) => {
+                if self.mark(&class) {
+                    let class = class.borrow();
+                    let methods = class.methods.values().cloned();
+                    let methods =
+                        methods.map(|method| Gray::Value(Value::Obj(Object::Closure(method))));
+                    self.gray.extend(methods);
+                }
+            }
+            Object::Instance(instance) => {
+                if self.mark(&instance) {
+                    let instance = instance.borrow();
+                    self.gray
+                        .push(Gray::Value(Value::Obj(Object::Class(Rc::clone(
+                            &instance.class,
+                        )))));
+                    let fields = instance.fields.values().cloned().map(Gray::Value);
+                    self.gray.extend(fields);
+                }
+            }
+            Object::BoundMethod(bound) => {
+                self.gray.push(Gray::Value(bound.receiver.clone()));
+                let method = Value::Obj(Object::Closure(Rc::clone(&bound.method)));
+                self.gray.push(Gray::Value(method));
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::Context;
+use std::{io::Write, path::Path};
+
+mod chunk;
+mod compiler;
+mod debug;
+mod engine;
+mod errors;
+mod gc;
+mod object;
+mod serialize;
+mod value;
+mod vm;
+
+pub use engine::VmEngine;
+pub use errors::{CompileError, InterpretError, LoadError, RuntimeError};
+pub use gc::GcOptions;
+pub use vm::Vm;
+
+/// Runs the file which can contain either source code or compiled bytecode.
+pub fn run_file(path: &Path, gc_options: GcOptions) -> anyhow::Result<()> {
+    let file_content = read_file(path)?;
+
+    let mut vm = Vm::with_gc_options(gc_options);
+    if serialize::is_compiled(&file_content) {
+        vm.run_compiled(&file_content)?;
+    } else {
+        vm.interpret(into_source(file_content, path)?)?;
+    }
+
+    Ok(())
+}
+
+/// Compiles the source code file writing its bytecode into the output file.
+pub fn compile_file(path: &Path, output: &Path) -> anyhow::Result<()> {
+    let file_content = read_file(path)?;
+    let bytes = compile(into_source(file_content, path)?)?;
+
+    std::fs::write(output, bytes).with_context(|| {
+        format!(
+            "Error while writing output file. Path: {}",
+            output.display()
+        )
+    })
+}
+
+/// Prints the disassembled bytecode of the file without executing it.
+/// The file can contain either source code or compiled bytecode.
+pub fn dump_file(path: &Path) -> anyhow::Result<()> {
+    let file_content = read_file(path)?;
+
+    let function = if serialize::is_compiled(&file_content) {
+        serialize::decode(&file_content)?
+    } else {
+        let source = into_source(file_content, path)?;
+        compiler::compile(source).map_err(InterpretError::Compile)?
+    };
+    print!("{}", disassemble_function(&function));
+
+    Ok(())
+}
+
+/// Compiles the source code returning the disassembly of its bytecode.
+pub fn disassemble(source: String) -> Result<String, InterpretError> {
+    let function = compiler::compile(source).map_err(InterpretError::Compile)?;
+    Ok(disassemble_function(&function))
+}
+
+fn disassemble_function(function: &object::Function) -> String {
+    let mut output = String::new();
+    debug::disassemble_function(&mut output, function).expect("Writing to string can't fail");
+
+    output
+}
+
+/// Compiles the source code into bytes which can be executed later using
+/// [`Vm::run_compiled()`].
+pub fn compile(source: String) -> Result<Vec<u8>, InterpretError> {
+    let function = compiler::compile(source).map_err(InterpretError::Compile)?;
+    Ok(serialize::encode(&function))
+}
+
+fn read_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path)
+        .with_context(|| format!("Error while reading input file. Path: {}", path.display()))
+}
+
+fn into_source(content: Vec<u8>, path: &Path) -> anyhow::Result<String> {
+    String::from_utf8(content)
+        .with_context(|| format!("Input file isn't valid UTF-8. Path: {}", path.display()))
+}
+
+pub fn run_prompt(gc_options: GcOptions) -> anyhow::Result<()> {
+    println!("Welcome to rclox virtual machine!");
+    println!("To exit press <C-d> or <C-c>");
+
+    // The same VM is used for all lines, keeping their global definitions.
+    let mut vm = Vm::with_gc_options(gc_options);
+    let mut content = String::new();
+    loop {
+        content.clear();
+        print!("> ");
+
+        std::io::stdout()
+            .flush()
+            .context("Error while flushing stdout")?;
+
+        let read = std::io::stdin()
+            .read_line(&mut content)
+            .context("Error while reading from stdin")?;
+
+        if read == 0 {
+            println!("Bye Bye!");
+            return Ok(());
+        }
+
+        // Don't stop on errors
+        match vm.interpret(content.clone()) {
+            Ok(()) => {}
+            Err(err @ InterpretError::Compile(_)) => eprintln!("Compile Error:\n{err}"),
+            Err(err @ InterpretError::Runtime(_)) => eprintln!("Runtime Error:\n{err}"),
+            Err(err @ InterpretError::Load(_)) => eprintln!("Load Error:\n{err}"),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use anyhow::bail;
+use bytecode_rs::{GcOptions, compile_file, dump_file, run_file, run_prompt};
+
+const USAGE: &str = "Usage: rclox [--gc-stress] [--gc-log] [--dump-bytecode] [script]
+       rclox compile <script> [-o <output>]
+       rclox [--gc-stress] [--gc-log] run <script>";
+
+fn main() -> anyhow::Result<()> {
+    // Flags for debugging the garbage collector can be combined with running
+    // the scripts and the REPL.
+    let (gc_flags, args): (Vec<_>, Vec<_>) =
+        std::env::args().partition(|arg| arg.starts_with("--gc-"));
+
+    let mut gc_options = GcOptions::default();
+    for flag in gc_flags {
+        match flag.as_str() {
+            "--gc-stress" => gc_options.stress = true,
+            "--gc-log" => gc_options.log = true,
+            _ => bail!("Unknown flag '{flag}'\n{USAGE}"),
+        }
+    }
+
+    match args.as_slice() {
+        [] => panic!("Environment arguments must starts with the path of the binary file"),
+        // No args => Run interactive REPL session.
+        [_] => run_prompt(gc_options),
+        // Flag for printing the bytecode of the file instead of running it.
+        [_, flag, path] if flag == "--dump-bytecode" => dump_file(&PathBuf::from(path)),
+        // Compile the file into bytecode file next to it by default.
+        [_, cmd, path] if cmd == "compile" => {
+            let path = PathBuf::from(path);
+            compile_file(&path, &path.with_extension("loxc"))
+        }
+        [_, cmd, path, flag, output] if cmd == "compile" && flag == "-o" => {
+            compile_file(&PathBuf::from(path), &PathBuf::from(output))
+        }
+        // Files can contain source code or compiled bytecode.
+        [_, cmd, path] if cmd == "run" => run_file(&PathBuf::from(path), gc_options),
+        // File provided => Use it
+        [_, path] => run_file(&PathBuf::from(path), gc_options),
+        // We don't support more handling more than one file.
+        _ => bail!(USAGE),
+    }
+}
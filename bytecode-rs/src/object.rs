@@ -0,0 +1,131 @@
+//! Heap objects of the virtual machine.
+//!
+//! Objects are shared via reference counting. Objects referencing each other
+//! in cycles, like instances storing themselves in their fields, are freed by
+//! the garbage collector.
+
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+use crate::{chunk::Chunk, value::Value};
+
+pub type ClassRef = Rc<RefCell<Class>>;
+pub type InstanceRef = Rc<RefCell<Instance>>;
+pub type UpvalueRef = Rc<RefCell<Upvalue>>;
+
+#[derive(Debug, Clone)]
+pub enum Object {
+    String(Rc<str>),
+    Function(Rc<Function>),
+    Native(Rc<Native>),
+    Closure(Rc<Closure>),
+    Class(ClassRef),
+    Instance(InstanceRef),
+    BoundMethod(Rc<BoundMethod>),
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Function(a), Object::Function(b)) => Rc::ptr_eq(a, b),
+            (Object::Native(a), Object::Native(b)) => Rc::ptr_eq(a, b),
+            (Object::Closure(a), Object::Closure(b)) => Rc::ptr_eq(a, b),
+            (Object::Class(a), Object::Class(b)) => Rc::ptr_eq(a, b),
+            (Object::Instance(a), Object::Instance(b)) => Rc::ptr_eq(a, b),
+            (Object::BoundMethod(a), Object::BoundMethod(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Object::String(text) => f.write_str(text),
+            Object::Function(function) => write!(f, "{function}"),
+            Object::Native(_) => f.write_str("<native fn>"),
+            Object::Closure(closure) => write!(f, "{}", closure.function),
+            Object::Class(class) => f.write_str(&class.borrow().name),
+            Object::Instance(instance) => {
+                write!(f, "{} instance", instance.borrow().class.borrow().name)
+            }
+            Object::BoundMethod(bound) => write!(f, "{}", bound.method.function),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Function {
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+    /// Name of the function, which is missing for the top level script.
+    pub name: Option<Rc<str>>,
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {name}>"),
+            None => f.write_str("<script>"),
+        }
+    }
+}
+
+pub type NativeFn = fn(&[Value]) -> Value;
+
+#[derive(Debug)]
+pub struct Native {
+    pub function: NativeFn,
+}
+
+/// Variable captured by a closure.
+#[derive(Debug)]
+pub enum Upvalue {
+    /// The variable still lives on the stack in the slot with this index.
+    Open(usize),
+    /// The variable has been moved from the stack into the upvalue itself.
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<UpvalueRef>,
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: Rc<str>,
+    pub methods: HashMap<Rc<str>, Rc<Closure>>,
+}
+
+impl Class {
+    pub fn new(name: Rc<str>) -> Self {
+        Self {
+            name,
+            methods: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: ClassRef,
+    pub fields: HashMap<Rc<str>, Value>,
+}
+
+impl Instance {
+    pub fn new(class: ClassRef) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
@@ -0,0 +1,444 @@
+//! Binary encoding of compiled functions, which allows running scripts later
+//! without compiling them again.
+//!
+//! Files start with the magic bytes and the version of the format, followed by
+//! the function of the top level script. All numbers are encoded in little
+//! endian, and strings are prefixed with their length in bytes.
+
+use std::rc::Rc;
+
+use crate::{
+    chunk::{Chunk, OpCode},
+    errors::LoadError,
+    object::{Function, Object},
+    value::Value,
+};
+
+/// Bytes at the start of every compiled file.
+pub const MAGIC: &[u8; 4] = b"LOXC";
+
+/// Version of the format, which must be increased on any change in the
+/// encoding or in the op codes of the bytecode.
+pub const FORMAT_VERSION: u16 = 1;
+
+// Tags of the encoded constants.
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_NUMBER: u8 = 3;
+const TAG_STRING: u8 = 4;
+const TAG_FUNCTION: u8 = 5;
+
+/// Checks if the bytes start with the magic bytes of compiled files.
+pub fn is_compiled(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Encodes the function of the top level script with its header.
+pub fn encode(function: &Function) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    write_function(&mut bytes, function);
+
+    bytes
+}
+
+fn write_u32(bytes: &mut Vec<u8>, num: usize) {
+    let num = u32::try_from(num).expect("Sizes in chunks are limited by the compiler");
+    bytes.extend_from_slice(&num.to_le_bytes());
+}
+
+fn write_str(bytes: &mut Vec<u8>, text: &str) {
+    write_u32(bytes, text.len());
+    bytes.extend_from_slice(text.as_bytes());
+}
+
+fn write_function(bytes: &mut Vec<u8>, function: &Function) {
+    match &function.name {
+        Some(name) => {
+            bytes.push(1);
+            write_str(bytes, name);
+        }
+        None => bytes.push(0),
+    }
+    write_u32(bytes, function.arity);
+    write_u32(bytes, function.upvalue_count);
+
+    let chunk = &function.chunk;
+    write_u32(bytes, chunk.code.len());
+    bytes.extend_from_slice(&chunk.code);
+    for line in &chunk.lines {
+        write_u32(bytes, *line);
+    }
+
+    write_u32(bytes, chunk.constants.len());
+    for constant in &chunk.constants {
+        write_constant(bytes, constant);
+    }
+}
+
+fn write_constant(bytes: &mut Vec<u8>, constant: &Value) {
+    match constant {
+        Value::Nil => bytes.push(TAG_NIL),
+        Value::Bool(false) => bytes.push(TAG_FALSE),
+        Value::Bool(true) => bytes.push(TAG_TRUE),
+        Value::Number(num) => {
+            bytes.push(TAG_NUMBER);
+            bytes.extend_from_slice(&num.to_le_bytes());
+        }
+        Value::Obj(Object::String(text)) => {
+            bytes.push(TAG_STRING);
+            write_str(bytes, text);
+        }
+        Value::Obj(Object::Function(function)) => {
+            bytes.push(TAG_FUNCTION);
+            write_function(bytes, function);
+        }
+        Value::Obj(object) => {
+            unreachable!("Compiler only creates strings and functions constants. Got: {object}")
+        }
+    }
+}
+
+/// Decodes the function of the top level script, making sure its bytecode is
+/// valid to be executed.
+pub fn decode(bytes: &[u8]) -> Result<Function, LoadError> {
+    let mut reader = Reader { bytes, position: 0 };
+
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(LoadError::InvalidMagic);
+    }
+
+    let version = u16::from_le_bytes(reader.take_array()?);
+    if version != FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+
+    let function = reader.read_function()?;
+    if reader.position != bytes.len() {
+        return Err(LoadError::TrailingBytes);
+    }
+
+    Ok(function)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], LoadError> {
+        let end = self.position + len;
+        let slice = self
+            .bytes
+            .get(self.position..end)
+            .ok_or(LoadError::UnexpectedEnd)?;
+        self.position = end;
+
+        Ok(slice)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], LoadError> {
+        let slice = self.take(N)?;
+        Ok(slice.try_into().expect("Slice has the length of the array"))
+    }
+
+    fn read_u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<usize, LoadError> {
+        Ok(u32::from_le_bytes(self.take_array()?) as usize)
+    }
+
+    fn read_str(&mut self) -> Result<Rc<str>, LoadError> {
+        let len = self.read_u32()?;
+        let text = std::str::from_utf8(self.take(len)?).map_err(|_| LoadError::InvalidString)?;
+
+        Ok(text.into())
+    }
+
+    fn read_function(&mut self) -> Result<Function, LoadError> {
+        let name = match self.read_u8()? {
+            0 => None,
+            _ => Some(self.read_str()?),
+        };
+        let arity = self.read_u32()?;
+        let upvalue_count = self.read_u32()?;
+
+        let code_len = self.read_u32()?;
+        let code = self.take(code_len)?.to_vec();
+        let lines = (0..code_len)
+            .map(|_| self.read_u32())
+            .collect::<Result<_, _>>()?;
+
+        let constants_count = self.read_u32()?;
+        let constants = (0..constants_count)
+            .map(|_| self.read_constant())
+            .collect::<Result<_, _>>()?;
+
+        let function = Function {
+            arity,
+            upvalue_count,
+            chunk: Chunk {
+                code,
+                lines,
+                constants,
+            },
+            name,
+        };
+        verify_function(&function)?;
+
+        Ok(function)
+    }
+
+    fn read_constant(&mut self) -> Result<Value, LoadError> {
+        let value = match self.read_u8()? {
+            TAG_NIL => Value::Nil,
+            TAG_FALSE => Value::Bool(false),
+            TAG_TRUE => Value::Bool(true),
+            TAG_NUMBER => Value::Number(f64::from_le_bytes(self.take_array()?)),
+            TAG_STRING => Value::Obj(Object::String(self.read_str()?)),
+            TAG_FUNCTION => Value::Obj(Object::Function(Rc::new(self.read_function()?))),
+            tag => return Err(LoadError::InvalidConstant(tag)),
+        };
+
+        Ok(value)
+    }
+}
+
+/// Checks that the bytecode of the function can run without crashing the
+/// virtual machine: all instructions are known with their operands referring
+/// to constants of the expected types, jumps land on instructions, and the
+/// stack never underflows nor is accessed past its top.
+fn verify_function(function: &Function) -> Result<(), LoadError> {
+    let lengths = instruction_lengths(&function.chunk)?;
+    verify_stack(function, &lengths)
+}
+
+/// Returns the length of the instruction starting at each offset of the chunk,
+/// which is `None` for offsets inside of instructions.
+fn instruction_lengths(chunk: &Chunk) -> Result<Vec<Option<usize>>, LoadError> {
+    let code = &chunk.code;
+    let operand = |offset: usize| {
+        code.get(offset)
+            .copied()
+            .ok_or(LoadError::InvalidBytecode(offset))
+    };
+    let constant = |offset: usize| {
+        let index = operand(offset)? as usize;
+        chunk
+            .constants
+            .get(index)
+            .ok_or(LoadError::InvalidBytecode(offset))
+    };
+    let string_constant = |offset: usize| match constant(offset)? {
+        Value::Obj(Object::String(_)) => Ok(()),
+        _ => Err(LoadError::InvalidBytecode(offset)),
+    };
+
+    let mut lengths = vec![None; code.len()];
+    let mut offset = 0;
+    while offset < code.len() {
+        let op = OpCode::from_byte(code[offset]).ok_or(LoadError::InvalidBytecode(offset))?;
+
+        let len = match op {
+            OpCode::Constant => {
+                constant(offset + 1)?;
+                2
+            }
+            OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Class
+            | OpCode::Method => {
+                string_constant(offset + 1)?;
+                2
+            }
+            OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue
+            | OpCode::Call => {
+                operand(offset + 1)?;
+                2
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+                operand(offset + 2)?;
+                3
+            }
+            OpCode::Invoke | OpCode::SuperInvoke => {
+                string_constant(offset + 1)?;
+                operand(offset + 2)?;
+                3
+            }
+            OpCode::Closure => {
+                let Value::Obj(Object::Function(function)) = constant(offset + 1)? else {
+                    return Err(LoadError::InvalidBytecode(offset));
+                };
+                let len = 2 + function.upvalue_count * 2;
+                operand(offset + len - 1)?;
+                len
+            }
+            OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::Pop
+            | OpCode::Equal
+            | OpCode::Greater
+            | OpCode::Less
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Not
+            | OpCode::Negate
+            | OpCode::Print
+            | OpCode::CloseUpvalue
+            | OpCode::Return
+            | OpCode::Inherit => 1,
+        };
+        lengths[offset] = Some(len);
+        offset += len;
+    }
+
+    Ok(lengths)
+}
+
+/// Follows all paths through the code tracking the height of the stack in the
+/// frame of the function, which must be the same on every path reaching an
+/// instruction.
+fn verify_stack(function: &Function, lengths: &[Option<usize>]) -> Result<(), LoadError> {
+    let code = &function.chunk.code;
+    let operand = |offset: usize| code[offset] as usize;
+
+    let mut heights = vec![None; code.len()];
+    // Frames start with the called function followed by its arguments.
+    let mut pending = vec![(0, function.arity + 1)];
+    while let Some((offset, height)) = pending.pop() {
+        // Functions must end with a return instead of running past their code.
+        let Some(len) = lengths.get(offset).copied().flatten() else {
+            return Err(LoadError::InvalidBytecode(offset));
+        };
+        match heights[offset] {
+            Some(known) if known == height => continue,
+            Some(_) => return Err(LoadError::InvalidBytecode(offset)),
+            None => heights[offset] = Some(height),
+        }
+
+        let op = OpCode::from_byte(code[offset]).expect("Op codes are checked already");
+        // Count of values the instruction needs on the stack, and the count
+        // of values it leaves instead of them.
+        let (needed, pushed) = match op {
+            OpCode::Constant
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::GetGlobal
+            | OpCode::Class => (0, 1),
+            OpCode::GetLocal => {
+                if operand(offset + 1) >= height {
+                    return Err(LoadError::InvalidBytecode(offset));
+                }
+                (0, 1)
+            }
+            OpCode::SetLocal => {
+                if operand(offset + 1) >= height {
+                    return Err(LoadError::InvalidBytecode(offset));
+                }
+                (1, 1)
+            }
+            OpCode::GetUpvalue | OpCode::SetUpvalue => {
+                if operand(offset + 1) >= function.upvalue_count {
+                    return Err(LoadError::InvalidBytecode(offset));
+                }
+                if op == OpCode::GetUpvalue {
+                    (0, 1)
+                } else {
+                    (1, 1)
+                }
+            }
+            OpCode::Pop | OpCode::DefineGlobal | OpCode::Print | OpCode::CloseUpvalue => (1, 0),
+            OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::Not
+            | OpCode::Negate
+            | OpCode::JumpIfFalse => (1, 1),
+            OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Equal
+            | OpCode::Greater
+            | OpCode::Less
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide => (2, 1),
+            // Both pop the value on top, using the class below it.
+            OpCode::Inherit | OpCode::Method => (2, 1),
+            OpCode::Jump | OpCode::Loop => (0, 0),
+            OpCode::Call | OpCode::Invoke => {
+                let arg_count = operand(offset + len - 1);
+                (arg_count + 1, 1)
+            }
+            // The superclass is on top of the receiver and the arguments.
+            OpCode::SuperInvoke => (operand(offset + 2) + 2, 1),
+            OpCode::Closure => {
+                verify_captures(function, &code[offset + 2..offset + len], height)
+                    .ok_or(LoadError::InvalidBytecode(offset))?;
+                (0, 1)
+            }
+            // The result is returned on top of the called function, which the
+            // top level script pops as well.
+            OpCode::Return => (2, 0),
+        };
+        if height < needed {
+            return Err(LoadError::InvalidBytecode(offset));
+        }
+        let height = height - needed + pushed;
+
+        let next = offset + len;
+        match op {
+            OpCode::Return => {}
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+                let jump = (operand(offset + 1) << 8) | operand(offset + 2);
+                let target = match op {
+                    OpCode::Loop => next
+                        .checked_sub(jump)
+                        .ok_or(LoadError::InvalidBytecode(offset))?,
+                    _ => next + jump,
+                };
+                if lengths.get(target).copied().flatten().is_none() {
+                    return Err(LoadError::InvalidBytecode(offset));
+                }
+                pending.push((target, height));
+                if op == OpCode::JumpIfFalse {
+                    pending.push((next, height));
+                }
+            }
+            _ => pending.push((next, height)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the pairs of operands of a closure, which capture either a local of
+/// the function below the top of the stack or one of its upvalues.
+fn verify_captures(function: &Function, captures: &[u8], height: usize) -> Option<()> {
+    for capture in captures.chunks_exact(2) {
+        let index = capture[1] as usize;
+        let valid = match capture[0] {
+            1 => index < height,
+            0 => index < function.upvalue_count,
+            _ => false,
+        };
+        valid.then_some(())?;
+    }
+
+    Some(())
+}
@@ -0,0 +1,84 @@
+use std::fmt::Display;
+
+use crate::object::Object;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Obj(Object),
+}
+
+impl Value {
+    pub fn is_falsey(&self) -> bool {
+        matches!(self, Value::Nil | Value::Bool(false))
+    }
+}
+
+/// Strings are compared by their content while other objects are only
+/// equal to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Obj(a), Value::Obj(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => f.write_str("nil"),
+            Value::Bool(val) => write!(f, "{val}"),
+            Value::Number(val) => write_number(f, *val),
+            Value::Obj(object) => write!(f, "{object}"),
+        }
+    }
+}
+
+/// Writes the number the same way clox does with the `%g` format of `printf`,
+/// which uses six significant digits without trailing zeros and switches to
+/// scientific notation for small and large exponents.
+fn write_number(f: &mut std::fmt::Formatter<'_>, num: f64) -> std::fmt::Result {
+    if num.is_nan() {
+        return f.write_str("nan");
+    }
+    if num.is_infinite() {
+        let text = if num > 0.0 { "inf" } else { "-inf" };
+        return f.write_str(text);
+    }
+    if num == 0.0 {
+        // Keeps the sign of negative zero.
+        return write!(f, "{num}");
+    }
+
+    // The exponent must be taken after rounding to the significant digits.
+    let scientific = format!("{num:.5e}");
+    let (mantissa, exponent) = scientific
+        .split_once('e')
+        .expect("Scientific notation always contains an exponent");
+    let exponent: i32 = exponent.parse().expect("Exponent must be a number");
+
+    if !(-4..6).contains(&exponent) {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        write!(f, "{}e{sign}{:02}", trim_fraction(mantissa), exponent.abs())
+    } else {
+        let precision = (5 - exponent) as usize;
+        write!(f, "{}", trim_fraction(&format!("{num:.precision$}")))
+    }
+}
+
+/// Removes the trailing zeros of the fraction and the decimal point if nothing
+/// is left after it.
+fn trim_fraction(text: &str) -> &str {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text
+    }
+}
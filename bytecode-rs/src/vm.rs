@@ -0,0 +1,643 @@
+use std::{collections::HashMap, rc::Rc, time::SystemTime};
+
+use crate::{
+    chunk::OpCode,
+    compiler::compile,
+    errors::{InterpretError, RuntimeError},
+    gc::{GcOptions, Heap},
+    object::{
+        BoundMethod, Class, ClassRef, Closure, Function, Instance, Native, NativeFn, Object,
+        Upvalue, UpvalueRef,
+    },
+    serialize,
+    value::Value,
+};
+
+const FRAMES_MAX: usize = 64;
+const STACK_MAX: usize = FRAMES_MAX * (u8::MAX as usize + 1);
+
+/// Invocation of a function which is still running.
+#[derive(Debug)]
+struct CallFrame {
+    closure: Rc<Closure>,
+    /// Index of the next instruction in the chunk of the function.
+    ip: usize,
+    /// Index of the first stack slot the function can use, which holds the
+    /// called function itself followed by its arguments.
+    slots: usize,
+}
+
+/// Stack-based virtual machine executing the compiled bytecode.
+///
+/// Global variables are kept between interpreted sources, which keeps the
+/// definitions of previous lines in REPL sessions.
+#[derive(Debug)]
+pub struct Vm {
+    frames: Vec<CallFrame>,
+    stack: Vec<Value>,
+    globals: HashMap<Rc<str>, Value>,
+    /// Upvalues which are still pointing to variables on the stack.
+    open_upvalues: Vec<UpvalueRef>,
+    init_string: Rc<str>,
+    heap: Heap,
+    /// Printed values captured instead of writing them to stdout.
+    captured: Option<String>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Self::with_gc_options(GcOptions::default())
+    }
+
+    pub fn with_gc_options(options: GcOptions) -> Self {
+        let mut vm = Self {
+            frames: Vec::with_capacity(FRAMES_MAX),
+            stack: Vec::with_capacity(STACK_MAX),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            init_string: "init".into(),
+            heap: Heap::new(options),
+            captured: None,
+        };
+
+        vm.define_native("clock", clock_native);
+
+        vm
+    }
+
+    /// Starts capturing the printed values until they are taken.
+    pub fn capture_output(&mut self) {
+        self.captured = Some(String::new());
+    }
+
+    pub fn take_output(&mut self) -> Option<String> {
+        self.captured.take()
+    }
+
+    /// Compiles then executes the given source code.
+    pub fn interpret(&mut self, source: String) -> Result<(), InterpretError> {
+        let function = compile(source).map_err(InterpretError::Compile)?;
+        self.execute(function)
+    }
+
+    /// Loads then executes the bytecode compiled into the given bytes.
+    pub fn run_compiled(&mut self, bytes: &[u8]) -> Result<(), InterpretError> {
+        let function = serialize::decode(bytes)?;
+        self.execute(function)
+    }
+
+    /// Executes the function of the top level script.
+    fn execute(&mut self, function: Function) -> Result<(), InterpretError> {
+        let closure = Rc::new(Closure {
+            function: Rc::new(function),
+            upvalues: Vec::new(),
+        });
+        self.push(Value::Obj(Object::Closure(Rc::clone(&closure))));
+        self.call(closure, 0).map_err(InterpretError::Runtime)?;
+
+        self.run().map_err(InterpretError::Runtime)
+    }
+
+    fn define_native(&mut self, name: &str, function: NativeFn) {
+        let native = Native { function };
+        self.globals
+            .insert(name.into(), Value::Obj(Object::Native(Rc::new(native))));
+    }
+
+    fn reset_stack(&mut self) {
+        self.stack.clear();
+        self.frames.clear();
+        self.open_upvalues.clear();
+    }
+
+    /// Creates runtime error with the trace of the current calls, resetting
+    /// the stack afterwards.
+    fn runtime_error(&mut self, message: impl Into<String>) -> RuntimeError {
+        let trace = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                let function = &frame.closure.function;
+                // The instruction pointer already points to the next instruction
+                // while the error comes from the previous one.
+                let line = function.chunk.lines[frame.ip - 1];
+                match &function.name {
+                    Some(name) => format!("[line {line}] in {name}()"),
+                    None => format!("[line {line}] in script"),
+                }
+            })
+            .collect();
+
+        self.reset_stack();
+
+        RuntimeError {
+            message: message.into(),
+            trace,
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("Stack can't be empty while popping")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames
+            .last()
+            .expect("There is always a frame while running")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames
+            .last_mut()
+            .expect("There is always a frame while running")
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.closure.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_short(&mut self) -> u16 {
+        let high = self.read_byte() as u16;
+        let low = self.read_byte() as u16;
+        (high << 8) | low
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_byte() as usize;
+        self.frame().closure.function.chunk.constants[index].clone()
+    }
+
+    fn read_string(&mut self) -> Rc<str> {
+        match self.read_constant() {
+            Value::Obj(Object::String(text)) => text,
+            constant => unreachable!("Constant must be a string but was {constant:?}"),
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, arg_count: usize) -> Result<(), RuntimeError> {
+        let arity = closure.function.arity;
+        if arg_count != arity {
+            return Err(
+                self.runtime_error(format!("Expected {arity} arguments but got {arg_count}."))
+            );
+        }
+
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.runtime_error("Stack overflow."));
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - arg_count - 1,
+        });
+
+        Ok(())
+    }
+
+    fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<(), RuntimeError> {
+        let callee_slot = self.stack.len() - arg_count - 1;
+        match callee {
+            Value::Obj(Object::BoundMethod(bound)) => {
+                self.stack[callee_slot] = bound.receiver.clone();
+                self.call(Rc::clone(&bound.method), arg_count)
+            }
+            Value::Obj(Object::Class(class)) => {
+                let instance = self.heap.alloc_instance(Instance::new(Rc::clone(&class)));
+                self.stack[callee_slot] = Value::Obj(Object::Instance(instance));
+
+                let initializer = class.borrow().methods.get(&self.init_string).cloned();
+                match initializer {
+                    Some(initializer) => self.call(initializer, arg_count),
+                    None if arg_count != 0 => {
+                        Err(self
+                            .runtime_error(format!("Expected 0 arguments but got {arg_count}.")))
+                    }
+                    None => Ok(()),
+                }
+            }
+            Value::Obj(Object::Closure(closure)) => self.call(closure, arg_count),
+            Value::Obj(Object::Native(native)) => {
+                let result = (native.function)(&self.stack[callee_slot + 1..]);
+                self.stack.truncate(callee_slot);
+                self.push(result);
+                Ok(())
+            }
+            _ => Err(self.runtime_error("Can only call functions and classes.")),
+        }
+    }
+
+    fn invoke_from_class(
+        &mut self,
+        class: ClassRef,
+        name: Rc<str>,
+        arg_count: usize,
+    ) -> Result<(), RuntimeError> {
+        let method = class.borrow().methods.get(&name).cloned();
+        match method {
+            Some(method) => self.call(method, arg_count),
+            None => Err(self.runtime_error(format!("Undefined property '{name}'."))),
+        }
+    }
+
+    /// Calls the method of the receiver directly without creating a bound
+    /// method for it first.
+    fn invoke(&mut self, name: Rc<str>, arg_count: usize) -> Result<(), RuntimeError> {
+        let Value::Obj(Object::Instance(instance)) = self.peek(arg_count) else {
+            return Err(self.runtime_error("Only instances have methods."));
+        };
+        let instance = Rc::clone(instance);
+
+        // Fields shadow methods and can contain functions too.
+        let field = instance.borrow().fields.get(&name).cloned();
+        if let Some(value) = field {
+            let callee_slot = self.stack.len() - arg_count - 1;
+            self.stack[callee_slot] = value.clone();
+            return self.call_value(value, arg_count);
+        }
+
+        let class = Rc::clone(&instance.borrow().class);
+        self.invoke_from_class(class, name, arg_count)
+    }
+
+    /// Replaces the instance on top of the stack with its method with the
+    /// given name bound to it.
+    fn bind_method(&mut self, class: ClassRef, name: Rc<str>) -> Result<(), RuntimeError> {
+        let Some(method) = class.borrow().methods.get(&name).cloned() else {
+            return Err(self.runtime_error(format!("Undefined property '{name}'.")));
+        };
+
+        let bound = BoundMethod {
+            receiver: self.pop(),
+            method,
+        };
+        self.push(Value::Obj(Object::BoundMethod(Rc::new(bound))));
+
+        Ok(())
+    }
+
+    /// Returns the upvalue pointing to the variable in the given stack slot,
+    /// reusing the open one if the variable has been already captured.
+    fn capture_upvalue(&mut self, slot: usize) -> UpvalueRef {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+
+        if let Some(upvalue) = existing {
+            return Rc::clone(upvalue);
+        }
+
+        let upvalue = self.heap.alloc_upvalue(Upvalue::Open(slot));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+        upvalue
+    }
+
+    /// Moves the variables from the given stack slot and above into their
+    /// upvalues since they are going to be popped from the stack.
+    fn close_upvalues(&mut self, last: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= last => {
+                    *upvalue = Upvalue::Closed(stack[slot].clone());
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    /// Defines the closure on top of the stack as method of the class below it.
+    /// The compiler makes sure of their types, which are only checked for
+    /// loaded bytecode.
+    fn define_method(&mut self, name: Rc<str>) -> Result<(), RuntimeError> {
+        let (Value::Obj(Object::Class(class)), Value::Obj(Object::Closure(method))) =
+            (self.peek(1), self.peek(0))
+        else {
+            return Err(self.runtime_error("Methods must be closures defined on classes."));
+        };
+
+        class.borrow_mut().methods.insert(name, Rc::clone(method));
+        self.pop();
+
+        Ok(())
+    }
+
+    fn binary_op(&mut self, op: fn(f64, f64) -> Value) -> Result<(), RuntimeError> {
+        let (Value::Number(a), Value::Number(b)) = (self.peek(1), self.peek(0)) else {
+            return Err(self.runtime_error("Operands must be numbers."));
+        };
+
+        let result = op(*a, *b);
+        self.pop();
+        self.pop();
+        self.push(result);
+
+        Ok(())
+    }
+
+    /// Collects the garbage using the values of the virtual machine as roots.
+    fn collect_garbage(&mut self) {
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| Value::Obj(Object::Closure(Rc::clone(&frame.closure))));
+        let values = self
+            .stack
+            .iter()
+            .chain(self.globals.values())
+            .cloned()
+            .chain(frames);
+
+        self.heap.collect(values, &self.open_upvalues);
+    }
+
+    fn run(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            // Collecting between instructions makes sure that all objects in
+            // use are reachable from the stack.
+            if self.heap.should_collect() {
+                self.collect_garbage();
+            }
+
+            let instruction = self.read_byte();
+            let op = OpCode::from_byte(instruction)
+                .unwrap_or_else(|| unreachable!("Invalid op code {instruction}"));
+
+            match op {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Bool(true)),
+                OpCode::False => self.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.push(self.stack[slot].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    let Some(value) = self.globals.get(&name).cloned() else {
+                        return Err(self.runtime_error(format!("Undefined variable '{name}'.")));
+                    };
+                    self.push(value);
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&name) {
+                        Some(global) => *global = value,
+                        None => {
+                            return Err(self.runtime_error(format!("Undefined variable '{name}'.")));
+                        }
+                    }
+                }
+                OpCode::GetUpvalue => {
+                    let slot = self.read_byte() as usize;
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[slot]);
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let slot = self.read_byte() as usize;
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[slot]);
+                    let value = self.peek(0).clone();
+                    match &mut *upvalue.borrow_mut() {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::GetProperty => {
+                    let Value::Obj(Object::Instance(instance)) = self.peek(0) else {
+                        return Err(self.runtime_error("Only instances have properties."));
+                    };
+                    let instance = Rc::clone(instance);
+                    let name = self.read_string();
+
+                    let field = instance.borrow().fields.get(&name).cloned();
+                    match field {
+                        Some(value) => {
+                            // Instance
+                            self.pop();
+                            self.push(value);
+                        }
+                        None => {
+                            let class = Rc::clone(&instance.borrow().class);
+                            self.bind_method(class, name)?;
+                        }
+                    }
+                }
+                OpCode::SetProperty => {
+                    let Value::Obj(Object::Instance(instance)) = self.peek(1) else {
+                        return Err(self.runtime_error("Only instances have fields."));
+                    };
+                    let instance = Rc::clone(instance);
+                    let name = self.read_string();
+
+                    // Removes the instance below the value, keeping the value
+                    // as the result of the assignment.
+                    let value = self.pop();
+                    self.pop();
+                    instance.borrow_mut().fields.insert(name, value.clone());
+                    self.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let superclass = self.pop_class()?;
+                    self.bind_method(superclass, name)?;
+                }
+                OpCode::Equal => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.push(Value::Bool(a == b));
+                }
+                OpCode::Greater => self.binary_op(|a, b| Value::Bool(a > b))?,
+                OpCode::Less => self.binary_op(|a, b| Value::Bool(a < b))?,
+                OpCode::Add => match (self.peek(1), self.peek(0)) {
+                    (Value::Obj(Object::String(a)), Value::Obj(Object::String(b))) => {
+                        let result: Rc<str> = format!("{a}{b}").into();
+                        self.pop();
+                        self.pop();
+                        self.push(Value::Obj(Object::String(result)));
+                    }
+                    (Value::Number(_), Value::Number(_)) => {
+                        self.binary_op(|a, b| Value::Number(a + b))?;
+                    }
+                    _ => {
+                        return Err(
+                            self.runtime_error("Operands must be two numbers or two strings.")
+                        );
+                    }
+                },
+                OpCode::Subtract => self.binary_op(|a, b| Value::Number(a - b))?,
+                OpCode::Multiply => self.binary_op(|a, b| Value::Number(a * b))?,
+                OpCode::Divide => {
+                    if let (Value::Number(_), Value::Number(0.0)) = (self.peek(1), self.peek(0)) {
+                        return Err(self.runtime_error("Division by zero."));
+                    }
+                    self.binary_op(|a, b| Value::Number(a / b))?;
+                }
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Bool(value.is_falsey()));
+                }
+                OpCode::Negate => {
+                    let Value::Number(num) = *self.peek(0) else {
+                        return Err(self.runtime_error("Operand must be a number."));
+                    };
+                    self.pop();
+                    self.push(Value::Number(-num));
+                }
+                OpCode::Print => {
+                    let value = self.pop();
+                    match &mut self.captured {
+                        Some(captured) => captured.push_str(&format!("{value}\n")),
+                        None => println!("{value}"),
+                    }
+                }
+                OpCode::Jump => {
+                    let offset = self.read_short() as usize;
+                    self.frame_mut().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_short() as usize;
+                    if self.peek(0).is_falsey() {
+                        self.frame_mut().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = self.read_short() as usize;
+                    self.frame_mut().ip -= offset;
+                }
+                OpCode::Call => {
+                    let arg_count = self.read_byte() as usize;
+                    let callee = self.peek(arg_count).clone();
+                    self.call_value(callee, arg_count)?;
+                }
+                OpCode::Invoke => {
+                    let method = self.read_string();
+                    let arg_count = self.read_byte() as usize;
+                    self.invoke(method, arg_count)?;
+                }
+                OpCode::SuperInvoke => {
+                    let method = self.read_string();
+                    let arg_count = self.read_byte() as usize;
+                    let superclass = self.pop_class()?;
+                    self.invoke_from_class(superclass, method, arg_count)?;
+                }
+                OpCode::Closure => {
+                    let Value::Obj(Object::Function(function)) = self.read_constant() else {
+                        unreachable!("Closures are always created from function constants");
+                    };
+
+                    let upvalues = (0..function.upvalue_count)
+                        .map(|_| {
+                            let is_local = self.read_byte() == 1;
+                            let index = self.read_byte() as usize;
+                            if is_local {
+                                let slot = self.frame().slots + index;
+                                self.capture_upvalue(slot)
+                            } else {
+                                Rc::clone(&self.frame().closure.upvalues[index])
+                            }
+                        })
+                        .collect();
+
+                    let closure = Closure { function, upvalues };
+                    self.push(Value::Obj(Object::Closure(Rc::new(closure))));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("Returning from a running frame");
+                    self.close_upvalues(frame.slots);
+
+                    if self.frames.is_empty() {
+                        // The top level script
+                        self.pop();
+                        return Ok(());
+                    }
+
+                    self.stack.truncate(frame.slots);
+                    self.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_string();
+                    let class = self.heap.alloc_class(Class::new(name));
+                    self.push(Value::Obj(Object::Class(class)));
+                }
+                OpCode::Inherit => {
+                    let Value::Obj(Object::Class(superclass)) = self.peek(1) else {
+                        return Err(self.runtime_error("Superclass must be a class."));
+                    };
+                    let methods = superclass.borrow().methods.clone();
+
+                    let subclass = self.pop_class()?;
+                    subclass.borrow_mut().methods.extend(methods);
+                }
+                OpCode::Method => {
+                    let name = self.read_string();
+                    self.define_method(name)?;
+                }
+            }
+        }
+    }
+
+    /// Pops the class the compiler made sure to be on top of the stack, which
+    /// is only checked for loaded bytecode.
+    fn pop_class(&mut self) -> Result<ClassRef, RuntimeError> {
+        match self.pop() {
+            Value::Obj(Object::Class(class)) => Ok(class),
+            _ => Err(self.runtime_error("Value must be a class.")),
+        }
+    }
+}
+
+fn clock_native(_: &[Value]) -> Value {
+    let elapsed = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+
+    Value::Number(elapsed.as_secs_f64())
+}
@@ -0,0 +1,85 @@
+//! Tests of the disassembler printing the bytecode of the compiled scripts,
+//! including the functions declared inside them.
+
+use std::{fs, process::Command};
+
+use bytecode_rs::{compile, disassemble};
+
+const PROGRAM: &str = "fun counter() {
+  var count = 0;
+  fun add() { count = count + 1; return count; }
+  return add;
+}
+if (counter()() > 0) print \"up\";
+";
+
+const DISASSEMBLY: &str = "== <script> ==
+0000    5 OP_CLOSURE          1 <fn counter>
+0002    | OP_DEFINE_GLOBAL    0 'counter'
+0004    6 OP_GET_GLOBAL       2 'counter'
+0006    | OP_CALL             0
+0008    | OP_CALL             0
+0010    | OP_CONSTANT         3 '0'
+0012    | OP_GREATER
+0013    | OP_JUMP_IF_FALSE   13 -> 23
+0016    | OP_POP
+0017    | OP_CONSTANT         4 'up'
+0019    | OP_PRINT
+0020    | OP_JUMP            20 -> 24
+0023    | OP_POP
+0024    7 OP_NIL
+0025    | OP_RETURN
+
+== <fn counter> ==
+0000    2 OP_CONSTANT         0 '0'
+0002    3 OP_CLOSURE          1 <fn add>
+0004      |                     local 1
+0006    4 OP_GET_LOCAL        2
+0008    | OP_RETURN
+0009    5 OP_NIL
+0010    | OP_RETURN
+
+== <fn add> ==
+0000    3 OP_GET_UPVALUE      0
+0002    | OP_CONSTANT         0 '1'
+0004    | OP_ADD
+0005    | OP_SET_UPVALUE      0
+0007    | OP_POP
+0008    | OP_GET_UPVALUE      0
+0010    | OP_RETURN
+0011    | OP_NIL
+0012    | OP_RETURN
+";
+
+#[test]
+fn disassembles_jumps_closures_and_nested_functions() {
+    assert_eq!(disassemble(PROGRAM.to_owned()).unwrap(), DISASSEMBLY);
+}
+
+#[test]
+fn errors_in_the_code_fail_the_disassembling() {
+    assert!(disassemble(String::from("print 1 +;")).is_err());
+}
+
+#[test]
+fn dumps_the_bytecode_of_source_and_compiled_files() {
+    let dir = std::env::temp_dir().join(format!("rclox-disassemble-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("counter.lox");
+    let compiled = dir.join("counter.loxc");
+    fs::write(&source, PROGRAM).unwrap();
+    fs::write(&compiled, compile(PROGRAM.to_owned()).unwrap()).unwrap();
+
+    for path in [&source, &compiled] {
+        let output = Command::new(env!("CARGO_BIN_EXE_rclox"))
+            .arg("--dump-bytecode")
+            .arg(path)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", path.display());
+        // The script is only printed without running it.
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), DISASSEMBLY);
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,46 @@
+//! Tests of the garbage collector running the shared scripts with a collection
+//! after each allocation, which must not change their output.
+
+use std::{fs, path::Path};
+
+use bytecode_rs::{GcOptions, Vm};
+
+/// Runs the code on a new virtual machine, returning its output followed by
+/// the error stopping it if any.
+fn run(code: &str, options: GcOptions) -> String {
+    let mut vm = Vm::with_gc_options(options);
+    vm.capture_output();
+    let result = vm.interpret(code.to_owned());
+    let mut output = vm.take_output().expect("Output is captured while running");
+    if let Err(err) = result {
+        output.push_str(&format!("{err}\n"));
+    }
+
+    output
+}
+
+#[test]
+fn stress_collections_keep_the_output_of_scripts() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../tree-walk-rs/tests/scripts");
+    let mut scripts: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    scripts.sort();
+    assert!(!scripts.is_empty(), "No scripts found");
+
+    let stress = GcOptions {
+        stress: true,
+        ..GcOptions::default()
+    };
+    for script in scripts {
+        let code = fs::read_to_string(&script).unwrap();
+        assert_eq!(
+            run(&code, stress),
+            run(&code, GcOptions::default()),
+            "{}",
+            script.display()
+        );
+    }
+}
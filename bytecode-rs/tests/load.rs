@@ -0,0 +1,133 @@
+//! Tests of loading compiled bytecode, where malformed files must be rejected
+//! with a load error instead of crashing the virtual machine.
+
+use std::{fs, path::Path};
+
+use bytecode_rs::{InterpretError, LoadError, Vm, compile};
+
+// Byte values of the op codes used by the tests.
+const NIL: u8 = 1;
+const POP: u8 = 4;
+const GET_LOCAL: u8 = 5;
+const GET_UPVALUE: u8 = 10;
+const JUMP: u8 = 25;
+const JUMP_IF_FALSE: u8 = 26;
+const LOOP: u8 = 27;
+const CLOSURE: u8 = 31;
+const RETURN: u8 = 33;
+
+const TAG_FUNCTION: u8 = 5;
+
+/// Encodes a function without name with the given code and constants, where
+/// all instructions are on the first line.
+fn function(arity: u32, upvalue_count: u32, code: &[u8], constants: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = vec![0];
+    bytes.extend_from_slice(&arity.to_le_bytes());
+    bytes.extend_from_slice(&upvalue_count.to_le_bytes());
+    bytes.extend_from_slice(&(code.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(code);
+    for _ in code {
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+    }
+    bytes.extend_from_slice(&(constants.len() as u32).to_le_bytes());
+    for constant in constants {
+        bytes.extend_from_slice(constant);
+    }
+
+    bytes
+}
+
+/// Encodes the file of the top level script with the given code.
+fn script(code: &[u8], constants: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = b"LOXC".to_vec();
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend(function(0, 0, code, constants));
+
+    bytes
+}
+
+fn load(bytes: &[u8]) -> Result<(), InterpretError> {
+    let mut vm = Vm::new();
+    vm.capture_output();
+    vm.run_compiled(bytes)
+}
+
+fn assert_invalid(code: &[u8], constants: &[&[u8]]) {
+    match load(&script(code, constants)) {
+        Err(InterpretError::Load(LoadError::InvalidBytecode(_))) => {}
+        result => panic!("Expected invalid bytecode for {code:?}, got {result:?}"),
+    }
+}
+
+#[test]
+fn valid_script_runs() {
+    load(&script(&[NIL, RETURN], &[])).unwrap();
+}
+
+#[test]
+fn rejects_locals_above_the_stack() {
+    assert_invalid(&[GET_LOCAL, 200, RETURN], &[]);
+}
+
+#[test]
+fn rejects_stack_underflow() {
+    assert_invalid(&[POP, RETURN], &[]);
+    // Returning needs the result on top of the called function.
+    assert_invalid(&[RETURN], &[]);
+}
+
+#[test]
+fn rejects_upvalues_the_function_doesnt_have() {
+    assert_invalid(&[GET_UPVALUE, 0, RETURN], &[]);
+}
+
+#[test]
+fn rejects_invalid_captures_of_closures() {
+    let mut inner = vec![TAG_FUNCTION];
+    inner.extend(function(0, 1, &[NIL, RETURN], &[]));
+
+    // Local above the top of the stack.
+    assert_invalid(&[CLOSURE, 0, 1, 5, RETURN], &[&inner]);
+    // Upvalue of the script, which has none.
+    assert_invalid(&[CLOSURE, 0, 0, 0, RETURN], &[&inner]);
+    // Neither local nor upvalue.
+    assert_invalid(&[CLOSURE, 0, 2, 0, RETURN], &[&inner]);
+}
+
+#[test]
+fn rejects_jumps_inside_of_instructions() {
+    // The target is the operand of the local.
+    assert_invalid(&[JUMP, 0, 1, GET_LOCAL, 0, RETURN], &[]);
+    assert_invalid(&[NIL, LOOP, 0, 2, RETURN], &[]);
+}
+
+#[test]
+fn rejects_paths_with_different_stack_heights() {
+    // Pushes nil only when the condition is true.
+    assert_invalid(&[NIL, JUMP_IF_FALSE, 0, 1, NIL, POP, RETURN], &[]);
+}
+
+#[test]
+fn rejects_code_running_past_its_end() {
+    assert_invalid(&[NIL], &[]);
+    assert_invalid(&[NIL, JUMP_IF_FALSE, 0, 1, RETURN], &[]);
+}
+
+#[test]
+fn compiled_scripts_are_valid() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../tree-walk-rs/tests/scripts");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "lox") {
+            continue;
+        }
+
+        // Scripts using features of the tree-walk interpreter don't compile.
+        let Ok(bytes) = compile(fs::read_to_string(&path).unwrap()) else {
+            continue;
+        };
+        if let Err(InterpretError::Load(err)) = load(&bytes) {
+            panic!("{}: {err}", path.display());
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Comparison of syntax trees by their code instead of their identity.
+//!
+//! The derived equality compares the IDs of the nodes and the tokens and the
+//! positions of the tokens too, so the same code parsed twice never gives
+//! equal trees. Trees are the same here when they only differ in those and in
+//! grouping parentheses, which don't change the code since the nesting of the
+//! nodes already holds it.
+
+use std::rc::Rc;
+
+use super::{Expr, FuncDeclaration, Stmt};
+use crate::Token;
+
+impl Expr {
+    /// Checks if both expressions are the same code, ignoring the IDs and
+    /// the positions of their nodes and tokens and the grouping parentheses.
+    pub fn same_as(&self, other: &Expr) -> bool {
+        match (ungroup(self), ungroup(other)) {
+            (
+                Expr::Binary {
+                    left,
+                    operator,
+                    right,
+                    ..
+                },
+                Expr::Binary {
+                    left: other_left,
+                    operator: other_operator,
+                    right: other_right,
+                    ..
+                },
+            )
+            | (
+                Expr::Logical {
+                    left,
+                    operator,
+                    right,
+                    ..
+                },
+                Expr::Logical {
+                    left: other_left,
+                    operator: other_operator,
+                    right: other_right,
+                    ..
+                },
+            ) => {
+                same_token(operator, other_operator)
+                    && left.same_as(other_left)
+                    && right.same_as(other_right)
+            }
+            (
+                Expr::Call {
+                    callee, arguments, ..
+                },
+                Expr::Call {
+                    callee: other_callee,
+                    arguments: other_arguments,
+                    ..
+                },
+            ) => callee.same_as(other_callee) && same_exprs(arguments, other_arguments),
+            (
+                Expr::Get { object, name, .. },
+                Expr::Get {
+                    object: other_object,
+                    name: other_name,
+                    ..
+                },
+            ) => same_token(name, other_name) && object.same_as(other_object),
+            (
+                Expr::Index { object, index, .. },
+                Expr::Index {
+                    object: other_object,
+                    index: other_index,
+                    ..
+                },
+            ) => object.same_as(other_object) && index.same_as(other_index),
+            (
+                Expr::SetIndex {
+                    object,
+                    index,
+                    value,
+                    ..
+                },
+                Expr::SetIndex {
+                    object: other_object,
+                    index: other_index,
+                    value: other_value,
+                    ..
+                },
+            ) => {
+                object.same_as(other_object)
+                    && index.same_as(other_index)
+                    && value.same_as(other_value)
+            }
+            (
+                Expr::Literal { value, .. },
+                Expr::Literal {
+                    value: other_value, ..
+                },
+            ) => value == other_value,
+            (
+                Expr::Set {
+                    object,
+                    name,
+                    value,
+                    ..
+                },
+                Expr::Set {
+                    object: other_object,
+                    name: other_name,
+                    value: other_value,
+                    ..
+                },
+            ) => {
+                same_token(name, other_name)
+                    && object.same_as(other_object)
+                    && value.same_as(other_value)
+            }
+            (
+                Expr::Super { method, .. },
+                Expr::Super {
+                    method: other_method,
+                    ..
+                },
+            ) => same_token(method, other_method),
+            (Expr::This { .. }, Expr::This { .. }) => true,
+            (
+                Expr::Unary {
+                    operator, right, ..
+                },
+                Expr::Unary {
+                    operator: other_operator,
+                    right: other_right,
+                    ..
+                },
+            ) => same_token(operator, other_operator) && right.same_as(other_right),
+            (
+                Expr::Variable { name, .. },
+                Expr::Variable {
+                    name: other_name, ..
+                },
+            ) => same_token(name, other_name),
+            (
+                Expr::Assign { name, value, .. },
+                Expr::Assign {
+                    name: other_name,
+                    value: other_value,
+                    ..
+                },
+            ) => same_token(name, other_name) && value.same_as(other_value),
+            _ => false,
+        }
+    }
+}
+
+impl Stmt {
+    /// Checks if both statements are the same code, ignoring the IDs and the
+    /// positions of their nodes and tokens and the grouping parentheses of
+    /// their expressions.
+    pub fn same_as(&self, other: &Stmt) -> bool {
+        match (self, other) {
+            (Stmt::Expression(expr), Stmt::Expression(other_expr)) => expr.same_as(other_expr),
+            (Stmt::Function(declaration), Stmt::Function(other_declaration)) => {
+                declaration.same_as(other_declaration)
+            }
+            (
+                Stmt::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                    ..
+                },
+                Stmt::If {
+                    condition: other_condition,
+                    then_branch: other_then,
+                    else_branch: other_else,
+                    ..
+                },
+            ) => {
+                condition.same_as(other_condition)
+                    && then_branch.same_as(other_then)
+                    && same_option(else_branch, other_else, |a, b| a.same_as(b))
+            }
+            (
+                Stmt::Print { expr, .. },
+                Stmt::Print {
+                    expr: other_expr, ..
+                },
+            ) => expr.same_as(other_expr),
+            (
+                Stmt::Return { value_expr, .. },
+                Stmt::Return {
+                    value_expr: other_value,
+                    ..
+                },
+            ) => same_option(value_expr, other_value, Expr::same_as),
+            (Stmt::Break { .. }, Stmt::Break { .. })
+            | (Stmt::Continue { .. }, Stmt::Continue { .. }) => true,
+            (
+                Stmt::Var {
+                    name, initializer, ..
+                },
+                Stmt::Var {
+                    name: other_name,
+                    initializer: other_initializer,
+                    ..
+                },
+            ) => {
+                same_token(name, other_name)
+                    && same_option(initializer, other_initializer, Expr::same_as)
+            }
+            (
+                Stmt::MultiAssign {
+                    targets, values, ..
+                },
+                Stmt::MultiAssign {
+                    targets: other_targets,
+                    values: other_values,
+                    ..
+                },
+            ) => same_exprs(targets, other_targets) && same_exprs(values, other_values),
+            (
+                Stmt::While {
+                    condition, body, ..
+                },
+                Stmt::While {
+                    condition: other_condition,
+                    body: other_body,
+                    ..
+                },
+            ) => condition.same_as(other_condition) && body.same_as(other_body),
+            (
+                Stmt::For {
+                    initializer,
+                    condition,
+                    increment,
+                    body,
+                    ..
+                },
+                Stmt::For {
+                    initializer: other_initializer,
+                    condition: other_condition,
+                    increment: other_increment,
+                    body: other_body,
+                    ..
+                },
+            ) => {
+                same_option(initializer, other_initializer, |a, b| a.same_as(b))
+                    && same_option(condition, other_condition, |a, b| a.same_as(b))
+                    && same_option(increment, other_increment, |a, b| a.same_as(b))
+                    && body.same_as(other_body)
+            }
+            (
+                Stmt::ForIn {
+                    name,
+                    iterable,
+                    body,
+                    ..
+                },
+                Stmt::ForIn {
+                    name: other_name,
+                    iterable: other_iterable,
+                    body: other_body,
+                    ..
+                },
+            ) => {
+                same_token(name, other_name)
+                    && iterable.same_as(other_iterable)
+                    && body.same_as(other_body)
+            }
+            (
+                Stmt::Block { statements, .. },
+                Stmt::Block {
+                    statements: other_statements,
+                    ..
+                },
+            ) => same_stmts(statements, other_statements),
+            (
+                Stmt::Try { body, finally, .. },
+                Stmt::Try {
+                    body: other_body,
+                    finally: other_finally,
+                    ..
+                },
+            ) => body.same_as(other_body) && finally.same_as(other_finally),
+            (
+                Stmt::Export { declaration, .. },
+                Stmt::Export {
+                    declaration: other_declaration,
+                    ..
+                },
+            ) => declaration.same_as(other_declaration),
+            (
+                Stmt::Class {
+                    name,
+                    super_class,
+                    methods,
+                    doc,
+                    ..
+                },
+                Stmt::Class {
+                    name: other_name,
+                    super_class: other_super,
+                    methods: other_methods,
+                    doc: other_doc,
+                    ..
+                },
+            ) => {
+                same_token(name, other_name)
+                    && same_option(super_class, other_super, same_token)
+                    && same_methods(methods, other_methods)
+                    && doc == other_doc
+            }
+            (
+                Stmt::Extend { class, methods, .. },
+                Stmt::Extend {
+                    class: other_class,
+                    methods: other_methods,
+                    ..
+                },
+            ) => same_token(class, other_class) && same_methods(methods, other_methods),
+            _ => false,
+        }
+    }
+}
+
+impl FuncDeclaration {
+    /// Checks if both declarations are the same code, like [`Stmt::same_as()`].
+    pub fn same_as(&self, other: &FuncDeclaration) -> bool {
+        same_token(&self.name, &other.name)
+            && self.params.len() == other.params.len()
+            && self
+                .params
+                .iter()
+                .zip(&other.params)
+                .all(|(a, b)| same_token(a, b))
+            && same_stmts(&self.body, &other.body)
+            && self.doc == other.doc
+    }
+}
+
+fn ungroup(mut expr: &Expr) -> &Expr {
+    while let Expr::Grouping { expression, .. } = expr {
+        expr = expression;
+    }
+
+    expr
+}
+
+fn same_token(token: &Token, other: &Token) -> bool {
+    token.typ == other.typ && token.lexeme == other.lexeme
+}
+
+fn same_option<T>(value: &Option<T>, other: &Option<T>, same: impl Fn(&T, &T) -> bool) -> bool {
+    match (value, other) {
+        (Some(value), Some(other)) => same(value, other),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn same_exprs(exprs: &[Expr], others: &[Expr]) -> bool {
+    exprs.len() == others.len() && exprs.iter().zip(others).all(|(a, b)| a.same_as(b))
+}
+
+fn same_stmts(stmts: &[Stmt], others: &[Stmt]) -> bool {
+    stmts.len() == others.len() && stmts.iter().zip(others).all(|(a, b)| a.same_as(b))
+}
+
+fn same_methods(methods: &[Rc<FuncDeclaration>], others: &[Rc<FuncDeclaration>]) -> bool {
+    methods.len() == others.len() && methods.iter().zip(others).all(|(a, b)| a.same_as(b))
+}
@@ -0,0 +1,189 @@
+//! Abstract Syntax Tree
+
+use std::{fmt::Debug, rc::Rc};
+
+use super::{LiteralValue, printer};
+use crate::{NodeId, Token};
+
+// NOTE: I ported the visitor pattern from the book into Rust pattern matching
+// on enums since this what they wanted to achieve.
+//
+// Sub expressions are shared behind `Rc` instead of `Box` so the interpreter can
+// keep them in its pending tasks while evaluating them iteratively.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Binary {
+        id: NodeId,
+        left: Rc<Expr>,
+        operator: Token,
+        right: Rc<Expr>,
+    },
+    Call {
+        id: NodeId,
+        callee: Rc<Expr>,
+        paren: Token,
+        arguments: Rc<[Expr]>,
+    },
+    Get {
+        id: NodeId,
+        object: Rc<Expr>,
+        name: Token,
+    },
+    /// Subscript like `xs[0]`, where the bracket is the closing one.
+    Index {
+        id: NodeId,
+        object: Rc<Expr>,
+        bracket: Token,
+        index: Rc<Expr>,
+    },
+    /// Assignment to a subscript like `xs[0] = v`.
+    SetIndex {
+        id: NodeId,
+        object: Rc<Expr>,
+        bracket: Token,
+        index: Rc<Expr>,
+        value: Rc<Expr>,
+    },
+    Grouping {
+        id: NodeId,
+        expression: Rc<Expr>,
+    },
+    Literal {
+        id: NodeId,
+        value: LiteralValue,
+    },
+    Logical {
+        id: NodeId,
+        left: Rc<Expr>,
+        operator: Token,
+        right: Rc<Expr>,
+    },
+    Set {
+        id: NodeId,
+        object: Rc<Expr>,
+        name: Token,
+        value: Rc<Expr>,
+    },
+    Super {
+        id: NodeId,
+        keyword: Token,
+        method: Token,
+    },
+    This {
+        id: NodeId,
+        keyword: Token,
+    },
+    Unary {
+        id: NodeId,
+        operator: Token,
+        right: Rc<Expr>,
+    },
+    Variable {
+        id: NodeId,
+        name: Token,
+    },
+    Assign {
+        id: NodeId,
+        name: Token,
+        value: Rc<Expr>,
+    },
+}
+
+impl Expr {
+    pub fn id(&self) -> NodeId {
+        match self {
+            Expr::Binary { id, .. }
+            | Expr::Call { id, .. }
+            | Expr::Get { id, .. }
+            | Expr::Index { id, .. }
+            | Expr::SetIndex { id, .. }
+            | Expr::Grouping { id, .. }
+            | Expr::Literal { id, .. }
+            | Expr::Logical { id, .. }
+            | Expr::Set { id, .. }
+            | Expr::Super { id, .. }
+            | Expr::This { id, .. }
+            | Expr::Unary { id, .. }
+            | Expr::Variable { id, .. }
+            | Expr::Assign { id, .. } => *id,
+        }
+    }
+
+    /// Writes the expression back as Lox code, which is used to point at
+    /// expressions in error messages.
+    pub fn to_source(&self) -> String {
+        printer::print_expr(self)
+    }
+
+    /// Method is for debugging purpose only.
+    #[allow(unused)]
+    pub fn print(&self) -> String {
+        fn parenthesize(name: &str, exprs: &[&Expr]) -> String {
+            let mut text = format!("({name}");
+            for expr in exprs {
+                text.push(' ');
+                text.push_str(&expr.print());
+            }
+            text.push(')');
+
+            text
+        }
+
+        match self {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => parenthesize(&operator.lexeme, &[left, right]),
+            Expr::Grouping { expression, .. } => parenthesize("group", &[expression]),
+            Expr::Literal { value, .. } => value.to_string(),
+            Expr::Unary {
+                operator, right, ..
+            } => parenthesize(&operator.lexeme, &[right]),
+            Expr::Variable { name, .. } => format!("Variable: {name}"),
+            Expr::Assign {
+                name,
+                value: expression,
+                ..
+            } => parenthesize("assign", &[expression]),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => parenthesize(&operator.lexeme, &[left, right]),
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => {
+                let exprs: Vec<_> = std::iter::once(callee.as_ref())
+                    .chain(arguments.iter())
+                    .collect();
+                parenthesize("function", &exprs)
+            }
+            Expr::Get { object, name, .. } => {
+                parenthesize(format!("Get {name}").as_str(), &[object])
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => parenthesize(format!("Set {name}").as_str(), &[object, value]),
+            Expr::Index { object, index, .. } => parenthesize("Index", &[object, index]),
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => parenthesize("SetIndex", &[object, index, value]),
+            Expr::This { keyword, .. } => String::from("This"),
+            Expr::Super {
+                keyword, method, ..
+            } => format!("super.{}", method.lexeme),
+        }
+    }
+}
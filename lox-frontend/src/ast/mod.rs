@@ -1,19 +1,35 @@
+mod equality;
 mod expression;
+mod printer;
 mod statement;
 
 use std::fmt::Display;
 
+use crate::LoxString;
+
 pub use expression::Expr;
+pub use printer::print_program;
 pub use statement::{FuncDeclaration, Stmt};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum LiteralValue {
     Nil,
     Boolean(bool),
-    Text(String),
+    Text(LoxString),
     Number(f64),
 }
 
+impl LiteralValue {
+    /// Truthiness of the literal matching the one of the runtime values.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            LiteralValue::Nil => false,
+            LiteralValue::Boolean(val) => *val,
+            LiteralValue::Text(..) | LiteralValue::Number(..) => true,
+        }
+    }
+}
+
 impl Display for LiteralValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
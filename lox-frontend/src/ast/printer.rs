@@ -0,0 +1,507 @@
+//! Printing of syntax trees back to Lox code.
+//!
+//! The printed code parses back into the same tree apart from the positions
+//! and IDs of its nodes. Parentheses are added where the tree nests operators
+//! in a way which the precedence of the operators can't express otherwise, and
+//! `then` branches are wrapped in braces where an `else` would be taken by a
+//! nested `if` statement.
+
+use std::rc::Rc;
+
+use super::{Expr, FuncDeclaration, LiteralValue, Stmt};
+use crate::TokenType as TT;
+
+const INDENT: &str = "    ";
+
+/// Prints the statements as a Lox program, with each top level statement on
+/// its own line.
+pub fn print_program(stmts: &[Stmt]) -> String {
+    let mut printer = Printer::default();
+    for stmt in stmts {
+        printer.line(stmt);
+    }
+
+    printer.out
+}
+
+pub(super) fn print_expr(expr: &Expr) -> String {
+    let mut printer = Printer::default();
+    printer.expr(expr, Precedence::Assignment);
+    printer.out
+}
+
+pub(super) fn print_stmt(stmt: &Stmt) -> String {
+    let mut printer = Printer::default();
+    printer.stmt(stmt);
+    printer.out
+}
+
+/// Binding of the expressions from the loosest to the tightest, following the
+/// rules of the parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    fn of(expr: &Expr) -> Self {
+        match expr {
+            Expr::Assign { .. } | Expr::Set { .. } | Expr::SetIndex { .. } => Self::Assignment,
+            Expr::Logical { operator, .. } if operator.typ == TT::Or => Self::Or,
+            Expr::Logical { .. } => Self::And,
+            Expr::Binary { operator, .. } => match operator.typ {
+                TT::EqualEqual | TT::BangEqual => Self::Equality,
+                TT::Greater | TT::GreaterEqual | TT::Less | TT::LessEqual => Self::Comparison,
+                TT::Plus | TT::Minus => Self::Term,
+                _ => Self::Factor,
+            },
+            Expr::Unary { .. } => Self::Unary,
+            Expr::Call { .. } | Expr::Get { .. } | Expr::Index { .. } => Self::Call,
+            Expr::Grouping { .. }
+            | Expr::Literal { .. }
+            | Expr::Super { .. }
+            | Expr::This { .. }
+            | Expr::Variable { .. } => Self::Primary,
+        }
+    }
+
+    /// Binding of the right operand of left associative operators.
+    fn tighter(self) -> Self {
+        match self {
+            Self::Assignment => Self::Or,
+            Self::Or => Self::And,
+            Self::And => Self::Equality,
+            Self::Equality => Self::Comparison,
+            Self::Comparison => Self::Term,
+            Self::Term => Self::Factor,
+            Self::Factor => Self::Unary,
+            Self::Unary => Self::Call,
+            Self::Call | Self::Primary => Self::Primary,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Printer {
+    out: String,
+    indent: usize,
+}
+
+impl Printer {
+    /// Prints the statement on its own line at the current indentation.
+    fn line(&mut self, stmt: &Stmt) {
+        if let Some(doc) = doc(stmt) {
+            self.doc(doc);
+        }
+
+        self.indentation();
+        self.stmt(stmt);
+        self.out.push('\n');
+    }
+
+    fn doc(&mut self, doc: &str) {
+        for line in doc.lines() {
+            self.indentation();
+            self.out.push_str("///");
+            if !line.is_empty() {
+                self.out.push(' ');
+                self.out.push_str(line);
+            }
+            self.out.push('\n');
+        }
+    }
+
+    fn indentation(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression(expr) => {
+                self.expr(expr, Precedence::Assignment);
+                self.out.push(';');
+            }
+            Stmt::Function(declaration) => {
+                self.out.push_str("fun ");
+                self.function(declaration);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.out.push_str("if (");
+                self.expr(condition, Precedence::Assignment);
+                self.out.push(')');
+                let Some(else_branch) = else_branch else {
+                    self.body(then_branch);
+                    return;
+                };
+
+                let dangles = dangles(then_branch);
+                if dangles {
+                    self.out.push_str(" {\n");
+                    self.indent += 1;
+                    self.line(then_branch);
+                    self.indent -= 1;
+                    self.indentation();
+                    self.out.push('}');
+                } else {
+                    self.body(then_branch);
+                }
+
+                if dangles || matches!(then_branch.as_ref(), Stmt::Block { .. }) {
+                    self.out.push(' ');
+                } else {
+                    self.out.push('\n');
+                    self.indentation();
+                }
+                self.out.push_str("else");
+                if let Stmt::If { .. } = else_branch.as_ref() {
+                    self.out.push(' ');
+                    self.stmt(else_branch);
+                } else {
+                    self.body(else_branch);
+                }
+            }
+            Stmt::Print { expr, .. } => {
+                self.out.push_str("print ");
+                self.expr(expr, Precedence::Assignment);
+                self.out.push(';');
+            }
+            Stmt::Return { value_expr, .. } => {
+                self.out.push_str("return");
+                if let Some(value) = value_expr {
+                    self.out.push(' ');
+                    self.expr(value, Precedence::Assignment);
+                }
+                self.out.push(';');
+            }
+            Stmt::Break { .. } => self.out.push_str("break;"),
+            Stmt::Continue { .. } => self.out.push_str("continue;"),
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                self.out.push_str("var ");
+                self.out.push_str(&name.lexeme);
+                if let Some(initializer) = initializer {
+                    self.out.push_str(" = ");
+                    self.expr(initializer, Precedence::Assignment);
+                }
+                self.out.push(';');
+            }
+            Stmt::MultiAssign {
+                targets, values, ..
+            } => {
+                self.list(targets, Precedence::Call);
+                self.out.push_str(" = ");
+                self.list(values, Precedence::Assignment);
+                self.out.push(';');
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.out.push_str("while (");
+                self.expr(condition, Precedence::Assignment);
+                self.out.push(')');
+                self.body(body);
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.out.push_str("for (");
+                match initializer {
+                    Some(initializer) => self.stmt(initializer),
+                    None => self.out.push(';'),
+                }
+                if let Some(condition) = condition {
+                    self.out.push(' ');
+                    self.expr(condition, Precedence::Assignment);
+                }
+                self.out.push(';');
+                if let Some(increment) = increment {
+                    self.out.push(' ');
+                    self.expr(increment, Precedence::Assignment);
+                }
+                self.out.push(')');
+                self.body(body);
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                self.out.push_str("for (var ");
+                self.out.push_str(&name.lexeme);
+                self.out.push_str(" in ");
+                self.expr(iterable, Precedence::Assignment);
+                self.out.push(')');
+                self.body(body);
+            }
+            Stmt::Block { statements, .. } => self.block(statements),
+            Stmt::Try { body, finally, .. } => {
+                self.out.push_str("try ");
+                self.stmt(body);
+                self.out.push_str(" finally ");
+                self.stmt(finally);
+            }
+            Stmt::Export { declaration, .. } => {
+                self.out.push_str("export ");
+                self.stmt(declaration);
+            }
+            Stmt::Class {
+                name,
+                super_class,
+                methods,
+                ..
+            } => {
+                self.out.push_str("class ");
+                self.out.push_str(&name.lexeme);
+                if let Some(super_class) = super_class {
+                    self.out.push_str(" < ");
+                    self.out.push_str(&super_class.lexeme);
+                }
+                self.methods(methods);
+            }
+            Stmt::Extend { class, methods, .. } => {
+                self.out.push_str("extend ");
+                self.out.push_str(&class.lexeme);
+                self.methods(methods);
+            }
+        }
+    }
+
+    /// Prints the body of a branch or a loop, which stays on the line of its
+    /// header when it's a block.
+    fn body(&mut self, stmt: &Stmt) {
+        if let Stmt::Block { statements, .. } = stmt {
+            self.out.push(' ');
+            self.block(statements);
+        } else {
+            self.out.push('\n');
+            self.indent += 1;
+            self.indentation();
+            self.stmt(stmt);
+            self.indent -= 1;
+        }
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        if stmts.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.indent += 1;
+        for stmt in stmts {
+            self.line(stmt);
+        }
+        self.indent -= 1;
+        self.indentation();
+        self.out.push('}');
+    }
+
+    fn function(&mut self, declaration: &FuncDeclaration) {
+        self.out.push_str(&declaration.name.lexeme);
+        self.out.push('(');
+        for (index, param) in declaration.params.iter().enumerate() {
+            if index > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&param.lexeme);
+        }
+        self.out.push_str(") ");
+        self.block(&declaration.body);
+    }
+
+    fn methods(&mut self, methods: &[Rc<FuncDeclaration>]) {
+        if methods.is_empty() {
+            self.out.push_str(" {}");
+            return;
+        }
+
+        self.out.push_str(" {\n");
+        self.indent += 1;
+        for method in methods {
+            if let Some(doc) = &method.doc {
+                self.doc(doc);
+            }
+            self.indentation();
+            self.function(method);
+            self.out.push('\n');
+        }
+        self.indent -= 1;
+        self.indentation();
+        self.out.push('}');
+    }
+
+    fn list(&mut self, exprs: &[Expr], precedence: Precedence) {
+        for (index, expr) in exprs.iter().enumerate() {
+            if index > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr, precedence);
+        }
+    }
+
+    /// Prints the expression in parentheses when it binds looser than the
+    /// given precedence.
+    fn expr(&mut self, expr: &Expr, precedence: Precedence) {
+        let own = Precedence::of(expr);
+        let parenthesize = own < precedence;
+        if parenthesize {
+            self.out.push('(');
+        }
+
+        match expr {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            }
+            | Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                // Comparisons can't be chained, so both operands bind tighter.
+                let left_precedence = if own == Precedence::Comparison {
+                    own.tighter()
+                } else {
+                    own
+                };
+                self.expr(left, left_precedence);
+                self.out.push(' ');
+                self.out.push_str(&operator.lexeme);
+                self.out.push(' ');
+                self.expr(right, own.tighter());
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.expr(callee, Precedence::Call);
+                self.out.push('(');
+                self.list(arguments, Precedence::Assignment);
+                self.out.push(')');
+            }
+            Expr::Get { object, name, .. } => {
+                self.expr(object, Precedence::Call);
+                self.out.push('.');
+                self.out.push_str(&name.lexeme);
+            }
+            Expr::Index { object, index, .. } => {
+                self.expr(object, Precedence::Call);
+                self.out.push('[');
+                self.expr(index, Precedence::Assignment);
+                self.out.push(']');
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expr(object, Precedence::Call);
+                self.out.push('[');
+                self.expr(index, Precedence::Assignment);
+                self.out.push_str("] = ");
+                self.expr(value, Precedence::Assignment);
+            }
+            Expr::Grouping { expression, .. } => {
+                self.out.push('(');
+                self.expr(expression, Precedence::Assignment);
+                self.out.push(')');
+            }
+            Expr::Literal { value, .. } => match value {
+                LiteralValue::Nil => self.out.push_str("nil"),
+                LiteralValue::Text(text) => {
+                    self.out.push('"');
+                    self.out.push_str(text);
+                    self.out.push('"');
+                }
+                value => self.out.push_str(&value.to_string()),
+            },
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => {
+                self.expr(object, Precedence::Call);
+                self.out.push('.');
+                self.out.push_str(&name.lexeme);
+                self.out.push_str(" = ");
+                self.expr(value, Precedence::Assignment);
+            }
+            Expr::Super { method, .. } => {
+                self.out.push_str("super.");
+                self.out.push_str(&method.lexeme);
+            }
+            Expr::This { .. } => self.out.push_str("this"),
+            Expr::Unary {
+                operator, right, ..
+            } => {
+                self.out.push_str(&operator.lexeme);
+                self.expr(right, Precedence::Unary);
+            }
+            Expr::Variable { name, .. } => self.out.push_str(&name.lexeme),
+            Expr::Assign { name, value, .. } => {
+                self.out.push_str(&name.lexeme);
+                self.out.push_str(" = ");
+                self.expr(value, Precedence::Assignment);
+            }
+        }
+
+        if parenthesize {
+            self.out.push(')');
+        }
+    }
+}
+
+/// Text of the doc comment of the declaration.
+fn doc(stmt: &Stmt) -> Option<&str> {
+    match stmt {
+        Stmt::Function(declaration) => declaration.doc.as_deref(),
+        Stmt::Class { doc, .. } => doc.as_deref(),
+        Stmt::Export { declaration, .. } => doc(declaration),
+        _ => None,
+    }
+}
+
+/// Checks if the statement ends with an `if` statement without `else`, which
+/// would take the `else` following the statement.
+fn dangles(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::If {
+            else_branch: None, ..
+        } => true,
+        Stmt::If {
+            else_branch: Some(else_branch),
+            ..
+        } => dangles(else_branch),
+        Stmt::While { body, .. } | Stmt::For { body, .. } | Stmt::ForIn { body, .. } => {
+            dangles(body)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,175 @@
+use std::rc::Rc;
+
+use crate::{NodeId, Token};
+
+use super::{Expr, printer};
+
+/// Statements holding the children which the interpreter has to come back to
+/// after executing other statements (branches, loop bodies and blocks) share them
+/// behind `Rc`, letting the pending tasks of the interpreter own them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    /// Expression statements share the node of their expression.
+    Expression(Expr),
+    /// Declarations are shared with the functions created from them at runtime,
+    /// avoiding cloning the whole body each time a function or a method is created.
+    Function(Rc<FuncDeclaration>),
+    If {
+        id: NodeId,
+        /// The `if` keyword, where errors of the condition are reported.
+        keyword: Token,
+        condition: Expr,
+        then_branch: Rc<Stmt>,
+        else_branch: Option<Rc<Stmt>>,
+    },
+    Print {
+        id: NodeId,
+        expr: Expr,
+    },
+    Return {
+        id: NodeId,
+        keyword: Token,
+        value_expr: Option<Expr>,
+    },
+    Break {
+        id: NodeId,
+        keyword: Token,
+    },
+    Continue {
+        id: NodeId,
+        keyword: Token,
+    },
+    Var {
+        id: NodeId,
+        name: Token,
+        initializer: Option<Expr>,
+    },
+    /// Assignment of multiple values at once, like `a, b = b, a;`. Objects of
+    /// the property and subscript targets (with their indices) are evaluated
+    /// first, then all the values, before assigning them to the targets from
+    /// left to right.
+    MultiAssign {
+        id: NodeId,
+        /// Variables, properties and subscripts, which are `Expr::Variable`,
+        /// `Expr::Get` and `Expr::Index`.
+        targets: Rc<[Expr]>,
+        values: Rc<[Expr]>,
+    },
+    While {
+        id: NodeId,
+        keyword: Token,
+        condition: Rc<Expr>,
+        body: Rc<Stmt>,
+    },
+    /// Variables declared in the initializer are scoped to the loop.
+    For {
+        id: NodeId,
+        keyword: Token,
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Rc<Expr>>,
+        increment: Option<Rc<Expr>>,
+        body: Rc<Stmt>,
+    },
+    /// Loop over the elements of a list, the characters of a string or the
+    /// values of an iterator, where each iteration has its own variable.
+    ForIn {
+        id: NodeId,
+        name: Token,
+        /// The `in` keyword, where errors of the iteration are reported.
+        keyword: Token,
+        iterable: Expr,
+        body: Rc<Stmt>,
+    },
+    Block {
+        id: NodeId,
+        statements: Rc<[Stmt]>,
+    },
+    /// Block followed by the `finally` block, which runs however the first
+    /// one is left: normally, by `return`, `break` and `continue`, or by a
+    /// runtime error, which is reported once the `finally` block is done.
+    Try {
+        id: NodeId,
+        keyword: Token,
+        body: Rc<Stmt>,
+        finally: Rc<Stmt>,
+    },
+    /// Declaration of a variable, function or class in the namespace shared
+    /// by all scripts instead of the one of the current script.
+    Export {
+        id: NodeId,
+        keyword: Token,
+        declaration: Rc<Stmt>,
+    },
+    Class {
+        id: NodeId,
+        name: Token,
+        //NOTE: In the book it says that this is an `Expr::Var`
+        // Since `Expr::Var = {name: Token}` I used the name as super_class
+        super_class: Option<Token>,
+        methods: Vec<Rc<FuncDeclaration>>,
+        /// Text of the doc comment before the declaration.
+        doc: Option<Rc<str>>,
+    },
+    /// Methods added to an existing class, which are visible to its existing
+    /// instances too.
+    Extend {
+        id: NodeId,
+        keyword: Token,
+        /// Name of the variable holding the class.
+        class: Token,
+        methods: Vec<Rc<FuncDeclaration>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Wrapper around function declaration infos to avoid repeat them in
+/// both `Stmt` and `LoxCallable`
+pub struct FuncDeclaration {
+    pub id: NodeId,
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Rc<[Stmt]>,
+    /// Text of the doc comment before the declaration.
+    pub doc: Option<Rc<str>>,
+}
+
+impl FuncDeclaration {
+    pub fn new(id: NodeId, name: Token, params: Vec<Token>, body: impl Into<Rc<[Stmt]>>) -> Self {
+        Self {
+            id,
+            name,
+            params,
+            body: body.into(),
+            doc: None,
+        }
+    }
+}
+
+impl Stmt {
+    pub fn id(&self) -> NodeId {
+        match self {
+            Stmt::Expression(expr) => expr.id(),
+            Stmt::Function(declaration) => declaration.id,
+            Stmt::If { id, .. }
+            | Stmt::Print { id, .. }
+            | Stmt::Return { id, .. }
+            | Stmt::Break { id, .. }
+            | Stmt::Continue { id, .. }
+            | Stmt::Var { id, .. }
+            | Stmt::MultiAssign { id, .. }
+            | Stmt::While { id, .. }
+            | Stmt::For { id, .. }
+            | Stmt::ForIn { id, .. }
+            | Stmt::Block { id, .. }
+            | Stmt::Try { id, .. }
+            | Stmt::Export { id, .. }
+            | Stmt::Class { id, .. }
+            | Stmt::Extend { id, .. } => *id,
+        }
+    }
+
+    /// Writes the statement back as Lox code.
+    pub fn to_source(&self) -> String {
+        printer::print_stmt(self)
+    }
+}
@@ -0,0 +1,717 @@
+//! Stable codes of the diagnostics reported by the scanner, the parser, the
+//! resolver and at runtime, with the extended descriptions printed by
+//! `rlox explain`.
+//!
+//! Codes are assigned to the kinds of errors instead of their exact messages,
+//! so all messages about a missing token share one code for example. Each
+//! diagnostic gets its code where it's created, using the constants below.
+//!
+//! Codes are grouped by the stage reporting them:
+//! - `E00xx`: Scanning.
+//! - `E01xx`: Parsing.
+//! - `E02xx`: Resolving, including the warnings.
+//! - `E03xx`: Runtime errors.
+//! - `E04xx`: Aborted executions.
+
+/// Code of a kind of diagnostics with its description.
+#[derive(Debug)]
+pub struct ErrorCode {
+    /// Code like `E0214`, which never changes once assigned.
+    pub code: &'static str,
+    /// Short summary of the kind of errors.
+    pub title: &'static str,
+    /// Extended description with examples.
+    pub explanation: &'static str,
+}
+
+/// Returns the code with the given name, like `E0214`.
+pub fn find(code: &str) -> Option<&'static ErrorCode> {
+    ERROR_CODES
+        .iter()
+        .find(|entry| entry.code.eq_ignore_ascii_case(code))
+}
+
+/// Returns all the codes in order.
+pub fn all() -> &'static [ErrorCode] {
+    ERROR_CODES
+}
+
+pub const UNEXPECTED_CHARACTER: &str = "E0001";
+pub const UNTERMINATED_STRING: &str = "E0002";
+pub const WRONG_ASSIGNMENT_COUNT: &str = "E0101";
+pub const EXPECTED_EXPRESSION: &str = "E0102";
+pub const MISSING_EXPORT_DECLARATION: &str = "E0103";
+pub const MISSING_TOKEN: &str = "E0104";
+pub const INVALID_ASSIGNMENT_TARGET: &str = "E0105";
+pub const TOO_MANY_ARGUMENTS: &str = "E0106";
+pub const NESTED_TOO_DEEPLY: &str = "E0107";
+pub const CHAINED_COMPARISONS: &str = "E0108";
+pub const RESERVED_WORD: &str = "E0109";
+pub const READ_IN_OWN_INITIALIZER: &str = "E0201";
+pub const DUPLICATE_DECLARATION: &str = "E0202";
+pub const TOP_LEVEL_RETURN: &str = "E0203";
+pub const INITIALIZER_RETURN_VALUE: &str = "E0204";
+pub const THIS_OUTSIDE_CLASS: &str = "E0205";
+pub const INVALID_SUPER: &str = "E0206";
+pub const SELF_INHERITANCE: &str = "E0207";
+pub const LOOP_CONTROL_OUTSIDE_LOOP: &str = "E0208";
+pub const PRIVATE_ACCESS: &str = "E0209";
+pub const NESTED_EXPORT: &str = "E0210";
+pub const INVALID_EXTENSION: &str = "E0211";
+pub const UNUSED_VARIABLE: &str = "E0212";
+pub const SHADOWED_VARIABLE: &str = "E0213";
+pub const UNDEFINED_VARIABLE: &str = "E0214";
+pub const WRONG_ARGUMENT_COUNT: &str = "E0215";
+pub const UNREACHABLE_CODE: &str = "E0216";
+pub const OPERAND_TYPE: &str = "E0301";
+pub const DIVISION_BY_ZERO: &str = "E0302";
+pub const NOT_CALLABLE: &str = "E0303";
+pub const UNDEFINED_PROPERTY: &str = "E0304";
+pub const NOT_AN_INSTANCE: &str = "E0305";
+pub const INVALID_SUBSCRIPT: &str = "E0306";
+pub const STACK_OVERFLOW: &str = "E0307";
+pub const NON_BOOLEAN_CONDITION: &str = "E0308";
+pub const NOT_ITERABLE: &str = "E0309";
+pub const INVALID_SUPERCLASS: &str = "E0310";
+pub const INVALID_COROUTINE_USE: &str = "E0311";
+pub const INVALID_SUSPENSION: &str = "E0312";
+pub const CAPABILITY_DISABLED: &str = "E0313";
+pub const STRING_TOO_LONG: &str = "E0314";
+pub const INVALID_ARGUMENT: &str = "E0315";
+pub const INTERNAL_ERROR: &str = "E0316";
+pub const ARITY_MISMATCH: &str = "E0317";
+pub const PRIVATE_MEMBER: &str = "E0318";
+pub const STEP_LIMIT_EXCEEDED: &str = "E0401";
+pub const LOOP_LIMIT_EXCEEDED: &str = "E0402";
+pub const TIME_LIMIT_EXCEEDED: &str = "E0403";
+pub const ALLOCATION_LIMIT_EXCEEDED: &str = "E0404";
+pub const CANCELLED: &str = "E0405";
+pub const MEMORY_LIMIT_EXCEEDED: &str = "E0406";
+
+/// Codes in the order of their names.
+static ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: UNEXPECTED_CHARACTER,
+        title: "Unexpected character",
+        explanation: "\
+The source code contains a character which isn't part of any token of Lox.
+
+    var price = 5$;
+    // Unexpected character '$'.
+
+Remove the character or put it inside a string.",
+    },
+    ErrorCode {
+        code: UNTERMINATED_STRING,
+        title: "Unterminated string",
+        explanation: "\
+A string literal isn't closed with a double quote before the end of the
+file. Strings can span multiple lines, so a missing quote swallows all the
+code after it.
+
+    print \"Hello;
+
+Add the closing quote: `print \"Hello\";`.",
+    },
+    ErrorCode {
+        code: WRONG_ASSIGNMENT_COUNT,
+        title: "Wrong count of assigned values",
+        explanation: "\
+A multiple assignment must have exactly one value for each target.
+
+    a, b = 1, 2, 3;
+    // Expect 2 values to assign, but got 3.
+
+Add or remove targets or values until their counts are the same.",
+    },
+    ErrorCode {
+        code: EXPECTED_EXPRESSION,
+        title: "Expected expression",
+        explanation: "\
+The parser needs an expression, but found a token which can't start one.
+
+    var x = ;
+    print 1 + * 2;
+
+Complete the expression or remove the extra operator.",
+    },
+    ErrorCode {
+        code: MISSING_EXPORT_DECLARATION,
+        title: "Missing declaration after 'export'",
+        explanation: "\
+Only the declarations of variables, functions and classes can be exported.
+
+    export print 1;
+
+Export a declaration instead: `export var limit = 10;`.",
+    },
+    ErrorCode {
+        code: MISSING_TOKEN,
+        title: "Missing token",
+        explanation: "\
+The parser expected a specific token, like a semicolon at the end of a
+statement or a parenthesis closing a call, but found another one.
+
+    print \"done\"
+    if x > 1) {}
+
+Add the missing token at the position in the message: `print \"done\";`
+and `if (x > 1) {}`.",
+    },
+    ErrorCode {
+        code: INVALID_ASSIGNMENT_TARGET,
+        title: "Invalid assignment target",
+        explanation: "\
+Only variables, properties and subscripts can be assigned.
+
+    1 = x;
+    a + b = c;
+    f() = 2;
+
+Assign a variable like `a = c;`, a property like `point.x = 1;` or a
+subscript like `items[0] = 2;` instead.",
+    },
+    ErrorCode {
+        code: TOO_MANY_ARGUMENTS,
+        title: "Too many arguments or parameters",
+        explanation: "\
+Calls and functions can only have a limited count of arguments and
+parameters, which is 255 by default.
+
+    fun many(a1, a2, a3, ..., a300) {}
+
+Pass the values in a list or an instance instead.",
+    },
+    ErrorCode {
+        code: NESTED_TOO_DEEPLY,
+        title: "Code nested too deeply",
+        explanation: "\
+Expressions and statements can only be nested up to a limit, which keeps
+the parser from running out of stack on generated or malicious code.
+
+    print ((((((((((((1))))))))))));  // repeated thousands of times
+
+Split the code into functions or variables.",
+    },
+    ErrorCode {
+        code: CHAINED_COMPARISONS,
+        title: "Chained comparisons",
+        explanation: "\
+Comparisons can't be chained, since `1 < x < 10` would compare the boolean
+result of `1 < x` with `10`.
+
+    if (1 < x < 10) print x;
+
+Combine the comparisons with `and`: `if (1 < x and x < 10) print x;`. Store
+a middle operand like a call in a variable first, so it's only evaluated once.",
+    },
+    ErrorCode {
+        code: RESERVED_WORD,
+        title: "Reserved word as identifier",
+        explanation: "\
+Reserved words like `class`, `fun` and `var` can't be used as the names of
+variables, functions, classes, parameters or properties.
+
+    var class = 1;
+    fun fun() {}
+
+Choose another name, like `klass` or `run`.",
+    },
+    ErrorCode {
+        code: READ_IN_OWN_INITIALIZER,
+        title: "Variable read in its own initializer",
+        explanation: "\
+A local variable can't be used in the expression initializing it, since it
+has no value yet.
+
+    var a = \"outer\";
+    {
+      var a = a;
+    }
+
+Give the local variable another name to read the outer one.",
+    },
+    ErrorCode {
+        code: DUPLICATE_DECLARATION,
+        title: "Duplicate declaration",
+        explanation: "\
+A local scope, the parameters of a function or the body of a class declares
+the same name twice.
+
+    {
+      var a = 1;
+      var a = 2;
+    }
+    fun f(x, x) {}
+
+Rename one of them, or assign the existing variable instead: `a = 2;`.
+Globals can be declared again, replacing the previous value.",
+    },
+    ErrorCode {
+        code: TOP_LEVEL_RETURN,
+        title: "Return outside of functions",
+        explanation: "\
+`return` can only be used inside functions and methods.
+
+    return 1;
+
+Move the code into a function, or remove the `return`.",
+    },
+    ErrorCode {
+        code: INITIALIZER_RETURN_VALUE,
+        title: "Return value from an initializer",
+        explanation: "\
+Initializers always return the created instance, so `return` can't have a
+value inside `init`.
+
+    class Point {
+      init(x) { return x; }
+    }
+
+Use `return;` to leave the initializer early.",
+    },
+    ErrorCode {
+        code: THIS_OUTSIDE_CLASS,
+        title: "'this' outside of classes",
+        explanation: "\
+`this` refers to the instance a method is called on, so it can only be
+used inside the methods of classes.
+
+    fun name() { return this.name; }
+
+Pass the instance as a parameter instead, or move the function into a
+class.",
+    },
+    ErrorCode {
+        code: INVALID_SUPER,
+        title: "Invalid use of 'super'",
+        explanation: "\
+`super` calls the methods of the superclass, so it can only be used inside
+the methods of classes with a superclass. Methods added with `extend` can't
+use it either.
+
+    class A {
+      m() { return super.m(); }
+    }
+
+Declare the superclass with `class A < Base {}`, or call the method on
+`this` instead.",
+    },
+    ErrorCode {
+        code: SELF_INHERITANCE,
+        title: "Class inheriting from itself",
+        explanation: "\
+The superclass of a class must be another class.
+
+    class A < A {}
+
+Remove the superclass or name another class.",
+    },
+    ErrorCode {
+        code: LOOP_CONTROL_OUTSIDE_LOOP,
+        title: "Loop control outside of loops",
+        explanation: "\
+`break` and `continue` can only be used inside loops, and not inside the
+functions declared in loops.
+
+    if (done) break;
+
+Move the statement into a `while` or `for` loop.",
+    },
+    ErrorCode {
+        code: PRIVATE_ACCESS,
+        title: "Access to private members",
+        explanation: "\
+Members with names starting with an underscore are private, and can only be
+accessed through `this` inside their class.
+
+    class Account {
+      init() { this._balance = 0; }
+    }
+    print Account()._balance;
+
+Add a public method returning the value instead.",
+    },
+    ErrorCode {
+        code: NESTED_EXPORT,
+        title: "Export outside of top level",
+        explanation: "\
+Only declarations on top level can be exported, since the ones in blocks
+and functions are local.
+
+    fun f() {
+      export var a = 1;
+    }
+
+Move the declaration to top level.",
+    },
+    ErrorCode {
+        code: INVALID_EXTENSION,
+        title: "Invalid extension method",
+        explanation: "\
+`extend` can only add public methods to existing classes, since the
+existing instances are already initialized and private members belong to
+the class itself.
+
+    extend Point {
+      init() {}
+      _secret() {}
+    }
+
+Declare the initializer and the private methods in the class itself.",
+    },
+    ErrorCode {
+        code: UNUSED_VARIABLE,
+        title: "Unused local variable",
+        explanation: "\
+Strict mode requires the local variables declared with `var` to be used.
+
+    fun area(w, h) {
+      var result = w * h;
+      return w * h;
+    }
+
+Use the variable or remove its declaration.",
+    },
+    ErrorCode {
+        code: SHADOWED_VARIABLE,
+        title: "Shadowed variable",
+        explanation: "\
+Strict mode doesn't allow local variables and parameters hiding variables
+of the enclosing scopes or the globals of the script.
+
+    var count = 0;
+    fun add(count) {
+      return count + 1;
+    }
+
+Give the local variable a distinct name.",
+    },
+    ErrorCode {
+        code: UNDEFINED_VARIABLE,
+        title: "Undefined variable",
+        explanation: "\
+The variable isn't declared in any scope. It's reported as a warning before
+running the code, or as an error in strict mode, and fails at runtime once
+the variable is used.
+
+    var total = 1;
+    print totl;
+
+Fix the name or declare the variable before using it.",
+    },
+    ErrorCode {
+        code: WRONG_ARGUMENT_COUNT,
+        title: "Wrong count of arguments",
+        explanation: "\
+Functions, methods and classes must be called with exactly as many
+arguments as they have parameters. Calls of functions and classes which are
+never reassigned are checked before running the code, while the other calls
+fail at runtime with `E0317`.
+
+    fun add(a, b) { return a + b; }
+    print add(1);
+
+Pass the missing arguments or remove the extra ones.",
+    },
+    ErrorCode {
+        code: UNREACHABLE_CODE,
+        title: "Unreachable code",
+        explanation: "\
+The statement can never run, since it comes after a `return`, `break` or
+`continue` in the same block, or after a loop which never ends.
+
+    fun f() {
+      return 1;
+      print \"never\";
+    }
+
+Remove the statement or move it before the statement leaving the block.",
+    },
+    ErrorCode {
+        code: OPERAND_TYPE,
+        title: "Wrong type of operands",
+        explanation: "\
+The operator can't work with the types of its operands. Arithmetic and
+comparisons need numbers, while `+` adds two numbers or concatenates two
+strings. Values are never converted implicitly.
+
+    print \"count: \" + 3;
+    print -\"a\";
+
+Use operands of the expected types, like `print \"count: \" + \"3\";`.",
+    },
+    ErrorCode {
+        code: DIVISION_BY_ZERO,
+        title: "Division by zero",
+        explanation: "\
+Numbers can't be divided by zero, unless the interpreter follows IEEE 754
+on division, which results in infinity or `NaN` instead.
+
+    print 1 / 0;
+
+Check the divisor before dividing.",
+    },
+    ErrorCode {
+        code: NOT_CALLABLE,
+        title: "Calling a value which isn't callable",
+        explanation: "\
+Only functions, methods and classes can be called.
+
+    var name = \"lox\";
+    name();
+
+Check that the variable holds a function, or remove the parentheses.",
+    },
+    ErrorCode {
+        code: UNDEFINED_PROPERTY,
+        title: "Undefined property",
+        explanation: "\
+The instance has neither a field nor a method with the name.
+
+    class Point {}
+    print Point().x;
+
+Assign the field first, like in the initializer: `this.x = 0;`.",
+    },
+    ErrorCode {
+        code: NOT_AN_INSTANCE,
+        title: "Properties of values which aren't instances",
+        explanation: "\
+Only instances of classes have fields and methods.
+
+    var n = 1;
+    print n.size;
+    n.size = 2;
+
+Use an instance to keep the values, or a `HashMap`.",
+    },
+    ErrorCode {
+        code: INVALID_SUBSCRIPT,
+        title: "Invalid subscript",
+        explanation: "\
+Subscripts need a whole number between zero and the length of the list or
+the string, and only lists, strings and instances with `get` and `set`
+methods support them.
+
+    print \"abc\"[3];
+    print 42[0];
+
+Check the index against the length from `len(value)` first.",
+    },
+    ErrorCode {
+        code: STACK_OVERFLOW,
+        title: "Stack overflow",
+        explanation: "\
+The calls are nested deeper than the limit of the interpreter, which is
+usually caused by a recursion without a base case.
+
+    fun forever(n) { return forever(n + 1); }
+    forever(0);
+
+Add a condition stopping the recursion, or use a loop.",
+    },
+    ErrorCode {
+        code: NON_BOOLEAN_CONDITION,
+        title: "Condition which isn't a boolean",
+        explanation: "\
+With `--strict-bool` or `--strict`, the conditions of `if`, `while` and
+`for` must be booleans instead of using the truthiness of their values.
+
+    var count = 0;
+    if (count) print \"some\";
+
+Compare the value explicitly: `if (count > 0) print \"some\";`.",
+    },
+    ErrorCode {
+        code: NOT_ITERABLE,
+        title: "Value which isn't iterable",
+        explanation: "\
+`for-in` loops iterate over lists, the characters of strings, and instances
+with an `iterator` method returning an object with `hasNext` and `next`.
+
+    for (var x in 42) print x;
+
+Loop over a list or implement the iterator methods.",
+    },
+    ErrorCode {
+        code: INVALID_SUPERCLASS,
+        title: "Invalid superclass",
+        explanation: "\
+Classes can only inherit from classes, and `extend` only works on classes.
+
+    var Base = \"base\";
+    class A < Base {}
+
+Name a class as superclass.",
+    },
+    ErrorCode {
+        code: INVALID_COROUTINE_USE,
+        title: "Invalid use of coroutines",
+        explanation: "\
+`yield` can only suspend coroutines created with `coroutine(fn)` which are
+running with `resume`, and finished or running coroutines can't be resumed.
+
+    yield(1);
+    var co = coroutine(fun() {});
+    resume(co, nil);
+    resume(co, nil);
+
+Check `co.isDone()` before resuming.",
+    },
+    ErrorCode {
+        code: INVALID_SUSPENSION,
+        title: "Invalid suspension",
+        explanation: "\
+Scripts can only be suspended by natives when they are started as
+resumable by the embedder, and not inside calls made by natives or inside
+coroutines.
+
+Start the script with the resumable API of the interpreter.",
+    },
+    ErrorCode {
+        code: CAPABILITY_DISABLED,
+        title: "Capability disabled",
+        explanation: "\
+The sandbox policy of the interpreter doesn't allow the native or the native
+class, or disables a capability the native needs, like reading the time.
+Methods of native classes are only allowed together with their classes.
+
+    print clock();
+
+Ask the embedder to allow the native, or avoid using it.",
+    },
+    ErrorCode {
+        code: STRING_TOO_LONG,
+        title: "String too long",
+        explanation: "\
+Concatenating the strings would create a string longer than the limit set
+by the embedder.
+
+    var s = \"a\";
+    while (true) s = s + s;
+
+Keep the strings shorter, or ask the embedder to raise the limit.",
+    },
+    ErrorCode {
+        code: INVALID_ARGUMENT,
+        title: "Invalid value in a native call",
+        explanation: "\
+A native function or a method of a native class was called with a value it
+can't work with, or the native failed otherwise. The message describes what
+the native expected.
+
+    print len(42);
+    // Can only get the length of lists and strings, but got the number 42.
+
+Check the values passed to the native, like their types and ranges.",
+    },
+    ErrorCode {
+        code: INTERNAL_ERROR,
+        title: "Internal error",
+        explanation: "\
+The interpreter found itself in a state which valid programs can't cause,
+like an expression which the resolver didn't see before running it. This is
+a bug of the interpreter or of the program embedding it, rather than of the
+script.
+
+Please report the error together with the script causing it.",
+    },
+    ErrorCode {
+        code: ARITY_MISMATCH,
+        title: "Wrong count of arguments at runtime",
+        explanation: "\
+The called function, method, class or native takes another count of
+arguments. Calls which can't be checked before running the code, like the
+calls of natives and methods, are checked once they run.
+
+    print clock(1);
+    class Point { move(dx, dy) {} }
+    Point().move(1);
+
+Pass the missing arguments or remove the extra ones.",
+    },
+    ErrorCode {
+        code: PRIVATE_MEMBER,
+        title: "Private member of another class",
+        explanation: "\
+Private members belong to the class declaring them, which for fields is the
+class of the method setting them first. Methods added with `extend` belong to
+the extended class, while subclasses can't access the private members of
+their superclasses.
+
+    class Account {
+      init() { this._balance = 0; }
+    }
+    class Savings < Account {
+      interest() { return this._balance * 0.01; }
+    }
+    print Savings().interest();
+
+Add a method to the class owning the member which the other classes call.",
+    },
+    ErrorCode {
+        code: STEP_LIMIT_EXCEEDED,
+        title: "Step limit exceeded",
+        explanation: "\
+The program evaluated more statements and expressions than allowed by the
+embedder, which protects against programs running forever.
+
+    while (true) {}
+
+Make the program finish sooner, or ask the embedder to raise the limit.",
+    },
+    ErrorCode {
+        code: LOOP_LIMIT_EXCEEDED,
+        title: "Loop limit exceeded",
+        explanation: "\
+A loop ran more iterations than allowed by the embedder.
+
+    for (var i = 0; i < 1000000000; i = i + 1) {}
+
+Make the loop finish sooner, or ask the embedder to raise the limit.",
+    },
+    ErrorCode {
+        code: TIME_LIMIT_EXCEEDED,
+        title: "Time limit exceeded",
+        explanation: "\
+The program ran longer than allowed by the sandbox policy of the embedder.
+
+Make the program finish sooner, or ask the embedder to raise the limit.",
+    },
+    ErrorCode {
+        code: ALLOCATION_LIMIT_EXCEEDED,
+        title: "Allocation limit exceeded",
+        explanation: "\
+The program created more objects than allowed by the sandbox policy of the
+embedder.
+
+    var objects = HashMap();
+    var i = 0;
+    while (true) {
+      objects.set(i, HashMap());
+      i = i + 1;
+    }
+
+Create fewer objects, or ask the embedder to raise the limit.",
+    },
+    ErrorCode {
+        code: CANCELLED,
+        title: "Execution cancelled",
+        explanation: "\
+The embedder cancelled the program through the cancel handle of the
+interpreter, like when the user stops it.",
+    },
+    ErrorCode {
+        code: MEMORY_LIMIT_EXCEEDED,
+        title: "Memory limit exceeded",
+        explanation: "\
+The strings and lists created by the program take more memory than allowed by
+the sandbox policy of the embedder.
+
+    var text = \"text\";
+    while (true) text = text + text;
+
+Build smaller strings and lists, or ask the embedder to raise the limit.",
+    },
+];
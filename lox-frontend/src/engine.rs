@@ -0,0 +1,41 @@
+//! Common interface of the backends executing Lox programs, letting the same
+//! programs run on each of them and compare their results.
+
+use std::fmt::Display;
+
+/// Output printed by a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Output {
+    /// Lines printed by the `print` statements.
+    pub stdout: String,
+}
+
+/// Errors reported while running a program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// Output printed before and between the errors.
+    pub output: Output,
+    /// Messages of the scan, parse, static and runtime errors formatted the
+    /// same way the backend reports them.
+    pub errors: Vec<String>,
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.errors.join("\n"))
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Backend executing Lox source code.
+///
+/// Engines keep their global state between runs, so later sources can use the
+/// definitions of the previous ones.
+pub trait Engine {
+    /// Name of the engine as selected in the command line.
+    fn name(&self) -> &'static str;
+
+    /// Runs the source code capturing its output instead of printing it.
+    fn run(&mut self, source: &str) -> Result<Output, Diagnostics>;
+}
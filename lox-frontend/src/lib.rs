@@ -0,0 +1,18 @@
+//! Frontend of the Lox language shared by its implementations.
+//!
+//! Source code is scanned into tokens first, which are then either parsed into
+//! the syntax tree or compiled directly by implementations without one.
+
+pub mod ast;
+pub mod codes;
+pub mod engine;
+pub mod locale;
+mod parser;
+mod scanner;
+mod source_map;
+mod string;
+
+pub use parser::{ParseError, ParseResults, Parser, ParserOptions};
+pub use scanner::{ScanError, ScanResults, Scanner, Token, TokenType};
+pub use source_map::{NodeId, SourceMap, Span, render_snippet};
+pub use string::LoxString;
@@ -0,0 +1,618 @@
+//! Translations of the messages of the diagnostics.
+//!
+//! Messages are created in English and translated when they are displayed,
+//! using the catalog of the selected language. Diagnostics keep their messages
+//! as templates with the arguments formatted into them, and each entry of the
+//! catalog maps the code of a diagnostic and its English template to the
+//! translated template, where the arguments fill the `{}` placeholders in the
+//! same order. Messages missing in the catalog are shown in English.
+//!
+//! The language is selected for the whole process, since the diagnostics of
+//! the scanner, the parser and the interpreters are displayed without any
+//! context. The codes of the diagnostics and the line markers stay the same
+//! in all languages, so tools can still parse them.
+
+use std::{
+    fmt::{Display, Write},
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::codes;
+
+/// Languages of the messages of the diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum Lang {
+    #[default]
+    English,
+    German,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    /// Parses the language from its code like `de`, ignoring the region and
+    /// the encoding of locales like `de_DE.UTF-8`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.split(['_', '-', '.']).next().unwrap_or_default();
+        match code.to_ascii_lowercase().as_str() {
+            "en" => Ok(Lang::English),
+            "de" => Ok(Lang::German),
+            _ => Err(format!("Unsupported language '{s}'. Supported: en, de")),
+        }
+    }
+}
+
+impl Lang {
+    fn catalog(self) -> &'static [(&'static str, &'static str, &'static str)] {
+        match self {
+            Lang::English => &[],
+            Lang::German => GERMAN,
+        }
+    }
+}
+
+static LANG: AtomicU8 = AtomicU8::new(Lang::English as u8);
+
+/// Sets the language of the messages displayed from now on.
+pub fn set_lang(lang: Lang) {
+    LANG.store(lang as u8, Ordering::Relaxed);
+}
+
+/// Returns the language of the displayed messages.
+pub fn lang() -> Lang {
+    match LANG.load(Ordering::Relaxed) {
+        1 => Lang::German,
+        _ => Lang::English,
+    }
+}
+
+/// Message of a diagnostic made of its English template and the arguments
+/// formatted into the `{}` placeholders of the template in order, like names
+/// and counts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    template: &'static str,
+    args: Box<[Box<str>]>,
+}
+
+impl Message {
+    pub fn new<T: Display>(template: &'static str, args: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            template,
+            args: args.into_iter().map(|arg| arg.to_string().into()).collect(),
+        }
+    }
+
+    pub fn template(&self) -> &'static str {
+        self.template
+    }
+
+    pub fn args(&self) -> &[Box<str>] {
+        &self.args
+    }
+}
+
+impl From<&'static str> for Message {
+    fn from(template: &'static str) -> Self {
+        Self {
+            template,
+            args: Box::default(),
+        }
+    }
+}
+
+impl From<String> for Message {
+    /// Messages formatted up front don't have a template to be translated
+    /// with, so they are always shown in English.
+    fn from(message: String) -> Self {
+        Self::new("{}", [message])
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fill(f, self.template, &self.args)
+    }
+}
+
+/// Formats the message of the diagnostic with the given code in the selected
+/// language, keeping it in English when the catalog doesn't have it.
+pub fn translate(code: &str, message: &Message) -> String {
+    let template = lang()
+        .catalog()
+        .iter()
+        .find(|(entry_code, english, _)| *entry_code == code && *english == message.template)
+        .map_or(message.template, |(_, _, translation)| translation);
+
+    let mut translated = String::new();
+    fill(&mut translated, template, &message.args).expect("Writing to strings can't fail");
+    translated
+}
+
+/// Writes the template with its placeholders replaced by the arguments,
+/// keeping the placeholders without arguments as they are.
+fn fill(out: &mut impl Write, template: &str, args: &[Box<str>]) -> std::fmt::Result {
+    let mut args = args.iter();
+    let mut pieces = template.split("{}");
+    out.write_str(pieces.next().unwrap_or_default())?;
+    for piece in pieces {
+        out.write_str(args.next().map_or("{}", |arg| arg))?;
+        out.write_str(piece)?;
+    }
+
+    Ok(())
+}
+
+/// Translates the words framing the messages, like `Warning`, and the
+/// messages without codes of the command line, which may be templates filled
+/// like the ones of [`Message`].
+pub fn translate_word(word: &'static str) -> &'static str {
+    match (lang(), word) {
+        (Lang::German, "Error") => "Fehler",
+        (Lang::German, "Errors") => "Fehler",
+        (Lang::German, "Warning") => "Warnung",
+        (Lang::German, "at column") => "in Spalte",
+        (Lang::German, "note") => "Hinweis",
+        (Lang::German, "help") => "Hilfe",
+        (Lang::German, "Caused by") => "Verursacht durch",
+        (Lang::German, "in {} (line {})") => "in {} (Zeile {})",
+        (Lang::German, "at top level (line {})") => "auf oberster Ebene (Zeile {})",
+        (Lang::German, "Execution suspended.") => "Ausführung ausgesetzt.",
+        (Lang::German, "Execution paused.") => "Ausführung pausiert.",
+        (Lang::German, "Unrecoverable error in rlox: {}") => "Nicht behebbarer Fehler in rlox: {}",
+        (Lang::German, "Scanning failed with {} errors") => {
+            "Das Scannen ist mit {} Fehlern fehlgeschlagen"
+        }
+        (Lang::German, "Parsing failed with {} errors") => {
+            "Das Parsen ist mit {} Fehlern fehlgeschlagen"
+        }
+        (Lang::German, "Error while reading input file. Path: {}") => {
+            "Fehler beim Lesen der Eingabedatei. Pfad: {}"
+        }
+        (Lang::German, "Error while running file. Path: {}") => {
+            "Fehler beim Ausführen der Datei. Pfad: {}"
+        }
+        (Lang::German, "Error while running prelude. Path: {}") => {
+            "Fehler beim Ausführen des Prelude. Pfad: {}"
+        }
+        _ => word,
+    }
+}
+
+/// Catalog of the German messages by their codes and English templates.
+/// The values in the messages, like `the number 1`, are described in German
+/// by the interpreters.
+static GERMAN: &[(&str, &str, &str)] = &[
+    // Scanning
+    (
+        codes::UNEXPECTED_CHARACTER,
+        "Unexpected character '{}'.",
+        "Unerwartetes Zeichen '{}'.",
+    ),
+    (
+        codes::UNTERMINATED_STRING,
+        "Unterminated String",
+        "Nicht abgeschlossene Zeichenkette",
+    ),
+    (
+        codes::UNTERMINATED_STRING,
+        "string started here",
+        "die Zeichenkette beginnt hier",
+    ),
+    // Parsing
+    (
+        codes::WRONG_ASSIGNMENT_COUNT,
+        "Expect {} values to assign, but got {}.",
+        "{} Werte zum Zuweisen erwartet, aber {} erhalten.",
+    ),
+    (
+        codes::EXPECTED_EXPRESSION,
+        "Expect expression, found {}",
+        "Ausdruck erwartet, aber {} gefunden",
+    ),
+    (
+        codes::MISSING_EXPORT_DECLARATION,
+        "Expect declaration after 'export'.",
+        "Deklaration nach 'export' erwartet.",
+    ),
+    (
+        codes::MISSING_TOKEN,
+        "Expect end of expression.",
+        "Ende des Ausdrucks erwartet.",
+    ),
+    (
+        codes::MISSING_TOKEN,
+        "Expect ';' after value.",
+        "';' nach dem Wert erwartet.",
+    ),
+    (
+        codes::MISSING_TOKEN,
+        "Expect ';' after expression.",
+        "';' nach dem Ausdruck erwartet.",
+    ),
+    (
+        codes::MISSING_TOKEN,
+        "Expect ';' after variable declaration.",
+        "';' nach der Variablendeklaration erwartet.",
+    ),
+    (
+        codes::MISSING_TOKEN,
+        "Expect ')' after arguments.",
+        "')' nach den Argumenten erwartet.",
+    ),
+    (
+        codes::MISSING_TOKEN,
+        "Expect ')' after expression.",
+        "')' nach dem Ausdruck erwartet.",
+    ),
+    (
+        codes::MISSING_TOKEN,
+        "Expect '}' after block.",
+        "'}' nach dem Block erwartet.",
+    ),
+    (
+        codes::MISSING_TOKEN,
+        "Expect variable name",
+        "Variablenname erwartet",
+    ),
+    (
+        codes::MISSING_TOKEN,
+        "Expect parameter name.",
+        "Parametername erwartet.",
+    ),
+    (
+        codes::MISSING_TOKEN,
+        "add ';' after '{}'",
+        "';' nach '{}' einfügen",
+    ),
+    (
+        codes::INVALID_ASSIGNMENT_TARGET,
+        "Invalid assignment target.",
+        "Ungültiges Ziel der Zuweisung.",
+    ),
+    (
+        codes::TOO_MANY_ARGUMENTS,
+        "Can't have more than {} arguments.",
+        "Es sind höchstens {} Argumente erlaubt.",
+    ),
+    (
+        codes::TOO_MANY_ARGUMENTS,
+        "Can't have more than {} parameters.",
+        "Es sind höchstens {} Parameter erlaubt.",
+    ),
+    (
+        codes::NESTED_TOO_DEEPLY,
+        "Can't nest code deeper than {} levels.",
+        "Code kann nicht tiefer als {} Ebenen verschachtelt werden.",
+    ),
+    (
+        codes::CHAINED_COMPARISONS,
+        "Can't chain comparisons. Use '{} {} {} and {} {} {}' instead.",
+        "Vergleiche können nicht verkettet werden. Verwende stattdessen '{} {} {} and {} {} {}'.",
+    ),
+    (
+        codes::CHAINED_COMPARISONS,
+        "Can't chain comparisons. Use 'var value = {};' and '{} {} value and value {} {}' instead.",
+        "Vergleiche können nicht verkettet werden. Verwende stattdessen 'var value = {};' und '{} {} value and value {} {}'.",
+    ),
+    (
+        codes::RESERVED_WORD,
+        "'{}' is a reserved word and can't be used as an identifier.",
+        "'{}' ist ein reserviertes Wort und kann nicht als Bezeichner verwendet werden.",
+    ),
+    // Resolving
+    (
+        codes::READ_IN_OWN_INITIALIZER,
+        "Can't read local variable in its own initializer.",
+        "Lokale Variable kann nicht in ihrer eigenen Initialisierung gelesen werden.",
+    ),
+    (
+        codes::DUPLICATE_DECLARATION,
+        "Already a variable with the same name in this scope",
+        "In diesem Gültigkeitsbereich gibt es bereits eine Variable mit diesem Namen",
+    ),
+    (
+        codes::DUPLICATE_DECLARATION,
+        "Duplicate parameter name '{}'.",
+        "Doppelter Parametername '{}'.",
+    ),
+    (
+        codes::TOP_LEVEL_RETURN,
+        "Can't return from top level code",
+        "Auf oberster Ebene ist kein 'return' möglich",
+    ),
+    (
+        codes::THIS_OUTSIDE_CLASS,
+        "Can't use 'this' outside of a class.",
+        "'this' kann nur innerhalb einer Klasse verwendet werden.",
+    ),
+    (
+        codes::SELF_INHERITANCE,
+        "A class can't inherit from itself.",
+        "Eine Klasse kann nicht von sich selbst erben.",
+    ),
+    (
+        codes::LOOP_CONTROL_OUTSIDE_LOOP,
+        "Can't use '{}' outside of a loop.",
+        "'{}' kann nur innerhalb einer Schleife verwendet werden.",
+    ),
+    (
+        codes::UNUSED_VARIABLE,
+        "Local variable '{}' is never used.",
+        "Die lokale Variable '{}' wird nie verwendet.",
+    ),
+    (
+        codes::SHADOWED_VARIABLE,
+        "Variable '{}' shadows a variable of an enclosing scope.",
+        "Die Variable '{}' verdeckt eine Variable eines umgebenden Gültigkeitsbereichs.",
+    ),
+    (
+        codes::UNDEFINED_VARIABLE,
+        "Undefined variable '{}'.",
+        "Undefinierte Variable '{}'.",
+    ),
+    (
+        codes::WRONG_ARGUMENT_COUNT,
+        "Expected {} arguments but got {}.",
+        "{} Argumente erwartet, aber {} erhalten.",
+    ),
+    (
+        codes::UNREACHABLE_CODE,
+        "Unreachable code.",
+        "Unerreichbarer Code.",
+    ),
+    (
+        codes::DUPLICATE_DECLARATION,
+        "Already a method named '{}' in this class.",
+        "In dieser Klasse gibt es bereits eine Methode namens '{}'.",
+    ),
+    (
+        codes::INITIALIZER_RETURN_VALUE,
+        "Can't return a value fron an initializer.",
+        "Eine Initialisierung kann keinen Wert zurückgeben.",
+    ),
+    (
+        codes::NESTED_EXPORT,
+        "Can only export declarations on top level.",
+        "Nur Deklarationen auf oberster Ebene können exportiert werden.",
+    ),
+    (
+        codes::INVALID_EXTENSION,
+        "Can't add an initializer to an existing class.",
+        "Einer bestehenden Klasse kann keine Initialisierung hinzugefügt werden.",
+    ),
+    (
+        codes::INVALID_EXTENSION,
+        "Can't add private methods to an existing class.",
+        "Einer bestehenden Klasse können keine privaten Methoden hinzugefügt werden.",
+    ),
+    (
+        codes::INVALID_SUPER,
+        "Can't use 'super' outside of a class",
+        "'super' kann nur innerhalb einer Klasse verwendet werden",
+    ),
+    (
+        codes::INVALID_SUPER,
+        "Can't use 'super' in a class with no superclass",
+        "'super' kann nicht in einer Klasse ohne Oberklasse verwendet werden",
+    ),
+    (
+        codes::INVALID_SUPER,
+        "Can't use 'super' in methods added with 'extend'.",
+        "'super' kann nicht in Methoden verwendet werden, die mit 'extend' hinzugefügt wurden.",
+    ),
+    (
+        codes::PRIVATE_ACCESS,
+        "Can't access private method '{}' through 'super'.",
+        "Auf die private Methode '{}' kann nicht über 'super' zugegriffen werden.",
+    ),
+    (
+        codes::PRIVATE_ACCESS,
+        "Can't access private member '{}' except through 'this' inside its class.",
+        "Auf das private Element '{}' kann nur über 'this' innerhalb seiner Klasse zugegriffen werden.",
+    ),
+    // Runtime
+    (
+        codes::DIVISION_BY_ZERO,
+        "Division by zero.",
+        "Division durch null.",
+    ),
+    (
+        codes::NOT_CALLABLE,
+        "Can only call functions and classes.",
+        "Nur Funktionen und Klassen können aufgerufen werden.",
+    ),
+    (
+        codes::UNDEFINED_PROPERTY,
+        "Undefined property '{}'.",
+        "Undefinierte Eigenschaft '{}'.",
+    ),
+    (
+        codes::ARITY_MISMATCH,
+        "Expected {} arguments but got {}.",
+        "{} Argumente erwartet, aber {} erhalten.",
+    ),
+    (
+        codes::PRIVATE_MEMBER,
+        "'{}' is private to class {}.",
+        "'{}' ist privat in der Klasse {}.",
+    ),
+    (codes::STACK_OVERFLOW, "Stack overflow.", "Stapelüberlauf."),
+    (
+        codes::OPERAND_TYPE,
+        "Operand of '-' must be a number, but it was {}.",
+        "Der Operand von '-' muss eine Zahl sein, aber er war {}.",
+    ),
+    (
+        codes::OPERAND_TYPE,
+        "Operands of '{}' must be two numbers or two strings, but the left operand was {}.",
+        "Die Operanden von '{}' müssen zwei Zahlen oder zwei Zeichenketten sein, aber der linke Operand war {}.",
+    ),
+    (
+        codes::OPERAND_TYPE,
+        "Operands of '{}' must be two numbers or two strings, but the right operand was {}.",
+        "Die Operanden von '{}' müssen zwei Zahlen oder zwei Zeichenketten sein, aber der rechte Operand war {}.",
+    ),
+    (
+        codes::OPERAND_TYPE,
+        "Operands of '{}' must be numbers, but the left operand was {}.",
+        "Die Operanden von '{}' müssen Zahlen sein, aber der linke Operand war {}.",
+    ),
+    (
+        codes::OPERAND_TYPE,
+        "Operands of '{}' must be numbers, but the right operand was {}.",
+        "Die Operanden von '{}' müssen Zahlen sein, aber der rechte Operand war {}.",
+    ),
+    (
+        codes::OPERAND_TYPE,
+        "Operands of '{}' must both be instances to use 'compareTo', but the left operand was {}.",
+        "Die Operanden von '{}' müssen beide Instanzen sein, um 'compareTo' zu verwenden, aber der linke Operand war {}.",
+    ),
+    (
+        codes::OPERAND_TYPE,
+        "Operands of '{}' must both be instances to use 'compareTo', but the right operand was {}.",
+        "Die Operanden von '{}' müssen beide Instanzen sein, um 'compareTo' zu verwenden, aber der rechte Operand war {}.",
+    ),
+    (
+        codes::OPERAND_TYPE,
+        "'compareTo' must return a number, but it returned {}.",
+        "'compareTo' muss eine Zahl zurückgeben, aber das Ergebnis war {}.",
+    ),
+    (
+        codes::NOT_AN_INSTANCE,
+        "Only instances have fields, but '{}' was {}.",
+        "Nur Instanzen haben Felder, aber '{}' war {}.",
+    ),
+    (
+        codes::NOT_AN_INSTANCE,
+        "Only instances have properties, but '{}' was {}.",
+        "Nur Instanzen haben Eigenschaften, aber '{}' war {}.",
+    ),
+    (
+        codes::INVALID_SUBSCRIPT,
+        "Only lists, strings and instances with a 'get' method can be indexed, but '{}' was {}.",
+        "Nur Listen, Zeichenketten und Instanzen mit einer Methode 'get' können indiziert werden, aber '{}' war {}.",
+    ),
+    (
+        codes::INVALID_SUBSCRIPT,
+        "Only lists and instances with a 'set' method can be assigned by index, but '{}' was {}.",
+        "Nur Listen und Instanzen mit einer Methode 'set' können per Index zugewiesen werden, aber '{}' war {}.",
+    ),
+    (
+        codes::NOT_ITERABLE,
+        "Can only iterate over lists, strings and instances with an 'iterator' method, but got {}.",
+        "Es kann nur über Listen, Zeichenketten und Instanzen mit einer Methode 'iterator' iteriert werden, aber der Wert war {}.",
+    ),
+    (
+        codes::NOT_ITERABLE,
+        "Iterators must have a '{}' method, but got {}.",
+        "Iteratoren müssen eine Methode '{}' haben, aber der Iterator war {}.",
+    ),
+    (
+        codes::NON_BOOLEAN_CONDITION,
+        "Condition of '{}' must be a boolean, but got {}.",
+        "Die Bedingung von '{}' muss ein Wahrheitswert sein, aber sie war {}.",
+    ),
+    (
+        codes::STRING_TOO_LONG,
+        "String length exceeds the limit of {} bytes.",
+        "Die Länge der Zeichenkette überschreitet die Grenze von {} Bytes.",
+    ),
+    (
+        codes::INVALID_SUPERCLASS,
+        "Superclass must be a class.",
+        "Die Oberklasse muss eine Klasse sein.",
+    ),
+    (
+        codes::INVALID_SUPERCLASS,
+        "Can only extend classes.",
+        "Nur Klassen können erweitert werden.",
+    ),
+    (
+        codes::INVALID_COROUTINE_USE,
+        "Coroutine isn't created with 'coroutine()'.",
+        "Die Koroutine wurde nicht mit 'coroutine()' erstellt.",
+    ),
+    (
+        codes::INVALID_COROUTINE_USE,
+        "Coroutine has no values left.",
+        "Die Koroutine hat keine Werte mehr.",
+    ),
+    (
+        codes::INVALID_COROUTINE_USE,
+        "Can only yield inside coroutines.",
+        "'yield' ist nur innerhalb von Koroutinen möglich.",
+    ),
+    (
+        codes::INVALID_COROUTINE_USE,
+        "Can't yield inside calls made by natives.",
+        "'yield' ist nicht in Aufrufen durch native Funktionen möglich.",
+    ),
+    (
+        codes::INVALID_COROUTINE_USE,
+        "Coroutines can only be resumed from natives.",
+        "Koroutinen können nur aus nativen Funktionen fortgesetzt werden.",
+    ),
+    (
+        codes::INVALID_COROUTINE_USE,
+        "Can't resume a running coroutine.",
+        "Eine laufende Koroutine kann nicht fortgesetzt werden.",
+    ),
+    (
+        codes::INVALID_COROUTINE_USE,
+        "Can't resume a finished coroutine.",
+        "Eine beendete Koroutine kann nicht fortgesetzt werden.",
+    ),
+    (
+        codes::INVALID_SUSPENSION,
+        "Can't suspend scripts inside coroutines.",
+        "Skripte können nicht innerhalb von Koroutinen ausgesetzt werden.",
+    ),
+    (
+        codes::INVALID_SUSPENSION,
+        "Can't suspend scripts inside calls made by natives.",
+        "Skripte können nicht in Aufrufen durch native Funktionen ausgesetzt werden.",
+    ),
+    (
+        codes::INVALID_SUSPENSION,
+        "Can't suspend scripts which aren't started as resumable.",
+        "Skripte können nur ausgesetzt werden, wenn sie fortsetzbar gestartet wurden.",
+    ),
+    (
+        codes::STEP_LIMIT_EXCEEDED,
+        "Execution aborted after exceeding the limit of {} steps.",
+        "Ausführung abgebrochen, nachdem die Grenze von {} Schritten überschritten wurde.",
+    ),
+    (
+        codes::LOOP_LIMIT_EXCEEDED,
+        "Execution aborted after a loop exceeded the limit of {} iterations.",
+        "Ausführung abgebrochen, nachdem eine Schleife die Grenze von {} Durchläufen überschritten hat.",
+    ),
+    (
+        codes::TIME_LIMIT_EXCEEDED,
+        "Execution aborted after exceeding the time limit of {}.",
+        "Ausführung abgebrochen, nachdem die Zeitgrenze von {} überschritten wurde.",
+    ),
+    (
+        codes::ALLOCATION_LIMIT_EXCEEDED,
+        "Execution aborted after exceeding the limit of {} allocations.",
+        "Ausführung abgebrochen, nachdem die Grenze von {} Allokationen überschritten wurde.",
+    ),
+    (
+        codes::MEMORY_LIMIT_EXCEEDED,
+        "Execution aborted after exceeding the memory limit of {} bytes.",
+        "Ausführung abgebrochen, nachdem die Speichergrenze von {} Bytes überschritten wurde.",
+    ),
+    (
+        codes::CANCELLED,
+        "Execution cancelled.",
+        "Ausführung abgebrochen.",
+    ),
+    (
+        codes::INTERNAL_ERROR,
+        "Internal error: {}",
+        "Interner Fehler: {}",
+    ),
+];
@@ -0,0 +1,61 @@
+use std::{error::Error, fmt::Display};
+
+use crate::{
+    Token,
+    locale::{self, Message},
+};
+
+/// Result of the parsing functions, where the errors are boxed since they are
+/// much bigger than most of the parsed nodes.
+pub type ParseResult<T> = std::result::Result<T, Box<ParseError>>;
+
+/// Error while parsing tokens.
+#[derive(Debug)]
+pub struct ParseError {
+    pub token: Token,
+    code: &'static str,
+    pub message: Message,
+    /// Suggestion for fixing the error, shown after the message.
+    pub help: Option<Message>,
+}
+
+impl ParseError {
+    pub fn new(token: Token, code: &'static str, message: impl Into<Message>) -> Self {
+        Self {
+            token,
+            code,
+            message: message.into(),
+            help: None,
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<Message>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Stable code of the error, like `E0104`.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = locale::translate(self.code, &self.message);
+        writeln!(f, "[{}] {message}", self.code())?;
+        write!(f, "[line {}]", self.token.line)?;
+        if let Some(help) = &self.help {
+            write!(
+                f,
+                "\n  {}: {}",
+                locale::translate_word("help"),
+                locale::translate(self.code, help)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ParseError {}
@@ -0,0 +1,1305 @@
+mod error;
+mod options;
+
+use std::{collections::HashMap, rc::Rc};
+
+pub use error::ParseError;
+use error::ParseResult;
+pub use options::ParserOptions;
+
+use crate::{
+    NodeId, SourceMap, Span, Token, TokenType as TT,
+    ast::{Expr, FuncDeclaration, LiteralValue, Stmt},
+    codes,
+    locale::Message,
+    scanner::get_keyword,
+};
+
+#[derive(Debug)]
+pub struct Parser {
+    tokens: Vec<Token>,
+    current: usize,
+    errors: Vec<ParseError>,
+    options: ParserOptions,
+    /// Depth of the statements and expressions being parsed.
+    depth: usize,
+    /// Doc comments keyed by the ID of the token following them.
+    docs: HashMap<u64, Rc<str>>,
+    source_map: SourceMap,
+}
+
+pub struct ParseResults {
+    pub stmts: Vec<Stmt>,
+    pub errors: Vec<ParseError>,
+    /// Spans of the parsed nodes.
+    pub source_map: SourceMap,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        Self::with_options(tokens, ParserOptions::default())
+    }
+
+    /// Creates the parser for the tokens, which can include the comments
+    /// scanned in trivia mode. Doc comments starting with `///` document the
+    /// function and class declarations after them, while all other comments
+    /// are dropped.
+    pub fn with_options(tokens: Vec<Token>, options: ParserOptions) -> Self {
+        let (tokens, docs) = split_docs(tokens);
+        Self {
+            tokens,
+            current: 0,
+            errors: Vec::new(),
+            options,
+            depth: 0,
+            docs,
+            source_map: SourceMap::default(),
+        }
+    }
+
+    /// Starts the IDs of the nodes from the given one instead of 0, keeping
+    /// them unique across several sources running in the same program, like
+    /// the lines of the REPL.
+    pub fn with_first_node_id(mut self, id: NodeId) -> Self {
+        self.source_map = SourceMap::starting_at(id);
+        self
+    }
+
+    /// Parses all the tokens, skipping the statements with errors to report
+    /// as many errors as possible in one run.
+    pub fn parse(mut self) -> ParseResults {
+        let mut stmts = Vec::new();
+        while !self.at_end() {
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt)
+            }
+        }
+
+        ParseResults {
+            stmts,
+            errors: self.errors,
+            source_map: self.source_map,
+        }
+    }
+
+    /// Parses all the tokens as a single expression without the trailing
+    /// semicolon, returning it with the spans of its nodes.
+    pub fn parse_expression(mut self) -> Result<(Expr, SourceMap), Box<ParseError>> {
+        let expr = self.expression()?;
+        if !self.at_end() {
+            return Err(Box::new(ParseError::new(
+                self.peek().to_owned(),
+                codes::MISSING_TOKEN,
+                "Expect end of expression.",
+            )));
+        }
+
+        Ok((expr, self.source_map))
+    }
+
+    /// Definition:
+    /// ```text
+    /// declaration → exportDecl
+    ///             | classDecl
+    ///             | extendDecl
+    ///             | funDecl
+    ///             | varDecl
+    ///             | statement ;
+    /// ```
+    fn declaration(&mut self) -> Option<Stmt> {
+        let doc = self.docs.remove(&self.peek().id());
+        let res = if self.match_then_consume(&[TT::Export]) {
+            self.export_declaration(doc)
+        } else if self.match_then_consume(&[TT::Class]) {
+            self.class_declaration(doc)
+        } else if self.match_then_consume(&[TT::Extend]) {
+            self.extend_declaration()
+        } else if self.match_then_consume(&[TT::Fun]) {
+            self.function_declaration("function", doc)
+        } else if self.match_then_consume(&[TT::Var]) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        };
+
+        match res {
+            Ok(stmt) => Some(stmt),
+            Err(err) => {
+                self.errors.push(*err);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    /// Definition:
+    /// ```text
+    /// exportDecl → "export" ( classDecl | funDecl | varDecl ) ;
+    /// ```
+    fn export_declaration(&mut self, doc: Option<Rc<str>>) -> ParseResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let start = keyword.span;
+        let declaration = if self.match_then_consume(&[TT::Class]) {
+            self.class_declaration(doc)?
+        } else if self.match_then_consume(&[TT::Fun]) {
+            self.function_declaration("function", doc)?
+        } else if self.match_then_consume(&[TT::Var]) {
+            self.var_declaration()?
+        } else {
+            return Err(Box::new(ParseError::new(
+                self.peek().to_owned(),
+                codes::MISSING_EXPORT_DECLARATION,
+                "Expect declaration after 'export'.",
+            )));
+        };
+
+        Ok(Stmt::Export {
+            id: self.node(start),
+            keyword,
+            declaration: Rc::new(declaration),
+        })
+    }
+
+    /// Definition:
+    /// ```text
+    /// classDecl → "class" IDENTIFIER ( "<" IDENTIFIER )?
+    ///             "{" function* "}" ;
+    /// ```
+    fn class_declaration(&mut self, doc: Option<Rc<str>>) -> ParseResult<Stmt> {
+        let start = self.previous().span;
+        let name = self.consume_identifier("Expect class name.")?.to_owned();
+
+        let super_class = if self.match_then_consume(&[TT::Less]) {
+            let name = self.consume_identifier("Expect superclass name")?;
+            Some(name.to_owned())
+        } else {
+            None
+        };
+
+        let methods = self.class_body()?;
+
+        let stmt = Stmt::Class {
+            id: self.node(start),
+            name,
+            super_class,
+            methods,
+            doc,
+        };
+
+        Ok(stmt)
+    }
+
+    /// Definition:
+    /// ```text
+    /// extendDecl → "extend" IDENTIFIER "{" function* "}" ;
+    /// ```
+    fn extend_declaration(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let start = keyword.span;
+        let class = self.consume_identifier("Expect class name.")?.to_owned();
+        let methods = self.class_body()?;
+
+        Ok(Stmt::Extend {
+            id: self.node(start),
+            keyword,
+            class,
+            methods,
+        })
+    }
+
+    /// Parses the methods between the braces of class declarations and
+    /// extensions.
+    fn class_body(&mut self) -> ParseResult<Vec<Rc<FuncDeclaration>>> {
+        self.consume(&TT::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+
+        while !self.check(&TT::RightBrace) && !self.at_end() {
+            let doc = self.docs.remove(&self.peek().id());
+            let func = self.function_declaration("method", doc)?;
+            let Stmt::Function(method) = func else {
+                panic!("Function declaration must return function")
+            };
+
+            methods.push(method);
+        }
+
+        self.consume(&TT::RightBrace, "Expect '}' after class body.")?;
+
+        Ok(methods)
+    }
+
+    /// Definition:
+    /// ```text
+    /// funDecl     → "fun" function ;
+    /// function    → IDENTIFIER "(" parameters? ")" block ;
+    /// parameters  → IDENTIFIER ( "," IDENTIFIER )* ","? ;
+    /// ```
+    fn function_declaration(&mut self, kind: &str, doc: Option<Rc<str>>) -> ParseResult<Stmt> {
+        // Functions start at the keyword, while methods start at their names.
+        let start = if self.previous().typ == TT::Fun {
+            self.previous().span
+        } else {
+            self.peek().span
+        };
+
+        // Name:
+        let name = self
+            .consume_identifier(format!("Expect {kind} name."))?
+            .to_owned();
+
+        self.consume(&TT::LeftParen, format!("Expect '(' after {kind} name."))?;
+
+        // Parameters
+        let mut params = Vec::new();
+
+        if !self.check(&TT::RightParen) {
+            loop {
+                if params.len() >= self.options.max_args {
+                    return Err(Box::new(ParseError::new(
+                        self.peek().to_owned(),
+                        codes::TOO_MANY_ARGUMENTS,
+                        Message::new(
+                            "Can't have more than {} parameters.",
+                            [self.options.max_args],
+                        ),
+                    )));
+                }
+                match self.consume_identifier("Expect parameter name.") {
+                    Ok(param) => params.push(param.to_owned()),
+                    // The malformed parameter keeps its place with the token it
+                    // starts with, which can't be referenced as a name, so the
+                    // calls passing all the arguments don't fail on the arity.
+                    Err(err) => {
+                        params.push(self.peek().to_owned());
+                        self.recover_list_element(err)?;
+                    }
+                }
+
+                // Trailing commas are allowed.
+                if !self.match_then_consume(&[TT::Comma]) || self.check(&TT::RightParen) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(&TT::RightParen, "Expect ')' after parameters")?;
+
+        // Body:
+        self.consume(&TT::LeftBrace, format!("Expect '{{' before {kind} body."))?;
+        let body = self.block()?;
+
+        let mut declaration = FuncDeclaration::new(self.node(start), name, params, body);
+        declaration.doc = doc;
+        let declaration = Rc::new(declaration);
+
+        let stmt = Stmt::Function(declaration);
+
+        Ok(stmt)
+    }
+
+    fn var_declaration(&mut self) -> ParseResult<Stmt> {
+        let start = self.previous().span;
+        let name = self.consume_identifier("Expect variable name")?.to_owned();
+
+        let initializer = if self.match_then_consume(&[TT::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        self.consume_semicolon("Expect ';' after variable declaration.")?;
+
+        let stmt = Stmt::Var {
+            id: self.node(start),
+            name,
+            initializer,
+        };
+
+        Ok(stmt)
+    }
+
+    /// Definition:
+    /// ```text
+    /// statement → exprStmt
+    ///           | multiAssign
+    ///           | forStmt
+    ///           | ifStmt
+    ///           | printStmt
+    ///           | returnStmt
+    ///           | breakStmt
+    ///           | continueStmt
+    ///           | whileStmt
+    ///           | tryStmt
+    ///           | block ;
+    /// ```
+    fn statement(&mut self) -> ParseResult<Stmt> {
+        self.nested(Self::statement_inner)
+    }
+
+    fn statement_inner(&mut self) -> ParseResult<Stmt> {
+        if self.match_then_consume(&[TT::For]) {
+            return self.for_statement();
+        }
+        if self.match_then_consume(&[TT::If]) {
+            return self.if_statement();
+        }
+        if self.match_then_consume(&[TT::Print]) {
+            return self.print_statement();
+        }
+
+        if self.match_then_consume(&[TT::Return]) {
+            return self.return_statement();
+        }
+
+        if self.match_then_consume(&[TT::Break]) {
+            let keyword = self.previous().to_owned();
+            self.consume_semicolon("Expect ';' after 'break'.")?;
+            return Ok(Stmt::Break {
+                id: self.node(keyword.span),
+                keyword,
+            });
+        }
+
+        if self.match_then_consume(&[TT::Continue]) {
+            let keyword = self.previous().to_owned();
+            self.consume_semicolon("Expect ';' after 'continue'.")?;
+            return Ok(Stmt::Continue {
+                id: self.node(keyword.span),
+                keyword,
+            });
+        }
+
+        if self.match_then_consume(&[TT::While]) {
+            return self.while_statement();
+        }
+
+        if self.match_then_consume(&[TT::Try]) {
+            return self.try_statement();
+        }
+
+        if self.match_then_consume(&[TT::LeftBrace]) {
+            let start = self.previous().span;
+            // Nesting is already counted for the statement.
+            let statements = self.block_inner()?;
+            return Ok(Stmt::Block {
+                id: self.node(start),
+                statements: statements.into(),
+            });
+        }
+
+        self.expr_statement()
+    }
+
+    /// Definition:
+    /// ```text
+    /// ifStmt  → "if" "(" expression ")" statement
+    ///         ( "else" statement )? ;
+    /// ```
+    fn if_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let start = keyword.span;
+        self.consume(&TT::LeftParen, "Expect '(' after 'if'")?;
+        let condition = self.expression()?;
+        self.consume(&TT::RightParen, "Expect ')' after condition")?;
+
+        let then_stmt = self.statement()?;
+        let then_branch = Rc::new(then_stmt);
+        let else_branch = if self.match_then_consume(&[TT::Else]) {
+            let else_stmt = self.statement()?;
+            Some(Rc::new(else_stmt))
+        } else {
+            None
+        };
+
+        let stmt = Stmt::If {
+            id: self.node(start),
+            keyword,
+            condition,
+            then_branch,
+            else_branch,
+        };
+
+        Ok(stmt)
+    }
+
+    /// Definition:
+    /// ```text
+    /// forStmt → "for" "(" ( varDecl | exprStmt | ";" )
+    ///           expression? ";"
+    ///           expression? ")" statement
+    ///         | "for" "(" "var" IDENTIFIER "in" expression ")" statement ;
+    /// ```
+    fn for_statement(&mut self) -> ParseResult<Stmt> {
+        // NOTE: Unlike the book, the for loop isn't desugared into a while loop
+        // since it's clearer to have its own statement, and errors in its clauses
+        // are reported on the loop itself instead of the synthetic blocks.
+
+        let keyword = self.previous().to_owned();
+        let start = keyword.span;
+        self.consume(&TT::LeftParen, "Expect '(' after for.")?;
+
+        if self.at_for_in() {
+            return self.for_in_statement(start);
+        }
+
+        let initializer = if self.match_then_consume(&[TT::SemiColon]) {
+            None
+        } else if self.match_then_consume(&[TT::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expr_statement()?)
+        };
+
+        let condition = if !self.check(&TT::SemiColon) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume_semicolon("Expect ';' after loop condition")?;
+
+        let increment = if !self.check(&TT::RightParen) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(&TT::RightParen, "Expect ')' after for cluase.")?;
+
+        let body = self.statement()?;
+
+        let stmt = Stmt::For {
+            id: self.node(start),
+            keyword,
+            initializer: initializer.map(Box::new),
+            condition: condition.map(Rc::new),
+            increment: increment.map(Rc::new),
+            body: Rc::new(body),
+        };
+
+        Ok(stmt)
+    }
+
+    /// Checks if the clause of the for loop starts with `var name in`.
+    fn at_for_in(&self) -> bool {
+        match self.tokens.get(self.current..self.current + 3) {
+            Some([var, name, keyword]) => {
+                var.typ == TT::Var && matches!(name.typ, TT::Identifier(_)) && keyword.typ == TT::In
+            }
+            _ => false,
+        }
+    }
+
+    fn for_in_statement(&mut self, start: Span) -> ParseResult<Stmt> {
+        self.consume(&TT::Var, "Expect 'var' before loop variable.")?;
+        let name = self.consume_identifier("Expect variable name")?.to_owned();
+        let keyword = self
+            .consume(&TT::In, "Expect 'in' after loop variable.")?
+            .to_owned();
+        let iterable = self.expression()?;
+        self.consume(&TT::RightParen, "Expect ')' after for-in clause.")?;
+
+        let body = self.statement()?;
+
+        let stmt = Stmt::ForIn {
+            id: self.node(start),
+            name,
+            keyword,
+            iterable,
+            body: Rc::new(body),
+        };
+
+        Ok(stmt)
+    }
+
+    fn block(&mut self) -> ParseResult<Vec<Stmt>> {
+        self.nested(Self::block_inner)
+    }
+
+    fn block_inner(&mut self) -> ParseResult<Vec<Stmt>> {
+        let mut stmts = Vec::new();
+        while !self.check(&TT::RightBrace) && !self.at_end() {
+            if let Some(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
+        }
+
+        self.consume(&TT::RightBrace, "Expect '}' after block.")?;
+
+        Ok(stmts)
+    }
+
+    /// Definition:
+    /// ```text
+    /// printStmt → "print" expression ";" ;
+    /// ```
+    fn print_statement(&mut self) -> ParseResult<Stmt> {
+        let start = self.previous().span;
+        let expr = self.expression()?;
+        self.consume_semicolon("Expect ';' after value.")?;
+
+        let stmt = Stmt::Print {
+            id: self.node(start),
+            expr,
+        };
+
+        Ok(stmt)
+    }
+
+    /// Definition:
+    /// ```text
+    // returnStmt → "return" expression? ";" ;
+    /// ```
+    fn return_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let value = if self.check(&TT::SemiColon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+
+        self.consume_semicolon("Expect ';' after return value")?;
+
+        let stmt = Stmt::Return {
+            id: self.node(keyword.span),
+            keyword,
+            value_expr: value,
+        };
+
+        Ok(stmt)
+    }
+
+    /// Definition:
+    /// ```text
+    /// whileStmt → "while" "(" expression ")" statement ;
+    /// ```
+    fn while_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let start = keyword.span;
+        self.consume(&TT::LeftParen, "Expect '(' after while.")?;
+        let condition = self.expression()?;
+        self.consume(&TT::RightParen, "Expect ')' after condition.")?;
+        let body = self.statement()?;
+
+        let stmt = Stmt::While {
+            id: self.node(start),
+            keyword,
+            condition: Rc::new(condition),
+            body: Rc::new(body),
+        };
+
+        Ok(stmt)
+    }
+
+    /// Definition:
+    /// ```text
+    /// tryStmt → "try" block "finally" block ;
+    /// ```
+    fn try_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let body = self.try_block("Expect '{' after 'try'.")?;
+        self.consume(&TT::Finally, "Expect 'finally' after try block.")?;
+        let finally = self.try_block("Expect '{' after 'finally'.")?;
+
+        let stmt = Stmt::Try {
+            id: self.node(keyword.span),
+            keyword,
+            body: Rc::new(body),
+            finally: Rc::new(finally),
+        };
+
+        Ok(stmt)
+    }
+
+    /// Parses one of the blocks of try statements.
+    fn try_block(&mut self, error_msg: &'static str) -> ParseResult<Stmt> {
+        let start = self.consume(&TT::LeftBrace, error_msg)?.span;
+        let statements = self.block()?;
+
+        Ok(Stmt::Block {
+            id: self.node(start),
+            statements: statements.into(),
+        })
+    }
+
+    fn expr_statement(&mut self) -> ParseResult<Stmt> {
+        let start = self.peek().span;
+        let expr = self.expression()?;
+        if self.check(&TT::Comma) {
+            return self.multi_assignment(start, expr);
+        }
+        self.consume_semicolon("Expect ';' after expression.")?;
+
+        let stmt = Stmt::Expression(expr);
+
+        Ok(stmt)
+    }
+
+    /// Definition:
+    /// ```text
+    /// multiAssign → target ( "," target )+ "=" expression ( "," expression )+ ";" ;
+    /// target      → ( call "." )? IDENTIFIER | call "[" expression "]" ;
+    /// ```
+    ///
+    /// Only statements can assign multiple targets, since commas separate the
+    /// arguments of calls.
+    fn multi_assignment(&mut self, start: Span, first: Expr) -> ParseResult<Stmt> {
+        let mut targets = vec![first];
+        while self.match_then_consume(&[TT::Comma]) {
+            targets.push(self.nested(Self::or)?);
+        }
+        let equals = self
+            .consume(&TT::Equal, "Expect '=' after assignment targets.")?
+            .to_owned();
+        if !targets.iter().all(|target| {
+            matches!(
+                target,
+                Expr::Variable { .. } | Expr::Get { .. } | Expr::Index { .. }
+            )
+        }) {
+            return Err(Box::new(ParseError::new(
+                equals,
+                codes::INVALID_ASSIGNMENT_TARGET,
+                "Invalid assignment target.",
+            )));
+        }
+
+        let mut values = vec![self.expression()?];
+        while self.match_then_consume(&[TT::Comma]) {
+            values.push(self.expression()?);
+        }
+        if values.len() != targets.len() {
+            return Err(Box::new(ParseError::new(
+                equals,
+                codes::WRONG_ASSIGNMENT_COUNT,
+                Message::new(
+                    "Expect {} values to assign, but got {}.",
+                    [targets.len(), values.len()],
+                ),
+            )));
+        }
+        self.consume_semicolon("Expect ';' after assignment.")?;
+
+        Ok(Stmt::MultiAssign {
+            id: self.node(start),
+            targets: targets.into(),
+            values: values.into(),
+        })
+    }
+
+    /// Definition: `expression → assignment;`
+    fn expression(&mut self) -> ParseResult<Expr> {
+        self.nested(Self::assignment)
+    }
+
+    /// Parses the nested statement or expression after checking that the
+    /// code isn't nested deeper than allowed.
+    ///
+    /// The depth is restored once it's parsed, which also drops the levels the
+    /// links of operator chains in it entered.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        let depth = self.depth;
+        self.link()?;
+        let result = parse(self);
+        self.depth = depth;
+        result
+    }
+
+    /// Enters the next level of nesting, failing when the code is nested
+    /// deeper than allowed.
+    ///
+    /// Each operator of a left associative chain like `1 + 2 + 3` is a link
+    /// nesting the operations before it one level deeper, so long chains
+    /// can't overflow the stack of the passes walking the tree either.
+    fn link(&mut self) -> ParseResult<()> {
+        if self.depth >= self.options.max_nesting_depth {
+            return Err(Box::new(ParseError::new(
+                self.peek().to_owned(),
+                codes::NESTED_TOO_DEEPLY,
+                Message::new(
+                    "Can't nest code deeper than {} levels.",
+                    [self.options.max_nesting_depth],
+                ),
+            )));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Definition:
+    /// ```text
+    /// assignment → (call ".")? IDENTIFIER "=" assignment
+    ///            | call "[" expression "]" "=" assignment
+    ///            | logic_or ;
+    /// ```
+    ///
+    /// Assignments are right associative, so `a = b = 0` assigns `0` to `b`
+    /// and then the value of that assignment to `a`.
+    fn assignment(&mut self) -> ParseResult<Expr> {
+        let start = self.peek().span;
+        // L-Value
+        let expr = self.or()?;
+        if self.match_then_consume(&[TT::Equal]) {
+            // R-Value
+            let value = self.assignment()?;
+            match expr {
+                Expr::Variable { name, .. } => {
+                    return Ok(Expr::Assign {
+                        id: self.node(start),
+                        name,
+                        value: Rc::new(value),
+                    });
+                }
+                // This should solve chaining multiple fields then assign the last one
+                // Example: `foo.bar.baz = 1;`
+                Expr::Get { object, name, .. } => {
+                    let expr = Expr::Set {
+                        id: self.node(start),
+                        object,
+                        name,
+                        value: Rc::new(value),
+                    };
+                    return Ok(expr);
+                }
+                Expr::Index {
+                    object,
+                    bracket,
+                    index,
+                    ..
+                } => {
+                    return Ok(Expr::SetIndex {
+                        id: self.node(start),
+                        object,
+                        bracket,
+                        index,
+                        value: Rc::new(value),
+                    });
+                }
+                _ => {
+                    let equals = self.previous().to_owned();
+                    return Err(Box::new(ParseError::new(
+                        equals,
+                        codes::INVALID_ASSIGNMENT_TARGET,
+                        "Invalid assignment target.",
+                    )));
+                }
+            }
+        }
+
+        Ok(expr)
+    }
+
+    /// Definition
+    /// ```text
+    /// logic_or → logic_and ( "or" logic_and )* ;
+    /// ```
+    fn or(&mut self) -> ParseResult<Expr> {
+        let start = self.peek().span;
+        let mut expr = self.and()?;
+
+        while self.match_then_consume(&[TT::Or]) {
+            self.link()?;
+            let operator = self.previous().to_owned();
+            let right = self.and()?;
+            expr = Expr::Logical {
+                id: self.node(start),
+                left: Rc::new(expr),
+                operator,
+                right: Rc::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// Definition
+    /// ```text
+    /// logic_and → equality ( "and" equality )* ;
+    /// ```
+    fn and(&mut self) -> ParseResult<Expr> {
+        let start = self.peek().span;
+        let mut expr = self.equality()?;
+
+        while self.match_then_consume(&[TT::And]) {
+            self.link()?;
+            let operator = self.previous().to_owned();
+            let right = self.equality()?;
+            expr = Expr::Logical {
+                id: self.node(start),
+                left: Rc::new(expr),
+                operator,
+                right: Rc::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// Definition: `equality → comparison ( ( "!=" | "==" ) comparison )* ;`
+    fn equality(&mut self) -> ParseResult<Expr> {
+        let start = self.peek().span;
+        let mut expr = self.comparison()?;
+        while self.match_then_consume(&[TT::BangEqual, TT::EqualEqual]) {
+            self.link()?;
+            let operator = self.previous().to_owned();
+            let right = self.comparison()?;
+            expr = Expr::Binary {
+                id: self.node(start),
+                left: Rc::new(expr),
+                operator,
+                right: Rc::new(right),
+            }
+        }
+
+        Ok(expr)
+    }
+
+    /// Check if any matches then it consumes the current token.
+    fn match_then_consume(&mut self, tts: &[TT]) -> bool {
+        if tts.iter().any(|tt| self.check(tt)) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn check(&self, tt: &TT) -> bool {
+        &self.peek().typ == tt
+    }
+
+    fn advance(&mut self) -> &Token {
+        if !self.at_end() {
+            self.current += 1;
+        }
+
+        self.previous()
+    }
+
+    fn at_end(&self) -> bool {
+        matches!(self.peek().typ, TT::Eof)
+    }
+
+    fn previous(&self) -> &Token {
+        &self.tokens[self.current - 1]
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
+    }
+
+    /// Definition: `comparison → term ( ( ">" | ">=" | "<" | "<=" ) term )?`
+    ///
+    /// Comparisons can't be chained, since `a < b < c` would compare the
+    /// boolean result of `a < b` with `c`, which always fails at runtime.
+    fn comparison(&mut self) -> ParseResult<Expr> {
+        const COMPARISONS: &[TT] = &[TT::Greater, TT::GreaterEqual, TT::Less, TT::LessEqual];
+
+        let start = self.peek().span;
+        let expr = self.term()?;
+        if !self.match_then_consume(COMPARISONS) {
+            return Ok(expr);
+        }
+
+        let operator = self.previous().to_owned();
+        let right = self.term()?;
+        if self.match_then_consume(COMPARISONS) {
+            let chained = self.previous().to_owned();
+            let last = self.term()?;
+            let middle = right.to_source();
+            // Other operands would be evaluated twice when copied into both
+            // comparisons, so the suggestion stores them in a variable first.
+            let message = match right {
+                Expr::Variable { .. } | Expr::Literal { .. } => Message::new(
+                    "Can't chain comparisons. Use '{} {} {} and {} {} {}' instead.",
+                    [
+                        expr.to_source(),
+                        operator.lexeme.to_string(),
+                        middle.clone(),
+                        middle,
+                        chained.lexeme.to_string(),
+                        last.to_source(),
+                    ],
+                ),
+                _ => Message::new(
+                    "Can't chain comparisons. Use 'var value = {};' and '{} {} value and value {} {}' instead.",
+                    [
+                        middle,
+                        expr.to_source(),
+                        operator.lexeme.to_string(),
+                        chained.lexeme.to_string(),
+                        last.to_source(),
+                    ],
+                ),
+            };
+            return Err(Box::new(ParseError::new(
+                chained.clone(),
+                codes::CHAINED_COMPARISONS,
+                message,
+            )));
+        }
+
+        Ok(Expr::Binary {
+            id: self.node(start),
+            left: Rc::new(expr),
+            operator,
+            right: Rc::new(right),
+        })
+    }
+
+    /// Definition: `term → factor ( ( "-" | "+" ) factor )*;`
+    fn term(&mut self) -> ParseResult<Expr> {
+        let start = self.peek().span;
+        let mut expr = self.factor()?;
+
+        while self.match_then_consume(&[TT::Plus, TT::Minus]) {
+            self.link()?;
+            let operator = self.previous().to_owned();
+            let right = self.factor()?;
+            expr = Expr::Binary {
+                id: self.node(start),
+                left: Rc::new(expr),
+                operator,
+                right: Rc::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// Definition: `factor → unary ( ( "/" | "*" | "~/" | "%" ) unary )*`
+    fn factor(&mut self) -> ParseResult<Expr> {
+        let start = self.peek().span;
+        let mut expr = self.unary()?;
+
+        while self.match_then_consume(&[TT::Slash, TT::Star, TT::TildeSlash, TT::Percent]) {
+            self.link()?;
+            let operator = self.previous().to_owned();
+            let right = self.unary()?;
+            expr = Expr::Binary {
+                id: self.node(start),
+                left: Rc::new(expr),
+                operator,
+                right: Rc::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// Definition:
+    /// ```text
+    /// unary  → ( "!" | "-" ) unary
+    ///        | call ;
+    /// ```
+    fn unary(&mut self) -> ParseResult<Expr> {
+        if self.match_then_consume(&[TT::Bang, TT::Minus]) {
+            let operator = self.previous().to_owned();
+            let right = self.nested(Self::unary)?;
+            let expr = Expr::Unary {
+                id: self.node(operator.span),
+                operator,
+                right: Rc::new(right),
+            };
+
+            Ok(expr)
+        } else {
+            self.call()
+        }
+    }
+
+    /// Definition:
+    /// ```text
+    /// call      → primary ( "(" arguments? ")" | "." IDENTIFIER | "[" expression "]" )* ;
+    /// arguments → expression ( "," expression )* ","? ;
+    /// ```
+    fn call(&mut self) -> ParseResult<Expr> {
+        let start = self.peek().span;
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.match_then_consume(&[TT::LeftParen]) {
+                expr = self.finish_call(start, expr)?;
+            } else if self.match_then_consume(&[TT::Dot]) {
+                let name = self
+                    .consume_identifier("Expect property name after '.'.")?
+                    .to_owned();
+                expr = Expr::Get {
+                    id: self.node(start),
+                    object: Rc::new(expr),
+                    name,
+                };
+            } else if self.match_then_consume(&[TT::LeftBracket]) {
+                let index = self.expression()?;
+                let bracket = self
+                    .consume(&TT::RightBracket, "Expect ']' after index.")?
+                    .to_owned();
+                expr = Expr::Index {
+                    id: self.node(start),
+                    object: Rc::new(expr),
+                    bracket,
+                    index: Rc::new(index),
+                };
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, start: Span, callee: Expr) -> ParseResult<Expr> {
+        let mut arguments = Vec::new();
+        if !self.check(&TT::RightParen) {
+            loop {
+                if arguments.len() >= self.options.max_args {
+                    let current_token = self.peek().to_owned();
+                    return Err(Box::new(ParseError::new(
+                        current_token,
+                        codes::TOO_MANY_ARGUMENTS,
+                        Message::new(
+                            "Can't have more than {} arguments.",
+                            [self.options.max_args],
+                        ),
+                    )));
+                }
+
+                match self.expression() {
+                    Ok(argument) => arguments.push(argument),
+                    Err(err) => self.recover_list_element(err)?,
+                }
+
+                // Trailing commas are allowed.
+                if !self.match_then_consume(&[TT::Comma]) || self.check(&TT::RightParen) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self
+            .consume(&TT::RightParen, "Expect ')' after arguments.")?
+            .to_owned();
+
+        let expr = Expr::Call {
+            id: self.node(start),
+            callee: Rc::new(callee),
+            paren,
+            arguments: arguments.into(),
+        };
+
+        Ok(expr)
+    }
+
+    /// Definition:
+    /// ```text
+    // primary → "true" | "false" | "nil" | "this"
+    //         | NUMBER | STRING | IDENTIFIER | "(" expression ")"
+    //         | "super" "." IDENTIFIER ;
+    /// ```
+    fn primary(&mut self) -> ParseResult<Expr> {
+        let token = self.advance().to_owned();
+        let start = token.span;
+        let expr = match token.typ.to_owned() {
+            TT::False => Expr::Literal {
+                id: self.node(start),
+                value: LiteralValue::Boolean(false),
+            },
+            TT::True => Expr::Literal {
+                id: self.node(start),
+                value: LiteralValue::Boolean(true),
+            },
+            TT::Nil => Expr::Literal {
+                id: self.node(start),
+                value: LiteralValue::Nil,
+            },
+            TT::String(text) => Expr::Literal {
+                id: self.node(start),
+                value: LiteralValue::Text(text.into()),
+            },
+            TT::Number(num) => Expr::Literal {
+                id: self.node(start),
+                value: LiteralValue::Number(num),
+            },
+            TT::LeftParen => {
+                let expr = self.expression()?;
+                self.consume(&TT::RightParen, "Expect ')' after expression.")?;
+                Expr::Grouping {
+                    id: self.node(start),
+                    expression: Rc::new(expr),
+                }
+            }
+            TT::This => Expr::This {
+                id: self.node(start),
+                keyword: token,
+            },
+            TT::Identifier(..) => Expr::Variable {
+                id: self.node(start),
+                name: token,
+            },
+            TT::Super => {
+                self.consume(&TT::Dot, "Expect '.' after 'super'.")?;
+                let method = self
+                    .consume_identifier("Expect superclass method name.")?
+                    .to_owned();
+                Expr::Super {
+                    id: self.node(start),
+                    keyword: token,
+                    method,
+                }
+            }
+            unexpected => {
+                return Err(Box::new(ParseError::new(
+                    self.peek().to_owned(),
+                    codes::EXPECTED_EXPRESSION,
+                    Message::new("Expect expression, found {}", [format!("{unexpected:?}")]),
+                )));
+            }
+        };
+        Ok(expr)
+    }
+
+    /// Adds the node spanning from the given start to the end of the previous
+    /// token to the source map.
+    fn node(&mut self, start: Span) -> NodeId {
+        let span = start.to(self.previous().span);
+        self.source_map.add(span)
+    }
+
+    fn consume(&mut self, tt: &TT, error_msg: impl Into<Message>) -> ParseResult<&Token> {
+        if self.check(tt) {
+            Ok(self.advance())
+        } else {
+            Err(Box::new(ParseError::new(
+                self.peek().to_owned(),
+                codes::MISSING_TOKEN,
+                error_msg,
+            )))
+        }
+    }
+
+    /// Consumes the semicolon ending a statement. A missing one is reported at
+    /// the end of the statement instead of the next token, which is usually
+    /// the start of the following statement on another line.
+    fn consume_semicolon(&mut self, error_msg: &'static str) -> ParseResult<&Token> {
+        if self.check(&TT::SemiColon) {
+            Ok(self.advance())
+        } else {
+            let previous = self.previous();
+            let err = ParseError::new(previous.to_owned(), codes::MISSING_TOKEN, error_msg)
+                .with_help(Message::new("add ';' after '{}'", [&previous.lexeme]));
+            Err(Box::new(err))
+        }
+    }
+
+    // Same as consume function but with match because Identifier require
+    // checking for matching but not equality.
+    // Reserved words get their own error, since the generic one doesn't tell
+    // why `var class = 1;` is wrong.
+    fn consume_identifier(&mut self, error_msg: impl Into<Message>) -> ParseResult<&Token> {
+        let peek = self.peek();
+        let ident = match &peek.typ {
+            TT::Identifier(..) => self.advance(),
+            _ if get_keyword(&peek.lexeme).is_some() => {
+                return Err(Box::new(ParseError::new(
+                    peek.to_owned(),
+                    codes::RESERVED_WORD,
+                    Message::new(
+                        "'{}' is a reserved word and can't be used as an identifier.",
+                        [&peek.lexeme],
+                    ),
+                )));
+            }
+            _ => {
+                return Err(Box::new(ParseError::new(
+                    peek.to_owned(),
+                    codes::MISSING_TOKEN,
+                    error_msg,
+                )));
+            }
+        };
+
+        Ok(ident)
+    }
+
+    /// Skips the rest of a malformed parameter or argument until the comma or
+    /// the closing parenthesis after it, keeping the error. This continues
+    /// parsing the list and the function body after it, instead of
+    /// synchronizing on the statements of the body which cascades into
+    /// confusing errors. The error is returned when the list doesn't continue
+    /// on the same statement.
+    fn recover_list_element(&mut self, err: Box<ParseError>) -> ParseResult<()> {
+        let mut depth = 0usize;
+        loop {
+            match self.peek().typ {
+                TT::Comma | TT::RightParen if depth == 0 => break,
+                TT::LeftParen | TT::LeftBracket => depth += 1,
+                TT::RightParen | TT::RightBracket => depth -= 1,
+                TT::LeftBrace | TT::RightBrace | TT::SemiColon | TT::Eof => return Err(err),
+                _ => {}
+            }
+            self.advance();
+        }
+
+        self.errors.push(*err);
+        Ok(())
+    }
+
+    fn synchronize(&mut self) {
+        self.advance();
+        while !self.at_end() {
+            if self.previous().typ == TT::SemiColon {
+                return;
+            }
+            if matches!(
+                self.peek().typ,
+                TT::Export
+                    | TT::Extend
+                    | TT::Class
+                    | TT::Fun
+                    | TT::Var
+                    | TT::For
+                    | TT::If
+                    | TT::While
+                    | TT::Try
+                    | TT::Print
+                    | TT::Return
+                    | TT::Break
+                    | TT::Continue
+            ) {
+                return;
+            }
+
+            self.advance();
+        }
+    }
+}
+
+/// Removes the comments from the tokens, returning the text of the doc comments
+/// keyed by the ID of the token following them.
+/// Lines of the doc comments are joined without their `///` prefixes.
+fn split_docs(tokens: Vec<Token>) -> (Vec<Token>, HashMap<u64, Rc<str>>) {
+    let mut docs = HashMap::new();
+    let mut doc_lines: Vec<&str> = Vec::new();
+    let mut code_tokens = Vec::with_capacity(tokens.len());
+    for token in &tokens {
+        if token.typ != TT::Comment {
+            if !doc_lines.is_empty() {
+                docs.insert(token.id(), doc_lines.join("\n").into());
+                doc_lines.clear();
+            }
+            code_tokens.push(token.to_owned());
+            continue;
+        }
+
+        match token.lexeme.strip_prefix("///") {
+            Some(line) if !line.starts_with('/') => {
+                doc_lines.push(line.strip_prefix(' ').unwrap_or(line));
+            }
+            // Other comments between the doc comment and the declaration
+            // detach it.
+            _ => doc_lines.clear(),
+        }
+    }
+
+    (code_tokens, docs)
+}
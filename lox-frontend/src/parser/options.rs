@@ -0,0 +1,27 @@
+/// Default for the maximum count of parameters of functions and arguments of
+/// calls.
+pub const DEFAULT_MAX_ARGS: usize = 255;
+
+/// Default for the maximum depth of nested statements and expressions.
+/// Syntax trees are walked recursively, so the limit keeps deeply nested code
+/// from overflowing the stack.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 256;
+
+/// Limits of the parsed code.
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    /// Maximum count of parameters of functions and arguments of calls.
+    pub max_args: usize,
+    /// Maximum depth of nested statements, blocks and expressions, where each
+    /// operator of a chain like `1 + 2 + 3` counts as a level.
+    pub max_nesting_depth: usize,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            max_args: DEFAULT_MAX_ARGS,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result},
+};
+
+use crate::locale::{self, Message};
+
+/// Error while scanning code.
+#[derive(Debug)]
+pub struct ScanError {
+    line: usize,
+    code: &'static str,
+    message: Message,
+    /// Column of the error in its line starting from 1.
+    column: usize,
+    /// Explanation of the position of the error, shown after the message.
+    note: Option<&'static str>,
+}
+
+impl ScanError {
+    pub fn new(
+        line: usize,
+        code: &'static str,
+        message: impl Into<Message>,
+        column: usize,
+    ) -> Self {
+        Self {
+            line,
+            code,
+            message: message.into(),
+            column,
+            note: None,
+        }
+    }
+
+    pub fn with_note(mut self, note: &'static str) -> Self {
+        self.note = Some(note);
+        self
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Stable code of the error, like `E0001`.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "[line {}] {} {} {}: [{}] {}",
+            self.line,
+            locale::translate_word("Error"),
+            locale::translate_word("at column"),
+            self.column,
+            self.code(),
+            locale::translate(self.code, &self.message),
+        )?;
+        if let Some(note) = self.note {
+            write!(
+                f,
+                "\n  {}: {}",
+                locale::translate_word("note"),
+                locale::translate(self.code, &note.into())
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ScanError {}
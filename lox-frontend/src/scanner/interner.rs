@@ -0,0 +1,20 @@
+use std::{collections::HashSet, rc::Rc};
+
+/// Deduplicates strings so equal texts share the same allocation, which makes
+/// cloning them cheap and comparing them a pointer comparison in most cases.
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: HashSet<Rc<str>>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, text: &str) -> Rc<str> {
+        if let Some(interned) = self.strings.get(text) {
+            return interned.clone();
+        }
+
+        let interned: Rc<str> = Rc::from(text);
+        self.strings.insert(interned.clone());
+        interned
+    }
+}
@@ -0,0 +1,33 @@
+use super::token::TokenType as TT;
+
+/// Returns the token type of the reserved word if the identifier is one.
+pub fn get_keyword(ident: &str) -> Option<TT> {
+    let tt = match ident {
+        "and" => TT::And,
+        "break" => TT::Break,
+        "class" => TT::Class,
+        "continue" => TT::Continue,
+        "else" => TT::Else,
+        "export" => TT::Export,
+        "extend" => TT::Extend,
+        "false" => TT::False,
+        "finally" => TT::Finally,
+        "for" => TT::For,
+        "fun" => TT::Fun,
+        "if" => TT::If,
+        "in" => TT::In,
+        "nil" => TT::Nil,
+        "or" => TT::Or,
+        "print" => TT::Print,
+        "return" => TT::Return,
+        "super" => TT::Super,
+        "this" => TT::This,
+        "true" => TT::True,
+        "try" => TT::Try,
+        "var" => TT::Var,
+        "while" => TT::While,
+        _ => return None,
+    };
+
+    Some(tt)
+}
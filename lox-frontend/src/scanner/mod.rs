@@ -1,54 +1,109 @@
 mod error;
+mod interner;
 mod keyword;
 mod token;
 
+use std::rc::Rc;
+
 pub use error::ScanError;
-use keyword::get_keywords;
+use interner::Interner;
+pub(crate) use keyword::get_keyword;
 
 pub use token::{Token, TokenType};
 
+use crate::{Span, codes, locale::Message};
+
 use TokenType as TT;
 
 pub struct Scanner {
     source: String,
+    /// Count of the characters in the source, which the indices refer to
+    /// instead of bytes.
+    char_count: usize,
     tokens: Vec<Token>,
     start: usize,
     current: usize,
     line: usize,
+    /// Index of the first character in the current line.
+    line_start: usize,
+    /// Line and column of the first character in the current lexeme.
+    start_position: (usize, usize),
+    interner: Interner,
+    /// Text before each token, which is only kept in trivia mode.
+    trivia: Option<Vec<Rc<str>>>,
+    /// Index of the first character after the previous token.
+    trivia_start: usize,
+    /// ID of the next created token.
+    next_id: u64,
 }
 
 pub struct ScanResults {
     pub tokens: Vec<Token>,
     pub errors: Vec<ScanError>,
+    /// Whitespace and skipped text before each token in trivia mode, which is
+    /// empty otherwise. Joining the trivia and the lexemes of the tokens in
+    /// order gives the source code back.
+    pub trivia: Vec<Rc<str>>,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Self {
         Self {
+            char_count: source.chars().count(),
             source,
             tokens: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_position: (1, 1),
+            interner: Interner::default(),
+            trivia: None,
+            trivia_start: 0,
+            next_id: 0,
         }
     }
 
+    /// Starts the IDs of the tokens from the given one instead of 0, keeping
+    /// them unique across several sources running in the same program, like
+    /// the lines of the REPL.
+    pub fn with_first_id(mut self, id: u64) -> Self {
+        self.next_id = id;
+        self
+    }
+
+    /// Keeps comments as tokens and the whitespace before each token instead
+    /// of discarding them, which is needed by tools rewriting the source code.
+    /// Parsers don't expect comments, so the tokens must be filtered before
+    /// parsing them.
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = Some(Vec::new());
+        self
+    }
+
     pub fn scan_tokens(mut self) -> ScanResults {
         let mut errors = Vec::new();
         while !self.is_at_end() {
             // We are at the beginning of the next lexeme.
             self.start = self.current;
+            self.start_position = (self.line, self.start - self.line_start + 1);
             match self.scan_intern() {
                 Ok(()) => {}
                 Err(err) => errors.push(err),
             };
         }
 
-        self.tokens.push(Token::new(TT::Eof, "", self.line));
+        self.start = self.current;
+        self.start_position = (self.line, self.start - self.line_start + 1);
+        self.push_trivia();
+        let mut eof = self.new_token(TT::Eof, "".into());
+        eof.span = self.span();
+        self.tokens.push(eof);
 
         ScanResults {
             tokens: self.tokens,
             errors,
+            trivia: self.trivia.unwrap_or_default(),
         }
     }
 
@@ -60,12 +115,15 @@ impl Scanner {
             ')' => self.add_token(TT::RightParen),
             '{' => self.add_token(TT::LeftBrace),
             '}' => self.add_token(TT::RightBrace),
+            '[' => self.add_token(TT::LeftBracket),
+            ']' => self.add_token(TT::RightBracket),
             ',' => self.add_token(TT::Comma),
             '.' => self.add_token(TT::Dot),
             '-' => self.add_token(TT::Minus),
             '+' => self.add_token(TT::Plus),
             ';' => self.add_token(TT::SemiColon),
             '*' => self.add_token(TT::Star),
+            '%' => self.add_token(TT::Percent),
 
             // One or two character tokens
             '!' => {
@@ -96,6 +154,7 @@ impl Scanner {
                     self.add_token(TT::Greater);
                 }
             }
+            '~' if self.match_then_advance('/') => self.add_token(TT::TildeSlash),
 
             // Comments
             '/' => {
@@ -104,6 +163,9 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.current += 1;
                     }
+                    if self.trivia.is_some() {
+                        self.add_token(TT::Comment);
+                    }
                 } else {
                     self.add_token(TT::Slash);
                 }
@@ -116,7 +178,7 @@ impl Scanner {
             }
 
             // Empty characters.
-            '\n' => self.line += 1,
+            '\n' => self.new_line(),
             ' ' | '\r' | '\t' => {
                 // Ignore white spaces
             }
@@ -131,7 +193,14 @@ impl Scanner {
                 self.add_token(token);
             }
 
-            _ => return Err(ScanError::new(self.line, "Unexpected Character", None)),
+            ch => {
+                return Err(ScanError::new(
+                    self.line,
+                    codes::UNEXPECTED_CHARACTER,
+                    Message::new("Unexpected character '{}'.", [ch]),
+                    self.start - self.line_start + 1,
+                ));
+            }
         }
 
         Ok(())
@@ -139,7 +208,7 @@ impl Scanner {
 
     #[inline]
     fn is_at_end(&self) -> bool {
-        self.current >= self.source.len()
+        self.current >= self.char_count
     }
 
     /// Reads the next character and advance the current index
@@ -149,6 +218,16 @@ impl Scanner {
         ch
     }
 
+    /// Moves to the next line, which starts at the current index.
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
+    fn previous(&self) -> char {
+        self.source.chars().nth(self.current - 1).unwrap_or('\0')
+    }
+
     fn peek(&mut self) -> char {
         self.source.chars().nth(self.current).unwrap_or('\0')
     }
@@ -157,13 +236,49 @@ impl Scanner {
         self.source.chars().nth(self.current + 1).unwrap_or('\0')
     }
 
+    /// Keeps the text between the previous token and the current one in
+    /// trivia mode.
+    fn push_trivia(&mut self) {
+        if self.trivia.is_none() {
+            return;
+        }
+
+        let text = self.sub_string(self.trivia_start, self.start);
+        let text = self.interner.intern(&text);
+        self.trivia_start = self.current;
+        if let Some(trivia) = &mut self.trivia {
+            trivia.push(text);
+        }
+    }
+
     fn add_token(&mut self, token_t: TT) {
-        let text: String = self.sub_string(self.start, self.current);
+        self.push_trivia();
+        let text = self.sub_string(self.start, self.current);
+        let text = self.interner.intern(&text);
 
-        let token = Token::new(token_t, text, self.line);
+        let mut token = self.new_token(token_t, text);
+        token.span = self.span();
         self.tokens.push(token);
     }
 
+    fn new_token(&mut self, token_t: TT, text: Rc<str>) -> Token {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        Token::new(token_t, text, self.line).with_id(id)
+    }
+
+    /// Span of the current lexeme.
+    fn span(&self) -> Span {
+        let (line, column) = self.start_position;
+        Span {
+            start: self.start,
+            end: self.current,
+            line,
+            column,
+        }
+    }
+
     fn sub_string(&self, start: usize, end: usize) -> String {
         // This is more safe approach then indexing text directly because of
         // multi-bytes characters. Using `unicode_segmentation` crate is another option.
@@ -187,20 +302,39 @@ impl Scanner {
     /// index to it.
     /// This function assumes that the current character is the one after
     /// the opening quote.
+    /// Unterminated strings are reported at their opening quote, and continue
+    /// scanning at the end of the line they start at, so the errors in the
+    /// code after them are reported too.
     fn parse_string(&mut self) -> Result<TT, ScanError> {
+        let (start_line, start_line_start) = (self.line, self.line_start);
+        let (quote_line, quote_column) = self.start_position;
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-            }
             // Advance
             self.current += 1;
+            if self.previous() == '\n' {
+                self.new_line();
+            }
         }
 
         if self.is_at_end() {
-            return Err(ScanError::new(self.line, "Unterminated String", None));
+            let err = ScanError::new(
+                quote_line,
+                codes::UNTERMINATED_STRING,
+                "Unterminated String",
+                quote_column,
+            )
+            .with_note("string started here");
+            self.current = self.start + 1;
+            self.line = start_line;
+            self.line_start = start_line_start;
+            while self.peek() != '\n' && !self.is_at_end() {
+                self.current += 1;
+            }
+            return Err(err);
         }
 
         let text = self.sub_string(self.start + 1, self.current);
+        let text = self.interner.intern(&text);
         // The ending quote
         self.current += 1;
 
@@ -236,10 +370,7 @@ impl Scanner {
         }
 
         let ident = self.sub_string(self.start, self.current);
-        get_keywords()
-            .get(ident.as_str())
-            .map(|tt| tt.to_owned())
-            .unwrap_or_else(|| TT::Identifier(ident))
+        get_keyword(&ident).unwrap_or_else(|| TT::Identifier(self.interner.intern(&ident)))
     }
 }
 
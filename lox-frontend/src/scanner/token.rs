@@ -0,0 +1,117 @@
+use std::fmt::Display;
+use std::rc::Rc;
+
+use crate::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenType {
+    // single character tokens
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Dot,
+    Minus,
+    Plus,
+    SemiColon,
+    Slash,
+    Star,
+    Percent,
+
+    // One or two character tokens
+    Bang,
+    BangEqual,
+    Equal,
+    EqualEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    /// Floored integer division `~/`, since `//` starts comments.
+    TildeSlash,
+
+    /// Comment with its text, which is only emitted in trivia mode.
+    Comment,
+
+    // Literals
+    Identifier(Rc<str>),
+    String(Rc<str>),
+    Number(f64),
+
+    // Keywords
+    And,
+    Break,
+    Class,
+    Continue,
+    Else,
+    Export,
+    Extend,
+    False,
+    Finally,
+    Fun,
+    For,
+    If,
+    In,
+    Nil,
+    Or,
+    Print,
+    Return,
+    Super,
+    This,
+    True,
+    Try,
+    Var,
+    While,
+
+    Eof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    // NOTE: ID is needed to identify tokens in the same line
+    // like `for (var i = 0; i < 20; i = i + 1)`.
+    // It's assigned by the scanner, so scanning the same code gives the same
+    // tokens on any thread.
+    id: u64,
+    pub typ: TokenType,
+    pub lexeme: Rc<str>,
+    pub line: usize,
+    /// Position of the token in the source code, which is empty for the
+    /// tokens not created by the scanner.
+    pub span: Span,
+}
+
+impl Token {
+    /// Creates a token with the ID 0, where the scanner assigns the IDs of
+    /// the tokens it creates.
+    pub fn new(typ: TokenType, lexeme: impl Into<Rc<str>>, line: usize) -> Self {
+        Self {
+            id: 0,
+            typ,
+            lexeme: lexeme.into(),
+            line,
+            span: Span::default(),
+        }
+    }
+
+    /// Identifier of the token which stays the same when cloned. It's unique
+    /// among the tokens scanned by one scanner, and across scanners when each
+    /// one starts after the IDs of the previous ones.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(super) fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.lexeme)
+    }
+}
@@ -0,0 +1,118 @@
+//! Mapping of the syntax tree nodes back to the source code they are parsed from.
+
+/// Identifier of a node in the syntax tree assigned while parsing, which is
+/// kept when the tree is transformed so the rewritten nodes still point at
+/// the code they originate from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct NodeId(u32);
+
+/// Range of characters in the source code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    /// Index of the first character.
+    pub start: usize,
+    /// Index after the last character.
+    pub end: usize,
+    /// Line of the first character.
+    pub line: usize,
+    /// Column of the first character, starting from 1.
+    pub column: usize,
+}
+
+impl Span {
+    /// Span from the start of this span to the end of the given one.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end.max(self.start),
+            ..self
+        }
+    }
+}
+
+/// Spans of the nodes parsed from one source code.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    /// ID of the first node, which is after the IDs of the code parsed before
+    /// when several sources run in the same program.
+    first: u32,
+    spans: Vec<Span>,
+}
+
+impl SourceMap {
+    /// Creates an empty map where the IDs of the added nodes start from the
+    /// given one.
+    pub fn starting_at(first: NodeId) -> Self {
+        Self {
+            first: first.0,
+            spans: Vec::new(),
+        }
+    }
+
+    /// Adds a node with the given span, returning its ID.
+    pub fn add(&mut self, span: Span) -> NodeId {
+        let id = self.next_id();
+        self.spans.push(span);
+        id
+    }
+
+    /// ID of the next added node, where the nodes of the code parsed after
+    /// this one can start.
+    pub fn next_id(&self) -> NodeId {
+        NodeId(self.first + self.spans.len() as u32)
+    }
+
+    pub fn span(&self, id: NodeId) -> Option<Span> {
+        let index = id.0.checked_sub(self.first)?;
+        self.spans.get(index as usize).copied()
+    }
+
+    pub fn line(&self, id: NodeId) -> Option<usize> {
+        self.span(id).map(|span| span.line)
+    }
+
+    /// Text of the node in the source code it's parsed from.
+    pub fn snippet(&self, id: NodeId, source: &str) -> Option<String> {
+        let span = self.span(id)?;
+        let text = source
+            .chars()
+            .skip(span.start)
+            .take(span.end - span.start)
+            .collect();
+
+        Some(text)
+    }
+}
+
+/// Renders the line of the source code a diagnostic is reported at, with
+/// carets under the given count of characters from its column:
+///
+/// ```text
+///  --> line 1, column 9
+///   |
+/// 1 | var a = 1
+///   |         ^
+/// ```
+///
+/// Positions outside of the source, like the ones of the tokens created by
+/// the interpreters, render as nothing.
+pub fn render_snippet(source: &str, line: usize, column: usize, width: usize) -> String {
+    let Some(text) = line
+        .checked_sub(1)
+        .and_then(|index| source.lines().nth(index))
+    else {
+        return String::new();
+    };
+    if column == 0 {
+        return String::new();
+    }
+
+    let number = line.to_string();
+    let gutter = " ".repeat(number.len());
+    // Carets stay on the line even for spans continuing after it.
+    let width = width.clamp(1, (text.chars().count() + 1).saturating_sub(column).max(1));
+    format!(
+        "{gutter}--> line {line}, column {column}\n{gutter} |\n{number} | {text}\n{gutter} | {}{}",
+        " ".repeat(column - 1),
+        "^".repeat(width)
+    )
+}
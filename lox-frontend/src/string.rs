@@ -0,0 +1,73 @@
+use std::{
+    fmt::{Debug, Display},
+    ops::Deref,
+    rc::Rc,
+};
+
+/// Text of a string literal or value, which derefs to `str`.
+///
+/// The text is kept behind one thin pointer like the other values, since the
+/// fat pointer of `Rc<str>` alone would make every value a word bigger.
+/// Literals keep their text the same way, so evaluating them only clones the
+/// pointer.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LoxString(Rc<Rc<str>>);
+
+impl LoxString {
+    /// Shared text of the string, which is cloned without copying it.
+    pub fn to_rc(&self) -> Rc<str> {
+        Rc::clone(&self.0)
+    }
+
+    /// Address identifying the shared text, which stays the same for all
+    /// clones of the string.
+    pub fn as_ptr(&self) -> *const u8 {
+        Rc::as_ptr(&self.0).cast()
+    }
+}
+
+impl Deref for LoxString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for LoxString {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<Rc<str>> for LoxString {
+    fn from(text: Rc<str>) -> Self {
+        Self(Rc::new(text))
+    }
+}
+
+impl From<&str> for LoxString {
+    fn from(text: &str) -> Self {
+        Self::from(Rc::<str>::from(text))
+    }
+}
+
+impl From<String> for LoxString {
+    fn from(text: String) -> Self {
+        Self::from(Rc::<str>::from(text))
+    }
+}
+
+/// Strings are quoted like `str`, since lists and pretty printed values show
+/// the strings inside them this way.
+impl Debug for LoxString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl Display for LoxString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
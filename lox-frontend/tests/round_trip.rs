@@ -0,0 +1,435 @@
+//! Round trips of random syntax trees through the printer and the parser.
+//!
+//! Trees are generated without caring about the precedence of the operators,
+//! so the printer has to add the parentheses which keep the nesting of the
+//! nodes when the code is parsed back.
+
+use std::rc::Rc;
+
+use lox_frontend::{
+    NodeId, Parser, Scanner, SourceMap, Span, Token, TokenType as TT,
+    ast::{Expr, FuncDeclaration, LiteralValue, Stmt, print_program},
+};
+use proptest::{collection::vec, option, prelude::*};
+
+const NAMES: &[&str] = &["a", "b", "count", "item", "x1", "next_value"];
+
+fn id() -> NodeId {
+    SourceMap::default().add(Span::default())
+}
+
+fn token(typ: TT, lexeme: &str) -> Token {
+    Token::new(typ, lexeme, 1)
+}
+
+fn name() -> impl Strategy<Value = Token> {
+    prop::sample::select(NAMES).prop_map(|name| token(TT::Identifier(name.into()), name))
+}
+
+fn operator(operators: &'static [(TT, &'static str)]) -> impl Strategy<Value = Token> {
+    prop::sample::select(operators).prop_map(|(typ, lexeme)| token(typ, lexeme))
+}
+
+fn literal() -> impl Strategy<Value = LiteralValue> {
+    prop_oneof![
+        Just(LiteralValue::Nil),
+        any::<bool>().prop_map(LiteralValue::Boolean),
+        (0..1000u32, 0..4u32).prop_map(|(int, quarters)| {
+            LiteralValue::Number(int as f64 + quarters as f64 / 4.0)
+        }),
+        "[a-z ]{0,8}".prop_map(|text| LiteralValue::Text(text.into())),
+    ]
+}
+
+fn expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        4 => literal().prop_map(|value| Expr::Literal { id: id(), value }),
+        4 => name().prop_map(|name| Expr::Variable { id: id(), name }),
+        1 => Just(Expr::This {
+            id: id(),
+            keyword: token(TT::This, "this"),
+        }),
+        1 => name().prop_map(|method| Expr::Super {
+            id: id(),
+            keyword: token(TT::Super, "super"),
+            method,
+        }),
+    ];
+
+    leaf.prop_recursive(5, 48, 3, |inner| {
+        prop_oneof![
+            (
+                inner.clone(),
+                operator(&[
+                    (TT::Plus, "+"),
+                    (TT::Minus, "-"),
+                    (TT::Star, "*"),
+                    (TT::Slash, "/"),
+                    (TT::TildeSlash, "~/"),
+                    (TT::Percent, "%"),
+                    (TT::EqualEqual, "=="),
+                    (TT::BangEqual, "!="),
+                    (TT::Less, "<"),
+                    (TT::LessEqual, "<="),
+                    (TT::Greater, ">"),
+                    (TT::GreaterEqual, ">="),
+                ]),
+                inner.clone(),
+            )
+                .prop_map(|(left, operator, right)| Expr::Binary {
+                    id: id(),
+                    left: Rc::new(left),
+                    operator,
+                    right: Rc::new(right),
+                }),
+            (
+                inner.clone(),
+                operator(&[(TT::And, "and"), (TT::Or, "or")]),
+                inner.clone(),
+            )
+                .prop_map(|(left, operator, right)| Expr::Logical {
+                    id: id(),
+                    left: Rc::new(left),
+                    operator,
+                    right: Rc::new(right),
+                }),
+            (
+                operator(&[(TT::Bang, "!"), (TT::Minus, "-")]),
+                inner.clone()
+            )
+                .prop_map(|(operator, right)| Expr::Unary {
+                    id: id(),
+                    operator,
+                    right: Rc::new(right),
+                }),
+            (inner.clone(), vec(inner.clone(), 0..3)).prop_map(|(callee, arguments)| {
+                Expr::Call {
+                    id: id(),
+                    callee: Rc::new(callee),
+                    paren: token(TT::RightParen, ")"),
+                    arguments: arguments.into(),
+                }
+            }),
+            (inner.clone(), name()).prop_map(|(object, name)| Expr::Get {
+                id: id(),
+                object: Rc::new(object),
+                name,
+            }),
+            (inner.clone(), inner.clone()).prop_map(|(object, index)| Expr::Index {
+                id: id(),
+                object: Rc::new(object),
+                bracket: token(TT::RightBracket, "]"),
+                index: Rc::new(index),
+            }),
+            (inner.clone(), name(), inner.clone()).prop_map(|(object, name, value)| {
+                Expr::Set {
+                    id: id(),
+                    object: Rc::new(object),
+                    name,
+                    value: Rc::new(value),
+                }
+            }),
+            (inner.clone(), inner.clone(), inner.clone()).prop_map(|(object, index, value)| {
+                Expr::SetIndex {
+                    id: id(),
+                    object: Rc::new(object),
+                    bracket: token(TT::RightBracket, "]"),
+                    index: Rc::new(index),
+                    value: Rc::new(value),
+                }
+            }),
+            (name(), inner.clone()).prop_map(|(name, value)| Expr::Assign {
+                id: id(),
+                name,
+                value: Rc::new(value),
+            }),
+            inner.prop_map(|expression| Expr::Grouping {
+                id: id(),
+                expression: Rc::new(expression),
+            }),
+        ]
+    })
+}
+
+/// Targets of multiple assignments, which are variables, properties and
+/// subscripts.
+fn target() -> impl Strategy<Value = Expr> {
+    prop_oneof![
+        name().prop_map(|name| Expr::Variable { id: id(), name }),
+        (expr(), name()).prop_map(|(object, name)| Expr::Get {
+            id: id(),
+            object: Rc::new(object),
+            name,
+        }),
+        (expr(), expr()).prop_map(|(object, index)| Expr::Index {
+            id: id(),
+            object: Rc::new(object),
+            bracket: token(TT::RightBracket, "]"),
+            index: Rc::new(index),
+        }),
+    ]
+}
+
+fn var() -> impl Strategy<Value = Stmt> {
+    (name(), option::of(expr())).prop_map(|(name, initializer)| Stmt::Var {
+        id: id(),
+        name,
+        initializer,
+    })
+}
+
+fn doc() -> impl Strategy<Value = Option<Rc<str>>> {
+    option::weighted(0.3, vec("[a-z][a-z ]{0,10}", 1..3))
+        .prop_map(|lines| lines.map(|lines| lines.join("\n").into()))
+}
+
+fn function(body: impl Strategy<Value = Stmt>) -> impl Strategy<Value = FuncDeclaration> {
+    (name(), vec(name(), 0..3), vec(body, 0..3), doc()).prop_map(|(name, params, body, doc)| {
+        let mut declaration = FuncDeclaration::new(id(), name, params, body);
+        declaration.doc = doc;
+        declaration
+    })
+}
+
+fn block(stmts: Vec<Stmt>) -> Stmt {
+    Stmt::Block {
+        id: id(),
+        statements: stmts.into(),
+    }
+}
+
+/// Statements which aren't declarations, since only those can be the
+/// branches and the bodies of loops.
+fn statement() -> impl Strategy<Value = Stmt> {
+    let leaf = prop_oneof![
+        4 => expr().prop_map(Stmt::Expression),
+        2 => expr().prop_map(|expr| Stmt::Print { id: id(), expr }),
+        1 => option::of(expr()).prop_map(|value_expr| Stmt::Return {
+            id: id(),
+            keyword: token(TT::Return, "return"),
+            value_expr,
+        }),
+        1 => Just(Stmt::Break {
+            id: id(),
+            keyword: token(TT::Break, "break"),
+        }),
+        1 => Just(Stmt::Continue {
+            id: id(),
+            keyword: token(TT::Continue, "continue"),
+        }),
+        1 => (2..4usize)
+            .prop_flat_map(|count| (vec(target(), count), vec(expr(), count)))
+            .prop_map(|(targets, values)| Stmt::MultiAssign {
+                id: id(),
+                targets: targets.into(),
+                values: values.into(),
+            }),
+    ];
+
+    leaf.prop_recursive(4, 24, 3, |inner| {
+        let declaration = prop_oneof![
+            3 => inner.clone(),
+            1 => var(),
+            1 => function(inner.clone()).prop_map(|declaration| {
+                Stmt::Function(Rc::new(declaration))
+            }),
+        ];
+        prop_oneof![
+            vec(declaration, 0..4).prop_map(block),
+            (expr(), inner.clone(), option::of(inner.clone())).prop_map(
+                |(condition, then_branch, else_branch)| {
+                    // The printer can only keep the `else` away from the
+                    // nested `if` by wrapping it in a block.
+                    let then_branch = if else_branch.is_some() && dangles(&then_branch) {
+                        block(vec![then_branch])
+                    } else {
+                        then_branch
+                    };
+                    Stmt::If {
+                        id: id(),
+                        keyword: token(TT::If, "if"),
+                        condition,
+                        then_branch: Rc::new(then_branch),
+                        else_branch: else_branch.map(Rc::new),
+                    }
+                }
+            ),
+            (expr(), inner.clone()).prop_map(|(condition, body)| Stmt::While {
+                id: id(),
+                keyword: token(TT::While, "while"),
+                condition: Rc::new(condition),
+                body: Rc::new(body),
+            }),
+            (
+                option::of(prop_oneof![var(), expr().prop_map(Stmt::Expression)]),
+                option::of(expr()),
+                option::of(expr()),
+                inner.clone(),
+            )
+                .prop_map(|(initializer, condition, increment, body)| Stmt::For {
+                    id: id(),
+                    keyword: token(TT::For, "for"),
+                    initializer: initializer.map(Box::new),
+                    condition: condition.map(Rc::new),
+                    increment: increment.map(Rc::new),
+                    body: Rc::new(body),
+                }),
+            (name(), expr(), inner.clone()).prop_map(|(name, iterable, body)| Stmt::ForIn {
+                id: id(),
+                name,
+                keyword: token(TT::In, "in"),
+                iterable,
+                body: Rc::new(body),
+            }),
+            (vec(inner.clone(), 0..3), vec(inner, 0..3)).prop_map(|(body, finally)| {
+                Stmt::Try {
+                    id: id(),
+                    keyword: token(TT::Try, "try"),
+                    body: Rc::new(block(body)),
+                    finally: Rc::new(block(finally)),
+                }
+            }),
+        ]
+    })
+}
+
+/// Checks if the statement ends with an `if` statement without `else`.
+fn dangles(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::If { else_branch, .. } => else_branch.as_deref().is_none_or(dangles),
+        Stmt::While { body, .. } | Stmt::For { body, .. } | Stmt::ForIn { body, .. } => {
+            dangles(body)
+        }
+        _ => false,
+    }
+}
+
+fn class() -> impl Strategy<Value = Stmt> {
+    (
+        name(),
+        option::of(name()),
+        vec(function(statement()).prop_map(Rc::new), 0..3),
+        doc(),
+    )
+        .prop_map(|(name, super_class, methods, doc)| Stmt::Class {
+            id: id(),
+            name,
+            super_class,
+            methods,
+            doc,
+        })
+}
+
+fn declaration() -> impl Strategy<Value = Stmt> {
+    let exported = prop_oneof![
+        var(),
+        function(statement()).prop_map(|declaration| Stmt::Function(Rc::new(declaration))),
+        class(),
+    ];
+
+    prop_oneof![
+        6 => statement(),
+        2 => var(),
+        2 => function(statement()).prop_map(|declaration| Stmt::Function(Rc::new(declaration))),
+        1 => class(),
+        1 => (name(), vec(function(statement()).prop_map(Rc::new), 0..3)).prop_map(
+            |(class, methods)| Stmt::Extend {
+                id: id(),
+                keyword: token(TT::Extend, "extend"),
+                class,
+                methods,
+            }
+        ),
+        1 => exported.prop_map(|declaration| Stmt::Export {
+            id: id(),
+            keyword: token(TT::Export, "export"),
+            declaration: Rc::new(declaration),
+        }),
+    ]
+}
+
+fn parse(code: &str) -> Vec<Stmt> {
+    let scan_res = Scanner::new(code.to_owned()).with_trivia().scan_tokens();
+    assert!(scan_res.errors.is_empty(), "{:?}", scan_res.errors);
+    let parse_res = Parser::new(scan_res.tokens).parse();
+    assert!(parse_res.errors.is_empty(), "{:?}", parse_res.errors);
+
+    parse_res.stmts
+}
+
+fn parse_expression(code: &str) -> Expr {
+    let scan_res = Scanner::new(code.to_owned()).scan_tokens();
+    assert!(scan_res.errors.is_empty(), "{:?}", scan_res.errors);
+
+    let (expr, _) = Parser::new(scan_res.tokens).parse_expression().unwrap();
+
+    expr
+}
+
+proptest! {
+    #[test]
+    fn printed_expressions_parse_back(expr in expr()) {
+        let code = expr.to_source();
+        let parsed = parse_expression(&code);
+
+        prop_assert!(parsed.same_as(&expr), "{code}");
+        prop_assert_eq!(parsed.to_source(), code);
+    }
+
+    #[test]
+    fn printed_programs_parse_back(stmts in vec(declaration(), 1..5)) {
+        let code = print_program(&stmts);
+        let parsed = parse(&code);
+
+        prop_assert_eq!(parsed.len(), stmts.len(), "{}", code);
+        for (parsed, stmt) in parsed.iter().zip(&stmts) {
+            prop_assert!(parsed.same_as(stmt), "{code}");
+        }
+        prop_assert_eq!(print_program(&parsed), code);
+    }
+}
+
+#[test]
+fn same_code_parsed_twice_is_the_same() {
+    let code = "fun add(a, b) { return (a + b) * 2; }\nprint add(1, 2);\n";
+
+    let first = parse(code);
+    let second = parse(code);
+    assert_eq!(first, second);
+
+    // Tokens scanned after other code have other IDs, but the same code.
+    let tokens = Scanner::new(code.to_owned())
+        .with_first_id(100)
+        .scan_tokens()
+        .tokens;
+    let shifted = Parser::new(tokens).parse().stmts;
+    assert_ne!(first, shifted);
+    assert!(first.iter().zip(&shifted).all(|(a, b)| a.same_as(b)));
+}
+
+#[test]
+fn printer_keeps_else_with_its_if() {
+    let inner = parse("if (b) print 1;").remove(0);
+    let else_branch = parse("print 2;").remove(0);
+    let stmt = Stmt::If {
+        id: id(),
+        keyword: token(TT::If, "if"),
+        condition: parse_expression("a"),
+        then_branch: Rc::new(inner.clone()),
+        else_branch: Some(Rc::new(else_branch.clone())),
+    };
+
+    let code = print_program(&[stmt]);
+    let parsed = parse(&code).remove(0);
+
+    let Stmt::If {
+        then_branch,
+        else_branch: Some(parsed_else),
+        ..
+    } = parsed
+    else {
+        panic!("Expect if statement with else in {code}");
+    };
+    assert!(then_branch.same_as(&block(vec![inner])), "{code}");
+    assert!(parsed_else.same_as(&else_branch), "{code}");
+}
@@ -0,0 +1,113 @@
+//! Benchmarks of the hot paths of the interpreter, running whole scripts
+//! through the library API with their output discarded.
+//!
+//! Run them with `cargo bench -p tree-walk-rs`, and compare the results before
+//! and after changing the environments, the variable lookups or the values.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use tree_walk_rs::{Interpreter, run};
+
+const FIB: &str = "
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+print fib(20);
+";
+
+const STRING_CONCAT: &str = r#"
+var text = "";
+for (var i = 0; i < 2000; i = i + 1) {
+  text = text + "a";
+}
+print text;
+"#;
+
+const METHOD_DISPATCH: &str = "
+class Counter {
+  init() {
+    this.count = 0;
+  }
+
+  increment() {
+    this.count = this.count + 1;
+  }
+}
+
+class StepCounter < Counter {
+  increment() {
+    super.increment();
+  }
+}
+
+var counter = StepCounter();
+for (var i = 0; i < 10000; i = i + 1) {
+  counter.increment();
+}
+print counter.count;
+";
+
+const CLOSURE_LOOKUP: &str = "
+fun outer() {
+  var value = 1;
+  fun level1() {
+    fun level2() {
+      fun level3() {
+        fun level4() {
+          fun level5() {
+            var sum = 0;
+            for (var i = 0; i < 10000; i = i + 1) {
+              sum = sum + value;
+            }
+            return sum;
+          }
+          return level5();
+        }
+        return level4();
+      }
+      return level3();
+    }
+    return level2();
+  }
+  return level1();
+}
+print outer();
+";
+
+const BIG_LOOP: &str = "
+var sum = 0;
+var i = 0;
+while (i < 100000) {
+  sum = sum + i;
+  i = i + 1;
+}
+print sum;
+";
+
+/// Runs the script on a new interpreter, which includes scanning, parsing and
+/// resolving it.
+fn run_script(code: &str) {
+    let mut interpreter = Interpreter::builder()
+        .output(std::io::sink())
+        .error_output(std::io::sink())
+        .build();
+
+    run(&mut interpreter, code.to_owned()).expect("Benchmark scripts must run without errors");
+}
+
+fn benchmarks(c: &mut Criterion) {
+    let scripts = [
+        ("fib", FIB),
+        ("string_concat", STRING_CONCAT),
+        ("method_dispatch", METHOD_DISPATCH),
+        ("closure_lookup", CLOSURE_LOOKUP),
+        ("big_loop", BIG_LOOP),
+    ];
+
+    for (name, code) in scripts {
+        c.bench_function(name, |b| b.iter(|| run_script(code)));
+    }
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+use lox_frontend::engine::{Diagnostics, Engine, Output};
+
+use crate::{Interpreter, RunError, run};
+
+/// Engine running the programs on the tree-walk interpreter.
+#[derive(Debug, Default)]
+pub struct TreeWalkEngine {
+    interpreter: Interpreter,
+}
+
+impl TreeWalkEngine {
+    pub fn new(interpreter: Interpreter) -> Self {
+        Self { interpreter }
+    }
+}
+
+impl Engine for TreeWalkEngine {
+    fn name(&self) -> &'static str {
+        "tree"
+    }
+
+    fn run(&mut self, source: &str) -> Result<Output, Diagnostics> {
+        self.interpreter.capture_output();
+        let result = run(&mut self.interpreter, source.to_owned());
+        let mut captured = self
+            .interpreter
+            .take_captured()
+            .expect("Output is captured while running");
+
+        match result {
+            // Scan errors are already reported by the interpreter.
+            Ok(_) | Err(RunError::Scan(_) | RunError::Parse(_)) => {}
+            Err(
+                err @ (RunError::Unrecoverable(_) | RunError::LoxError(_) | RunError::Runtime(_)),
+            ) => {
+                captured.errors.push(err.to_string());
+            }
+        }
+
+        if captured.errors.is_empty() {
+            Ok(captured.output)
+        } else {
+            Err(captured)
+        }
+    }
+}
@@ -1,55 +1,291 @@
+use lox_frontend::{
+    Span, Token, codes,
+    locale::{self, Message},
+};
 use thiserror::Error;
 
-use crate::{LoxValue, Token};
-
 pub type LoxResult<T> = std::result::Result<T, LoxError>;
 
 #[derive(Error, Debug)]
 /// General error for rlox interpreter.
 pub enum RunError {
-    #[error("Unrecoverable error in rlox: {0}")]
+    #[error("{}", Message::new(locale::translate_word("Unrecoverable error in rlox: {}"), [.0]))]
     Unrecoverable(#[from] anyhow::Error),
-    #[error("Scanning failed with {0} errors")]
+    #[error("{}", Message::new(locale::translate_word("Scanning failed with {} errors"), [.0]))]
     Scan(usize),
+    #[error("{}", Message::new(locale::translate_word("Parsing failed with {} errors"), [.0]))]
+    Parse(usize),
     #[error("{0}")]
     LoxError(#[from] LoxError),
+    /// Error stopping the execution of the statements.
+    #[error("{0}")]
+    Runtime(LoxError),
 }
 
-use std::fmt::{Display, Formatter, Result};
-pub fn format_err(f: &mut Formatter<'_>, line: usize, position: &str, message: &str) -> Result {
-    write!(f, "[line {line}] Error{position}: {message}",)
+impl RunError {
+    /// Converts the error into the one `rlox` reports, rendering the errors of
+    /// the code the same way they are reported while running it.
+    pub fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            RunError::Unrecoverable(err) => err,
+            // NOTE:
+            // Anyhow errors must be Send, while the current implementation
+            // misusing errors adding `Rc<RefCell<>>` to them.
+            err => anyhow::anyhow!("{err}"),
+        }
+    }
 }
 
+use std::{fmt::Display, rc::Rc, time::Duration};
+
+/// Count of the calls shown at each end of long stack traces.
+const SHOWN_TRACE_FRAMES: usize = 10;
+
 #[derive(Debug)]
 pub enum LoxError {
-    Error { token: Token, message: String },
-    // TODO: I think Error is misused here for return statements.
-    // For now I'll keep it like this to continue with the book but
-    // I should look into other solutions once the first part is done.
-    Return { value: Box<LoxValue> },
+    Error {
+        /// Token the error is reported at, which is boxed to keep the results
+        /// of the interpreter small.
+        token: Box<Token>,
+        /// Stable code of the error, like `E0214`.
+        code: &'static str,
+        message: Message,
+        /// Calls running when the error happened starting from the innermost
+        /// one, which is empty for errors outside of functions.
+        trace: Box<[TraceFrame]>,
+    },
+    /// Execution has been aborted because it exceeded the configured count
+    /// of evaluation steps.
+    StepLimitExceeded { limit: u64 },
+    /// Execution has been aborted because a loop ran more iterations than
+    /// allowed.
+    LoopLimitExceeded { limit: u64 },
+    /// Execution has been aborted because it ran longer than allowed by the
+    /// sandbox policy.
+    TimeLimitExceeded { limit: Duration },
+    /// Execution has been aborted because it created more objects than
+    /// allowed by the sandbox policy.
+    AllocationLimitExceeded { limit: u64 },
+    /// Execution has been aborted because its strings and lists took more
+    /// bytes than allowed by the sandbox policy.
+    MemoryLimitExceeded { limit: u64 },
+    /// Execution has been cancelled via the cancel handle of the interpreter.
+    Cancelled,
+    /// Execution has been suspended by a native waiting for the host, keeping
+    /// its state until it's resumed.
+    Suspended,
+    /// Execution has used up the time budget of its slice, keeping its state
+    /// until it continues.
+    Paused,
 }
 
 impl LoxError {
-    pub fn new(token: Token, message: impl Into<String>) -> Self {
+    pub fn new(token: Token, code: &'static str, message: impl Into<Message>) -> Self {
         Self::Error {
-            token,
+            token: Box::new(token),
+            code,
             message: message.into(),
+            trace: Box::default(),
+        }
+    }
+
+    /// Creates the error for broken invariants of the interpreter, like
+    /// expressions missing their infos from the resolver, so embedders get an
+    /// error instead of a crash.
+    pub fn internal(token: &Token, message: impl Display) -> Self {
+        Self::new(
+            token.to_owned(),
+            codes::INTERNAL_ERROR,
+            Message::new("Internal error: {}", [message]),
+        )
+    }
+
+    /// Stable code of the error, like `E0214`, which is `None` for suspended
+    /// and paused executions since they aren't failures.
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            LoxError::Error { code, .. } => Some(code),
+            LoxError::StepLimitExceeded { .. } => Some(codes::STEP_LIMIT_EXCEEDED),
+            LoxError::LoopLimitExceeded { .. } => Some(codes::LOOP_LIMIT_EXCEEDED),
+            LoxError::TimeLimitExceeded { .. } => Some(codes::TIME_LIMIT_EXCEEDED),
+            LoxError::AllocationLimitExceeded { .. } => Some(codes::ALLOCATION_LIMIT_EXCEEDED),
+            LoxError::MemoryLimitExceeded { .. } => Some(codes::MEMORY_LIMIT_EXCEEDED),
+            LoxError::Cancelled => Some(codes::CANCELLED),
+            LoxError::Suspended | LoxError::Paused => None,
+        }
+    }
+
+    /// Checks if the error must abort the whole execution instead of
+    /// only the current top level statement.
+    pub fn aborts_execution(&self) -> bool {
+        match self {
+            LoxError::Error { .. } => false,
+            LoxError::StepLimitExceeded { .. }
+            | LoxError::LoopLimitExceeded { .. }
+            | LoxError::TimeLimitExceeded { .. }
+            | LoxError::AllocationLimitExceeded { .. }
+            | LoxError::MemoryLimitExceeded { .. }
+            | LoxError::Cancelled
+            | LoxError::Suspended
+            | LoxError::Paused => true,
         }
     }
 }
 
 impl Display for LoxError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(code) = self.code() {
+            write!(f, "[{code}] ")?;
+        }
         match self {
-            LoxError::Error { token, message } => {
-                writeln!(f, "{message}")?;
-                write!(f, "[line {}]", token.line)
+            LoxError::Error {
+                token,
+                code,
+                message,
+                trace,
+            } => {
+                writeln!(f, "{}", locale::translate(code, message))?;
+                write!(f, "[line {}]", token.line)?;
+                // Only both ends of deep recursions are shown.
+                if trace.len() > 2 * SHOWN_TRACE_FRAMES {
+                    let omitted = trace.len() - 2 * SHOWN_TRACE_FRAMES;
+                    for frame in &trace[..SHOWN_TRACE_FRAMES] {
+                        write!(f, "\n{frame}")?;
+                    }
+                    write!(f, "\n... {omitted} more calls")?;
+                    for frame in &trace[trace.len() - SHOWN_TRACE_FRAMES..] {
+                        write!(f, "\n{frame}")?;
+                    }
+                } else {
+                    for frame in trace {
+                        write!(f, "\n{frame}")?;
+                    }
+                }
+
+                Ok(())
             }
-            LoxError::Return { value } => {
-                write!(f, "Return value: {value}")
+            LoxError::StepLimitExceeded { limit } => write_translated(
+                f,
+                codes::STEP_LIMIT_EXCEEDED,
+                Message::new(
+                    "Execution aborted after exceeding the limit of {} steps.",
+                    [limit],
+                ),
+            ),
+            LoxError::LoopLimitExceeded { limit } => write_translated(
+                f,
+                codes::LOOP_LIMIT_EXCEEDED,
+                Message::new(
+                    "Execution aborted after a loop exceeded the limit of {} iterations.",
+                    [limit],
+                ),
+            ),
+            LoxError::TimeLimitExceeded { limit } => write_translated(
+                f,
+                codes::TIME_LIMIT_EXCEEDED,
+                Message::new(
+                    "Execution aborted after exceeding the time limit of {}.",
+                    [format!("{limit:?}")],
+                ),
+            ),
+            LoxError::AllocationLimitExceeded { limit } => write_translated(
+                f,
+                codes::ALLOCATION_LIMIT_EXCEEDED,
+                Message::new(
+                    "Execution aborted after exceeding the limit of {} allocations.",
+                    [limit],
+                ),
+            ),
+            LoxError::MemoryLimitExceeded { limit } => write_translated(
+                f,
+                codes::MEMORY_LIMIT_EXCEEDED,
+                Message::new(
+                    "Execution aborted after exceeding the memory limit of {} bytes.",
+                    [limit],
+                ),
+            ),
+            LoxError::Cancelled => {
+                write_translated(f, codes::CANCELLED, Message::from("Execution cancelled."))
             }
+            LoxError::Suspended => f.write_str(locale::translate_word("Execution suspended.")),
+            LoxError::Paused => f.write_str(locale::translate_word("Execution paused.")),
         }
     }
 }
 
 impl std::error::Error for LoxError {}
+
+/// Writes the message of the error with the given code in the language of
+/// the diagnostics.
+fn write_translated(
+    f: &mut std::fmt::Formatter<'_>,
+    code: &str,
+    message: Message,
+) -> std::fmt::Result {
+    f.write_str(&locale::translate(code, &message))
+}
+
+/// Issue found while resolving the code, which is reported without stopping
+/// the execution.
+#[derive(Debug, Clone)]
+pub struct LoxWarning {
+    /// Span of the code the warning is about.
+    pub span: Span,
+    /// Stable code of the warning, like `E0216`.
+    pub code: &'static str,
+    pub message: Message,
+}
+
+impl LoxWarning {
+    pub fn new(span: Span, code: &'static str, message: impl Into<Message>) -> Self {
+        Self {
+            span,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for LoxWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{}: [{}] {}",
+            locale::translate_word("Warning"),
+            self.code,
+            locale::translate(self.code, &self.message)
+        )?;
+        write!(f, "[line {}]", self.span.line)
+    }
+}
+
+/// Line running in a function or on top level when a runtime error happened.
+#[derive(Debug, Clone)]
+pub struct TraceFrame {
+    /// Name of the function, which is `None` on top level.
+    pub function: Option<Rc<str>>,
+    pub line: usize,
+}
+
+impl Display for TraceFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.function {
+            Some(name) => write!(
+                f,
+                "{}",
+                Message::new(
+                    locale::translate_word("in {} (line {})"),
+                    [name.to_string(), self.line.to_string()]
+                )
+            ),
+            None => write!(
+                f,
+                "{}",
+                Message::new(
+                    locale::translate_word("at top level (line {})"),
+                    [self.line]
+                )
+            ),
+        }
+    }
+}
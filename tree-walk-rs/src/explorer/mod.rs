@@ -0,0 +1,246 @@
+//! Explorer stepping through a script statement by statement, including the
+//! statements of blocks and functions, showing the syntax tree, the chain of
+//! environments the next statement sees and the output printed so far.
+//!
+//! With the `tui` feature the explorer is a full-screen interface showing
+//! them side by side, otherwise it prints them after each step.
+
+mod tree;
+#[cfg(feature = "tui")]
+mod tui;
+
+use std::{path::Path, rc::Rc};
+
+use anyhow::Context;
+use lox_frontend::{Parser, SourceMap, ast::Stmt};
+
+use crate::{Interpreter, LoxValue, RunError, RunState, resolver::Resolver, scan};
+
+use tree::Tree;
+
+/// Variables of an environment with their values.
+type Bindings = Vec<(Rc<str>, LoxValue)>;
+
+/// Steps through the script interactively until it's done or the user quits.
+pub fn explore_file(path: &Path) -> anyhow::Result<()> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Error while reading input file. Path: {}", path.display()))?;
+
+    let exploration = Exploration::start(source)?;
+
+    #[cfg(feature = "tui")]
+    return tui::run(exploration, path);
+
+    #[cfg(not(feature = "tui"))]
+    lines::run(exploration)
+}
+
+/// Script being explored, which is paused before its next statement until
+/// it's done.
+struct Exploration {
+    source: String,
+    source_map: SourceMap,
+    /// Syntax tree of the whole script.
+    #[cfg(feature = "tui")]
+    tree: Tree,
+    interpreter: Interpreter,
+    /// Output and errors printed so far.
+    output: String,
+    /// Count of the executed statements.
+    steps: usize,
+    done: bool,
+}
+
+impl Exploration {
+    /// Scans, parses and resolves the script, pausing before its first
+    /// statement.
+    fn start(source: String) -> anyhow::Result<Self> {
+        let tokens = scan(source.clone(), 0).map_err(|errors| {
+            errors.iter().for_each(|err| eprintln!("{err}"));
+            anyhow::anyhow!("{}", RunError::Scan(errors.len()))
+        })?;
+        let parse_res = Parser::new(tokens).parse();
+        if !parse_res.errors.is_empty() {
+            parse_res.errors.iter().for_each(|err| eprintln!("{err}"));
+            anyhow::bail!("{}", RunError::Parse(parse_res.errors.len()));
+        }
+        let stmts = parse_res.stmts;
+
+        let mut interpreter = Interpreter::new();
+        Resolver::new(&mut interpreter)
+            .with_source_map(&parse_res.source_map)
+            .resolve(&stmts)
+            .map_err(|err| anyhow::anyhow!("{err}"))?;
+
+        let mut exploration = Self {
+            source,
+            source_map: parse_res.source_map,
+            #[cfg(feature = "tui")]
+            tree: Tree::new(&stmts),
+            interpreter,
+            output: String::new(),
+            steps: 0,
+            done: false,
+        };
+        exploration.interpreter.capture_output();
+        let state = exploration.interpreter.step_through(stmts);
+        exploration.finish_step(state);
+
+        Ok(exploration)
+    }
+
+    /// Executes the next statement, pausing before the one after it.
+    fn step(&mut self) {
+        if self.done {
+            return;
+        }
+        self.steps += 1;
+        let state = self.interpreter.continue_run();
+        self.finish_step(state);
+    }
+
+    /// Executes the rest of the script.
+    fn finish(&mut self) {
+        while !self.done {
+            self.step();
+        }
+    }
+
+    fn finish_step(&mut self, state: Result<RunState, RunError>) {
+        let captured = self
+            .interpreter
+            .take_captured()
+            .expect("Output is captured while exploring");
+        self.output.push_str(&captured.output.stdout);
+        for err in captured.errors {
+            self.output.push_str(&err);
+            self.output.push('\n');
+        }
+
+        match state {
+            Ok(RunState::Paused) => {}
+            Ok(RunState::Done(_)) => self.done = true,
+            // Scripts waiting for the host can't continue in the explorer.
+            Ok(RunState::Suspended(request)) => {
+                let message = format!("Script is suspended waiting for {request}.\n");
+                self.output.push_str(&message);
+                self.done = true;
+            }
+            Err(err) => {
+                self.output.push_str(&format!("{err}\n"));
+                self.done = true;
+            }
+        }
+        self.interpreter.capture_output();
+    }
+
+    /// Returns the statement the script continues with.
+    fn next_stmt(&self) -> Option<&Stmt> {
+        self.interpreter.next_stmt()
+    }
+
+    fn next_line(&self) -> Option<usize> {
+        self.source_map.line(self.next_stmt()?.id())
+    }
+
+    /// Source code of the next statement.
+    fn next_snippet(&self) -> Option<String> {
+        self.source_map
+            .snippet(self.next_stmt()?.id(), &self.source)
+    }
+
+    /// Returns the variables of the environments the next statement sees,
+    /// from the innermost scope to the globals, with the title of each one.
+    fn environments(&self) -> Vec<(String, Bindings)> {
+        let chain = self.interpreter.environment_chain();
+        let count = chain.len();
+        chain
+            .into_iter()
+            .enumerate()
+            .map(|(depth, bindings)| {
+                let title = match depth {
+                    0 if count == 1 => String::from("Globals"),
+                    0 => String::from("Current scope"),
+                    depth if depth + 1 == count => String::from("Globals"),
+                    depth => format!("Enclosing scope {depth}"),
+                };
+                (title, bindings)
+            })
+            .collect()
+    }
+}
+
+/// Explorer printing the state of the script after each step.
+#[cfg(not(feature = "tui"))]
+mod lines {
+    use std::io::Write;
+
+    use anyhow::Context;
+
+    use super::{Exploration, Tree};
+
+    const HELP: &str = "<Enter> step, c continue to the end, q quit";
+
+    pub fn run(mut exploration: Exploration) -> anyhow::Result<()> {
+        while !exploration.done {
+            print_state(&exploration);
+            match prompt()?.as_str() {
+                "" => exploration.step(),
+                "c" => exploration.finish(),
+                "q" => return Ok(()),
+                _ => println!("{HELP}"),
+            }
+        }
+
+        print_state(&exploration);
+        println!("== Done ==");
+
+        Ok(())
+    }
+
+    fn prompt() -> anyhow::Result<String> {
+        print!("[{HELP}] > ");
+        std::io::stdout()
+            .flush()
+            .context("Error while flushing stdout")?;
+
+        let mut input = String::new();
+        let read = std::io::stdin()
+            .read_line(&mut input)
+            .context("Error while reading from stdin")?;
+
+        // End of input quits the explorer.
+        if read == 0 {
+            return Ok(String::from("q"));
+        }
+
+        Ok(input.trim().to_owned())
+    }
+
+    fn print_state(exploration: &Exploration) {
+        for (title, bindings) in exploration.environments() {
+            println!("== {title} ==");
+            for (name, value) in bindings {
+                println!("  {name} = {value}");
+            }
+        }
+        println!("== Output ==");
+        for line in exploration.output.lines() {
+            println!("  {line}");
+        }
+
+        let Some(stmt) = exploration.next_stmt() else {
+            return;
+        };
+        let line = exploration.next_line().unwrap_or_default();
+        println!("== Next statement (line {line}) ==");
+        if let Some(source) = exploration.next_snippet() {
+            for line in source.lines() {
+                println!("  | {line}");
+            }
+        }
+        for line in Tree::new(std::slice::from_ref(stmt)).lines {
+            println!("  {line}");
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Syntax trees of the explored statements rendered with one node per line.
+
+use std::collections::HashMap;
+
+use lox_frontend::{
+    NodeId,
+    ast::{Expr, FuncDeclaration, Stmt},
+};
+
+/// Statements rendered as indented tree with one node per line.
+#[derive(Debug, Default)]
+pub struct Tree {
+    pub lines: Vec<String>,
+    /// Index of the line of each statement.
+    stmt_lines: HashMap<NodeId, usize>,
+}
+
+impl Tree {
+    pub fn new(stmts: &[Stmt]) -> Self {
+        let mut tree = Self::default();
+        for stmt in stmts {
+            tree.stmt(stmt, 0);
+        }
+        tree
+    }
+
+    /// Returns the index of the line of the statement.
+    #[cfg(feature = "tui")]
+    pub fn line_of(&self, stmt: &Stmt) -> Option<usize> {
+        self.stmt_lines.get(&stmt.id()).copied()
+    }
+
+    fn node(&mut self, depth: usize, text: impl AsRef<str>) {
+        self.lines
+            .push(format!("{}{}", "  ".repeat(depth), text.as_ref()));
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, depth: usize) {
+        self.stmt_lines.insert(stmt.id(), self.lines.len());
+        match stmt {
+            Stmt::Expression(expr) => {
+                self.node(depth, "Expression");
+                self.expr(expr, depth + 1);
+            }
+            Stmt::Function(declaration) => self.function("Function", declaration, depth),
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.node(depth, "If");
+                self.expr(condition, depth + 1);
+                self.stmt(then_branch, depth + 1);
+                if let Some(else_branch) = else_branch {
+                    self.node(depth, "Else");
+                    self.stmt(else_branch, depth + 1);
+                }
+            }
+            Stmt::Print { expr, .. } => {
+                self.node(depth, "Print");
+                self.expr(expr, depth + 1);
+            }
+            Stmt::Return { value_expr, .. } => {
+                self.node(depth, "Return");
+                if let Some(value) = value_expr {
+                    self.expr(value, depth + 1);
+                }
+            }
+            Stmt::Export { declaration, .. } => {
+                self.node(depth, "Export");
+                self.stmt(declaration, depth + 1);
+            }
+            Stmt::Break { .. } => self.node(depth, "Break"),
+            Stmt::Continue { .. } => self.node(depth, "Continue"),
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                self.node(depth, format!("Var {}", name.lexeme));
+                if let Some(initializer) = initializer {
+                    self.expr(initializer, depth + 1);
+                }
+            }
+            Stmt::MultiAssign {
+                targets, values, ..
+            } => {
+                self.node(depth, "MultiAssign");
+                for target in targets.iter() {
+                    self.expr(target, depth + 1);
+                }
+                self.node(depth, "Values");
+                for value in values.iter() {
+                    self.expr(value, depth + 1);
+                }
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                self.node(depth, "While");
+                self.expr(condition, depth + 1);
+                self.stmt(body, depth + 1);
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.node(depth, "For");
+                if let Some(initializer) = initializer {
+                    self.stmt(initializer, depth + 1);
+                }
+                if let Some(condition) = condition {
+                    self.expr(condition, depth + 1);
+                }
+                if let Some(increment) = increment {
+                    self.expr(increment, depth + 1);
+                }
+                self.stmt(body, depth + 1);
+            }
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+                ..
+            } => {
+                self.node(depth, format!("ForIn {}", name.lexeme));
+                self.expr(iterable, depth + 1);
+                self.stmt(body, depth + 1);
+            }
+            Stmt::Block { statements, .. } => {
+                self.node(depth, "Block");
+                for stmt in statements.iter() {
+                    self.stmt(stmt, depth + 1);
+                }
+            }
+            Stmt::Try { body, finally, .. } => {
+                self.node(depth, "Try");
+                self.stmt(body, depth + 1);
+                self.node(depth, "Finally");
+                self.stmt(finally, depth + 1);
+            }
+            Stmt::Class {
+                name,
+                super_class,
+                methods,
+                ..
+            } => {
+                match super_class {
+                    Some(super_class) => self.node(
+                        depth,
+                        format!("Class {} < {}", name.lexeme, super_class.lexeme),
+                    ),
+                    None => self.node(depth, format!("Class {}", name.lexeme)),
+                }
+                for method in methods {
+                    self.function("Method", method, depth + 1);
+                }
+            }
+            Stmt::Extend { class, methods, .. } => {
+                self.node(depth, format!("Extend {}", class.lexeme));
+                for method in methods {
+                    self.function("Method", method, depth + 1);
+                }
+            }
+        }
+    }
+
+    fn function(&mut self, kind: &str, declaration: &FuncDeclaration, depth: usize) {
+        let params: Vec<_> = declaration
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_ref())
+            .collect();
+        self.node(
+            depth,
+            format!("{kind} {}({})", declaration.name.lexeme, params.join(", ")),
+        );
+        for stmt in declaration.body.iter() {
+            self.stmt(stmt, depth + 1);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, depth: usize) {
+        match expr {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                self.node(depth, format!("Binary {}", operator.lexeme));
+                self.expr(left, depth + 1);
+                self.expr(right, depth + 1);
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                self.node(depth, format!("Logical {}", operator.lexeme));
+                self.expr(left, depth + 1);
+                self.expr(right, depth + 1);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.node(depth, "Call");
+                self.expr(callee, depth + 1);
+                for arg in arguments.iter() {
+                    self.expr(arg, depth + 1);
+                }
+            }
+            Expr::Get { object, name, .. } => {
+                self.node(depth, format!("Get .{}", name.lexeme));
+                self.expr(object, depth + 1);
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => {
+                self.node(depth, format!("Set .{}", name.lexeme));
+                self.expr(object, depth + 1);
+                self.expr(value, depth + 1);
+            }
+            Expr::Index { object, index, .. } => {
+                self.node(depth, "Index");
+                self.expr(object, depth + 1);
+                self.expr(index, depth + 1);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.node(depth, "SetIndex");
+                self.expr(object, depth + 1);
+                self.expr(index, depth + 1);
+                self.expr(value, depth + 1);
+            }
+            Expr::Grouping { expression, .. } => {
+                self.node(depth, "Grouping");
+                self.expr(expression, depth + 1);
+            }
+            Expr::Unary {
+                operator, right, ..
+            } => {
+                self.node(depth, format!("Unary {}", operator.lexeme));
+                self.expr(right, depth + 1);
+            }
+            Expr::Assign { name, value, .. } => {
+                self.node(depth, format!("Assign {}", name.lexeme));
+                self.expr(value, depth + 1);
+            }
+            // Leaves are shown with their source code.
+            Expr::Literal { .. }
+            | Expr::Super { .. }
+            | Expr::This { .. }
+            | Expr::Variable { .. } => {
+                self.node(depth, expr.to_source());
+            }
+        }
+    }
+}
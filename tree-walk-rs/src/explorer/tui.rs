@@ -0,0 +1,129 @@
+//! Full-screen explorer showing the syntax tree of the script, the chain of
+//! environments and the output side by side.
+
+use std::path::Path;
+
+use anyhow::Context;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListState, Paragraph},
+};
+
+use super::Exploration;
+
+const HELP: &str = " n/<Enter> step  c continue to the end  q quit ";
+
+/// Runs the explorer in the terminal until the user quits, restoring the
+/// terminal afterwards.
+pub fn run(mut exploration: Exploration, path: &Path) -> anyhow::Result<()> {
+    let title = format!(" {} ", path.display());
+    let mut terminal = ratatui::try_init().context("Error while setting up the terminal")?;
+    let result = event_loop(&mut terminal, &mut exploration, &title);
+    ratatui::restore();
+
+    result
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    exploration: &mut Exploration,
+    title: &str,
+) -> anyhow::Result<()> {
+    loop {
+        terminal
+            .draw(|frame| draw(frame, exploration, title))
+            .context("Error while drawing the explorer")?;
+
+        let Event::Key(key) = event::read().context("Error while reading terminal events")? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('n' | ' ') | KeyCode::Enter | KeyCode::Down => exploration.step(),
+            KeyCode::Char('c') => exploration.finish(),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, exploration: &Exploration, title: &str) {
+    let [main, status] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+    let [tree, environments, output] = Layout::horizontal([
+        Constraint::Percentage(40),
+        Constraint::Percentage(30),
+        Constraint::Percentage(30),
+    ])
+    .areas(main);
+
+    draw_tree(frame, exploration, title, tree);
+    draw_environments(frame, exploration, environments);
+    draw_output(frame, exploration, output);
+
+    let position = match exploration.next_line() {
+        Some(line) => format!(
+            " Step {}, next statement on line {line} ",
+            exploration.steps
+        ),
+        None => format!(" Done after {} steps ", exploration.steps),
+    };
+    let status_line = Line::from(vec![Span::from(position).reversed(), Span::from(HELP)]);
+    frame.render_widget(status_line, status);
+}
+
+/// Draws the syntax tree of the script, selecting the next statement so the
+/// list scrolls to it. The first line of its source code is shown below.
+fn draw_tree(frame: &mut Frame, exploration: &Exploration, title: &str, area: Rect) {
+    let selected = exploration
+        .next_stmt()
+        .and_then(|stmt| exploration.tree.line_of(stmt));
+    let mut state = ListState::default().with_selected(selected);
+    let snippet = exploration.next_snippet().unwrap_or_default();
+    let next = match snippet.lines().next() {
+        Some(line) => format!(" {} ", line.trim()),
+        None => String::new(),
+    };
+    let tree = List::new(exploration.tree.lines.iter().map(String::as_str))
+        .block(Block::bordered().title(title).title_bottom(next))
+        .highlight_style(Style::new().reversed())
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(tree, area, &mut state);
+}
+
+/// Draws the variables of the environments the next statement sees, from the
+/// innermost scope to the globals.
+fn draw_environments(frame: &mut Frame, exploration: &Exploration, area: Rect) {
+    let mut lines = Vec::new();
+    for (title, bindings) in exploration.environments() {
+        lines.push(Line::from(title).bold());
+        if bindings.is_empty() {
+            lines.push(Line::from("  (empty)").dim());
+        }
+        for (name, value) in bindings {
+            lines.push(Line::from(format!("  {name} = {value}")));
+        }
+    }
+
+    let environments = Paragraph::new(lines).block(Block::bordered().title(" Environments "));
+    frame.render_widget(environments, area);
+}
+
+/// Draws the output printed so far, scrolled to its last lines.
+fn draw_output(frame: &mut Frame, exploration: &Exploration, area: Rect) {
+    let height = usize::from(area.height.saturating_sub(2));
+    let hidden = exploration.output.lines().count().saturating_sub(height);
+    let scroll = u16::try_from(hidden).unwrap_or(u16::MAX);
+
+    let output = Paragraph::new(exploration.output.as_str())
+        .scroll((scroll, 0))
+        .block(Block::bordered().title(" Output "));
+    frame.render_widget(output, area);
+}
@@ -0,0 +1,206 @@
+use std::io::Write;
+
+use super::{
+    Capability, ExecutionObserver, Interpreter, InterpreterOptions, NativeClass, NumberFormat,
+    PrettyOptions, SandboxPolicy,
+    natives::{self, NativeFn, NativeFunction},
+    observer::Observer,
+};
+
+/// Destination of the text written by the interpreter.
+pub(super) struct Sink(pub Box<dyn Write>);
+
+impl std::fmt::Debug for Sink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Sink")
+    }
+}
+
+/// Configures all parts of the interpreter before creating it.
+pub struct InterpreterBuilder {
+    options: InterpreterOptions,
+    output: Box<dyn Write>,
+    error_output: Box<dyn Write>,
+    natives: Vec<NativeFunction>,
+    classes: Vec<NativeClass>,
+    observer: Option<Box<dyn ExecutionObserver>>,
+}
+
+impl Default for InterpreterBuilder {
+    fn default() -> Self {
+        Self {
+            options: InterpreterOptions::default(),
+            output: Box::new(std::io::stdout()),
+            error_output: Box::new(std::io::stderr()),
+            natives: natives::default_natives(),
+            classes: natives::default_classes(),
+            observer: None,
+        }
+    }
+}
+
+impl InterpreterBuilder {
+    /// Replaces all options with the given ones.
+    pub fn options(mut self, options: InterpreterOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets where the values printed by the programs are written, which is
+    /// stdout by default.
+    pub fn output(mut self, output: impl Write + 'static) -> Self {
+        self.output = Box::new(output);
+        self
+    }
+
+    /// Sets where the errors which don't stop the execution are written,
+    /// which is stderr by default.
+    pub fn error_output(mut self, error_output: impl Write + 'static) -> Self {
+        self.error_output = Box::new(error_output);
+        self
+    }
+
+    /// Defines a native function as global, replacing any native with the
+    /// same name.
+    pub fn native(self, name: &'static str, arity: usize, function: NativeFn) -> Self {
+        self.native_with_capabilities(name, arity, function, &[])
+    }
+
+    /// Defines a native function needing the given capabilities, which can't
+    /// be called when the sandbox policy disables any of them.
+    pub fn native_with_capabilities(
+        mut self,
+        name: &'static str,
+        arity: usize,
+        function: NativeFn,
+        capabilities: &'static [Capability],
+    ) -> Self {
+        self.natives.retain(|native| native.name != name);
+        self.natives
+            .push(NativeFunction::new(name, arity, function, capabilities));
+        self
+    }
+
+    /// Defines a class implemented in Rust as global, replacing any native
+    /// class with the same name.
+    pub fn class(mut self, class: NativeClass) -> Self {
+        self.classes
+            .retain(|defined| defined.name() != class.name());
+        self.classes.push(class);
+        self
+    }
+
+    /// Removes the natives defined by default, like `clock`, `same` and the
+    /// `StringBuilder` class.
+    pub fn without_default_natives(mut self) -> Self {
+        self.natives.clear();
+        self.classes.clear();
+        self
+    }
+
+    /// Registers the observer notified while executing the programs.
+    pub fn observer(mut self, observer: impl ExecutionObserver + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    pub fn max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.options.max_call_depth = max_call_depth;
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.options.max_steps = Some(max_steps);
+        self
+    }
+
+    pub fn max_args(mut self, max_args: usize) -> Self {
+        self.options.parser.max_args = max_args;
+        self
+    }
+
+    pub fn max_nesting_depth(mut self, max_nesting_depth: usize) -> Self {
+        self.options.parser.max_nesting_depth = max_nesting_depth;
+        self
+    }
+
+    pub fn max_string_length(mut self, max_string_length: usize) -> Self {
+        self.options.max_string_length = Some(max_string_length);
+        self
+    }
+
+    pub fn max_loop_iterations(mut self, max_loop_iterations: u64) -> Self {
+        self.options.max_loop_iterations = Some(max_loop_iterations);
+        self
+    }
+
+    pub fn sandbox(mut self, sandbox: SandboxPolicy) -> Self {
+        self.options.sandbox = sandbox;
+        self
+    }
+
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.options.deterministic = deterministic;
+        self
+    }
+
+    pub fn fold_constants(mut self, fold_constants: bool) -> Self {
+        self.options.fold_constants = fold_constants;
+        self
+    }
+
+    pub fn ieee_division(mut self, ieee_division: bool) -> Self {
+        self.options.ieee_division = ieee_division;
+        self
+    }
+
+    pub fn strict_bool(mut self, strict_bool: bool) -> Self {
+        self.options.strict_bool = strict_bool;
+        self
+    }
+
+    /// Enables all the checks of the strict mode, which can be configured
+    /// individually with their own methods afterwards.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options = self.options.strict(strict);
+        self
+    }
+
+    pub fn deny_undefined_globals(mut self, deny_undefined_globals: bool) -> Self {
+        self.options.deny_undefined_globals = deny_undefined_globals;
+        self
+    }
+
+    pub fn deny_unused_variables(mut self, deny_unused_variables: bool) -> Self {
+        self.options.deny_unused_variables = deny_unused_variables;
+        self
+    }
+
+    pub fn deny_shadowing(mut self, deny_shadowing: bool) -> Self {
+        self.options.deny_shadowing = deny_shadowing;
+        self
+    }
+
+    pub fn pretty_print(mut self, pretty_print: PrettyOptions) -> Self {
+        self.options.pretty_print = Some(pretty_print);
+        self
+    }
+
+    pub fn number_format(mut self, number_format: NumberFormat) -> Self {
+        self.options.number_format = Some(number_format);
+        self
+    }
+
+    pub fn stop_on_error(mut self, stop_on_error: bool) -> Self {
+        self.options.stop_on_error = stop_on_error;
+        self
+    }
+
+    pub fn build(self) -> Interpreter {
+        let mut interpreter = Interpreter::create(self.options, self.natives, self.classes);
+        interpreter.output = Sink(self.output);
+        interpreter.error_output = Sink(self.error_output);
+        interpreter.observer = self.observer.map(Observer);
+        interpreter
+    }
+}
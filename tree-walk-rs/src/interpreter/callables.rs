@@ -1,68 +1,79 @@
-use std::{cell::RefCell, fmt::Display, rc::Rc, time::SystemTime};
+use std::{cell::RefCell, fmt::Display, rc::Rc};
 
-use crate::{
-    Token, TokenType,
-    errors::{LoxError, LoxResult},
+use super::{
+    class::LoxClass,
+    function::LoxFunction,
+    heap::{Trace, Tracer},
+    native_class::BoundNativeMethod,
+    natives::NativeFunction,
 };
 
-use super::{Interpreter, LoxValue, class::LoxClass, function::LoxFunction};
-
 pub type LoxClassRef = Rc<RefCell<LoxClass>>;
 
-pub const CLOCK_NAME: &str = "clock";
-
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum LoxCallable {
-    Clock,
-    LoxFunction(LoxFunction),
+    Native(Rc<NativeFunction>),
+    /// Functions are shared behind a reference so values holding them stay
+    /// cheap to clone.
+    LoxFunction(Rc<LoxFunction>),
     Class(LoxClassRef),
+    /// Method of a native class bound to its instance.
+    NativeMethod(Rc<BoundNativeMethod>),
 }
 
 impl LoxCallable {
-    pub fn call(
-        &self,
-        interprerter: &mut Interpreter,
-        arguments: &[LoxValue],
-    ) -> LoxResult<LoxValue> {
-        match self {
-            LoxCallable::Clock => Self::clock(),
-            LoxCallable::LoxFunction(func) => func.call(interprerter, arguments),
-            LoxCallable::Class(lox_class) => lox_class.borrow().call(interprerter, arguments),
-        }
-    }
-
     pub fn arity(&self) -> usize {
         match self {
-            LoxCallable::Clock => 0,
+            LoxCallable::Native(native) => native.arity,
             LoxCallable::LoxFunction(func) => func.arity(),
             LoxCallable::Class(lox_class) => lox_class.borrow().arity(),
+            LoxCallable::NativeMethod(bound) => bound.method.arity,
         }
     }
+}
 
-    fn clock() -> LoxResult<LoxValue> {
-        SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map(|t| t.as_secs())
-            .map(|t| LoxValue::Number(t as f64))
-            .map_err(|err| {
-                LoxError::new(
-                    Token::new(TokenType::Fun, CLOCK_NAME, 0),
-                    format!("Error while calling system time: {err}"),
-                )
-            })
+/// Callables are equal when they are the same object.
+impl PartialEq for LoxCallable {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LoxCallable::Native(a), LoxCallable::Native(b)) => Rc::ptr_eq(a, b),
+            (LoxCallable::LoxFunction(a), LoxCallable::LoxFunction(b)) => Rc::ptr_eq(a, b),
+            (LoxCallable::Class(a), LoxCallable::Class(b)) => Rc::ptr_eq(a, b),
+            (LoxCallable::NativeMethod(a), LoxCallable::NativeMethod(b)) => {
+                Rc::ptr_eq(&a.method, &b.method) && Rc::ptr_eq(&a.this, &b.this)
+            }
+            _ => false,
+        }
     }
 }
 
 impl Display for LoxCallable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            LoxCallable::Clock => f.write_str("<native fn>"),
+            LoxCallable::Native(_) => f.write_str("<native fn>"),
             LoxCallable::LoxFunction(func) => {
                 write!(f, "{func}")
             }
             LoxCallable::Class(lox_class) => {
                 write!(f, "{}", lox_class.borrow())
             }
+            LoxCallable::NativeMethod(bound) => write!(f, "{bound}"),
+        }
+    }
+}
+
+impl Trace for LoxCallable {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            LoxCallable::Native(_) => {}
+            LoxCallable::LoxFunction(func) => tracer.visit_rc(func, |tracer| func.trace(tracer)),
+            LoxCallable::Class(lox_class) => {
+                tracer.visit_rc(lox_class, |tracer| lox_class.borrow().trace(tracer));
+            }
+            LoxCallable::NativeMethod(bound) => tracer.visit_rc(bound, |tracer| {
+                let this = &bound.this;
+                tracer.visit_rc(this, |tracer| this.borrow().trace(tracer));
+            }),
         }
     }
 }
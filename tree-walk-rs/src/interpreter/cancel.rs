@@ -0,0 +1,30 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+/// Handle to cancel the running execution of an interpreter, which can be
+/// triggered from any thread.
+///
+/// The interpreter checks the handle between statements and aborts the execution
+/// once it's cancelled. Each run resets the handle when it starts, so it can be
+/// used for the next runs.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    /// Requests the interpreter to stop the current execution.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+}
@@ -1,30 +1,69 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, rc::Rc};
 
-use crate::{errors::LoxError, interpreter::instance::LoxInstance};
+use super::{
+    callables::LoxClassRef,
+    function::LoxFunction,
+    heap::{Trace, Tracer},
+    native_class::{NativeClass, NativeMethod},
+};
 
-use super::{Interpreter, LoxValue, callables::LoxClassRef, function::LoxFunction};
+/// ID of the native classes, which never matches the token of a declaration
+/// since they have no private methods.
+const NATIVE_CLASS_ID: u64 = u64::MAX;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct LoxClass {
-    name: String,
-    methods: HashMap<String, LoxFunction>,
+    /// ID of the token naming the class in its declaration.
+    id: u64,
+    name: Rc<str>,
+    methods: HashMap<Rc<str>, LoxFunction>,
     super_class: Option<Box<LoxClassRef>>,
+    /// Definition of the class in Rust for native classes.
+    native: Option<Rc<NativeClass>>,
+    /// Text of the doc comment before the declaration.
+    pub doc: Option<Rc<str>>,
 }
 
 impl LoxClass {
     pub fn new(
-        name: String,
-        methods: HashMap<String, LoxFunction>,
+        id: u64,
+        name: Rc<str>,
+        methods: HashMap<Rc<str>, LoxFunction>,
         super_class: Option<LoxClassRef>,
+        doc: Option<Rc<str>>,
     ) -> Self {
         let super_class = super_class.map(Box::new);
         Self {
+            id,
             name,
             methods,
             super_class,
+            native: None,
+            doc,
         }
     }
 
+    /// Creates the class with the methods defined in Rust.
+    pub fn native(native: Rc<NativeClass>) -> Self {
+        Self {
+            id: NATIVE_CLASS_ID,
+            name: native.name().clone(),
+            methods: HashMap::new(),
+            super_class: None,
+            native: Some(native),
+            doc: None,
+        }
+    }
+
+    pub fn name(&self) -> &Rc<str> {
+        &self.name
+    }
+
+    /// ID of the token naming the class in its declaration.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     pub fn find_method(&self, name: &str) -> Option<LoxFunction> {
         if let Some(method) = self.methods.get(name) {
             return Some(method.to_owned());
@@ -39,23 +78,112 @@ impl LoxClass {
         None
     }
 
-    pub fn call(
-        &self,
-        interprerter: &mut Interpreter,
-        arguments: &[LoxValue],
-    ) -> Result<LoxValue, LoxError> {
-        let instance = LoxInstance::new(self.to_owned());
-        if let Some(initializer) = self.find_method("init") {
-            initializer
-                .bind(instance.clone())
-                .call(interprerter, arguments)?;
-        };
-        Ok(LoxValue::Instance(instance))
+    /// Adds the method to the class, replacing any method with the same name.
+    pub fn add_method(&mut self, name: Rc<str>, method: LoxFunction) {
+        self.methods.insert(name, method);
+    }
+
+    /// Finds the method defined in Rust by the native class at the root of
+    /// the hierarchy of this class. Methods declared in Lox take precedence
+    /// over them.
+    pub fn find_native_method(&self, name: &str) -> Option<Rc<NativeMethod>> {
+        self.native_class()?.find_method(name)
+    }
+
+    /// Finds the private method declared by the class with the given ID in the
+    /// hierarchy of this class. Private methods aren't inherited, so only the
+    /// methods of the declaring class itself are considered.
+    pub fn find_private_method(&self, class_id: u64, name: &str) -> Option<LoxFunction> {
+        if self.id == class_id {
+            return self.methods.get(name).cloned();
+        }
+
+        self.super_class
+            .as_ref()
+            .and_then(|super_c| super_c.borrow().find_private_method(class_id, name))
+    }
+
+    /// Name of the class with the given ID in the hierarchy of this class.
+    pub fn name_of(&self, class_id: u64) -> Option<Rc<str>> {
+        if self.id == class_id {
+            return Some(self.name.clone());
+        }
+
+        self.super_class
+            .as_ref()
+            .and_then(|super_c| super_c.borrow().name_of(class_id))
+    }
+
+    /// Name of the nearest class in the hierarchy of this class declaring a
+    /// method with the given name.
+    pub fn declaring_class(&self, name: &str) -> Option<Rc<str>> {
+        if self.methods.contains_key(name) {
+            return Some(self.name.clone());
+        }
+
+        self.super_class
+            .as_ref()
+            .and_then(|super_c| super_c.borrow().declaring_class(name))
+    }
+
+    pub fn super_class(&self) -> Option<LoxClassRef> {
+        self.super_class.as_deref().cloned()
+    }
+
+    /// Methods of the class together with the ones it inherits, which don't
+    /// include the private methods of the superclasses, sorted by their names.
+    pub fn methods(&self) -> Vec<(Rc<str>, LoxFunction)> {
+        let mut methods: Vec<_> = self
+            .methods
+            .iter()
+            .map(|(name, method)| (name.clone(), method.clone()))
+            .collect();
+        if let Some(super_class) = &self.super_class {
+            for (name, method) in super_class.borrow().methods() {
+                if !name.starts_with('_') && !self.methods.contains_key(&name) {
+                    methods.push((name, method));
+                }
+            }
+        }
+        methods.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        methods
+    }
+
+    /// Names of the methods declared in Lox like [`LoxClass::methods()`]
+    /// together with the methods of the native class at the root of the
+    /// hierarchy, sorted by their names.
+    pub fn method_names(&self) -> Vec<Rc<str>> {
+        let mut names: Vec<_> = self.methods().into_iter().map(|(name, _)| name).collect();
+        if let Some(native) = self.native_class() {
+            names.extend(native.method_names().cloned());
+        }
+        names.sort();
+        names.dedup();
+
+        names
+    }
+
+    /// Checks if instances of the class have a method with the given name,
+    /// either declared in Lox or by the native class it inherits from.
+    pub fn has_method(&self, name: &str) -> bool {
+        self.find_method(name).is_some() || self.find_native_method(name).is_some()
+    }
+
+    /// Native class at the root of the hierarchy of this class.
+    pub fn native_class(&self) -> Option<Rc<NativeClass>> {
+        match (&self.native, &self.super_class) {
+            (Some(native), _) => Some(native.clone()),
+            (None, Some(super_c)) => super_c.borrow().native_class(),
+            (None, None) => None,
+        }
     }
 
     pub fn arity(&self) -> usize {
         if let Some(initializer) = self.find_method("init") {
             initializer.arity()
+        } else if let Some(initializer) = self.find_native_method("init") {
+            initializer.arity
         } else {
             0
         }
@@ -67,3 +195,14 @@ impl Display for LoxClass {
         write!(f, "{}", self.name)
     }
 }
+
+impl Trace for LoxClass {
+    fn trace(&self, tracer: &mut Tracer) {
+        for method in self.methods.values() {
+            method.trace(tracer);
+        }
+        if let Some(super_class) = &self.super_class {
+            tracer.visit_rc(super_class, |tracer| super_class.borrow().trace(tracer));
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Coroutines running a function which can stop in the middle with `yield()`,
+//! continuing from there on the next `resume()`.
+//!
+//! The interpreter keeps its calls as tasks on its own stack, so suspending
+//! a coroutine moves the tasks pushed since it was resumed into the coroutine,
+//! together with the values and the environment of its running code. Resuming
+//! it pushes them back and continues running them like any other task.
+
+use std::{cell::RefCell, rc::Rc};
+
+use lox_frontend::codes;
+
+use crate::errors::LoxError;
+
+use super::{
+    EnvironmentRef, Interpreter, LoxValue,
+    heap::{Trace, Tracer},
+    instance::{LoxInstance, LoxInstanceRef},
+    native_class::NativeClass,
+    task::Task,
+};
+
+/// State of the coroutine instances.
+struct Coroutine(RefCell<Status>);
+
+enum Status {
+    /// Created with the function, which is called on the first resume.
+    Created(LoxValue),
+    /// Stopped by `yield()`, with the yielded value which wasn't consumed yet
+    /// when the coroutine is iterated.
+    Suspended {
+        frames: Frames,
+        peeked: Option<LoxValue>,
+    },
+    Running,
+    /// Returned from its function or failed with an error.
+    Done,
+}
+
+/// Running code of a suspended coroutine.
+struct Frames {
+    tasks: Vec<Task>,
+    values: Vec<LoxValue>,
+    environment: EnvironmentRef,
+    call_depth: usize,
+}
+
+impl Trace for Coroutine {
+    fn trace(&self, tracer: &mut Tracer) {
+        match &*self.0.borrow() {
+            Status::Created(function) => function.trace(tracer),
+            Status::Suspended { frames, peeked } => {
+                for task in &frames.tasks {
+                    task.trace(tracer);
+                }
+                for value in frames.values.iter().chain(peeked) {
+                    value.trace(tracer);
+                }
+                tracer.mark_env(&frames.environment);
+            }
+            Status::Running | Status::Done => {}
+        }
+    }
+}
+
+/// Class of the coroutines, which is iterable over the yielded values.
+pub fn coroutine_class() -> NativeClass {
+    NativeClass::new("Coroutine")
+        .method("isDone", 0, |interpreter, this, _| {
+            let coroutine = coroutine_state(interpreter, this)?;
+            let done = matches!(*coroutine.0.borrow(), Status::Done);
+            Ok(LoxValue::Boolean(done))
+        })
+        .method("iterator", 0, |_, this, _| {
+            Ok(LoxValue::Instance(this.clone()))
+        })
+        .method("hasNext", 0, |interpreter, this, _| {
+            let peeked = peek(interpreter, this)?;
+            Ok(LoxValue::Boolean(peeked))
+        })
+        .method("next", 0, |interpreter, this, _| {
+            if !peek(interpreter, this)? {
+                return Err(interpreter.fail_native(
+                    codes::INVALID_COROUTINE_USE,
+                    "Coroutine has no values left.",
+                ));
+            }
+            let coroutine = coroutine_state(interpreter, this)?;
+            let mut status = coroutine.0.borrow_mut();
+            let Status::Suspended { peeked, .. } = &mut *status else {
+                unreachable!("Coroutines with peeked values are suspended");
+            };
+            Ok(peeked.take().expect("Value is peeked above"))
+        })
+}
+
+fn coroutine_state(
+    interpreter: &mut Interpreter,
+    this: &LoxInstanceRef,
+) -> Result<Rc<Coroutine>, String> {
+    this.borrow().state_rc::<Coroutine>().ok_or_else(|| {
+        interpreter.fail_native(
+            codes::INVALID_COROUTINE_USE,
+            "Coroutine isn't created with 'coroutine()'.",
+        )
+    })
+}
+
+/// Creates a coroutine calling the function on the first resume, which can
+/// take the value of that resume as its parameter.
+pub fn coroutine(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let function = &args[0];
+    match function {
+        LoxValue::Callable(callable) if callable.arity() <= 1 => {}
+        LoxValue::Callable(callable) => {
+            let message = format!(
+                "Coroutine functions can take at most one parameter, but {} takes {}.",
+                function.describe(),
+                callable.arity()
+            );
+            return Err(interpreter.fail_native(codes::INVALID_COROUTINE_USE, message));
+        }
+        _ => {
+            let message = format!(
+                "Coroutines can only run functions and classes, but got {}.",
+                function.describe()
+            );
+            return Err(interpreter.fail_native(codes::INVALID_COROUTINE_USE, message));
+        }
+    }
+
+    interpreter.allocations += 1;
+    let instance = LoxInstance::new(interpreter.coroutine_class.clone());
+    let status = Status::Created(function.clone());
+    instance
+        .borrow_mut()
+        .set_traced_state(Coroutine(RefCell::new(status)));
+    Ok(LoxValue::Instance(instance))
+}
+
+/// Continues the coroutine until it yields or returns, where the value is
+/// returned from the `yield()` which suspended it.
+pub fn resume(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let coroutine = expect_coroutine(&args[0])
+        .map_err(|message| interpreter.fail_native(codes::INVALID_COROUTINE_USE, message))?;
+    match resume_coroutine(interpreter, &coroutine, args[1].clone())? {
+        Resumed::Yielded(value) | Resumed::Returned(value) => Ok(value),
+    }
+}
+
+/// Suspends the running coroutine, returning the value from the `resume()`
+/// which continued it.
+pub fn yield_value(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    if interpreter.running_coroutines == 0 {
+        return Err(interpreter.fail_native(
+            codes::INVALID_COROUTINE_USE,
+            "Can only yield inside coroutines.",
+        ));
+    }
+
+    interpreter.yielded = Some(args[0].clone());
+    Ok(LoxValue::Nil)
+}
+
+fn expect_coroutine(value: &LoxValue) -> Result<Rc<Coroutine>, String> {
+    let coroutine = match value {
+        LoxValue::Instance(instance) => instance.borrow().state_rc::<Coroutine>(),
+        _ => None,
+    };
+    coroutine.ok_or_else(|| format!("Can only resume coroutines, but got {}.", value.describe()))
+}
+
+enum Resumed {
+    Yielded(LoxValue),
+    Returned(LoxValue),
+}
+
+/// Resumes the coroutine of the iterated instance until it yields the next
+/// value, returning if it has one.
+fn peek(interpreter: &mut Interpreter, this: &LoxInstanceRef) -> Result<bool, String> {
+    let coroutine = coroutine_state(interpreter, this)?;
+    match &*coroutine.0.borrow() {
+        Status::Suspended {
+            peeked: Some(_), ..
+        } => return Ok(true),
+        Status::Done => return Ok(false),
+        _ => {}
+    }
+
+    match resume_coroutine(interpreter, &coroutine, LoxValue::Nil)? {
+        Resumed::Yielded(value) => {
+            if let Status::Suspended { peeked, .. } = &mut *coroutine.0.borrow_mut() {
+                *peeked = Some(value);
+            }
+            Ok(true)
+        }
+        // Values returned at the end aren't part of the iteration.
+        Resumed::Returned(_) => Ok(false),
+    }
+}
+
+fn resume_coroutine(
+    interpreter: &mut Interpreter,
+    coroutine: &Coroutine,
+    value: LoxValue,
+) -> Result<Resumed, String> {
+    let Some(paren) = interpreter.native_call.clone() else {
+        return Err(interpreter.fail_native(
+            codes::INVALID_COROUTINE_USE,
+            "Coroutines can only be resumed from natives.",
+        ));
+    };
+
+    let status = std::mem::replace(&mut *coroutine.0.borrow_mut(), Status::Running);
+    let checkpoint = interpreter.checkpoint();
+    let started = match status {
+        Status::Created(function) => {
+            let has_param =
+                matches!(&function, LoxValue::Callable(callable) if callable.arity() == 1);
+            let args = if has_param { vec![value] } else { Vec::new() };
+            // Native functions run right away inside the coroutine.
+            interpreter.running_coroutines += 1;
+            let called = interpreter.call_value(function, &paren, args, false);
+            interpreter.running_coroutines -= 1;
+            called
+        }
+        Status::Suspended {
+            frames,
+            peeked: None,
+        } => {
+            let mut tasks = frames.tasks;
+            // Stack traces show the calls as made by this resume.
+            let outermost = tasks.iter_mut().find_map(|task| match task {
+                Task::FinishCall { call_line, .. } => Some(call_line),
+                _ => None,
+            });
+            if let Some(call_line) = outermost {
+                *call_line = paren.line;
+            }
+            interpreter.tasks.extend(tasks);
+            interpreter.values.extend(frames.values);
+            interpreter.environment = frames.environment;
+            interpreter.call_depth += frames.call_depth;
+            // Value returned from the call to `yield()`.
+            interpreter.values.push(value);
+            Ok(())
+        }
+        Status::Suspended {
+            frames,
+            peeked: Some(peeked),
+        } => {
+            // The value peeked by iterating is returned before continuing.
+            *coroutine.0.borrow_mut() = Status::Suspended {
+                frames,
+                peeked: None,
+            };
+            return Ok(Resumed::Yielded(peeked));
+        }
+        Status::Running => {
+            return Err(interpreter.fail_native(
+                codes::INVALID_COROUTINE_USE,
+                "Can't resume a running coroutine.",
+            ));
+        }
+        Status::Done => {
+            *coroutine.0.borrow_mut() = Status::Done;
+            return Err(interpreter.fail_native(
+                codes::INVALID_COROUTINE_USE,
+                "Can't resume a finished coroutine.",
+            ));
+        }
+    };
+
+    interpreter.running_coroutines += 1;
+    let result = started.and_then(|()| interpreter.run_nested(checkpoint.tasks_len));
+    interpreter.running_coroutines -= 1;
+
+    let err = match result {
+        Ok(()) => {
+            *coroutine.0.borrow_mut() = Status::Done;
+            return Ok(Resumed::Returned(interpreter.pop_value()));
+        }
+        Err(LoxError::Suspended) if interpreter.yielded.is_some() => {
+            let frames = Frames {
+                tasks: interpreter.tasks.split_off(checkpoint.tasks_len),
+                values: interpreter.values.split_off(checkpoint.values_len),
+                environment: std::mem::replace(
+                    &mut interpreter.environment,
+                    checkpoint.environment.clone(),
+                ),
+                call_depth: interpreter.call_depth - checkpoint.call_depth,
+            };
+            interpreter.call_depth = checkpoint.call_depth;
+            *coroutine.0.borrow_mut() = Status::Suspended {
+                frames,
+                peeked: None,
+            };
+            let value = interpreter.yielded.take().expect("Yielded value is set");
+            return Ok(Resumed::Yielded(value));
+        }
+        // Natives waiting for the host can't continue the coroutine later.
+        Err(LoxError::Suspended) => {
+            interpreter.suspended = None;
+            LoxError::new(
+                paren,
+                codes::INVALID_SUSPENSION,
+                "Can't suspend scripts inside coroutines.",
+            )
+        }
+        Err(err) => err,
+    };
+
+    // Failed coroutines can't be resumed again.
+    *coroutine.0.borrow_mut() = Status::Done;
+    Err(interpreter.fail_native_call(err, &checkpoint))
+}
@@ -1,13 +1,28 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::{LoxValue, Token, errors::LoxError};
+use lox_frontend::{Token, codes, locale::Message};
+
+use crate::{
+    LoxValue,
+    errors::{LoxError, LoxResult},
+};
+
+use super::heap::{Trace, Tracer};
 
 pub type EnvironmentRef = Rc<RefCell<Environment>>;
 
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default)]
 pub struct Environment {
     pub enclosing: Option<EnvironmentRef>,
-    values: HashMap<String, LoxValue>,
+    /// Values of global variables, which are looked up by their names since the
+    /// resolver doesn't track them.
+    values: HashMap<Rc<str>, LoxValue>,
+    /// Values of local variables, indexed by the slots assigned in the resolver.
+    /// Values are pushed in the same order the resolver declared them.
+    slots: Vec<LoxValue>,
+    /// Names of the local variables in the slots, which are only kept while
+    /// stepping through scripts so their environments can be shown.
+    slot_names: Vec<Rc<str>>,
 }
 
 impl Environment {
@@ -20,10 +35,68 @@ impl Environment {
         Rc::new(RefCell::new(env))
     }
 
-    pub fn define(&mut self, key: String, value: LoxValue) {
+    /// Defines a global variable with the given name.
+    pub fn define(&mut self, key: Rc<str>, value: LoxValue) {
         self.values.insert(key, value);
     }
 
+    /// Removes the global variable with the given name returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<LoxValue> {
+        self.values.remove(key)
+    }
+
+    /// Returns the global variables sorted by their names.
+    pub fn global_values(&self) -> Vec<(Rc<str>, LoxValue)> {
+        let mut values: Vec<_> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        values
+    }
+
+    /// Looks up the global variable with the given name in this environment
+    /// and the enclosing ones.
+    pub fn get_global(&self, name: &str) -> Option<LoxValue> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.enclosing.as_ref()?.borrow().get_global(name),
+        }
+    }
+
+    /// Defines a local variable in the next slot.
+    pub fn push(&mut self, value: LoxValue) {
+        self.slots.push(value);
+    }
+
+    /// Defines a local variable in the next slot keeping its name.
+    pub fn push_named(&mut self, name: Rc<str>, value: LoxValue) {
+        // Slots pushed without names keep their numbers.
+        while self.slot_names.len() < self.slots.len() {
+            let slot = self.slot_names.len();
+            self.slot_names.push(format!("#{slot}").into());
+        }
+        self.slot_names.push(name);
+        self.slots.push(value);
+    }
+
+    /// Returns the variables of this environment without the enclosing ones,
+    /// where globals come first sorted by their names followed by the locals
+    /// in their slots. Locals without names are named by their slots, like
+    /// `#0`.
+    pub fn bindings(&self) -> Vec<(Rc<str>, LoxValue)> {
+        let mut bindings = self.global_values();
+        bindings.extend(self.slots.iter().enumerate().map(|(slot, value)| {
+            let name = match self.slot_names.get(slot) {
+                Some(name) => name.clone(),
+                None => format!("#{slot}").into(),
+            };
+            (name, value.clone())
+        }));
+        bindings
+    }
+
     pub fn get(&self, name: &Token) -> Result<LoxValue, LoxError> {
         if let Some(val) = self.values.get(&name.lexeme) {
             return Ok(val.to_owned());
@@ -35,32 +108,46 @@ impl Environment {
 
         Err(LoxError::new(
             name.to_owned(),
-            format!("Undefined variable '{}'.", name.lexeme),
+            codes::UNDEFINED_VARIABLE,
+            Message::new("Undefined variable '{}'.", [&name.lexeme]),
         ))
     }
 
-    pub fn get_at(current: EnvironmentRef, depth: usize, name: &str) -> LoxValue {
-        Self::find_ancestor(current, depth)
-            .borrow()
-            .values
-            .get(name)
-            .expect(" Value must be avaible since becuase it defined in locals")
-            .to_owned()
+    pub fn get_at(
+        current: &EnvironmentRef,
+        depth: usize,
+        slot: usize,
+        name: &Token,
+    ) -> LoxResult<LoxValue> {
+        Self::with_ancestor(current, depth, name, |env| match env.slots.get(slot) {
+            Some(value) => Ok(value.to_owned()),
+            None => Err(missing_slot_error(name, depth, slot)),
+        })
     }
 
-    fn find_ancestor(current: EnvironmentRef, distance: usize) -> EnvironmentRef {
-        let mut env = current;
-        for _ in 0..distance {
-            let temp = env
-                .borrow()
-                .enclosing
-                .as_ref()
-                .expect("It should contain a value")
-                .clone();
-            env = temp
+    /// Runs the given function on the ancestor with the given distance.
+    /// Ancestors are borrowed while walking the chain instead of cloning their
+    /// references, keeping variable access free from reference counting.
+    /// Chains shorter than the distance are reported at the given name.
+    fn with_ancestor<T>(
+        current: &EnvironmentRef,
+        distance: usize,
+        name: &Token,
+        func: impl FnOnce(&mut Environment) -> LoxResult<T>,
+    ) -> LoxResult<T> {
+        if distance == 0 {
+            return func(&mut current.borrow_mut());
         }
 
-        env
+        let env = current.borrow();
+        let Some(enclosing) = env.enclosing.as_ref() else {
+            let message = format!(
+                "Variable '{}' is missing an enclosing environment.",
+                name.lexeme
+            );
+            return Err(LoxError::internal(name, message));
+        };
+        Self::with_ancestor(enclosing, distance - 1, name, func)
     }
 
     pub fn assign(&mut self, name: &Token, value: LoxValue) -> Result<(), LoxError> {
@@ -75,14 +162,56 @@ impl Environment {
 
         Err(LoxError::new(
             name.to_owned(),
-            format!("Undefined variable '{}'.", name.lexeme),
+            codes::UNDEFINED_VARIABLE,
+            Message::new("Undefined variable '{}'.", [&name.lexeme]),
         ))
     }
 
-    pub fn assign_at(current: EnvironmentRef, distance: usize, name: &Token, value: LoxValue) {
-        Self::find_ancestor(current, distance)
-            .borrow_mut()
-            .values
-            .insert(name.lexeme.to_owned(), value);
+    /// Drops all the values and the enclosing environment, breaking any
+    /// reference cycles this environment is part of.
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.slots.clear();
+        self.slot_names.clear();
+        self.enclosing = None;
+    }
+
+    pub fn assign_at(
+        current: &EnvironmentRef,
+        distance: usize,
+        slot: usize,
+        name: &Token,
+        value: LoxValue,
+    ) -> LoxResult<()> {
+        Self::with_ancestor(current, distance, name, |env| {
+            match env.slots.get_mut(slot) {
+                Some(old_val) => {
+                    *old_val = value;
+                    Ok(())
+                }
+                None => Err(missing_slot_error(name, distance, slot)),
+            }
+        })
+    }
+}
+
+/// Error for local variables missing their slots, which happens when the
+/// statements run with the infos of the resolver for other statements.
+fn missing_slot_error(name: &Token, depth: usize, slot: usize) -> LoxError {
+    let message = format!(
+        "Variable '{}' has no slot {slot} at depth {depth}.",
+        name.lexeme
+    );
+    LoxError::internal(name, message)
+}
+
+impl Trace for Environment {
+    fn trace(&self, tracer: &mut Tracer) {
+        if let Some(enclosing) = &self.enclosing {
+            tracer.mark_env(enclosing);
+        }
+        for value in self.values.values().chain(self.slots.iter()) {
+            value.trace(tracer);
+        }
     }
 }
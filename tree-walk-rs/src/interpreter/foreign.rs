@@ -0,0 +1,43 @@
+use std::{any::Any, fmt::Display, rc::Rc};
+
+/// Opaque handle to data of the host, like files or game entities.
+/// Natives create and read them, while Lox code can only pass them around.
+#[derive(Debug, Clone)]
+pub struct Foreign(Rc<ForeignData>);
+
+/// Data is kept behind one thin pointer, which keeps the values small.
+#[derive(Debug)]
+struct ForeignData {
+    /// Name of the type shown to Lox code, like `File`.
+    type_name: &'static str,
+    value: Box<dyn Any>,
+}
+
+impl Foreign {
+    pub fn new(type_name: &'static str, value: impl Any) -> Self {
+        Self(Rc::new(ForeignData {
+            type_name,
+            value: Box::new(value),
+        }))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.0.type_name
+    }
+
+    /// Value of the host if it has the given type.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.value.downcast_ref()
+    }
+
+    /// Checks if both handles share the same value.
+    pub fn ptr_eq(&self, other: &Foreign) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Display for Foreign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<foreign {}>", self.0.type_name)
+    }
+}
@@ -1,23 +1,29 @@
-use std::fmt::Display;
+use std::{fmt::Display, rc::Rc};
 
-use crate::{ast::FuncDeclaration, errors::LoxError};
+use lox_frontend::ast::FuncDeclaration;
+
+use crate::errors::LoxResult;
 
 use super::{
     Interpreter, LoxValue,
     environment::{Environment, EnvironmentRef},
+    heap::{Trace, Tracer},
     instance::LoxInstanceRef,
 };
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct LoxFunction {
-    pub declaration: FuncDeclaration,
+    pub declaration: Rc<FuncDeclaration>,
     pub closure: EnvironmentRef,
     is_initializer: bool,
+    /// ID of the class the function is a method of, or is declared in, whose
+    /// private members it can access.
+    class_id: Option<u64>,
 }
 
 impl LoxFunction {
     pub fn new(
-        declaration: FuncDeclaration,
+        declaration: Rc<FuncDeclaration>,
         closure: EnvironmentRef,
         is_initializer: bool,
     ) -> Self {
@@ -25,52 +31,40 @@ impl LoxFunction {
             declaration,
             closure,
             is_initializer,
+            class_id: None,
         }
     }
 
+    /// Makes the function part of the class with the given ID.
+    pub fn in_class(mut self, class_id: Option<u64>) -> Self {
+        self.class_id = class_id;
+        self
+    }
+
+    pub fn class_id(&self) -> Option<u64> {
+        self.class_id
+    }
+
     pub fn arity(&self) -> usize {
         self.declaration.params.len()
     }
 
-    pub fn call(
-        &self,
-        interprerter: &mut Interpreter,
-        arguments: &[LoxValue],
-    ) -> Result<LoxValue, LoxError> {
-        let environment = Environment::with_enclosing(self.closure.clone());
-        let mut env_borrow = environment.borrow_mut();
-        for (arg, param) in arguments.iter().zip(self.declaration.params.iter()) {
-            env_borrow.define(param.lexeme.to_owned(), arg.to_owned());
-        }
-        drop(env_borrow);
-
-        match interprerter.execute_block(&self.declaration.body, environment) {
-            Ok(()) => {
-                let value = if self.is_initializer {
-                    Environment::get_at(self.closure.clone(), 0, "this")
-                } else {
-                    LoxValue::Nil
-                };
-                Ok(value)
-            }
-            Err(LoxError::Return { value }) => {
-                let value = if self.is_initializer {
-                    Environment::get_at(self.closure.clone(), 0, "this")
-                } else {
-                    *value
-                };
-                Ok(value)
-            }
-            Err(err) => Err(err),
+    /// Initializers always return `this` while other functions return the given value.
+    pub fn return_value(&self, value: LoxValue) -> LoxResult<LoxValue> {
+        if self.is_initializer {
+            Environment::get_at(&self.closure, 0, 0, &self.declaration.name)
+        } else {
+            Ok(value)
         }
     }
 
-    pub fn bind(&self, instance: LoxInstanceRef) -> LoxFunction {
-        let env = Environment::with_enclosing(self.closure.clone());
-        env.borrow_mut()
-            .define("this".into(), LoxValue::Instance(instance));
+    pub fn bind(&self, instance: LoxInstanceRef, interpreter: &mut Interpreter) -> LoxFunction {
+        let env = interpreter.new_environment(self.closure.clone());
+        // `this` is the only value in the bound environment.
+        let this = LoxValue::Instance(instance);
+        interpreter.push_local(&mut env.borrow_mut(), "this", this);
 
-        LoxFunction::new(self.declaration.clone(), env, self.is_initializer)
+        LoxFunction::new(self.declaration.clone(), env, self.is_initializer).in_class(self.class_id)
     }
 }
 
@@ -79,3 +73,11 @@ impl Display for LoxFunction {
         write!(f, "<fn {}>", self.declaration.name.lexeme)
     }
 }
+
+impl Trace for LoxFunction {
+    fn trace(&self, tracer: &mut Tracer) {
+        // Functions shared behind an `Rc` are visited by their holders.
+        tracer.stats.functions += 1;
+        tracer.mark_env(&self.closure);
+    }
+}
@@ -0,0 +1,260 @@
+//! The `HashMap` class mapping keys to values.
+//!
+//! Keys are found by their hashes like in any hash map. Instances with both
+//! `hash()` and `equals(other)` methods are compared by their values through
+//! these methods, while other instances are only equal to themselves. The
+//! methods are Lox code, so hashing calls back into the interpreter instead of
+//! implementing `Hash` and `Eq` for the values.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    rc::Rc,
+};
+
+use super::{
+    Interpreter, LoxValue,
+    heap::{Trace, Tracer},
+    instance::{LoxInstance, LoxInstanceRef},
+    native_class::NativeClass,
+};
+
+const UNINITIALIZED: &str = "HashMap isn't initialized.";
+
+/// Entries of a map in their insertion order.
+#[derive(Default)]
+struct Entries {
+    entries: Vec<Entry>,
+    /// Indices of the entries with the same hash of their keys.
+    buckets: HashMap<u64, Vec<usize>>,
+    /// Count of the changes to the entries, detecting the ones made while
+    /// calling the methods of the keys.
+    revision: u64,
+}
+
+struct Entry {
+    hash: u64,
+    key: LoxValue,
+    value: LoxValue,
+}
+
+/// State of the map instances, which hold Lox values unlike other native
+/// classes.
+#[derive(Default)]
+struct MapState(RefCell<Entries>);
+
+impl Trace for MapState {
+    fn trace(&self, tracer: &mut Tracer) {
+        for entry in &self.0.borrow().entries {
+            entry.key.trace(tracer);
+            entry.value.trace(tracer);
+        }
+    }
+}
+
+pub fn hash_map_class() -> NativeClass {
+    NativeClass::new("HashMap")
+        .method("init", 0, |_, this, _| {
+            this.borrow_mut().set_traced_state(MapState::default());
+            Ok(LoxValue::Nil)
+        })
+        .method("get", 1, |interpreter, this, args| {
+            let state = map_state(this)?;
+            let found = find(interpreter, &state, &args[0])?;
+            let entries = state.0.borrow();
+            Ok(found
+                .index
+                .map_or(LoxValue::Nil, |index| entries.entries[index].value.clone()))
+        })
+        .method("set", 2, |interpreter, this, args| {
+            let state = map_state(this)?;
+            let found = find(interpreter, &state, &args[0])?;
+            let mut entries = state.0.borrow_mut();
+            entries.revision += 1;
+            match found.index {
+                Some(index) => entries.entries[index].value = args[1].clone(),
+                None => {
+                    let index = entries.entries.len();
+                    entries.entries.push(Entry {
+                        hash: found.hash,
+                        key: args[0].clone(),
+                        value: args[1].clone(),
+                    });
+                    entries.buckets.entry(found.hash).or_default().push(index);
+                }
+            }
+            Ok(LoxValue::Nil)
+        })
+        .method("has", 1, |interpreter, this, args| {
+            let state = map_state(this)?;
+            let found = find(interpreter, &state, &args[0])?;
+            Ok(LoxValue::Boolean(found.index.is_some()))
+        })
+        .method("remove", 1, |interpreter, this, args| {
+            let state = map_state(this)?;
+            let Some(index) = find(interpreter, &state, &args[0])?.index else {
+                return Ok(LoxValue::Boolean(false));
+            };
+
+            let mut entries = state.0.borrow_mut();
+            entries.revision += 1;
+            entries.entries.remove(index);
+            // Entries after the removed one have moved.
+            let Entries {
+                entries, buckets, ..
+            } = &mut *entries;
+            buckets.clear();
+            for (index, entry) in entries.iter().enumerate() {
+                buckets.entry(entry.hash).or_default().push(index);
+            }
+            Ok(LoxValue::Boolean(true))
+        })
+        .method("size", 0, |_, this, _| {
+            let state = map_state(this)?;
+            let len = state.0.borrow().entries.len();
+            Ok(LoxValue::Number(len as f64))
+        })
+        .method("keys", 0, |interpreter, this, _| {
+            let state = map_state(this)?;
+            let keys = state
+                .0
+                .borrow()
+                .entries
+                .iter()
+                .map(|entry| entry.key.clone())
+                .collect::<Vec<_>>();
+            interpreter.allocations += 1;
+            interpreter.count_elements(keys.len());
+            Ok(LoxValue::list(keys))
+        })
+        .method("values", 0, |interpreter, this, _| {
+            let state = map_state(this)?;
+            let values = state
+                .0
+                .borrow()
+                .entries
+                .iter()
+                .map(|entry| entry.value.clone())
+                .collect::<Vec<_>>();
+            interpreter.allocations += 1;
+            interpreter.count_elements(values.len());
+            Ok(LoxValue::list(values))
+        })
+        // For-in loops iterate over the keys.
+        .method("iterator", 0, |interpreter, this, _| {
+            let state = map_state(this)?;
+            let keys = state
+                .0
+                .borrow()
+                .entries
+                .iter()
+                .map(|entry| entry.key.clone())
+                .collect::<Vec<_>>();
+            interpreter.allocations += 1;
+            interpreter.count_elements(keys.len());
+            Ok(LoxValue::list(keys))
+        })
+}
+
+fn map_state(this: &LoxInstanceRef) -> Result<Rc<MapState>, String> {
+    this.borrow()
+        .state_rc::<MapState>()
+        .ok_or_else(|| String::from(UNINITIALIZED))
+}
+
+/// Hash of the key with the index of its entry if the map has it.
+struct Found {
+    hash: u64,
+    index: Option<usize>,
+}
+
+/// Finds the entry of the key. The entries aren't borrowed while calling the
+/// methods of the keys, which fails if these methods change the map.
+fn find(interpreter: &mut Interpreter, state: &MapState, key: &LoxValue) -> Result<Found, String> {
+    let hash = hash_key(interpreter, key)?;
+    let (revision, candidates): (_, Vec<_>) = {
+        let entries = state.0.borrow();
+        let candidates = entries.buckets.get(&hash).into_iter().flatten();
+        let candidates = candidates.map(|&index| (index, entries.entries[index].key.clone()));
+        (entries.revision, candidates.collect())
+    };
+
+    let mut found = None;
+    for (index, candidate) in candidates {
+        if keys_equal(interpreter, key, &candidate)? {
+            found = Some(index);
+            break;
+        }
+    }
+    if state.0.borrow().revision != revision {
+        return Err(String::from(
+            "HashMap was changed while comparing its keys.",
+        ));
+    }
+
+    Ok(Found { hash, index: found })
+}
+
+/// Returns the `hash()` and `equals()` methods of instances compared by their
+/// values, which must have both methods.
+fn value_methods(
+    interpreter: &mut Interpreter,
+    instance: &LoxInstanceRef,
+) -> Option<(LoxValue, LoxValue)> {
+    let hash = LoxInstance::method(instance, "hash", interpreter)?;
+    let equals = LoxInstance::method(instance, "equals", interpreter)?;
+    Some((hash, equals))
+}
+
+fn hash_key(interpreter: &mut Interpreter, key: &LoxValue) -> Result<u64, String> {
+    let mut hasher = DefaultHasher::new();
+    match key {
+        LoxValue::Nil => 0.hash(&mut hasher),
+        LoxValue::Boolean(value) => value.hash(&mut hasher),
+        LoxValue::Number(num) => hash_number(*num, &mut hasher),
+        LoxValue::String(text) => text.hash(&mut hasher),
+        LoxValue::Instance(instance) => match value_methods(interpreter, instance) {
+            Some((hash, _)) => {
+                let hash = interpreter.call(&hash, Vec::new())?;
+                let Some(num) = hash.as_number() else {
+                    return Err(format!(
+                        "Method 'hash' must return a number, but got {}.",
+                        hash.describe()
+                    ));
+                };
+                hash_number(num, &mut hasher);
+            }
+            None => Rc::as_ptr(instance).hash(&mut hasher),
+        },
+        LoxValue::List(list) => Rc::as_ptr(list).hash(&mut hasher),
+        // Functions, classes and foreign values are only compared by identity
+        // in their bucket.
+        LoxValue::Callable(_) | LoxValue::Foreign(_) => key.type_name().hash(&mut hasher),
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Hashes numbers which are equal to the same value, like `0` and `-0`.
+fn hash_number(num: f64, hasher: &mut DefaultHasher) {
+    let num = if num == 0.0 { 0.0 } else { num };
+    num.to_bits().hash(hasher);
+}
+
+/// Checks if the key equals the key of an entry with the same hash. Instances
+/// compared by their values are only equal to other instances.
+fn keys_equal(
+    interpreter: &mut Interpreter,
+    key: &LoxValue,
+    other: &LoxValue,
+) -> Result<bool, String> {
+    if let (LoxValue::Instance(instance), LoxValue::Instance(_)) = (key, other)
+        && let Some((_, equals)) = value_methods(interpreter, instance)
+    {
+        let equal = interpreter.call(&equals, vec![other.clone()])?;
+        return Ok(equal.is_truthy());
+    }
+
+    Ok(key == other)
+}
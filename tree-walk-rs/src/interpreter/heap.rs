@@ -0,0 +1,300 @@
+//! Cycle collection for runtime environments.
+//!
+//! Closures capture the environment they are declared in while that same
+//! environment stores the closure as a value, which creates `Rc` cycles that
+//! are never freed on their own. The heap keeps weak handles to all environments
+//! created at runtime so a simple mark phase starting from the roots can find
+//! the unreachable ones. Those get cleared which breaks the cycles and lets
+//! `Rc` drop them.
+//!
+//! The host can still hold values which the roots don't reach, like closures
+//! fetched from the globals. The sweep is therefore conservative: it compares
+//! the strong counts of the objects reachable from the unreachable
+//! environments with the references found to them inside the heap (trial
+//! deletion), keeping everything reachable from objects with references from
+//! outside.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    rc::{Rc, Weak},
+};
+
+use super::environment::{Environment, EnvironmentRef};
+
+/// Minimum count of tracked environments before running a collection.
+const INITIAL_THRESHOLD: usize = 1024;
+
+/// Implemented by runtime types which can reference environments directly or
+/// through their children.
+///
+/// The state of native classes holding Lox values implements it by tracing
+/// the values, and is set with
+/// [`LoxInstance::set_traced_state()`](super::LoxInstance::set_traced_state).
+pub trait Trace {
+    fn trace(&self, tracer: &mut Tracer);
+}
+
+/// Counts of the objects reachable from the running code, which are returned
+/// from [`Interpreter::heap_stats()`](super::Interpreter::heap_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    pub environments: usize,
+    /// Environments which can't be reached anymore but are kept alive by
+    /// cycles until the next collection.
+    pub unreachable_environments: usize,
+    pub instances: usize,
+    pub functions: usize,
+    pub lists: usize,
+    pub strings: usize,
+}
+
+impl Display for HeapStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "environments: {} ({} unreachable), instances: {}, functions: {}, lists: {}, strings: {}",
+            self.environments,
+            self.unreachable_environments,
+            self.instances,
+            self.functions,
+            self.lists,
+            self.strings
+        )
+    }
+}
+
+/// Keeps track of visited objects while marking reachable environments.
+#[derive(Debug, Default)]
+pub struct Tracer {
+    visited: HashSet<usize>,
+    pending: Vec<EnvironmentRef>,
+    /// References between the shared objects, which are only recorded while
+    /// looking for references from outside the heap.
+    refs: Option<RefGraph>,
+    /// Counts of the visited objects by their kind.
+    pub stats: HeapStats,
+}
+
+impl Tracer {
+    /// Marks the environment as reachable, scheduling its content to be traced.
+    pub(crate) fn mark_env(&mut self, env: &EnvironmentRef) {
+        self.record_ref(env);
+        if self.visit(Rc::as_ptr(env)) {
+            self.stats.environments += 1;
+            self.pending.push(env.clone());
+        }
+    }
+
+    /// Marks an object behind the given pointer as visited returning `true` if
+    /// it hasn't been visited before.
+    /// Tracing must only continue into the children of the object when this
+    /// returns `true` to avoid looping forever on cyclic graphs.
+    /// Objects shared behind an `Rc` must use [`Tracer::visit_rc()`] instead.
+    pub fn visit<T: ?Sized>(&mut self, ptr: *const T) -> bool {
+        self.visited.insert(ptr.cast::<()>() as usize)
+    }
+
+    /// Marks the shared object as visited, tracing its children with the given
+    /// function the first time it's visited.
+    pub fn visit_rc<T: ?Sized>(&mut self, rc: &Rc<T>, trace: impl FnOnce(&mut Self)) {
+        self.record_ref(rc);
+        if !self.visit(Rc::as_ptr(rc)) {
+            return;
+        }
+
+        let parent = self.enter(Rc::as_ptr(rc));
+        trace(self);
+        self.leave(parent);
+    }
+
+    fn is_marked(&self, env: &EnvironmentRef) -> bool {
+        self.visited.contains(&address(env))
+    }
+
+    fn trace_pending(&mut self) {
+        // Using a work list instead of recursion keeps long environment chains
+        // from overflowing the stack.
+        while let Some(env) = self.pending.pop() {
+            let parent = self.enter(Rc::as_ptr(&env));
+            env.borrow().trace(self);
+            self.leave(parent);
+        }
+    }
+
+    /// Records the reference to the object from the one being traced.
+    fn record_ref<T: ?Sized>(&mut self, rc: &Rc<T>) {
+        let Some(refs) = &mut self.refs else {
+            return;
+        };
+
+        let ptr = address(rc);
+        refs.nodes
+            .entry(ptr)
+            .or_insert_with(|| RefNode::new(Rc::strong_count(rc)))
+            .internal += 1;
+        if let Some(parent) = refs.current {
+            refs.nodes.get_mut(&parent).unwrap().children.push(ptr);
+        }
+    }
+
+    /// Makes the object the holder of the references recorded next, returning
+    /// the previous one.
+    fn enter<T: ?Sized>(&mut self, ptr: *const T) -> Option<usize> {
+        let refs = self.refs.as_mut()?;
+        refs.current.replace(ptr.cast::<()>() as usize)
+    }
+
+    fn leave(&mut self, parent: Option<usize>) {
+        if let Some(refs) = &mut self.refs {
+            refs.current = parent;
+        }
+    }
+}
+
+/// Shared objects found while tracing the unreachable environments.
+#[derive(Debug, Default)]
+struct RefGraph {
+    nodes: HashMap<usize, RefNode>,
+    /// Object holding the references which are currently traced.
+    current: Option<usize>,
+}
+
+impl RefGraph {
+    /// Returns the objects reachable from the ones which are referenced from
+    /// outside the heap.
+    fn externally_reachable(&self) -> HashSet<usize> {
+        let mut reachable = HashSet::new();
+        let mut pending: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.strong > node.internal)
+            .map(|(ptr, _)| *ptr)
+            .collect();
+        while let Some(ptr) = pending.pop() {
+            if reachable.insert(ptr) {
+                pending.extend(&self.nodes[&ptr].children);
+            }
+        }
+        reachable
+    }
+}
+
+#[derive(Debug)]
+struct RefNode {
+    /// Strong count of the object when it was found.
+    strong: usize,
+    /// Count of the references to the object found inside the heap.
+    internal: usize,
+    children: Vec<usize>,
+}
+
+impl RefNode {
+    fn new(strong: usize) -> Self {
+        Self {
+            strong,
+            internal: 0,
+            children: Vec::new(),
+        }
+    }
+}
+
+fn address<T: ?Sized>(rc: &Rc<T>) -> usize {
+    Rc::as_ptr(rc).cast::<()>() as usize
+}
+
+#[derive(Debug)]
+pub struct Heap {
+    environments: Vec<Weak<RefCell<Environment>>>,
+    next_collection: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            environments: Vec::new(),
+            next_collection: INITIAL_THRESHOLD,
+        }
+    }
+}
+
+impl Heap {
+    /// Registers the environment to be considered in the next collections.
+    pub fn track(&mut self, env: &EnvironmentRef) {
+        self.environments.push(Rc::downgrade(env));
+    }
+
+    /// Counts the objects reachable from the roots marked by the given function.
+    pub fn stats(&self, mark_roots: impl FnOnce(&mut Tracer)) -> HeapStats {
+        let mut tracer = Tracer::default();
+        mark_roots(&mut tracer);
+        tracer.trace_pending();
+
+        let mut stats = tracer.stats;
+        stats.unreachable_environments = self
+            .environments
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|env| !tracer.is_marked(env))
+            .count();
+        stats
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.environments.len() >= self.next_collection
+    }
+
+    /// Clears all tracked environments that can't be reached from the roots
+    /// marked in the tracer, nor from objects referenced outside the heap.
+    ///
+    /// This must only be called when all the values the interpreter uses are
+    /// reachable from the roots, otherwise environments still in use would get
+    /// cleared.
+    pub fn collect(&mut self, mut tracer: Tracer) {
+        tracer.trace_pending();
+
+        let mut unreachable = Vec::new();
+        self.environments.retain(|weak| match weak.upgrade() {
+            Some(env) if tracer.is_marked(&env) => true,
+            Some(env) => {
+                unreachable.push(env);
+                false
+            }
+            None => false,
+        });
+
+        // Trace the unreachable environments again, counting the references to
+        // the objects they reach. The vector holds one reference to each.
+        let mut refs = RefGraph::default();
+        for env in &unreachable {
+            refs.nodes
+                .insert(address(env), RefNode::new(Rc::strong_count(env) - 1));
+        }
+        tracer.refs = Some(refs);
+        for env in &unreachable {
+            if tracer.visit(Rc::as_ptr(env)) {
+                tracer.pending.push(env.clone());
+            }
+        }
+        tracer.trace_pending();
+
+        let alive = tracer.refs.take().unwrap().externally_reachable();
+        unreachable.retain(|env| {
+            let alive = alive.contains(&address(env));
+            if alive {
+                self.environments.push(Rc::downgrade(env));
+            }
+            !alive
+        });
+
+        // Clear after the sweep is done since dropping the values can free
+        // other environments.
+        for env in &unreachable {
+            env.borrow_mut().clear();
+        }
+        drop(unreachable);
+
+        self.next_collection = INITIAL_THRESHOLD.max(self.environments.len() * 2);
+    }
+}
@@ -1,48 +1,282 @@
-use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+use std::{any::Any, cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
 
-use crate::{Token, errors::LoxError};
+use lox_frontend::{Token, codes, locale::Message};
 
-use super::{LoxValue, callables::LoxCallable, class::LoxClass};
+use crate::{errors::LoxError, resolver::is_private};
+
+use super::{
+    Interpreter, LoxValue,
+    callables::{LoxCallable, LoxClassRef},
+    function::LoxFunction,
+    heap::{Trace, Tracer},
+    native_class::BoundNativeMethod,
+};
 
 pub type LoxInstanceRef = Rc<RefCell<LoxInstance>>;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct LoxInstance {
-    class: LoxClass,
-    fields: HashMap<String, LoxValue>,
+    class: LoxClassRef,
+    fields: HashMap<Rc<str>, LoxValue>,
+    /// IDs of the classes owning the private fields, which are the classes of
+    /// the methods setting them first.
+    private_owners: HashMap<Rc<str>, u64>,
+    /// Methods already bound to this instance, avoiding walking the class hierarchy
+    /// and binding a new closure each time a method is accessed.
+    bound_methods: HashMap<Rc<str>, Rc<LoxFunction>>,
+    /// Revision of the classes the bound methods were found in, which are
+    /// dropped once `extend` changes any class.
+    bound_revision: u64,
+    /// Rust value kept by the instances of native classes.
+    state: Option<Rc<dyn Any>>,
+    /// Traces the state of the native classes holding Lox values.
+    trace_state: Option<fn(&dyn Any, &mut Tracer)>,
+}
+
+fn trace_state<T: Any + Trace>(state: &dyn Any, tracer: &mut Tracer) {
+    if let Some(state) = state.downcast_ref::<T>() {
+        state.trace(tracer);
+    }
 }
 
 impl LoxInstance {
-    pub fn new(class: LoxClass) -> LoxInstanceRef {
-        let fields = HashMap::new();
-        let instance = Self { class, fields };
+    pub fn new(class: LoxClassRef) -> LoxInstanceRef {
+        let instance = Self {
+            class,
+            fields: HashMap::new(),
+            private_owners: HashMap::new(),
+            bound_methods: HashMap::new(),
+            bound_revision: 0,
+            state: None,
+            trace_state: None,
+        };
         Rc::new(RefCell::new(instance))
     }
 
-    pub fn get(inst_ref: LoxInstanceRef, name: &Token) -> Result<LoxValue, LoxError> {
+    pub fn get(
+        inst_ref: LoxInstanceRef,
+        name: &Token,
+        interpreter: &mut Interpreter,
+    ) -> Result<LoxValue, LoxError> {
+        let instance = inst_ref.borrow();
+        if let Some(value) = instance.fields.get(&name.lexeme) {
+            return Ok(value.to_owned());
+        }
+
+        drop(instance);
+
+        Self::method(&inst_ref, &name.lexeme, interpreter).ok_or_else(|| {
+            LoxError::new(
+                name.to_owned(),
+                codes::UNDEFINED_PROPERTY,
+                Message::new("Undefined property '{}'.", [&name.lexeme]),
+            )
+        })
+    }
+
+    /// Gets the method with the given name bound to the instance, which is
+    /// defined either in Lox or in its native class.
+    pub fn method(
+        inst_ref: &LoxInstanceRef,
+        name: &str,
+        interpreter: &mut Interpreter,
+    ) -> Option<LoxValue> {
+        if let Some(method) = Self::bound_method(inst_ref, name, interpreter) {
+            return Some(LoxValue::Callable(LoxCallable::LoxFunction(method)));
+        }
+
+        let method = inst_ref.borrow().class.borrow().find_native_method(name)?;
+        let bound = BoundNativeMethod {
+            method,
+            this: inst_ref.clone(),
+        };
+        Some(LoxValue::Callable(LoxCallable::NativeMethod(Rc::new(
+            bound,
+        ))))
+    }
+
+    /// Gets the private member with the given name, which is accessed from the
+    /// methods of the class with the given ID.
+    ///
+    /// Private members belong to the class declaring them, which for fields is
+    /// the class of the method setting them first. Other classes in the same
+    /// hierarchy can't access them.
+    pub fn get_private(
+        inst_ref: LoxInstanceRef,
+        name: &Token,
+        class_id: Option<u64>,
+        interpreter: &mut Interpreter,
+    ) -> Result<LoxValue, LoxError> {
         let instance = inst_ref.borrow();
         if let Some(value) = instance.fields.get(&name.lexeme) {
+            instance.check_owner(name, class_id)?;
             return Ok(value.to_owned());
         }
 
-        if let Some(method) = instance.class.find_method(&name.lexeme) {
-            let func = method.bind(inst_ref.clone());
-            return Ok(LoxValue::Callable(LoxCallable::LoxFunction(func)));
+        // Private methods aren't cached since classes in the same hierarchy can
+        // declare their own private methods with the same name.
+        let class = instance.class.clone();
+        drop(instance);
+        let method = class_id.and_then(|id| class.borrow().find_private_method(id, &name.lexeme));
+        if let Some(method) = method {
+            let method = Rc::new(method.bind(inst_ref, interpreter));
+            return Ok(LoxValue::Callable(LoxCallable::LoxFunction(method)));
+        }
+
+        let owner = class.borrow().declaring_class(&name.lexeme);
+        match owner {
+            Some(owner) => Err(private_error(name, &owner)),
+            None => Err(LoxError::new(
+                name.to_owned(),
+                codes::UNDEFINED_PROPERTY,
+                Message::new("Undefined property '{}'.", [&name.lexeme]),
+            )),
+        }
+    }
+
+    /// Sets the private field with the given name from the methods of the
+    /// class with the given ID, which owns the field once it's set first.
+    pub fn set_private(
+        &mut self,
+        name: &Token,
+        value: LoxValue,
+        class_id: Option<u64>,
+    ) -> Result<(), LoxError> {
+        self.check_owner(name, class_id)?;
+        if let Some(class_id) = class_id {
+            self.private_owners
+                .entry(name.lexeme.clone())
+                .or_insert(class_id);
+        }
+        self.set(name, value);
+
+        Ok(())
+    }
+
+    /// Checks that the private field with the given name is owned by the
+    /// class with the given ID if it's owned by any class.
+    fn check_owner(&self, name: &Token, class_id: Option<u64>) -> Result<(), LoxError> {
+        match self.private_owners.get(&name.lexeme) {
+            Some(owner) if Some(*owner) != class_id => {
+                let owner = self.class.borrow().name_of(*owner);
+                Err(private_error(name, owner.as_deref().unwrap_or("?")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the method of the class with the given name bound to the instance.
+    pub fn bound_method(
+        inst_ref: &LoxInstanceRef,
+        name: &str,
+        interpreter: &mut Interpreter,
+    ) -> Option<Rc<LoxFunction>> {
+        let mut instance = inst_ref.borrow_mut();
+        if instance.bound_revision != interpreter.class_revision {
+            instance.bound_methods.clear();
+            instance.bound_revision = interpreter.class_revision;
+        }
+        if let Some(method) = instance.bound_methods.get(name) {
+            return Some(method.to_owned());
+        }
+
+        let method = instance.class.borrow().find_method(name)?;
+        let func = Rc::new(method.bind(inst_ref.clone(), interpreter));
+        instance.bound_methods.insert(name.into(), func.clone());
+
+        Some(func)
+    }
+
+    pub fn class(&self) -> &LoxClassRef {
+        &self.class
+    }
+
+    /// Fields of the instance as name/value pairs sorted by their names.
+    pub fn fields(&self) -> Vec<(&Rc<str>, &LoxValue)> {
+        let mut fields: Vec<_> = self.fields.iter().collect();
+        fields.sort_by_key(|(name, _)| *name);
+        fields
+    }
+
+    /// Checks if the field with the given name is visible to the methods of the
+    /// class with the given ID, which is only the case for private fields when
+    /// the class owns them.
+    pub(crate) fn is_visible(&self, name: &str, class_id: Option<u64>) -> bool {
+        if !is_private(name) {
+            return true;
         }
 
-        Err(LoxError::new(
-            name.to_owned(),
-            format!("Undefined property '{}'.", name.lexeme),
-        ))
+        class_id.is_some()
+            && self
+                .private_owners
+                .get(name)
+                .is_none_or(|owner| Some(*owner) == class_id)
+    }
+
+    /// Rust state of the instance if it has the given type.
+    pub fn state<T: Any>(&self) -> Option<&T> {
+        self.state.as_deref()?.downcast_ref()
+    }
+
+    /// Shared handle to the Rust state of the instance if it has the given
+    /// type, which natives can keep while calling back into Lox code.
+    pub fn state_rc<T: Any>(&self) -> Option<Rc<T>> {
+        self.state.clone()?.downcast().ok()
+    }
+
+    /// Sets the Rust state of the instance, which is usually done in the
+    /// `init` method of native classes.
+    /// State holding Lox values, like callbacks, must be set with
+    /// [`LoxInstance::set_traced_state()`] instead.
+    pub fn set_state(&mut self, state: impl Any) {
+        self.state = Some(Rc::new(state));
+        self.trace_state = None;
+    }
+
+    /// Sets the Rust state holding Lox values, which the garbage collector
+    /// traces like the fields.
+    pub fn set_traced_state<T: Any + Trace>(&mut self, state: T) {
+        self.state = Some(Rc::new(state));
+        self.trace_state = Some(trace_state::<T>);
     }
 
     pub fn set(&mut self, name: &Token, value: LoxValue) {
         self.fields.insert(name.lexeme.to_owned(), value);
     }
+
+    /// Sets the field with the given name, which is used by natives creating
+    /// instances.
+    pub fn set_field(&mut self, name: &str, value: LoxValue) {
+        self.fields.insert(name.into(), value);
+    }
+}
+
+fn private_error(name: &Token, owner: &str) -> LoxError {
+    LoxError::new(
+        name.to_owned(),
+        codes::PRIVATE_MEMBER,
+        Message::new("'{}' is private to class {}.", [&*name.lexeme, owner]),
+    )
 }
 
 impl Display for LoxInstance {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} instance", self.class)
+        write!(f, "{} instance", self.class.borrow())
+    }
+}
+
+impl Trace for LoxInstance {
+    fn trace(&self, tracer: &mut Tracer) {
+        tracer.stats.instances += 1;
+        tracer.visit_rc(&self.class, |tracer| self.class.borrow().trace(tracer));
+        for value in self.fields.values() {
+            value.trace(tracer);
+        }
+        for method in self.bound_methods.values() {
+            tracer.visit_rc(method, |tracer| method.trace(tracer));
+        }
+        if let (Some(state), Some(trace)) = (&self.state, self.trace_state) {
+            tracer.visit_rc(state, |tracer| trace(&**state, tracer));
+        }
     }
 }
@@ -0,0 +1,981 @@
+//! Experimental JIT compiler for the typed subset of Lox, enabled with the
+//! `jit` feature.
+//!
+//! Top-level functions which only work on numbers and booleans are lowered
+//! to Cranelift IR and compiled to native code, skipping the dynamic checks
+//! and the environments of the interpreter. Functions call other compiled
+//! functions directly, since the global functions they refer to are guarded
+//! once on entering the compiled code.
+//!
+//! The subset includes:
+//! - Number parameters and number return values.
+//! - Local variables, which keep the type of their initializer.
+//! - Arithmetics, comparisons, equality and logical operators on values of
+//!   matching types.
+//! - Conditions and loops on booleans.
+//! - Calls of other global functions in the subset.
+//!
+//! Compiled code has no side effects, so any case it can't handle at runtime,
+//! like division by zero or deep recursion, bails out to run the same call in
+//! the interpreter again, which then reports the errors the usual way. Hosts
+//! which Cranelift doesn't support run everything in the interpreter.
+
+use std::{collections::HashMap, mem::ManuallyDrop, mem::offset_of, rc::Rc};
+
+use cranelift_codegen::{
+    Context as CodegenContext,
+    ir::{
+        AbiParam, Block, InstBuilder, MemFlagsData, Signature, UserFuncName, Value,
+        condcodes::{FloatCC, IntCC},
+        types,
+    },
+    settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{FuncId, Linkage, Module, default_libcall_names};
+use lox_frontend::{
+    Token, TokenType as TT,
+    ast::{Expr, FuncDeclaration, LiteralValue, Stmt},
+};
+
+use super::{
+    LoxValue, callables::LoxCallable, cancel::CancelHandle, environment::EnvironmentRef, floor_div,
+    floor_mod, function::LoxFunction,
+};
+
+/// Maximum count of nested calls inside compiled code, which run on the
+/// native stack unlike the calls in the interpreter.
+const MAX_NATIVE_DEPTH: u64 = 512;
+
+/// Native code of the entry of a unit, which calls its first function with the
+/// arguments read from the array.
+type Entry = unsafe extern "C" fn(*mut Runtime, *const f64) -> f64;
+
+/// Global functions the compiled code refers to by their names.
+type Guards = Vec<(Token, Rc<FuncDeclaration>)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Number,
+    Boolean,
+}
+
+impl Type {
+    fn ir(self) -> types::Type {
+        match self {
+            Type::Number => types::F64,
+            Type::Boolean => types::I8,
+        }
+    }
+}
+
+/// Lowered expression with its static type. Numbers are `f64` values and
+/// booleans are `i8` values being zero or one.
+#[derive(Clone, Copy)]
+enum Typed {
+    Number(Value),
+    Boolean(Value),
+}
+
+impl Typed {
+    fn typ(self) -> Type {
+        match self {
+            Typed::Number(_) => Type::Number,
+            Typed::Boolean(_) => Type::Boolean,
+        }
+    }
+
+    fn value(self) -> Value {
+        match self {
+            Typed::Number(value) | Typed::Boolean(value) => value,
+        }
+    }
+}
+
+/// State of a call of compiled code, which all compiled functions get a
+/// pointer to as their first parameter.
+#[repr(C)]
+struct Runtime {
+    /// Count of the nested calls inside compiled code.
+    depth: u64,
+    max_depth: u64,
+    cancel: *const CancelHandle,
+    /// Set by the compiled code when it bails out.
+    bailout: u8,
+    ieee_division: u8,
+}
+
+extern "C" fn is_cancelled(runtime: *const Runtime) -> u8 {
+    // SAFETY: Compiled code passes the runtime of the running call, whose
+    // cancel handle outlives it.
+    let cancel = unsafe { &*(*runtime).cancel };
+    u8::from(cancel.is_cancelled())
+}
+
+extern "C" fn jit_floor_div(left: f64, right: f64) -> f64 {
+    floor_div(left, right)
+}
+
+extern "C" fn jit_floor_mod(left: f64, right: f64) -> f64 {
+    floor_mod(left, right)
+}
+
+/// Functions of the interpreter called by the compiled code.
+const HELPERS: [(&str, *const u8); 3] = [
+    ("lox_is_cancelled", is_cancelled as *const u8),
+    ("lox_floor_div", jit_floor_div as *const u8),
+    ("lox_floor_mod", jit_floor_mod as *const u8),
+];
+
+/// Native code of a function together with the global functions it calls.
+struct CompiledUnit {
+    /// Module owning the native code, which is freed with the unit.
+    module: ManuallyDrop<JITModule>,
+    entry: Entry,
+    guards: Guards,
+}
+
+impl Drop for CompiledUnit {
+    fn drop(&mut self) {
+        // SAFETY: Calls of the native code hold the unit, so it isn't running
+        // anymore.
+        unsafe { ManuallyDrop::take(&mut self.module).free_memory() };
+    }
+}
+
+/// Settings of the interpreter applying to compiled code.
+pub struct Limits<'a> {
+    /// Count of the nested calls in the interpreter.
+    pub depth: usize,
+    pub max_depth: usize,
+    pub ieee_division: bool,
+    pub cancel: &'a CancelHandle,
+}
+
+/// Caches the compiled units by the IDs of the names of the function
+/// declarations, remembering the ones outside the subset as well.
+#[derive(Default)]
+pub struct Jit {
+    units: HashMap<u64, Option<Rc<CompiledUnit>>>,
+    /// Call depth of the interpreter at the last bailout. Calls nested inside
+    /// the call run by the interpreter instead would bail out the same way,
+    /// so they skip the compiled code until it returns.
+    bailout_depth: Option<usize>,
+}
+
+impl std::fmt::Debug for Jit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Jit")
+            .field("units", &self.units.len())
+            .field("bailout_depth", &self.bailout_depth)
+            .finish()
+    }
+}
+
+impl Jit {
+    /// Calls the native code of the function if it's in the typed subset and
+    /// the arguments have the expected types, returning `None` when the call
+    /// must be done by the interpreter instead.
+    pub fn call(
+        &mut self,
+        function: &LoxFunction,
+        args: &[LoxValue],
+        globals: &EnvironmentRef,
+        limits: Limits<'_>,
+    ) -> Option<LoxValue> {
+        // Only top-level functions can be referred to by their global names.
+        if !Rc::ptr_eq(&function.closure, globals) || args.len() != function.arity() {
+            return None;
+        }
+
+        match self.bailout_depth {
+            Some(depth) if limits.depth > depth => return None,
+            Some(_) => self.bailout_depth = None,
+            None => {}
+        }
+
+        let args = args
+            .iter()
+            .map(|arg| match arg {
+                LoxValue::Number(num) => Some(*num),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let unit = self
+            .units
+            .entry(function.declaration.name.id())
+            .or_insert_with(|| compile(&function.declaration, globals).map(Rc::new))
+            .clone()?;
+
+        // Global functions can be redefined after compiling the code.
+        let guards_hold = unit.guards.iter().all(|(name, declaration)| {
+            global_function(globals, name)
+                .is_some_and(|global| Rc::ptr_eq(&global.declaration, declaration))
+        });
+        if !guards_hold {
+            self.units.remove(&function.declaration.name.id());
+            return None;
+        }
+
+        let mut runtime = Runtime {
+            depth: 0,
+            max_depth: MAX_NATIVE_DEPTH.min(limits.max_depth as u64),
+            cancel: limits.cancel,
+            bailout: 0,
+            ieee_division: u8::from(limits.ieee_division),
+        };
+        // SAFETY: The entry reads as many arguments as the function has
+        // parameters, which is the length of the arguments checked above.
+        let result = unsafe { (unit.entry)(&mut runtime, args.as_ptr()) };
+
+        if runtime.bailout != 0 {
+            self.bailout_depth = Some(limits.depth);
+            return None;
+        }
+
+        Some(LoxValue::Number(result))
+    }
+}
+
+/// Returns the top-level Lox function defined as global with the given name.
+fn global_function(globals: &EnvironmentRef, name: &Token) -> Option<Rc<LoxFunction>> {
+    match globals.borrow().get(name) {
+        Ok(LoxValue::Callable(LoxCallable::LoxFunction(function)))
+            if Rc::ptr_eq(&function.closure, globals) =>
+        {
+            Some(function)
+        }
+        _ => None,
+    }
+}
+
+/// Creates the module for the native code of the host, returning `None` if
+/// Cranelift doesn't support it.
+fn new_module() -> Option<JITModule> {
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").ok()?;
+    // The code is loaded anywhere in memory, so calls use absolute addresses.
+    flags.set("use_colocated_libcalls", "false").ok()?;
+    flags.set("is_pic", "false").ok()?;
+    let isa = cranelift_native::builder()
+        .ok()?
+        .finish(settings::Flags::new(flags))
+        .ok()?;
+
+    let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+    builder.symbols(HELPERS);
+    Some(JITModule::new(builder))
+}
+
+/// Compiles the function with all global functions it calls, returning `None`
+/// if any of them is outside of the typed subset.
+fn compile(declaration: &Rc<FuncDeclaration>, globals: &EnvironmentRef) -> Option<CompiledUnit> {
+    let mut module = new_module()?;
+    let Some((entry, guards)) = define(&mut module, declaration, globals) else {
+        // SAFETY: None of the code of the module has been called.
+        unsafe { module.free_memory() };
+        return None;
+    };
+
+    let code = module.get_finalized_function(entry);
+    // SAFETY: The entry is compiled with the signature of `Entry` for the
+    // calling convention of the host.
+    let entry = unsafe { std::mem::transmute::<*const u8, Entry>(code) };
+
+    Some(CompiledUnit {
+        module: ManuallyDrop::new(module),
+        entry,
+        guards,
+    })
+}
+
+/// Defines the functions of the unit in the module, returning its entry with
+/// the guards of the global functions.
+fn define(
+    module: &mut JITModule,
+    declaration: &Rc<FuncDeclaration>,
+    globals: &EnvironmentRef,
+) -> Option<(FuncId, Guards)> {
+    let mut helpers = HashMap::new();
+    for (name, signature) in [
+        ("lox_is_cancelled", runtime_signature(module, 0, types::I8)),
+        ("lox_floor_div", number_signature(module, 2)),
+        ("lox_floor_mod", number_signature(module, 2)),
+    ] {
+        let id = module
+            .declare_function(name, Linkage::Import, &signature)
+            .ok()?;
+        helpers.insert(name, id);
+    }
+
+    let mut compiler = Compiler {
+        globals,
+        module,
+        helpers,
+        indices: HashMap::new(),
+        guards: Vec::new(),
+        pending: Vec::new(),
+    };
+    compiler.declare(declaration.clone())?;
+    compiler
+        .guards
+        .push((declaration.name.clone(), declaration.clone()));
+
+    let mut ctx = compiler.module.make_context();
+    let mut func_ctx = FunctionBuilderContext::new();
+    let mut compiled = 0;
+    while compiled < compiler.pending.len() {
+        let (declaration, id) = compiler.pending[compiled].clone();
+        compiler.function(&declaration, id, &mut ctx, &mut func_ctx)?;
+        compiled += 1;
+    }
+    let entry = compiler.entry(declaration.params.len(), &mut ctx, &mut func_ctx)?;
+    compiler.module.finalize_definitions().ok()?;
+
+    Some((entry, compiler.guards))
+}
+
+/// Signature taking the runtime and the given count of numbers.
+fn runtime_signature(module: &JITModule, params: usize, returns: types::Type) -> Signature {
+    let mut signature = module.make_signature();
+    let pointer = module.target_config().pointer_type();
+    signature.params.push(AbiParam::new(pointer));
+    signature
+        .params
+        .extend(std::iter::repeat_n(AbiParam::new(types::F64), params));
+    signature.returns.push(AbiParam::new(returns));
+    signature
+}
+
+fn number_signature(module: &JITModule, params: usize) -> Signature {
+    let mut signature = module.make_signature();
+    signature
+        .params
+        .extend(std::iter::repeat_n(AbiParam::new(types::F64), params));
+    signature.returns.push(AbiParam::new(types::F64));
+    signature
+}
+
+struct Compiler<'a> {
+    globals: &'a EnvironmentRef,
+    module: &'a mut JITModule,
+    /// Functions of the interpreter by their symbols.
+    helpers: HashMap<&'static str, FuncId>,
+    /// Indices of the compiled functions by their global names.
+    indices: HashMap<Rc<str>, usize>,
+    guards: Guards,
+    /// Declarations of the functions to compile with their IDs in the module,
+    /// in the order of their indices.
+    pending: Vec<(Rc<FuncDeclaration>, FuncId)>,
+}
+
+impl Compiler<'_> {
+    /// Declares the function in the module to compile it later.
+    fn declare(&mut self, declaration: Rc<FuncDeclaration>) -> Option<FuncId> {
+        let signature = runtime_signature(self.module, declaration.params.len(), types::F64);
+        let id = self.module.declare_anonymous_function(&signature).ok()?;
+        self.indices
+            .insert(declaration.name.lexeme.clone(), self.pending.len());
+        self.pending.push((declaration, id));
+        Some(id)
+    }
+
+    /// Returns the ID of the global function to call, compiling it later.
+    fn callee(&mut self, name: &Token, arity: usize) -> Option<FuncId> {
+        if let Some(&index) = self.indices.get(&name.lexeme) {
+            let (declaration, id) = &self.pending[index];
+            return (declaration.params.len() == arity).then_some(*id);
+        }
+
+        let function = global_function(self.globals, name)?;
+        if function.arity() != arity {
+            return None;
+        }
+
+        self.guards
+            .push((name.clone(), function.declaration.clone()));
+        self.declare(function.declaration.clone())
+    }
+
+    fn function(
+        &mut self,
+        declaration: &FuncDeclaration,
+        id: FuncId,
+        ctx: &mut CodegenContext,
+        func_ctx: &mut FunctionBuilderContext,
+    ) -> Option<()> {
+        // Falling off the end of the function returns nil.
+        if !always_returns(&declaration.body) {
+            return None;
+        }
+
+        ctx.func.signature = runtime_signature(self.module, declaration.params.len(), types::F64);
+        ctx.func.name = UserFuncName::user(0, id.as_u32());
+        let mut lowering = Lowering::new(self, &mut ctx.func, func_ctx);
+        let params = lowering.params();
+        lowering.scopes.push(HashMap::new());
+        for (param, value) in declaration.params.iter().zip(params) {
+            let var = lowering.declare(param, Type::Number);
+            lowering.builder.def_var(var, value);
+        }
+
+        lowering.enter_call();
+        lowering.stmts(&declaration.body)?;
+        // The end of the body is unreachable since all paths return.
+        let bail = lowering.bail;
+        lowering.builder.ins().jump(bail, &[]);
+        lowering.finish();
+
+        self.module.define_function(id, ctx).ok()?;
+        self.module.clear_context(ctx);
+        Some(())
+    }
+
+    /// Defines the entry calling the function at the first index with the
+    /// arguments read from an array.
+    fn entry(
+        &mut self,
+        arity: usize,
+        ctx: &mut CodegenContext,
+        func_ctx: &mut FunctionBuilderContext,
+    ) -> Option<FuncId> {
+        let pointer = self.module.target_config().pointer_type();
+        let mut signature = self.module.make_signature();
+        signature.params.push(AbiParam::new(pointer));
+        signature.params.push(AbiParam::new(pointer));
+        signature.returns.push(AbiParam::new(types::F64));
+        let id = self.module.declare_anonymous_function(&signature).ok()?;
+
+        ctx.func.signature = signature;
+        ctx.func.name = UserFuncName::user(0, id.as_u32());
+        let mut builder = FunctionBuilder::new(&mut ctx.func, func_ctx);
+        let block = builder.create_block();
+        builder.append_block_params_for_function_params(block);
+        builder.switch_to_block(block);
+        builder.seal_block(block);
+
+        let (runtime, array) = (
+            builder.block_params(block)[0],
+            builder.block_params(block)[1],
+        );
+        let mut args = vec![runtime];
+        for index in 0..arity {
+            let offset = i32::try_from(index * size_of::<f64>()).ok()?;
+            args.push(
+                builder
+                    .ins()
+                    .load(types::F64, MemFlagsData::trusted(), array, offset),
+            );
+        }
+        let function = self
+            .module
+            .declare_func_in_func(self.pending[0].1, builder.func);
+        let call = builder.ins().call(function, &args);
+        let result = builder.inst_results(call)[0];
+        builder.ins().return_(&[result]);
+        builder.finalize(self.module.target_config());
+
+        self.module.define_function(id, ctx).ok()?;
+        self.module.clear_context(ctx);
+        Some(id)
+    }
+}
+
+/// Lowers the body of a function into Cranelift IR.
+struct Lowering<'c, 'a, 'f> {
+    compiler: &'c mut Compiler<'a>,
+    builder: FunctionBuilder<'f>,
+    /// Pointer to the runtime of the call.
+    runtime: Value,
+    /// Block returning from the function after flagging the bailout.
+    bail: Block,
+    /// Local scopes with the variables and their types.
+    scopes: Vec<HashMap<Rc<str>, (Variable, Type)>>,
+    /// Blocks which `continue` and `break` jump to in the enclosing loops.
+    loops: Vec<(Block, Block)>,
+}
+
+impl<'c, 'a, 'f> Lowering<'c, 'a, 'f> {
+    fn new(
+        compiler: &'c mut Compiler<'a>,
+        func: &'f mut cranelift_codegen::ir::Function,
+        func_ctx: &'f mut FunctionBuilderContext,
+    ) -> Self {
+        let mut builder = FunctionBuilder::new(func, func_ctx);
+        let block = builder.create_block();
+        builder.append_block_params_for_function_params(block);
+        builder.switch_to_block(block);
+        builder.seal_block(block);
+        let runtime = builder.block_params(block)[0];
+        let bail = builder.create_block();
+
+        Self {
+            compiler,
+            builder,
+            runtime,
+            bail,
+            scopes: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    /// Returns the values of the parameters of the Lox function.
+    fn params(&self) -> Vec<Value> {
+        let entry = self
+            .builder
+            .current_block()
+            .expect("Lowering starts in the entry block");
+        self.builder.block_params(entry)[1..].to_vec()
+    }
+
+    fn finish(mut self) {
+        self.builder.switch_to_block(self.bail);
+        let flag = self.builder.ins().iconst(types::I8, 1);
+        self.store(flag, offset_of!(Runtime, bailout));
+        let zero = self.builder.ins().f64const(0.0);
+        self.builder.ins().return_(&[zero]);
+
+        self.builder.seal_all_blocks();
+        self.builder.finalize(self.compiler.module.target_config());
+    }
+
+    fn declare(&mut self, name: &Token, typ: Type) -> Variable {
+        let var = self.builder.declare_var(typ.ir());
+        self.scopes
+            .last_mut()
+            .expect("Functions always have a scope")
+            .insert(name.lexeme.clone(), (var, typ));
+        var
+    }
+
+    fn lookup(&self, name: &Token) -> Option<(Variable, Type)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme).copied())
+    }
+
+    fn load(&mut self, typ: types::Type, offset: usize) -> Value {
+        let offset = i32::try_from(offset).expect("Fields of the runtime have small offsets");
+        self.builder
+            .ins()
+            .load(typ, MemFlagsData::trusted(), self.runtime, offset)
+    }
+
+    fn store(&mut self, value: Value, offset: usize) {
+        let offset = i32::try_from(offset).expect("Fields of the runtime have small offsets");
+        self.builder
+            .ins()
+            .store(MemFlagsData::trusted(), value, self.runtime, offset);
+    }
+
+    /// Bails out if the condition is true, continuing in a new block otherwise.
+    fn bail_if(&mut self, condition: Value) {
+        let next = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(condition, self.bail, &[], next, &[]);
+        self.builder.switch_to_block(next);
+        self.builder.seal_block(next);
+    }
+
+    /// Continues in a new block without predecessors after jumping away, so
+    /// the code after `return`, `break` and `continue` has a place.
+    fn unreachable(&mut self) {
+        let block = self.builder.create_block();
+        self.builder.switch_to_block(block);
+        self.builder.seal_block(block);
+    }
+
+    fn check_cancelled(&mut self) {
+        let helper = self.helper("lox_is_cancelled");
+        let call = self.builder.ins().call(helper, &[self.runtime]);
+        let cancelled = self.builder.inst_results(call)[0];
+        self.bail_if(cancelled);
+    }
+
+    /// Counts the call as nested, bailing out when the calls are too deep.
+    fn enter_call(&mut self) {
+        let depth = self.load(types::I64, offset_of!(Runtime, depth));
+        let depth = self.builder.ins().iadd_imm_s(depth, 1);
+        let max_depth = self.load(types::I64, offset_of!(Runtime, max_depth));
+        let too_deep = self
+            .builder
+            .ins()
+            .icmp(IntCC::UnsignedGreaterThan, depth, max_depth);
+        self.bail_if(too_deep);
+        self.store(depth, offset_of!(Runtime, depth));
+        self.check_cancelled();
+    }
+
+    fn return_value(&mut self, value: Value) {
+        let depth = self.load(types::I64, offset_of!(Runtime, depth));
+        let depth = self.builder.ins().iadd_imm_s(depth, -1);
+        self.store(depth, offset_of!(Runtime, depth));
+        self.builder.ins().return_(&[value]);
+        self.unreachable();
+    }
+
+    fn helper(&mut self, name: &str) -> cranelift_codegen::ir::FuncRef {
+        let id = self.compiler.helpers[name];
+        self.compiler
+            .module
+            .declare_func_in_func(id, self.builder.func)
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) -> Option<()> {
+        stmts.iter().try_for_each(|stmt| self.stmt(stmt))
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> Option<()> {
+        self.scopes.push(HashMap::new());
+        let block = self.stmts(stmts);
+        self.scopes.pop();
+        block
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Option<()> {
+        match stmt {
+            Stmt::Expression(expr) => {
+                self.expr(expr)?;
+            }
+            Stmt::Var {
+                name,
+                initializer: Some(initializer),
+                ..
+            } => {
+                // The variable isn't visible in its own initializer.
+                let initializer = self.expr(initializer)?;
+                let var = self.declare(name, initializer.typ());
+                self.builder.def_var(var, initializer.value());
+            }
+            Stmt::Block { statements, .. } => self.block(statements)?,
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.condition(condition)?;
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge = self.builder.create_block();
+                self.builder
+                    .ins()
+                    .brif(condition, then_block, &[], else_block, &[]);
+
+                self.builder.switch_to_block(then_block);
+                self.block(std::slice::from_ref(then_branch))?;
+                self.builder.ins().jump(merge, &[]);
+
+                self.builder.switch_to_block(else_block);
+                if let Some(else_branch) = else_branch {
+                    self.block(std::slice::from_ref(else_branch))?;
+                }
+                self.builder.ins().jump(merge, &[]);
+
+                self.builder.switch_to_block(merge);
+            }
+            Stmt::While {
+                condition, body, ..
+            } => self.looped(Some(condition), body, None)?,
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.scopes.push(HashMap::new());
+                let for_loop = match initializer {
+                    Some(initializer) => self.stmt(initializer),
+                    None => Some(()),
+                }
+                .and_then(|()| self.looped(condition.as_deref(), body, increment.as_deref()));
+                self.scopes.pop();
+                for_loop?;
+            }
+            Stmt::Return {
+                value_expr: Some(value),
+                ..
+            } => {
+                let Typed::Number(value) = self.expr(value)? else {
+                    return None;
+                };
+                self.return_value(value);
+            }
+            Stmt::Break { .. } => {
+                let &(_, exit) = self.loops.last()?;
+                self.builder.ins().jump(exit, &[]);
+                self.unreachable();
+            }
+            Stmt::Continue { .. } => {
+                let &(next, _) = self.loops.last()?;
+                self.builder.ins().jump(next, &[]);
+                self.unreachable();
+            }
+            // Everything else has side effects or works on other types.
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    /// Lowers a loop, where `continue` jumps to the increment.
+    fn looped(
+        &mut self,
+        condition: Option<&Expr>,
+        body: &Stmt,
+        increment: Option<&Expr>,
+    ) -> Option<()> {
+        let header = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let next = self.builder.create_block();
+        let exit = self.builder.create_block();
+        self.builder.ins().jump(header, &[]);
+
+        self.builder.switch_to_block(header);
+        match condition {
+            Some(condition) => {
+                let condition = self.condition(condition)?;
+                self.builder
+                    .ins()
+                    .brif(condition, body_block, &[], exit, &[]);
+            }
+            None => {
+                self.builder.ins().jump(body_block, &[]);
+            }
+        }
+
+        self.builder.switch_to_block(body_block);
+        self.check_cancelled();
+        self.loops.push((next, exit));
+        let body = self.block(std::slice::from_ref(body));
+        self.loops.pop();
+        body?;
+        self.builder.ins().jump(next, &[]);
+
+        self.builder.switch_to_block(next);
+        if let Some(increment) = increment {
+            self.expr(increment)?;
+        }
+        self.builder.ins().jump(header, &[]);
+
+        self.builder.switch_to_block(exit);
+        Some(())
+    }
+
+    fn condition(&mut self, condition: &Expr) -> Option<Value> {
+        // Numbers are always truthy, which is most likely a mistake in the
+        // program so it's left to the interpreter.
+        match self.expr(condition)? {
+            Typed::Boolean(value) => Some(value),
+            Typed::Number(_) => None,
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Option<Typed> {
+        let typed = match expr {
+            Expr::Literal {
+                value: LiteralValue::Number(num),
+                ..
+            } => Typed::Number(self.builder.ins().f64const(*num)),
+            Expr::Literal {
+                value: LiteralValue::Boolean(val),
+                ..
+            } => Typed::Boolean(self.builder.ins().iconst(types::I8, i64::from(*val))),
+            Expr::Grouping { expression, .. } => self.expr(expression)?,
+            Expr::Variable { name, .. } => {
+                let (var, typ) = self.lookup(name)?;
+                let value = self.builder.use_var(var);
+                match typ {
+                    Type::Number => Typed::Number(value),
+                    Type::Boolean => Typed::Boolean(value),
+                }
+            }
+            Expr::Assign { name, value, .. } => {
+                let (var, typ) = self.lookup(name)?;
+                let value = self.expr(value)?;
+                // Variables keep their types in compiled code.
+                if value.typ() != typ {
+                    return None;
+                }
+                self.builder.def_var(var, value.value());
+                value
+            }
+            Expr::Unary {
+                operator, right, ..
+            } => match (&operator.typ, self.expr(right)?) {
+                (TT::Minus, Typed::Number(right)) => Typed::Number(self.builder.ins().fneg(right)),
+                (TT::Bang, Typed::Boolean(right)) => {
+                    Typed::Boolean(self.builder.ins().bxor_imm_u(right, 1))
+                }
+                _ => return None,
+            },
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left = self.expr(left)?;
+                let right = self.expr(right)?;
+                self.binary(left, &operator.typ, right)?
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let Typed::Boolean(left) = self.expr(left)? else {
+                    return None;
+                };
+                let right_block = self.builder.create_block();
+                let merge = self.builder.create_block();
+                let result = self.builder.append_block_param(merge, types::I8);
+                // The right operand is only evaluated when it decides the result.
+                match operator.typ {
+                    TT::Or => {
+                        self.builder
+                            .ins()
+                            .brif(left, merge, &[left.into()], right_block, &[])
+                    }
+                    _ => self
+                        .builder
+                        .ins()
+                        .brif(left, right_block, &[], merge, &[left.into()]),
+                };
+
+                self.builder.switch_to_block(right_block);
+                let Typed::Boolean(right) = self.expr(right)? else {
+                    return None;
+                };
+                self.builder.ins().jump(merge, &[right.into()]);
+
+                self.builder.switch_to_block(merge);
+                Typed::Boolean(result)
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                // Only direct calls of global functions are monomorphic.
+                let Expr::Variable { name, .. } = callee.as_ref() else {
+                    return None;
+                };
+                if self.lookup(name).is_some() {
+                    return None;
+                }
+                let id = self.compiler.callee(name, arguments.len())?;
+                let mut args = vec![self.runtime];
+                for arg in arguments.iter() {
+                    let Typed::Number(arg) = self.expr(arg)? else {
+                        return None;
+                    };
+                    args.push(arg);
+                }
+
+                let function = self
+                    .compiler
+                    .module
+                    .declare_func_in_func(id, self.builder.func);
+                let call = self.builder.ins().call(function, &args);
+                let result = self.builder.inst_results(call)[0];
+                // Bailing out of the callee leaves the whole compiled code.
+                let bailout = self.load(types::I8, offset_of!(Runtime, bailout));
+                self.bail_if(bailout);
+                Typed::Number(result)
+            }
+            // Strings, nil, globals and objects are dynamic.
+            _ => return None,
+        };
+
+        Some(typed)
+    }
+
+    fn binary(&mut self, left: Typed, operator: &TT, right: Typed) -> Option<Typed> {
+        use Typed as T;
+
+        let ins = self.builder.ins();
+        let typed = match (left, operator, right) {
+            (T::Number(l), TT::Plus, T::Number(r)) => T::Number(ins.fadd(l, r)),
+            (T::Number(l), TT::Minus, T::Number(r)) => T::Number(ins.fsub(l, r)),
+            (T::Number(l), TT::Star, T::Number(r)) => T::Number(ins.fmul(l, r)),
+            (T::Number(l), TT::Slash, T::Number(r)) => {
+                self.check_divisor(r);
+                T::Number(self.builder.ins().fdiv(l, r))
+            }
+            (T::Number(l), TT::TildeSlash, T::Number(r)) => {
+                self.check_divisor(r);
+                T::Number(self.call_helper("lox_floor_div", l, r))
+            }
+            (T::Number(l), TT::Percent, T::Number(r)) => {
+                self.check_divisor(r);
+                T::Number(self.call_helper("lox_floor_mod", l, r))
+            }
+            (T::Number(l), TT::Greater, T::Number(r)) => {
+                T::Boolean(ins.fcmp(FloatCC::GreaterThan, l, r))
+            }
+            (T::Number(l), TT::GreaterEqual, T::Number(r)) => {
+                T::Boolean(ins.fcmp(FloatCC::GreaterThanOrEqual, l, r))
+            }
+            (T::Number(l), TT::Less, T::Number(r)) => T::Boolean(ins.fcmp(FloatCC::LessThan, l, r)),
+            (T::Number(l), TT::LessEqual, T::Number(r)) => {
+                T::Boolean(ins.fcmp(FloatCC::LessThanOrEqual, l, r))
+            }
+            (T::Number(l), TT::EqualEqual, T::Number(r)) => {
+                T::Boolean(ins.fcmp(FloatCC::Equal, l, r))
+            }
+            (T::Number(l), TT::BangEqual, T::Number(r)) => {
+                T::Boolean(ins.fcmp(FloatCC::NotEqual, l, r))
+            }
+            (T::Boolean(l), TT::EqualEqual, T::Boolean(r)) => {
+                T::Boolean(ins.icmp(IntCC::Equal, l, r))
+            }
+            (T::Boolean(l), TT::BangEqual, T::Boolean(r)) => {
+                T::Boolean(ins.icmp(IntCC::NotEqual, l, r))
+            }
+            _ => return None,
+        };
+
+        Some(typed)
+    }
+
+    /// Bails out on dividing by zero unless the division follows IEEE 754.
+    fn check_divisor(&mut self, divisor: Value) {
+        let zero = self.builder.ins().f64const(0.0);
+        let is_zero = self.builder.ins().fcmp(FloatCC::Equal, divisor, zero);
+        let ieee_division = self.load(types::I8, offset_of!(Runtime, ieee_division));
+        let strict = self
+            .builder
+            .ins()
+            .icmp_imm_u(IntCC::Equal, ieee_division, 0);
+        let fails = self.builder.ins().band(is_zero, strict);
+        self.bail_if(fails);
+    }
+
+    fn call_helper(&mut self, name: &str, left: Value, right: Value) -> Value {
+        let helper = self.helper(name);
+        let call = self.builder.ins().call(helper, &[left, right]);
+        self.builder.inst_results(call)[0]
+    }
+}
+
+/// Checks if the statements return a value on all paths.
+fn always_returns(stmts: &[Stmt]) -> bool {
+    stmts.iter().any(|stmt| match stmt {
+        Stmt::Return { .. } => true,
+        Stmt::Block { statements, .. } => always_returns(statements),
+        Stmt::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => {
+            always_returns(std::slice::from_ref(then_branch))
+                && always_returns(std::slice::from_ref(else_branch))
+        }
+        _ => false,
+    })
+}
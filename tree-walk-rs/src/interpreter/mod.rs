@@ -1,33 +1,207 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io::Write,
+    rc::Rc,
+    time::Instant,
+};
 
-use callables::{CLOCK_NAME, LoxCallable};
-use class::LoxClass;
+use callables::{LoxCallable, LoxClassRef};
 use function::LoxFunction;
-use instance::LoxInstance;
 
-use crate::{
-    Token, TokenType as TT,
+use lox_frontend::{
+    NodeId, ParseResults, Parser, ScanError, Token, TokenType as TT,
     ast::{Expr, FuncDeclaration, Stmt},
-    errors::{LoxError, LoxResult},
+    codes,
+    engine::Diagnostics,
+    locale::Message,
+};
+
+use crate::{
+    RunError,
+    errors::{LoxError, LoxResult, LoxWarning, TraceFrame},
+    resolver::{Resolver, is_private},
 };
 
+mod builder;
 mod callables;
+mod cancel;
 mod class;
+mod coroutines;
 mod environment;
+mod foreign;
 mod function;
+mod hash_map;
+mod heap;
 mod instance;
+#[cfg(feature = "jit")]
+mod jit;
+mod native_class;
+mod natives;
+mod number_format;
+mod observer;
+mod options;
+#[cfg(feature = "regex")]
+mod patterns;
+mod pretty;
+mod resumable;
+mod sandbox;
+mod snapshot;
+mod subscript;
+mod task;
+mod timers;
 mod values;
 
+use builder::Sink;
 use environment::{Environment, EnvironmentRef};
-pub use values::LoxValue;
+use heap::Heap;
+use native_class::BoundNativeMethod;
+use natives::NativeFunction;
+use observer::{ExitedStmt, Observer};
+use resumable::Script;
+use task::{ControlFlow, ForInLoop, ForLoop, Iteration, Task};
+use timers::Timers;
+
+pub use builder::InterpreterBuilder;
+pub use cancel::CancelHandle;
+pub use class::LoxClass;
+pub use foreign::Foreign;
+pub use heap::{HeapStats, Trace, Tracer};
+pub use instance::{LoxInstance, LoxInstanceRef};
+pub use lox_frontend::LoxString;
+pub use native_class::NativeClass;
+pub use natives::NativeFn;
+pub use number_format::NumberFormat;
+pub use observer::ExecutionObserver;
+pub use options::InterpreterOptions;
+pub use pretty::{DEFAULT_PRETTY_DEPTH, PrettyOptions};
+pub use resumable::RunState;
+pub use sandbox::{Capability, SandboxPolicy};
+pub use snapshot::{EnvDiff, EnvSnapshot};
+pub use values::{LoxListRef, LoxValue};
+pub(crate) use values::{floor_div, floor_mod};
+
+fn natives_environment(natives: &[(Rc<str>, LoxValue)]) -> EnvironmentRef {
+    let mut globals = Environment::default();
+    for (name, native) in natives {
+        globals.define(name.clone(), native.clone());
+    }
+    Rc::new(RefCell::new(globals))
+}
+
+/// Count of steps between checking the duration of the run.
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+/// Count of tasks between checking the time budget of resumable runs, which
+/// is checked more often than the duration since budgets are short slices.
+const BUDGET_CHECK_INTERVAL: u64 = 64;
+
+/// Location of a local variable as resolved by the resolver.
+#[derive(Debug, Clone, Copy)]
+struct LocalSlot {
+    /// Count of environments between the current one and the one
+    /// the variable is defined in.
+    depth: usize,
+    /// Index of the variable in its environment.
+    slot: usize,
+}
+
+/// State of the interpreter before executing code, which is restored when
+/// the code fails.
+#[derive(Debug)]
+struct Checkpoint {
+    tasks_len: usize,
+    values_len: usize,
+    environment: EnvironmentRef,
+    call_depth: usize,
+}
 
 #[derive(Debug)]
 pub struct Interpreter {
+    /// Global bindings of the natives, which are defined again on resets.
+    natives: Vec<(Rc<str>, LoxValue)>,
     globals: EnvironmentRef,
     environment: EnvironmentRef,
-    // This is implemented as a map in the book, however this is not possible
-    // in rust because `Expr` can't implement `Ord, Eq, Hash`
-    locals: Vec<(Expr, usize)>,
+    /// Resolved depths and slots of local variables.
+    /// In the book this map is keyed by the expressions themselves, which we
+    /// identify by the IDs of their nodes. Class and extension statements are
+    /// keyed by their own IDs for the classes they look up.
+    locals: HashMap<NodeId, LocalSlot>,
+    /// ID of the first token of the next scanned code, keeping the IDs of the
+    /// tokens unique across all the code run by the interpreter.
+    next_token_id: u64,
+    /// ID of the first node of the next parsed code, keeping the keys of the
+    /// resolved nodes unique across all the code run by the interpreter.
+    next_node_id: NodeId,
+    heap: Heap,
+    options: InterpreterOptions,
+    /// Count of the currently nested function calls.
+    call_depth: usize,
+    /// Count of the evaluated statements and expressions in the current run.
+    steps: u64,
+    /// Count of the objects created in the current run.
+    allocations: u64,
+    /// Count of the bytes of the strings and list elements created in the
+    /// current run.
+    memory: u64,
+    /// Class of the weak handles created by the `weak` native, which isn't
+    /// defined as a global.
+    weak_class: LoxClassRef,
+    /// Class of the instances returned by the `gcStats` native.
+    heap_stats_class: LoxClassRef,
+    /// Class of the coroutines created by the `coroutine` native.
+    coroutine_class: LoxClassRef,
+    /// Count of the coroutines running inside each other.
+    running_coroutines: usize,
+    /// Value passed to `yield()` by the running coroutine, which suspends it.
+    yielded: Option<LoxValue>,
+    /// Count of the classes changed with `extend`, telling instances when the
+    /// methods bound to them get outdated.
+    class_revision: u64,
+    run_started: Instant,
+    cancel: CancelHandle,
+    /// Pending tasks of the running execution.
+    tasks: Vec<Task>,
+    /// Values of the evaluated expressions, which are consumed by the tasks.
+    values: Vec<LoxValue>,
+    /// Destination of the printed values.
+    output: Sink,
+    /// Destination of the errors which don't stop the execution.
+    error_output: Sink,
+    /// Printed values and reported errors captured instead of writing them
+    /// to their outputs.
+    captured: Option<Diagnostics>,
+    /// Count of the errors reported without stopping the execution.
+    reported_errors: usize,
+    /// Time returned by `clock()` next in deterministic mode.
+    fake_time: f64,
+    /// Callbacks scheduled with `setTimeout` and `setInterval`.
+    timers: Timers,
+    observer: Option<Observer>,
+    /// Script started in resumable mode, which is kept while it's suspended.
+    script: Option<Script>,
+    /// Checks if natives can suspend the running code.
+    resumable: bool,
+    /// Request of the native suspending the running code for the host.
+    suspended: Option<LoxValue>,
+    /// End of the time budget of the running slice of a resumable script.
+    deadline: Option<Instant>,
+    /// Count of the tasks run in the current slice.
+    slice_tasks: u64,
+    /// Checks if the running script pauses before each statement, which also
+    /// keeps the names of the local variables for `environment_chain()`.
+    stepping: bool,
+    /// Checks if the stepping script continues with the statement it paused
+    /// before, which runs without pausing again.
+    step_resumed: bool,
+    /// Token of the call of the running native, which is used for the calls
+    /// it makes back into Lox code.
+    native_call: Option<Token>,
+    /// Error of the call made back into Lox code by the running native,
+    /// which is rethrown once the native fails with its message.
+    native_error: Option<LoxError>,
+    #[cfg(feature = "jit")]
+    jit: jit::Jit,
 }
 
 impl Default for Interpreter {
@@ -38,125 +212,1566 @@ impl Default for Interpreter {
 
 impl Interpreter {
     pub fn new() -> Self {
-        let mut globals = Environment::default();
-        globals.define(CLOCK_NAME.into(), LoxValue::Callable(LoxCallable::Clock));
-        let globals = Rc::new(RefCell::new(globals));
+        Self::builder().build()
+    }
+
+    pub fn with_options(options: InterpreterOptions) -> Self {
+        Self::builder().options(options).build()
+    }
+
+    /// Returns builder for configuring the options, outputs and natives of
+    /// the interpreter.
+    pub fn builder() -> InterpreterBuilder {
+        InterpreterBuilder::default()
+    }
+
+    fn create(
+        options: InterpreterOptions,
+        natives: Vec<NativeFunction>,
+        classes: Vec<NativeClass>,
+    ) -> Self {
+        let mut natives = natives::bindings(natives);
+        // Native classes are defined again on resets like the native functions.
+        natives.extend(classes.into_iter().map(|class| {
+            let name = class.name().clone();
+            let class = LoxClass::native(Rc::new(class));
+            let class = LoxCallable::Class(Rc::new(RefCell::new(class)));
+            (name, LoxValue::Callable(class))
+        }));
+        let globals = natives_environment(&natives);
         let environment = globals.clone();
 
         Self {
+            natives,
             globals,
             environment,
-            locals: Vec::new(),
+            locals: HashMap::new(),
+            next_token_id: 0,
+            next_node_id: NodeId::default(),
+            heap: Heap::default(),
+            options,
+            call_depth: 0,
+            steps: 0,
+            allocations: 0,
+            memory: 0,
+            weak_class: Rc::new(RefCell::new(LoxClass::native(Rc::new(
+                natives::weak_class(),
+            )))),
+            heap_stats_class: Rc::new(RefCell::new(LoxClass::native(Rc::new(NativeClass::new(
+                "HeapStats",
+            ))))),
+            coroutine_class: Rc::new(RefCell::new(LoxClass::native(Rc::new(
+                coroutines::coroutine_class(),
+            )))),
+            running_coroutines: 0,
+            yielded: None,
+            class_revision: 0,
+            run_started: Instant::now(),
+            cancel: CancelHandle::default(),
+            tasks: Vec::new(),
+            values: Vec::new(),
+            output: Sink(Box::new(std::io::stdout())),
+            error_output: Sink(Box::new(std::io::stderr())),
+            captured: None,
+            reported_errors: 0,
+            fake_time: 0.0,
+            timers: Timers::default(),
+            observer: None,
+            script: None,
+            resumable: false,
+            suspended: None,
+            deadline: None,
+            slice_tasks: 0,
+            stepping: false,
+            step_resumed: false,
+            native_call: None,
+            native_error: None,
+            #[cfg(feature = "jit")]
+            jit: jit::Jit::default(),
+        }
+    }
+
+    /// Clears all global definitions of the previous runs, keeping only the
+    /// natives while reusing the configuration of the interpreter.
+    pub fn reset(&mut self) {
+        // Suspended scripts are dropped with the pending tasks of their calls.
+        self.script = None;
+        self.tasks.clear();
+        self.values.clear();
+        self.call_depth = 0;
+        self.running_coroutines = 0;
+        self.yielded = None;
+        self.timers = Timers::default();
+
+        let old_globals = std::mem::replace(&mut self.globals, natives_environment(&self.natives));
+        self.environment = self.globals.clone();
+        // Functions defined as globals reference the old environment in
+        // cycles, which aren't tracked by the heap.
+        old_globals.borrow_mut().clear();
+        self.collect_garbage();
+
+        self.locals.clear();
+        self.fake_time = 0.0;
+        #[cfg(feature = "jit")]
+        {
+            self.jit = jit::Jit::default();
         }
     }
-    pub fn interpret(&mut self, stmts: &[Stmt]) {
+
+    /// Scans, parses, resolves and executes the source code in a child
+    /// environment of the globals, which is discarded afterwards.
+    /// Definitions of the source aren't visible in the later runs, while
+    /// assigning the existing globals still changes them.
+    pub fn run_isolated(&mut self, source: &str) -> Result<(), RunError> {
+        let globals = self.globals.clone();
+        let isolated = self.new_environment(globals.clone());
+        self.globals = isolated.clone();
+        self.environment = isolated;
+
+        let result = crate::run(self, source.to_owned()).map(|_| ());
+
+        self.globals = globals.clone();
+        self.environment = globals;
+        self.collect_garbage_if_needed();
+
+        result
+    }
+
+    /// Scans, parses, resolves and executes the source code in the global
+    /// environment, returning the value of the last statement when it's an
+    /// expression statement, like `config_width * 2;`.
+    pub fn eval(&mut self, source: &str) -> Result<Option<LoxValue>, RunError> {
+        crate::run(self, source.to_owned())
+    }
+
+    /// Executes the given statements reporting errors of each top level statement
+    /// and continuing with the next ones.
+    /// Returns the value of the last statement when it's an expression
+    /// statement which didn't fail.
+    /// Returns an error only when the execution has been aborted, or on the
+    /// first runtime error in stop-on-error mode.
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> LoxResult<Option<LoxValue>> {
+        self.start_run();
+        let mut last_value = None;
         for stmt in stmts {
-            match self.execute(stmt) {
-                Ok(()) => {}
-                Err(err) => eprintln!("{err}"),
+            last_value = match self.execute(stmt) {
+                Ok(value) => value,
+                Err(err) => {
+                    self.handle_top_level_error(err)?;
+                    None
+                }
+            };
+
+            self.collect_garbage_if_needed();
+        }
+
+        Ok(last_value)
+    }
+
+    /// Parses the source code as a single expression then evaluates it in the
+    /// current scope of the interpreter, which is the global one between runs.
+    /// Resolving the expression doesn't change how the variables of the
+    /// previous runs are resolved, since it can't declare any locals.
+    pub fn eval_expr_in_current_scope(&mut self, source: &str) -> Result<LoxValue, RunError> {
+        let tokens = self.scan(source.to_owned()).map_err(|errors| {
+            let count = errors.len();
+            errors.into_iter().for_each(|err| self.report_error(err));
+            RunError::Scan(count)
+        })?;
+        let (expr, source_map) = Parser::with_options(tokens, self.options.parser.clone())
+            .with_first_node_id(self.next_node_id)
+            .parse_expression()
+            .map_err(|err| {
+                self.report_error(err);
+                RunError::Parse(1)
+            })?;
+        self.next_node_id = source_map.next_id();
+
+        let stmt = Stmt::Expression(expr);
+        Resolver::new(self).resolve(std::slice::from_ref(&stmt))?;
+        let Stmt::Expression(expr) = stmt else {
+            unreachable!("Statement is created from the expression")
+        };
+
+        self.start_run();
+        let value = self.evaluate(&expr)?;
+        Ok(value)
+    }
+
+    pub fn options(&self) -> &InterpreterOptions {
+        &self.options
+    }
+
+    /// Starts capturing the printed values and reported errors until they are
+    /// taken.
+    pub(crate) fn capture_output(&mut self) {
+        self.captured = Some(Diagnostics::default());
+    }
+
+    pub(crate) fn take_captured(&mut self) -> Option<Diagnostics> {
+        self.captured.take()
+    }
+
+    /// Returns the global variables sorted by their names.
+    pub fn global_values(&self) -> Vec<(Rc<str>, LoxValue)> {
+        self.globals.borrow().global_values()
+    }
+
+    /// Iterates over the global variables defined by the scripts sorted by
+    /// their names. Natives are skipped unless they are redefined.
+    pub fn globals(&self) -> impl Iterator<Item = (Rc<str>, LoxValue)> + '_ {
+        self.global_values().into_iter().filter(|(name, value)| {
+            !self
+                .natives
+                .iter()
+                .any(|(native, binding)| native == name && binding == value)
+        })
+    }
+
+    /// Returns the value of the global variable with the given name.
+    pub fn get_global(&self, name: &str) -> Option<LoxValue> {
+        self.globals.borrow().get_global(name)
+    }
+
+    /// Returns the global variable with the given name if it's a number.
+    pub fn get_global_number(&self, name: &str) -> Option<f64> {
+        self.get_global(name)?.as_number()
+    }
+
+    /// Returns the global variable with the given name if it's a boolean.
+    pub fn get_global_bool(&self, name: &str) -> Option<bool> {
+        self.get_global(name)?.as_bool()
+    }
+
+    /// Returns the global variable with the given name if it's a string.
+    pub fn get_global_string(&self, name: &str) -> Option<Rc<str>> {
+        match self.get_global(name)? {
+            LoxValue::String(text) => Some(text.to_rc()),
+            _ => None,
+        }
+    }
+
+    /// Returns the variables of each environment in the chain of the running
+    /// code, from the innermost scope to the globals and the ones shared
+    /// between isolated scripts. Natives are skipped like in `globals()`.
+    /// Local variables are named by their slots, like `#0`, unless they are
+    /// defined while stepping through a script, which keeps their names.
+    pub fn environment_chain(&self) -> Vec<Vec<(Rc<str>, LoxValue)>> {
+        let mut chain = Vec::new();
+        let mut next = Some(self.environment.clone());
+        while let Some(env) = next {
+            if Rc::ptr_eq(&env, &self.globals) {
+                chain.push(self.globals().collect());
+            } else {
+                chain.push(env.borrow().bindings());
             }
+            next = env.borrow().enclosing.clone();
+        }
+
+        chain
+    }
+
+    /// Captures the current bindings of the global variables, which can be
+    /// compared with later snapshots.
+    pub fn snapshot(&self) -> EnvSnapshot {
+        EnvSnapshot::new(self.global_values())
+    }
+
+    /// Scans the source code with the IDs of its tokens following the IDs of
+    /// the code scanned before, since the resolved variables are keyed by them.
+    pub(crate) fn scan(&mut self, source: String) -> Result<Vec<Token>, Vec<ScanError>> {
+        let tokens = crate::scan(source, self.next_token_id)?;
+        // End of file is the last scanned token, so it has the highest ID.
+        if let Some(eof) = tokens.last() {
+            self.next_token_id = eof.id() + 1;
+        }
+
+        Ok(tokens)
+    }
+
+    /// Parses the tokens with the IDs of their nodes following the IDs of the
+    /// code parsed before, since the resolved nodes are keyed by them.
+    pub(crate) fn parse(&mut self, tokens: Vec<Token>) -> ParseResults {
+        let parse_res = Parser::with_options(tokens, self.options.parser.clone())
+            .with_first_node_id(self.next_node_id)
+            .parse();
+        self.next_node_id = parse_res.source_map.next_id();
+
+        parse_res
+    }
+
+    pub(crate) fn reported_errors(&self) -> usize {
+        self.reported_errors
+    }
+
+    /// Reports the error which doesn't stop the execution.
+    pub(crate) fn report_error(&mut self, err: impl std::fmt::Display) {
+        self.reported_errors += 1;
+        match &mut self.captured {
+            Some(captured) => captured.errors.push(err.to_string()),
+            None => writeln!(self.error_output.0, "{err}").expect("Error while writing errors"),
+        }
+    }
+
+    /// Reports the warning to the error output. Captured runs leave warnings
+    /// out, since engines only compare the output and the errors.
+    pub(crate) fn report_warning(&mut self, warning: &LoxWarning) {
+        if self.captured.is_none() {
+            writeln!(self.error_output.0, "{warning}").expect("Error while writing errors");
+        }
+    }
+
+    /// Registers the observer notified while executing the programs, replacing
+    /// the previous one.
+    pub fn set_observer(&mut self, observer: impl ExecutionObserver + 'static) {
+        self.observer = Some(Observer(Box::new(observer)));
+    }
+
+    /// Removes the registered observer returning it.
+    pub fn take_observer(&mut self) -> Option<Box<dyn ExecutionObserver>> {
+        self.observer.take().map(|observer| observer.0)
+    }
+
+    fn observe(&mut self, notify: impl FnOnce(&mut dyn ExecutionObserver)) {
+        if let Some(observer) = &mut self.observer {
+            notify(observer.0.as_mut());
         }
     }
 
-    pub fn resolve(&mut self, expr: &Expr, depth: usize) {
-        if let Some((_exp, dep)) = self.locals.iter_mut().find(|(ex, _dep)| ex == expr) {
-            *dep = depth;
+    /// Returns a handle which can be used to cancel the execution from other threads.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Resets the counted steps, allocations, memory and time for a new run,
+    /// along with the cancel handle so cancelling an earlier run which didn't
+    /// reach the end of a top level statement doesn't abort this one.
+    fn start_run(&mut self) {
+        self.cancel.reset();
+        self.steps = 0;
+        self.allocations = 0;
+        self.memory = 0;
+        self.run_started = Instant::now();
+    }
+
+    fn budget_used_up(&mut self) -> bool {
+        let Some(deadline) = self.deadline else {
+            return false;
+        };
+        self.slice_tasks += 1;
+        self.slice_tasks.is_multiple_of(BUDGET_CHECK_INTERVAL) && Instant::now() >= deadline
+    }
+
+    /// Counts an evaluation step, failing when the configured limit is exceeded.
+    fn consume_step(&mut self) -> LoxResult<()> {
+        self.steps += 1;
+        if let Some(limit) = self.options.max_steps
+            && self.steps > limit
+        {
+            return Err(LoxError::StepLimitExceeded { limit });
+        }
+
+        let sandbox = &self.options.sandbox;
+        if let Some(limit) = sandbox.max_allocations
+            && self.allocations > limit
+        {
+            return Err(LoxError::AllocationLimitExceeded { limit });
+        }
+        if let Some(limit) = sandbox.max_memory
+            && self.memory > limit
+        {
+            return Err(LoxError::MemoryLimitExceeded { limit });
+        }
+        // Reading the time is expensive compared to one step.
+        if let Some(limit) = sandbox.max_duration
+            && self.steps.is_multiple_of(TIME_CHECK_INTERVAL)
+            && self.run_started.elapsed() > limit
+        {
+            return Err(LoxError::TimeLimitExceeded { limit });
+        }
+
+        Ok(())
+    }
+
+    /// Counts the bytes of a created string, which are checked against the
+    /// memory limit of the sandbox at the next step.
+    pub(super) fn count_string(&mut self, len: usize) {
+        self.memory += len as u64;
+    }
+
+    /// Counts the elements added to lists like the bytes of strings.
+    pub(super) fn count_elements(&mut self, count: usize) {
+        self.memory += (count * size_of::<LoxValue>()) as u64;
+    }
+
+    /// Counts the objects reachable from the globals and the running code.
+    /// Values held only by the native being called aren't counted.
+    pub fn heap_stats(&self) -> HeapStats {
+        self.heap.stats(|tracer| self.mark_roots(tracer))
+    }
+
+    fn collect_garbage(&mut self) {
+        let mut tracer = Tracer::default();
+        self.mark_roots(&mut tracer);
+        self.heap.collect(tracer);
+    }
+
+    /// Marks the values which the globals, the running code and the pending
+    /// timers reference.
+    fn mark_roots(&self, tracer: &mut Tracer) {
+        tracer.mark_env(&self.globals);
+        tracer.mark_env(&self.environment);
+        for value in &self.values {
+            value.trace(tracer);
+        }
+        for task in &self.tasks {
+            task.trace(tracer);
+        }
+        self.timers.trace(tracer);
+    }
+
+    /// Runs the cycle collector when the tracked environments exceeded the threshold
+    /// and the interpreter is on top level.
+    /// Top level is the only point where all living values are reachable from
+    /// the globals, while function calls and expressions can still hold values
+    /// in the middle of their evaluation.
+    fn collect_garbage_if_needed(&mut self) {
+        if self.heap.should_collect()
+            && self.values.is_empty()
+            && Rc::ptr_eq(&self.environment, &self.globals)
+        {
+            self.collect_garbage();
+        }
+    }
+
+    /// Creates a new environment enclosed by the given one, tracking it for
+    /// cycle collection.
+    fn new_environment(&mut self, enclosing: EnvironmentRef) -> EnvironmentRef {
+        self.allocations += 1;
+        let env = Environment::with_enclosing(enclosing);
+        self.heap.track(&env);
+        env
+    }
+
+    pub(crate) fn resolve(&mut self, id: NodeId, depth: usize, slot: usize) {
+        self.locals.insert(id, LocalSlot { depth, slot });
+    }
+
+    /// Defines a variable in the current environment.
+    /// Variables on top level are globals which are defined by name, while
+    /// local variables take the next slot in their scope.
+    fn define_variable(&mut self, name: &Token, value: LoxValue) {
+        let mut env = self.environment.borrow_mut();
+        if Rc::ptr_eq(&self.environment, &self.globals) {
+            env.define(name.lexeme.to_owned(), value);
+        } else {
+            self.push_local(&mut env, &name.lexeme, value);
+        }
+    }
+
+    /// Defines the local variable in the next slot of the environment, which
+    /// keeps its name while stepping.
+    pub(super) fn push_local(&self, env: &mut Environment, name: &str, value: LoxValue) {
+        if self.stepping {
+            env.push_named(name.into(), value);
         } else {
-            self.locals.push((expr.to_owned(), depth));
+            env.push(value);
         }
     }
 
-    fn execute(&mut self, stmt: &Stmt) -> LoxResult<()> {
+    /// Moves the global declared by the current script into the namespace
+    /// shared by all scripts, which encloses the globals of the scripts
+    /// running isolated. Otherwise the scripts already share their globals.
+    fn export(&mut self, name: &Token) {
+        let mut globals = self.globals.borrow_mut();
+        let Some(shared) = globals.enclosing.clone() else {
+            return;
+        };
+        let value = globals
+            .remove(&name.lexeme)
+            .expect("Exported declaration is executed before");
+        shared.borrow_mut().define(name.lexeme.to_owned(), value);
+    }
+
+    /// Handles the error of a top level statement, reporting it when the
+    /// execution continues with the next statement and returning it otherwise.
+    fn handle_top_level_error(&mut self, err: LoxError) -> LoxResult<()> {
+        if err.aborts_execution() {
+            self.cancel.reset();
+            return Err(err);
+        }
+        if self.options.stop_on_error {
+            return Err(err);
+        }
+
+        self.report_error(err);
+        Ok(())
+    }
+
+    /// Executes the top level statement to its end, running all the tasks it
+    /// pushes, and returns the value of expression statements.
+    /// On errors, the pending tasks of the statement are dropped and the state
+    /// of the interpreter is restored to the one before the execution.
+    fn execute(&mut self, stmt: &Stmt) -> LoxResult<Option<LoxValue>> {
+        let checkpoint = self.checkpoint();
+        let result = self
+            .start_top_level(stmt)
+            .and_then(|()| self.run(checkpoint.tasks_len));
+        self.finish_top_level(stmt, &checkpoint, result)
+    }
+
+    fn start_top_level(&mut self, stmt: &Stmt) -> LoxResult<()> {
+        match stmt {
+            // The value is kept on the stack instead of being discarded.
+            Stmt::Expression(expr) => self.enter_stmt(stmt).and_then(|()| self.start_expr(expr)),
+            stmt => self.start_stmt(stmt),
+        }
+    }
+
+    /// Finishes the top level statement once its tasks are done or failed.
+    /// Suspended statements keep their state until they are resumed.
+    fn finish_top_level(
+        &mut self,
+        stmt: &Stmt,
+        checkpoint: &Checkpoint,
+        result: LoxResult<()>,
+    ) -> LoxResult<Option<LoxValue>> {
+        if let Err(err @ (LoxError::Suspended | LoxError::Paused)) = result {
+            return Err(err);
+        }
+
+        let result = result.map_err(|err| self.add_trace(err, checkpoint.tasks_len));
+        match &result {
+            Ok(()) => self.observe(|observer| observer.on_stmt_exit(stmt)),
+            Err(err) => self.observe(|observer| observer.on_error(err)),
+        }
+        if let Err(err) = result {
+            self.restore(checkpoint);
+            return Err(err);
+        }
+
+        let value = matches!(stmt, Stmt::Expression(..)).then(|| self.pop_value());
+        Ok(value)
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> LoxResult<LoxValue> {
+        let checkpoint = self.checkpoint();
+        let result = self
+            .start_expr(expr)
+            .and_then(|()| self.run(checkpoint.tasks_len))
+            .map_err(|err| self.add_trace(err, checkpoint.tasks_len));
+        match result {
+            Ok(()) => Ok(self.pop_value()),
+            Err(err) => {
+                self.observe(|observer| observer.on_error(&err));
+                self.restore(&checkpoint);
+                Err(err)
+            }
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            tasks_len: self.tasks.len(),
+            values_len: self.values.len(),
+            environment: self.environment.clone(),
+            call_depth: self.call_depth,
+        }
+    }
+
+    /// Drops the tasks and values pushed after the checkpoint, restoring the
+    /// state of the interpreter to the one it had then.
+    fn restore(&mut self, checkpoint: &Checkpoint) {
+        self.tasks.truncate(checkpoint.tasks_len);
+        self.values.truncate(checkpoint.values_len);
+        self.environment = checkpoint.environment.clone();
+        self.call_depth = checkpoint.call_depth;
+    }
+
+    /// ID of the class the running function belongs to, which is the one
+    /// whose private members the running code can access.
+    pub(crate) fn current_class_id(&self) -> Option<u64> {
+        self.tasks.iter().rev().find_map(|task| match task {
+            Task::FinishCall { function, .. } => Some(function.class_id()),
+            _ => None,
+        })?
+    }
+
+    /// Adds the calls running above the given base of the tasks to the stack
+    /// trace of the runtime error.
+    fn add_trace(&self, mut err: LoxError, base: usize) -> LoxError {
+        let LoxError::Error { token, trace, .. } = &mut err else {
+            return err;
+        };
+        if !trace.is_empty() {
+            return err;
+        }
+
+        let mut frames = Vec::new();
+        let mut line = token.line;
+        for task in self.tasks[base..].iter().rev() {
+            if let Task::FinishCall {
+                function,
+                call_line,
+                ..
+            } = task
+            {
+                frames.push(TraceFrame {
+                    function: Some(function.declaration.name.lexeme.clone()),
+                    line,
+                });
+                line = *call_line;
+            }
+        }
+        if !frames.is_empty() {
+            frames.push(TraceFrame {
+                function: None,
+                line,
+            });
+        }
+        *trace = frames.into();
+
+        err
+    }
+
+    /// Runs the pending tasks until the stack is back at the given length.
+    /// Resumable scripts pause between the tasks once their time budget is
+    /// used up, or before each statement while stepping.
+    fn run(&mut self, base: usize) -> LoxResult<()> {
+        while self.tasks.len() > base {
+            if self.budget_used_up() || self.step_due() {
+                return Err(LoxError::Paused);
+            }
+            let task = self.tasks.pop().expect("Stack is longer than its base");
+            if let Err(err) = self.run_task(task) {
+                self.run_finally(err, base, base)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the innermost `finally` block pending above the base when the
+    /// error is a runtime error, which is reported again once the block is
+    /// done. Other errors are returned right away without running them.
+    /// Stack traces include the calls above the trace base.
+    fn run_finally(&mut self, err: LoxError, base: usize, trace_base: usize) -> LoxResult<()> {
+        if !matches!(err, LoxError::Error { .. }) {
+            return Err(err);
+        }
+        let Some(index) = self.tasks[base..]
+            .iter()
+            .rposition(|task| matches!(task, Task::Finally { .. }))
+        else {
+            return Err(err);
+        };
+
+        // The trace is taken before the calls inside the try block are dropped.
+        let err = self.add_trace(err, trace_base);
+        let dropped = self.tasks.split_off(base + index + 1);
+        for task in dropped.into_iter().rev() {
+            if let Task::ExitStmt(exited) = task {
+                self.observe(|observer| observer.on_stmt_exit(exited.stmt()));
+            }
+        }
+
+        let Some(Task::Finally {
+            finally,
+            environment,
+            values_len,
+            call_depth,
+        }) = self.tasks.pop()
+        else {
+            unreachable!("Finally task is found above");
+        };
+        self.environment = environment;
+        self.values.truncate(values_len);
+        self.call_depth = call_depth;
+        self.tasks.push(Task::Rethrow(err));
+        self.tasks.push(Task::Execute(finally));
+
+        Ok(())
+    }
+
+    /// Checks if the current function is inside a try block, whose `finally`
+    /// block runs before the function returns.
+    fn in_try_block(&self) -> bool {
+        self.tasks
+            .iter()
+            .rev()
+            .take_while(|task| !matches!(task, Task::FinishCall { .. }))
+            .any(|task| matches!(task, Task::Finally { .. }))
+    }
+
+    /// Checks the limits of the execution before entering the statement.
+    fn enter_stmt(&mut self, stmt: &Stmt) -> LoxResult<()> {
+        if self.cancel.is_cancelled() {
+            return Err(LoxError::Cancelled);
+        }
+        self.consume_step()?;
+        self.observe(|observer| observer.on_stmt_enter(stmt));
+
+        Ok(())
+    }
+
+    /// Starts the execution of the statement by pushing the tasks for it.
+    fn start_stmt(&mut self, stmt: &Stmt) -> LoxResult<()> {
+        self.enter_stmt(stmt)?;
         match stmt {
             Stmt::Expression(expr) => {
                 // Expression on their own doesn't need the evaluated
                 // value from expression. Examples `1 + 2;` `true;`
-                let _ = self.evaluate(expr)?;
+                self.tasks.push(Task::Discard);
+                self.start_expr(expr)?;
             }
-            Stmt::Print(expr) => {
-                let val = self.evaluate(expr)?;
-                println!("{val}");
+            Stmt::Print { expr, .. } => {
+                self.tasks.push(Task::Print);
+                self.start_expr(expr)?;
             }
-            Stmt::Var { name, initializer } => {
-                let val = if let Some(expr) = initializer {
-                    self.evaluate(expr)?
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                if let Some(expr) = initializer {
+                    self.tasks.push(Task::DefineVariable(name.to_owned()));
+                    self.start_expr(expr)?;
                 } else {
-                    LoxValue::Nil
-                };
-
-                self.environment
-                    .borrow_mut()
-                    .define(name.lexeme.to_owned(), val);
+                    self.define_variable(name, LoxValue::Nil);
+                }
+            }
+            Stmt::MultiAssign {
+                targets, values, ..
+            } => {
+                self.tasks.push(Task::AssignTargets(targets.clone()));
+                self.tasks.push(Task::AssignValues {
+                    values: values.clone(),
+                    index: 0,
+                });
+                for target in targets.iter().rev() {
+                    match target {
+                        Expr::Get { object, .. } => {
+                            self.tasks.push(Task::Evaluate(object.clone()));
+                        }
+                        Expr::Index { object, index, .. } => {
+                            self.tasks.push(Task::Evaluate(index.clone()));
+                            self.tasks.push(Task::Evaluate(object.clone()));
+                        }
+                        _ => {}
+                    }
+                }
             }
-            Stmt::Block { statements } => {
-                let env = Environment::with_enclosing(self.environment.clone());
-                self.execute_block(statements, env)?;
+            Stmt::Block { statements, .. } => {
+                self.begin_scope();
+                self.tasks.push(Task::Statements {
+                    stmts: statements.clone(),
+                    index: 0,
+                });
             }
             Stmt::If {
+                keyword,
                 condition,
                 then_branch,
                 else_branch,
+                ..
             } => {
-                let cond_val = self.evaluate(condition)?;
-                if cond_val.is_truthy() {
-                    self.execute(then_branch)?;
-                } else if let Some(else_branch) = else_branch {
-                    self.execute(else_branch)?;
-                }
+                self.tasks.push(Task::Branch {
+                    keyword: keyword.to_owned(),
+                    then_branch: then_branch.clone(),
+                    else_branch: else_branch.clone(),
+                });
+                self.start_expr(condition)?;
+            }
+            Stmt::While {
+                keyword,
+                condition,
+                body,
+                ..
+            } => {
+                self.tasks.push(Task::WhileCondition {
+                    keyword: keyword.to_owned(),
+                    condition: condition.clone(),
+                    body: body.clone(),
+                    iterations: 0,
+                });
             }
-            Stmt::While { condition, body } => {
-                while self.evaluate(condition)?.is_truthy() {
-                    self.execute(body)?;
+            Stmt::For {
+                keyword,
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                let for_loop = Rc::new(ForLoop {
+                    keyword: keyword.to_owned(),
+                    condition: condition.clone(),
+                    increment: increment.clone(),
+                    body: body.clone(),
+                    iterations: Cell::new(0),
+                });
+                match initializer {
+                    // The initializer gets its own scope like a block around the loop.
+                    Some(initializer) => {
+                        self.begin_scope();
+                        self.tasks.push(Task::ForCondition(for_loop));
+                        self.start_stmt(initializer)?;
+                    }
+                    None => self.tasks.push(Task::ForCondition(for_loop)),
                 }
             }
+            Stmt::ForIn {
+                name,
+                keyword,
+                iterable,
+                body,
+                ..
+            } => {
+                let for_in = Rc::new(ForInLoop {
+                    name: name.clone(),
+                    keyword: keyword.clone(),
+                    body: body.clone(),
+                    iterations: Cell::new(0),
+                });
+                self.tasks.push(Task::ForInIterable(for_in));
+                self.start_expr(iterable)?;
+            }
+            Stmt::Try { body, finally, .. } => {
+                self.tasks.push(Task::Finally {
+                    finally: finally.clone(),
+                    environment: self.environment.clone(),
+                    values_len: self.values.len(),
+                    call_depth: self.call_depth,
+                });
+                self.tasks.push(Task::Execute(body.clone()));
+            }
             Stmt::Function(declaration) => {
-                let func =
-                    LoxFunction::new(declaration.to_owned(), self.environment.clone(), false);
-                let function = LoxCallable::LoxFunction(func);
-                self.environment.borrow_mut().define(
-                    declaration.name.lexeme.to_owned(),
-                    LoxValue::Callable(function),
-                );
+                let func = LoxFunction::new(declaration.clone(), self.environment.clone(), false)
+                    .in_class(self.current_class_id());
+                let function = LoxCallable::LoxFunction(Rc::new(func));
+                self.define_variable(&declaration.name, LoxValue::Callable(function));
             }
             Stmt::Return {
-                keyword: _,
+                keyword,
                 value_expr,
+                ..
+            } => match value_expr {
+                // Calls to functions in tail position replace the frame of the
+                // function currently being called instead of nesting a new one.
+                // Observers get the returns of all calls, so frames are kept
+                // while observing, and inside try blocks until their `finally`
+                // blocks are done.
+                Some(Expr::Call {
+                    callee,
+                    paren,
+                    arguments,
+                    ..
+                }) if self.observer.is_none() && !self.in_try_block() => {
+                    self.tasks.push(Task::Arguments {
+                        paren: paren.to_owned(),
+                        arguments: arguments.clone(),
+                        index: 0,
+                        tail: true,
+                    });
+                    self.tasks.push(Task::Evaluate(callee.clone()));
+                }
+                Some(expr) => {
+                    self.tasks.push(Task::Return(keyword.to_owned()));
+                    self.start_expr(expr)?;
+                }
+                None => self.unwind(ControlFlow::Return(LoxValue::Nil), keyword)?,
+            },
+            Stmt::Export { declaration, .. } => {
+                let name = match declaration.as_ref() {
+                    Stmt::Function(declaration) => &declaration.name,
+                    Stmt::Class { name, .. } | Stmt::Var { name, .. } => name,
+                    _ => unreachable!("Parser only exports declarations"),
+                };
+                self.tasks.push(Task::Export(name.to_owned()));
+                self.tasks.push(Task::Execute(declaration.clone()));
+            }
+            Stmt::Break { keyword, .. } => self.unwind(ControlFlow::Break, keyword)?,
+            Stmt::Continue { keyword, .. } => self.unwind(ControlFlow::Continue, keyword)?,
+            Stmt::Class {
+                name,
+                super_class,
+                methods,
+                doc,
+                id,
+            } => self.evaluate_class(*id, name, super_class.as_ref(), methods, doc.clone())?,
+            Stmt::Extend {
+                id, class, methods, ..
+            } => self.evaluate_extend(*id, class, methods)?,
+        };
+
+        Ok(())
+    }
+
+    /// Starts the evaluation of the expression, pushing its value directly when
+    /// it can be computed without evaluating other expressions, otherwise pushing
+    /// the tasks evaluating its children and combining their values.
+    fn start_expr(&mut self, expr: &Expr) -> LoxResult<()> {
+        self.consume_step()?;
+        match expr {
+            Expr::Grouping { expression, .. } => {
+                self.tasks.push(Task::Evaluate(expression.clone()))
+            }
+            Expr::Literal { value, .. } => self.values.push(value.into()),
+            Expr::Unary {
+                operator, right, ..
+            } => {
+                self.tasks.push(Task::Unary(operator.to_owned()));
+                self.tasks.push(Task::Evaluate(right.clone()));
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                self.tasks.push(Task::BinaryRight {
+                    operator: operator.to_owned(),
+                    right: right.clone(),
+                });
+                self.tasks.push(Task::Evaluate(left.clone()));
+            }
+            Expr::Variable { id, name } | Expr::This { id, keyword: name } => {
+                let value = self.lookup_variable(*id, name)?;
+                self.values.push(value);
+            }
+            Expr::Assign { id, name, value } => {
+                self.tasks.push(Task::Assign {
+                    id: *id,
+                    name: name.to_owned(),
+                });
+                self.tasks.push(Task::Evaluate(value.clone()));
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                // Evaluate left first and only evaluate right if logical expand to it.
+                // This is necessary to avoid any side effect from evaluating right.
+                self.tasks.push(Task::Logical {
+                    operator: operator.to_owned(),
+                    right: right.clone(),
+                });
+                self.tasks.push(Task::Evaluate(left.clone()));
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => {
+                self.tasks.push(Task::Arguments {
+                    paren: paren.to_owned(),
+                    arguments: arguments.clone(),
+                    index: 0,
+                    tail: false,
+                });
+                self.tasks.push(Task::Evaluate(callee.clone()));
+            }
+            Expr::Get { object, name, .. } => {
+                self.tasks.push(Task::Get {
+                    object: object.clone(),
+                    name: name.to_owned(),
+                });
+                self.tasks.push(Task::Evaluate(object.clone()));
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => {
+                self.tasks.push(Task::SetValue {
+                    object: object.clone(),
+                    name: name.to_owned(),
+                    value: value.clone(),
+                });
+                self.tasks.push(Task::Evaluate(object.clone()));
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+                ..
+            } => {
+                self.tasks.push(Task::Index {
+                    object: object.clone(),
+                    bracket: bracket.to_owned(),
+                });
+                self.tasks.push(Task::Evaluate(index.clone()));
+                self.tasks.push(Task::Evaluate(object.clone()));
+            }
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+                ..
+            } => {
+                self.tasks.push(Task::SetIndexValue {
+                    object: object.clone(),
+                    bracket: bracket.to_owned(),
+                    value: value.clone(),
+                });
+                self.tasks.push(Task::Evaluate(index.clone()));
+                self.tasks.push(Task::Evaluate(object.clone()));
+            }
+            Expr::Super {
+                id,
+                keyword,
+                method,
+            } => {
+                let value = self.evaluate_super(*id, keyword, method)?;
+                self.values.push(value);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pop_value(&mut self) -> LoxValue {
+        self.values
+            .pop()
+            .expect("Continuations are pushed after the values they consume")
+    }
+
+    fn run_task(&mut self, task: Task) -> LoxResult<()> {
+        match task {
+            Task::Statements { stmts, index } => {
+                if index + 1 < stmts.len() {
+                    self.tasks.push(Task::Statements {
+                        stmts: stmts.clone(),
+                        index: index + 1,
+                    });
+                }
+                if let Some(stmt) = stmts.get(index) {
+                    if self.observer.is_some() {
+                        self.tasks.push(Task::ExitStmt(ExitedStmt::InList {
+                            stmts: stmts.clone(),
+                            index,
+                        }));
+                    }
+                    self.start_stmt(stmt)?;
+                }
+            }
+            Task::Execute(stmt) => {
+                if self.observer.is_some() {
+                    self.tasks
+                        .push(Task::ExitStmt(ExitedStmt::Shared(stmt.clone())));
+                }
+                self.start_stmt(&stmt)?;
+            }
+            Task::ExitStmt(exited) => self.observe(|observer| observer.on_stmt_exit(exited.stmt())),
+            Task::Evaluate(expr) => self.start_expr(&expr)?,
+            Task::Discard => {
+                self.pop_value();
+            }
+            Task::Print => {
+                let val = self.pop_value();
+                let text = match (
+                    &val,
+                    &self.options.number_format,
+                    &self.options.pretty_print,
+                ) {
+                    (LoxValue::Number(num), Some(format), _) => format.format(*num),
+                    (_, _, Some(options)) => val.pretty_with(options),
+                    _ => val.to_string(),
+                };
+                match &mut self.captured {
+                    Some(captured) => {
+                        captured.output.stdout.push_str(&text);
+                        captured.output.stdout.push('\n');
+                    }
+                    None => writeln!(self.output.0, "{text}").expect("Error while writing output"),
+                }
+            }
+            Task::DefineVariable(name) => {
+                let val = self.pop_value();
+                self.define_variable(&name, val);
+            }
+            Task::RestoreEnvironment(env) => self.environment = env,
+            Task::Export(name) => self.export(&name),
+            Task::Branch {
+                keyword,
+                then_branch,
+                else_branch,
+            } => {
+                if self.pop_condition(&keyword)? {
+                    self.tasks.push(Task::Execute(then_branch));
+                } else if let Some(else_branch) = else_branch {
+                    self.tasks.push(Task::Execute(else_branch));
+                }
+            }
+            Task::WhileCondition {
+                keyword,
+                condition,
+                body,
+                iterations,
+            } => {
+                // Long running loops on top level must be able to free
+                // their cycles without waiting for the loop to end.
+                self.collect_garbage_if_needed();
+                self.tasks.push(Task::WhileBody {
+                    keyword,
+                    condition: condition.clone(),
+                    body,
+                    iterations,
+                });
+                self.start_expr(&condition)?;
+            }
+            Task::WhileBody {
+                keyword,
+                condition,
+                body,
+                iterations,
             } => {
-                let value = match value_expr {
-                    Some(expr) => self.evaluate(expr)?,
-                    None => LoxValue::Nil,
+                if self.pop_condition(&keyword)? {
+                    self.check_iterations(iterations)?;
+                    self.tasks.push(Task::WhileCondition {
+                        keyword,
+                        condition,
+                        body: body.clone(),
+                        iterations: iterations + 1,
+                    });
+                    self.tasks.push(Task::Execute(body));
+                }
+            }
+            Task::ForCondition(for_loop) => {
+                self.collect_garbage_if_needed();
+                match &for_loop.condition {
+                    Some(condition) => {
+                        self.tasks.push(Task::ForBody(for_loop.clone()));
+                        self.start_expr(condition)?;
+                    }
+                    None => self.run_for_body(for_loop)?,
+                }
+            }
+            Task::ForBody(for_loop) => {
+                if self.pop_condition(&for_loop.keyword)? {
+                    self.run_for_body(for_loop)?;
+                }
+            }
+            Task::ForIncrement(for_loop) => {
+                self.tasks.push(Task::ForCondition(for_loop.clone()));
+                if let Some(increment) = &for_loop.increment {
+                    self.tasks.push(Task::Discard);
+                    self.start_expr(increment)?;
+                }
+            }
+            Task::ForInIterable(for_in) => {
+                let iteration = match self.pop_value() {
+                    LoxValue::List(list) => Iteration::List { list, index: 0 },
+                    LoxValue::String(text) => Iteration::Chars { text, offset: 0 },
+                    iterable => {
+                        let Some(method) = self.iterator_method(&iterable, "iterator") else {
+                            return Err(LoxError::new(
+                                for_in.keyword.to_owned(),
+                                codes::NOT_ITERABLE,
+                                Message::new(
+                                    "Can only iterate over lists, strings and instances with an 'iterator' method, but got {}.",
+                                    [iterable.describe_translated()],
+                                ),
+                            ));
+                        };
+                        let keyword = for_in.keyword.clone();
+                        self.tasks.push(Task::ForInIterator(for_in));
+                        return self.call_value(method, &keyword, Vec::new(), false);
+                    }
+                };
+                self.tasks.push(Task::ForInNext { for_in, iteration });
+            }
+            Task::ForInIterator(for_in) => {
+                // Iterators can be lists and strings too, like the keys of maps.
+                let iteration = match self.pop_value() {
+                    LoxValue::List(list) => Iteration::List { list, index: 0 },
+                    LoxValue::String(text) => Iteration::Chars { text, offset: 0 },
+                    iterator => Iteration::Iterator(iterator),
                 };
+                self.tasks.push(Task::ForInNext { for_in, iteration });
+            }
+            Task::ForInNext { for_in, iteration } => {
+                self.collect_garbage_if_needed();
+                match iteration {
+                    Iteration::List { list, index } => {
+                        let element = list.borrow().get(index).cloned();
+                        if let Some(element) = element {
+                            let iteration = Iteration::List {
+                                list,
+                                index: index + 1,
+                            };
+                            self.run_for_in_body(for_in, iteration, element)?;
+                        }
+                    }
+                    Iteration::Chars { text, offset } => {
+                        if let Some(ch) = text[offset..].chars().next() {
+                            let iteration = Iteration::Chars {
+                                offset: offset + ch.len_utf8(),
+                                text,
+                            };
+                            let element = LoxValue::String(ch.to_string().into());
+                            self.run_for_in_body(for_in, iteration, element)?;
+                        }
+                    }
+                    Iteration::Iterator(iterator) => {
+                        let has_next = self.protocol_method(&iterator, "hasNext", &for_in)?;
+                        let keyword = for_in.keyword.clone();
+                        self.tasks.push(Task::ForInHasNext { for_in, iterator });
+                        self.call_value(has_next, &keyword, Vec::new(), false)?;
+                    }
+                }
+            }
+            Task::ForInHasNext { for_in, iterator } => {
+                if self.pop_value().is_truthy() {
+                    let next = self.protocol_method(&iterator, "next", &for_in)?;
+                    let keyword = for_in.keyword.clone();
+                    self.tasks.push(Task::ForInBody { for_in, iterator });
+                    self.call_value(next, &keyword, Vec::new(), false)?;
+                }
+            }
+            Task::ForInBody { for_in, iterator } => {
+                let element = self.pop_value();
+                self.run_for_in_body(for_in, Iteration::Iterator(iterator), element)?;
+            }
+            Task::Finally { finally, .. } => self.tasks.push(Task::Execute(finally)),
+            Task::ResumeUnwind(signal, keyword) => self.unwind(signal, &keyword)?,
+            Task::Rethrow(err) => return Err(err),
+            Task::Return(keyword) => {
+                let value = self.pop_value();
+                self.unwind(ControlFlow::Return(value), &keyword)?;
+            }
+            Task::Unary(operator) => {
+                let right = self.pop_value();
+                let value = self.evaluate_unary(&operator, right)?;
+                self.values.push(value);
+            }
+            Task::BinaryRight { operator, right } => {
+                self.tasks.push(Task::Binary(operator));
+                self.start_expr(&right)?;
+            }
+            Task::Binary(operator) => {
+                let right = self.pop_value();
+                let left = self.pop_value();
+                if let Some(hook) = self.operator_hook(&left, &operator, &right)? {
+                    self.tasks.push(Task::OperatorHookResult(operator.clone()));
+                    let hook = LoxValue::Callable(LoxCallable::LoxFunction(hook));
+                    self.call_value(hook, &operator, vec![right], false)?;
+                } else {
+                    let value = self.evaluate_binary(left, &operator, right)?;
+                    self.values.push(value);
+                }
+            }
+            Task::OperatorHookResult(operator) => {
+                let result = self.pop_value();
+                let value = operator_hook_result(&operator, result)?;
+                self.values.push(value);
+            }
+            Task::Logical { operator, right } => {
+                let left = self.pop_value();
+                if self.short_circuits(&left, &operator)? {
+                    self.values.push(left);
+                } else {
+                    self.start_expr(&right)?;
+                }
+            }
+            Task::Assign { id, name } => {
+                let value = self.pop_value();
+                self.assign_variable(id, &name, value.clone())?;
+                self.values.push(value);
+            }
+            Task::AssignValues { values, index } => {
+                if let Some(value) = values.get(index) {
+                    self.tasks.push(Task::AssignValues {
+                        values: values.clone(),
+                        index: index + 1,
+                    });
+                    self.start_expr(value)?;
+                }
+            }
+            Task::AssignTargets(targets) => self.assign_targets(targets),
+            Task::AssignTarget { targets, index } => {
+                let value = self.pop_value();
+                match &targets[index] {
+                    Expr::Variable { id, name } => self.assign_variable(*id, name, value)?,
+                    Expr::Get { object, name, .. } => {
+                        let object_value = self.pop_value();
+                        let LoxValue::Instance(instance) = object_value else {
+                            return Err(not_instance_error(
+                                codes::NOT_AN_INSTANCE,
+                                "Only instances have fields, but '{}' was {}.",
+                                object,
+                                name,
+                                &object_value,
+                            ));
+                        };
+                        instance.borrow_mut().set(name, value);
+                    }
+                    Expr::Index {
+                        object, bracket, ..
+                    } => {
+                        let index = self.pop_value();
+                        let object_value = self.pop_value();
+                        self.set_index(object_value, index, value, object, bracket)?;
+                    }
+                    target => unreachable!("Invalid assignment target {target:?}"),
+                }
+            }
+            Task::Arguments {
+                paren,
+                arguments,
+                index,
+                tail,
+            } => {
+                if let Some(arg) = arguments.get(index) {
+                    self.tasks.push(Task::Arguments {
+                        paren,
+                        arguments: arguments.clone(),
+                        index: index + 1,
+                        tail,
+                    });
+                    self.start_expr(arg)?;
+                } else {
+                    let args = self.values.split_off(self.values.len() - arguments.len());
+                    let callee = self.pop_value();
+                    self.call_value(callee, &paren, args, tail)?;
+                }
+            }
+            Task::FinishCall {
+                function,
+                caller_env,
+                ..
+            } => self.finish_call(&function, caller_env, LoxValue::Nil)?,
+            Task::Get { object, name } => {
+                let value = match self.pop_value() {
+                    LoxValue::Instance(lox_instance) if is_private(&name.lexeme) => {
+                        let class_id = self.current_class_id();
+                        LoxInstance::get_private(lox_instance, &name, class_id, self)?
+                    }
+                    LoxValue::Instance(lox_instance) => {
+                        LoxInstance::get(lox_instance, &name, self)?
+                    }
+                    value => {
+                        return Err(not_instance_error(
+                            codes::NOT_AN_INSTANCE,
+                            "Only instances have properties, but '{}' was {}.",
+                            &object,
+                            &name,
+                            &value,
+                        ));
+                    }
+                };
+                self.values.push(value);
+            }
+            Task::SetValue {
+                object,
+                name,
+                value,
+            } => {
+                match self.values.last() {
+                    Some(LoxValue::Instance(_)) => {}
+                    Some(object_value) => {
+                        return Err(not_instance_error(
+                            codes::NOT_AN_INSTANCE,
+                            "Only instances have fields, but '{}' was {}.",
+                            &object,
+                            &name,
+                            object_value,
+                        ));
+                    }
+                    None => {
+                        let message = "Object of the set expression is missing.";
+                        return Err(LoxError::internal(&name, message));
+                    }
+                }
+                self.tasks.push(Task::Set(name));
+                self.start_expr(&value)?;
+            }
+            Task::Set(name) => {
+                let value = self.pop_value();
+                let LoxValue::Instance(instance) = self.pop_value() else {
+                    let message = "Object of the set expression isn't an instance.";
+                    return Err(LoxError::internal(&name, message));
+                };
+                if is_private(&name.lexeme) {
+                    let class_id = self.current_class_id();
+                    instance
+                        .borrow_mut()
+                        .set_private(&name, value.clone(), class_id)?;
+                } else {
+                    instance.borrow_mut().set(&name, value.clone());
+                }
+                self.values.push(value);
+            }
+            Task::Index { object, bracket } => {
+                let index = self.pop_value();
+                let object_value = self.pop_value();
+                self.index_value(object_value, index, &object, &bracket)?;
+            }
+            Task::SetIndexValue {
+                object,
+                bracket,
+                value,
+            } => {
+                let object_value = self.values[self.values.len() - 2].clone();
+                self.check_assignable(&object_value, &object, &bracket)?;
+                self.tasks.push(Task::SetIndex { object, bracket });
+                self.start_expr(&value)?;
+            }
+            Task::SetIndex { object, bracket } => {
+                let value = self.pop_value();
+                let index = self.pop_value();
+                let object_value = self.pop_value();
+                // The value of the assignment stays below the result of `set`.
+                self.values.push(value.clone());
+                self.set_index(object_value, index, value, &object, &bracket)?;
+            }
+        }
+
+        Ok(())
+    }
 
-                // Misuse of errors since they will bubble up the call stack
-                return Err(LoxError::Return {
-                    value: Box::new(value),
-                });
-            }
-            Stmt::Class {
-                name,
-                super_class,
-                methods,
-            } => self.evaluate_class(name, super_class.as_ref(), methods)?,
-        };
+    /// Reorders the value stack so each target of a multiple assignment finds
+    /// its object and index with its value on top, then pushes the tasks
+    /// assigning the targets from left to right.
+    fn assign_targets(&mut self, targets: Rc<[Expr]>) {
+        let values = self.values.split_off(self.values.len() - targets.len());
+        let places_len = targets.iter().map(target_places).sum::<usize>();
+        let mut places = self
+            .values
+            .split_off(self.values.len() - places_len)
+            .into_iter();
+
+        let operands: Vec<Vec<_>> = targets
+            .iter()
+            .zip(values)
+            .map(|(target, value)| {
+                let mut operands: Vec<_> = places.by_ref().take(target_places(target)).collect();
+                operands.push(value);
+                operands
+            })
+            .collect();
+        for (index, operands) in operands.into_iter().enumerate().rev() {
+            self.values.extend(operands);
+            self.tasks.push(Task::AssignTarget {
+                targets: targets.clone(),
+                index,
+            });
+        }
+    }
+
+    /// Pops the evaluated condition of a branch or loop, which must be a
+    /// boolean in strict mode instead of using its truthiness.
+    fn pop_condition(&mut self, keyword: &Token) -> LoxResult<bool> {
+        match self.pop_value() {
+            LoxValue::Boolean(value) => Ok(value),
+            value if self.options.strict_bool => Err(LoxError::new(
+                keyword.to_owned(),
+                codes::NON_BOOLEAN_CONDITION,
+                Message::new(
+                    "Condition of '{}' must be a boolean, but got {}.",
+                    [keyword.lexeme.to_string(), value.describe_translated()],
+                ),
+            )),
+            value => Ok(value.is_truthy()),
+        }
+    }
+
+    /// Enters a new scope which is left once the tasks pushed after it are done.
+    fn begin_scope(&mut self) {
+        let env = self.new_environment(self.environment.clone());
+        let prev_env = std::mem::replace(&mut self.environment, env);
+        self.tasks.push(Task::RestoreEnvironment(prev_env));
+    }
+
+    fn run_for_body(&mut self, for_loop: Rc<ForLoop>) -> LoxResult<()> {
+        let iterations = for_loop.iterations.get();
+        self.check_iterations(iterations)?;
+        for_loop.iterations.set(iterations + 1);
+
+        let body = for_loop.body.clone();
+        self.tasks.push(Task::ForIncrement(for_loop));
+        self.tasks.push(Task::Execute(body));
+
+        Ok(())
+    }
+
+    /// Executes the body of the for-in loop with the element in a new scope,
+    /// so closures in the body capture the variable of their own iteration.
+    /// The loop continues with the given position afterwards.
+    fn run_for_in_body(
+        &mut self,
+        for_in: Rc<ForInLoop>,
+        iteration: Iteration,
+        element: LoxValue,
+    ) -> LoxResult<()> {
+        let iterations = for_in.iterations.get();
+        self.check_iterations(iterations)?;
+        for_in.iterations.set(iterations + 1);
+
+        let body = for_in.body.clone();
+        let name = for_in.name.clone();
+        self.tasks.push(Task::ForInNext { for_in, iteration });
+        self.begin_scope();
+        self.define_variable(&name, element);
+        self.tasks.push(Task::Execute(body));
 
         Ok(())
     }
 
+    /// Gets the method of the iterator protocol from the value, which only
+    /// instances can have.
+    fn iterator_method(&mut self, value: &LoxValue, name: &str) -> Option<LoxValue> {
+        match value {
+            LoxValue::Instance(instance) => LoxInstance::method(instance, name, self),
+            _ => None,
+        }
+    }
+
+    /// Gets the method which iterators returned from `iterator()` must have.
+    fn protocol_method(
+        &mut self,
+        iterator: &LoxValue,
+        name: &str,
+        for_in: &ForInLoop,
+    ) -> LoxResult<LoxValue> {
+        self.iterator_method(iterator, name).ok_or_else(|| {
+            LoxError::new(
+                for_in.keyword.to_owned(),
+                codes::NOT_ITERABLE,
+                Message::new(
+                    "Iterators must have a '{}' method, but got {}.",
+                    [name.to_owned(), iterator.describe_translated()],
+                ),
+            )
+        })
+    }
+
+    /// Checks that the loop can run another iteration after the given count.
+    fn check_iterations(&self, iterations: u64) -> LoxResult<()> {
+        match self.options.max_loop_iterations {
+            Some(limit) if iterations >= limit => Err(LoxError::LoopLimitExceeded { limit }),
+            _ => Ok(()),
+        }
+    }
+
     fn evaluate_class(
         &mut self,
+        id: NodeId,
         name: &Token,
         super_class: Option<&Token>,
-        methods: &[FuncDeclaration],
+        methods: &[Rc<FuncDeclaration>],
+        doc: Option<Rc<str>>,
     ) -> LoxResult<()> {
         let super_class = if let Some(super_class) = super_class {
-            let class = self.evaluate(&Expr::Variable {
-                name: super_class.to_owned(),
-            })?;
-            match class {
+            match self.lookup_variable(id, super_class)? {
                 LoxValue::Callable(LoxCallable::Class(class)) => Some(class),
                 _ => {
                     return Err(LoxError::new(
                         super_class.to_owned(),
+                        codes::INVALID_SUPERCLASS,
                         "Superclass must be a class.",
                     ));
                 }
@@ -165,195 +1780,166 @@ impl Interpreter {
             None
         };
 
-        self.environment
-            .borrow_mut()
-            .define(name.lexeme.clone(), LoxValue::Nil);
-
-        if let Some(super_class) = super_class.clone() {
-            self.environment = Environment::with_enclosing(self.environment.clone());
-            self.environment.borrow_mut().define(
-                "super".into(),
-                LoxValue::Callable(LoxCallable::Class(super_class)),
-            );
-        }
-
-        let has_super = super_class.is_some();
-        let s = scopeguard::guard(self, |s| {
-            if has_super {
-                let enclosing = s.environment.borrow().enclosing.as_ref().unwrap().clone();
-                s.environment = enclosing;
+        // Methods of sub classes are enclosed by an environment holding `super`
+        // in its only slot.
+        let methods_env = match &super_class {
+            Some(super_class) => {
+                let env = self.new_environment(self.environment.clone());
+                let super_value = LoxValue::Callable(LoxCallable::Class(super_class.clone()));
+                self.push_local(&mut env.borrow_mut(), "super", super_value);
+                env
             }
-        });
+            None => self.environment.clone(),
+        };
 
         let mut meth = HashMap::new();
 
         for method in methods {
-            let is_initializer = method.name.lexeme == "init";
-            let function =
-                LoxFunction::new(method.to_owned(), s.environment.clone(), is_initializer);
+            let is_initializer = &*method.name.lexeme == "init";
+            let function = LoxFunction::new(method.clone(), methods_env.clone(), is_initializer)
+                .in_class(Some(name.id()));
             meth.insert(method.name.lexeme.to_owned(), function);
         }
 
-        let klass = LoxClass::new(name.lexeme.clone(), meth, super_class);
+        let klass = LoxClass::new(name.id(), name.lexeme.clone(), meth, super_class, doc);
         let klass = Rc::new(RefCell::new(klass));
-        s.environment
-            .borrow_mut()
-            .assign(name, LoxValue::Callable(LoxCallable::Class(klass)))?;
+
+        // Unlike the book, the class is defined after creating its methods and not
+        // before. Methods look the class up when they are called only, and nothing
+        // else is defined in between, so the class still takes the slot assigned
+        // to it by the resolver.
+        self.define_variable(name, LoxValue::Callable(LoxCallable::Class(klass)));
 
         Ok(())
     }
 
-    fn execute_block(&mut self, statements: &[Stmt], environment: EnvironmentRef) -> LoxResult<()> {
-        let prev_env = self.environment.clone();
-
-        self.environment = environment;
-
-        let mut sel = scopeguard::guard(self, |s| {
-            s.environment = prev_env;
-        });
+    /// Adds the methods to the class, replacing the methods with the same names.
+    fn evaluate_extend(
+        &mut self,
+        id: NodeId,
+        name: &Token,
+        methods: &[Rc<FuncDeclaration>],
+    ) -> LoxResult<()> {
+        let LoxValue::Callable(LoxCallable::Class(class)) = self.lookup_variable(id, name)? else {
+            return Err(LoxError::new(
+                name.to_owned(),
+                codes::INVALID_SUPERCLASS,
+                "Can only extend classes.",
+            ));
+        };
 
-        for stmt in statements {
-            sel.execute(stmt)?;
+        let mut class = class.borrow_mut();
+        for method in methods {
+            // Added methods belong to the class, accessing its private members.
+            let function = LoxFunction::new(method.clone(), self.environment.clone(), false)
+                .in_class(Some(class.id()));
+            class.add_method(method.name.lexeme.clone(), function);
         }
+        // Methods bound to the instances before may be outdated now.
+        self.class_revision += 1;
 
         Ok(())
     }
 
-    fn evaluate(&mut self, expr: &Expr) -> LoxResult<LoxValue> {
-        match expr {
-            Expr::Grouping { expression } => self.evaluate(expression),
-            Expr::Literal { value } => Ok(value.into()),
-            Expr::Unary { operator, right } => self.evaluate_unary(operator, right),
-            Expr::Binary {
-                left,
-                operator,
-                right,
-            } => self.evaluate_binary(left, operator, right),
-            expr @ Expr::Variable { name } => self.lookup_variable(expr, name),
-            expr @ Expr::Assign { name, value } => self.assign_expr(expr, name, value),
-            Expr::Logical {
-                left,
-                operator,
-                right,
-            } => self.evaluate_logical(left, operator, right),
-            Expr::Call {
-                callee,
-                paren,
-                arguments,
-            } => self.evaluate_call(callee, paren, arguments),
-            Expr::Get { object, name } => self.evaluate_get(object, name),
-            Expr::Set {
-                object,
-                name,
-                value,
-            } => self.evaluate_set(object, name, value),
-            expr @ Expr::This { keyword } => self.lookup_variable(expr, keyword),
-            expr @ Expr::Super { keyword: _, method } => self.evaluate_super(expr, method),
-        }
-    }
-
-    fn evaluate_super(&mut self, expr: &Expr, method: &Token) -> LoxResult<LoxValue> {
-        let distance = self
-            .get_distance(expr)
-            .expect("Superclass is registered in resolver");
+    fn evaluate_super(
+        &mut self,
+        id: NodeId,
+        keyword: &Token,
+        method: &Token,
+    ) -> LoxResult<LoxValue> {
+        let Some(LocalSlot { depth, slot }) = self.get_local(id) else {
+            return Err(LoxError::internal(keyword, "'super' isn't resolved."));
+        };
 
-        let super_value = Environment::get_at(self.environment.clone(), distance, "super");
+        let super_value = Environment::get_at(&self.environment, depth, slot, keyword)?;
         let super_class = match &super_value {
             LoxValue::Callable(LoxCallable::Class(klass)) => klass,
-            _ => panic!("We must get class when asking fro 'super'"),
+            other => {
+                let message = format!("'super' refers to {}.", other.describe());
+                return Err(LoxError::internal(keyword, message));
+            }
         };
 
-        let this_instance = Environment::get_at(self.environment.clone(), distance - 1, "this");
-        let this_instance = match this_instance {
+        // `this` is always in the first slot of the environment enclosed by the super one.
+        let Some(this_depth) = depth.checked_sub(1) else {
+            return Err(LoxError::internal(keyword, "'this' isn't resolved."));
+        };
+        let this_instance = match Environment::get_at(&self.environment, this_depth, 0, keyword)? {
             LoxValue::Instance(inst) => inst,
-            _ => panic!("We must get instance when asking for 'this'"),
+            other => {
+                let message = format!("'this' refers to {}.", other.describe());
+                return Err(LoxError::internal(keyword, message));
+            }
         };
 
-        let method = super_class
-            .borrow()
-            .find_method(&method.lexeme)
-            .ok_or_else(|| {
-                LoxError::new(
-                    method.to_owned(),
-                    format!("Undefined property '{}'.", method.lexeme),
-                )
-            })?;
-
-        let method = method.bind(this_instance);
-        Ok(LoxValue::Callable(LoxCallable::LoxFunction(method)))
-    }
+        let super_class = super_class.borrow();
+        if let Some(method) = super_class.find_method(&method.lexeme) {
+            let method = method.bind(this_instance, self);
+            return Ok(LoxValue::Callable(LoxCallable::LoxFunction(Rc::new(
+                method,
+            ))));
+        }
 
-    fn evaluate_get(&mut self, object: &Expr, name: &Token) -> LoxResult<LoxValue> {
-        match self.evaluate(object)? {
-            LoxValue::Instance(lox_instance) => LoxInstance::get(lox_instance, name),
-            _ => Err(LoxError::new(
-                name.to_owned(),
-                "Only instances have properties.",
+        // Lox classes can inherit the methods of native classes.
+        match super_class.find_native_method(&method.lexeme) {
+            Some(method) => {
+                let bound = BoundNativeMethod {
+                    method,
+                    this: this_instance,
+                };
+                Ok(LoxValue::Callable(LoxCallable::NativeMethod(Rc::new(
+                    bound,
+                ))))
+            }
+            None => Err(LoxError::new(
+                method.to_owned(),
+                codes::UNDEFINED_PROPERTY,
+                Message::new("Undefined property '{}'.", [&method.lexeme]),
             )),
         }
     }
 
-    fn evaluate_set(&mut self, object: &Expr, name: &Token, value: &Expr) -> LoxResult<LoxValue> {
-        let object = self.evaluate(object)?;
-        let LoxValue::Instance(instance) = object else {
-            return Err(LoxError::new(
-                name.to_owned(),
-                "Only instances have fields.",
-            ));
-        };
-
-        let value = self.evaluate(value)?;
-        instance.borrow_mut().set(name, value.clone());
-
-        Ok(value)
-    }
-
-    fn lookup_variable(&mut self, main_expr: &Expr, name: &Token) -> LoxResult<LoxValue> {
-        let distance = self.get_distance(main_expr);
-        if let Some(dist) = distance {
-            let val = Environment::get_at(self.environment.clone(), dist, &name.lexeme);
-            Ok(val)
+    /// Looks up the variable referred to by the node with the given ID.
+    fn lookup_variable(&mut self, id: NodeId, name: &Token) -> LoxResult<LoxValue> {
+        if let Some(LocalSlot { depth, slot }) = self.get_local(id) {
+            Environment::get_at(&self.environment, depth, slot, name)
         } else {
-            self.globals.borrow().get(name)
+            // Globals are looked up through the enclosing environments, so
+            // functions see the globals of the script declaring them.
+            self.environment.borrow().get(name)
         }
     }
 
-    fn get_distance(&self, expr: &Expr) -> Option<usize> {
-        self.locals
-            .iter()
-            .find(|(ex, _depth)| ex == expr)
-            .map(|(_, depth)| *depth)
+    fn get_local(&self, id: NodeId) -> Option<LocalSlot> {
+        self.locals.get(&id).copied()
     }
 
-    fn assign_expr(&mut self, main_exp: &Expr, name: &Token, value: &Expr) -> LoxResult<LoxValue> {
-        let value = self.evaluate(value)?;
-        let dist = self.get_distance(main_exp);
-        if let Some(distance) = dist {
-            Environment::assign_at(self.environment.clone(), distance, name, value.clone());
+    fn assign_variable(&mut self, id: NodeId, name: &Token, value: LoxValue) -> LoxResult<()> {
+        if let Some(LocalSlot { depth, slot }) = self.get_local(id) {
+            Environment::assign_at(&self.environment, depth, slot, name, value)
         } else {
-            self.globals.borrow_mut().assign(name, value.clone())?;
+            self.environment.borrow_mut().assign(name, value)
         }
-
-        Ok(value)
     }
 
-    fn evaluate_call(
+    /// Calls the given value with the evaluated arguments after validating that
+    /// it can be called with them.
+    /// Lox functions are called by pushing their frames, and their values are
+    /// pushed once their frames are done, while other callables push their values
+    /// right away.
+    fn call_value(
         &mut self,
-        callee: &Expr,
+        callee: LoxValue,
         paren: &Token,
-        arguments: &[Expr],
-    ) -> LoxResult<LoxValue> {
-        let callee = self.evaluate(callee)?;
-        let mut args = Vec::with_capacity(arguments.len());
-        for arg in arguments {
-            args.push(self.evaluate(arg)?);
-        }
-
+        args: Vec<LoxValue>,
+        tail: bool,
+    ) -> LoxResult<()> {
         let callee = match callee {
             LoxValue::Callable(lox_callable) => lox_callable,
             _ => {
                 return Err(LoxError::new(
                     paren.to_owned(),
+                    codes::NOT_CALLABLE,
                     "Can only call functions and classes.",
                 ));
             }
@@ -362,33 +1948,383 @@ impl Interpreter {
         if callee.arity() != args.len() {
             return Err(LoxError::new(
                 paren.to_owned(),
-                format!(
+                codes::ARITY_MISMATCH,
+                Message::new(
                     "Expected {} arguments but got {}.",
-                    callee.arity(),
-                    args.len()
+                    [callee.arity(), args.len()],
                 ),
             ));
         }
 
-        callee.call(self, &args)
+        self.observe(|observer| {
+            observer.on_call(&LoxValue::Callable(callee.clone()), &args, paren.line)
+        });
+
+        let value = match callee {
+            LoxCallable::Native(native) => self.call_native(paren, |this| {
+                if let Err(message) = this.options.sandbox.check_native(&native) {
+                    return Err(this.fail_native(codes::CAPABILITY_DISABLED, message));
+                }
+                (native.function)(this, &args)
+            })?,
+            LoxCallable::LoxFunction(function) => match self.call_compiled(&function, &args) {
+                Some(value) => value,
+                None => return self.push_frame(function, paren, args, tail),
+            },
+            LoxCallable::Class(class) => {
+                // Lox classes inheriting from native classes need them allowed too.
+                if let Some(native) = class.borrow().native_class()
+                    && let Err(message) = self.options.sandbox.check_native_class(native.name())
+                {
+                    return Err(LoxError::new(
+                        paren.to_owned(),
+                        codes::CAPABILITY_DISABLED,
+                        message,
+                    ));
+                }
+                self.allocations += 1;
+                let instance = LoxInstance::new(class.clone());
+                let initializer = class.borrow().find_method("init");
+                let native_initializer = class.borrow().find_native_method("init");
+                match (initializer, native_initializer) {
+                    // Initializers return the instance they are bound to.
+                    (Some(init), _) => {
+                        let init = Rc::new(init.bind(instance, self));
+                        return self.push_frame(init, paren, args, tail);
+                    }
+                    (None, Some(init)) => {
+                        self.call_native(paren, |this| (init.function)(this, &instance, &args))?;
+                        LoxValue::Instance(instance)
+                    }
+                    (None, None) => LoxValue::Instance(instance),
+                }
+            }
+            LoxCallable::NativeMethod(bound) => self.call_native(paren, |this| {
+                let class = &bound.method.class;
+                if let Err(message) = this.options.sandbox.check_native_class(class) {
+                    return Err(this.fail_native(codes::CAPABILITY_DISABLED, message));
+                }
+                (bound.method.function)(this, &bound.this, &args)
+            })?,
+        };
+
+        // Natives waiting for the host or yielding from coroutines leave the
+        // value of the call to the resume, which pushes it like any other
+        // returned value.
+        if self.suspended.is_some() || self.yielded.is_some() {
+            if tail {
+                self.tasks.push(Task::Return(paren.to_owned()));
+            }
+            return Err(LoxError::Suspended);
+        }
+
+        self.observe(|observer| observer.on_return(&value));
+        if tail {
+            self.unwind(ControlFlow::Return(value), paren)?;
+        } else {
+            self.values.push(value);
+        }
+
+        Ok(())
+    }
+
+    /// Runs the native function or method, rethrowing the errors of the calls
+    /// it made back into Lox code when it fails.
+    fn call_native(
+        &mut self,
+        paren: &Token,
+        native: impl FnOnce(&mut Self) -> Result<LoxValue, String>,
+    ) -> LoxResult<LoxValue> {
+        let outer_call = self.native_call.replace(paren.to_owned());
+        let result = native(self);
+        self.native_call = outer_call;
+
+        // Natives can handle the errors of their calls and return normally.
+        let native_error = self.native_error.take();
+        result.map_err(|message| {
+            native_error.unwrap_or_else(|| {
+                LoxError::new(paren.to_owned(), codes::INVALID_ARGUMENT, message)
+            })
+        })
+    }
+
+    /// Fails the running native with an error of the given code instead of
+    /// the catch-all code of invalid arguments, returning the message the
+    /// native fails with.
+    pub(super) fn fail_native(&mut self, code: &'static str, message: impl Into<String>) -> String {
+        let message = message.into();
+        if let Some(paren) = &self.native_call {
+            self.native_error = Some(LoxError::new(paren.to_owned(), code, message.clone()));
+        }
+        message
+    }
+
+    /// Calls the value from a native with the given arguments, running Lox
+    /// functions until they return, like `sort` calling its comparator.
+    /// Natives must return the error of the call, which is then reported with
+    /// the line and the stack trace inside the called function.
+    pub fn call(&mut self, callee: &LoxValue, args: Vec<LoxValue>) -> Result<LoxValue, String> {
+        let Some(paren) = self.native_call.clone() else {
+            return Err(String::from("Values can only be called back from natives."));
+        };
+
+        let checkpoint = self.checkpoint();
+        let result = self
+            .call_value(callee.clone(), &paren, args, false)
+            .and_then(|()| self.run_nested(checkpoint.tasks_len));
+        let err = match result {
+            Ok(()) => return Ok(self.pop_value()),
+            // The native waiting for the call can't be continued later.
+            Err(LoxError::Suspended) if self.yielded.take().is_some() => LoxError::new(
+                paren,
+                codes::INVALID_COROUTINE_USE,
+                "Can't yield inside calls made by natives.",
+            ),
+            Err(LoxError::Suspended) => {
+                self.suspended = None;
+                LoxError::new(
+                    paren,
+                    codes::INVALID_SUSPENSION,
+                    "Can't suspend scripts inside calls made by natives.",
+                )
+            }
+            Err(err) => err,
+        };
+
+        Err(self.fail_native_call(err, &checkpoint))
+    }
+
+    /// Restores the state before the failed call made by a native, returning
+    /// the message the native fails with. The error itself is rethrown once
+    /// the native fails.
+    fn fail_native_call(&mut self, err: LoxError, checkpoint: &Checkpoint) -> String {
+        // The trace includes the calls outside of the native too.
+        let err = self.add_trace(err, 0);
+        self.restore(checkpoint);
+        let message = match &err {
+            LoxError::Error { message, .. } => message.to_string(),
+            err => err.to_string(),
+        };
+        self.native_error = Some(err);
+        message
+    }
+
+    /// Runs the tasks of a call made by a native until the stack is back at
+    /// the given length. Unlike `run()`, these can't pause on the time budget.
+    fn run_nested(&mut self, base: usize) -> LoxResult<()> {
+        while self.tasks.len() > base {
+            let task = self.tasks.pop().expect("Stack is longer than its base");
+            if let Err(err) = self.run_task(task) {
+                // Errors of calls made by natives are traced from the bottom.
+                self.run_finally(err, base, 0)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calls the compiled code of the function when it's in the typed subset,
+    /// returning `None` when it must be called in the interpreter instead.
+    #[cfg(feature = "jit")]
+    fn call_compiled(&mut self, function: &LoxFunction, args: &[LoxValue]) -> Option<LoxValue> {
+        // Compiled code doesn't count the steps, allocations and loop
+        // iterations, can't pause at the end of a time slice, and isn't
+        // observed or stepped through.
+        if self.options.max_steps.is_some()
+            || self.options.max_loop_iterations.is_some()
+            || self.options.sandbox.has_limits()
+            || self.deadline.is_some()
+            || self.observer.is_some()
+            || self.stepping
+        {
+            return None;
+        }
+
+        let limits = jit::Limits {
+            depth: self.call_depth,
+            max_depth: self.options.max_call_depth - self.call_depth,
+            ieee_division: self.options.ieee_division,
+            cancel: &self.cancel,
+        };
+        self.jit.call(function, args, &self.globals, limits)
+    }
+
+    #[cfg(not(feature = "jit"))]
+    fn call_compiled(&mut self, _function: &LoxFunction, _args: &[LoxValue]) -> Option<LoxValue> {
+        None
+    }
+
+    /// Pushes the frame for calling the given function with its arguments.
+    /// Frames of calls in tail position replace the one of the returning
+    /// function, so they don't count as nested calls.
+    fn push_frame(
+        &mut self,
+        function: Rc<LoxFunction>,
+        paren: &Token,
+        args: Vec<LoxValue>,
+        tail: bool,
+    ) -> LoxResult<()> {
+        // Replacing frames keep the call of the replaced one in stack traces.
+        let (caller_env, call_line) = if tail {
+            let (_, caller_env, call_line) = self.unwind_frame(paren)?;
+            (caller_env, call_line)
+        } else {
+            if self.call_depth >= self.options.max_call_depth {
+                return Err(LoxError::new(
+                    paren.to_owned(),
+                    codes::STACK_OVERFLOW,
+                    "Stack overflow.",
+                ));
+            }
+            self.call_depth += 1;
+            (self.environment.clone(), paren.line)
+        };
+
+        let environment = self.new_environment(function.closure.clone());
+        let mut env_borrow = environment.borrow_mut();
+        // Parameters take the first slots in the function scope.
+        for (param, arg) in function.declaration.params.iter().zip(args) {
+            self.push_local(&mut env_borrow, &param.lexeme, arg);
+        }
+        drop(env_borrow);
+        self.environment = environment;
+
+        let body = function.declaration.body.clone();
+        self.tasks.push(Task::FinishCall {
+            function,
+            caller_env,
+            call_line,
+        });
+        self.tasks.push(Task::Statements {
+            stmts: body,
+            index: 0,
+        });
+
+        Ok(())
+    }
+
+    /// Drops the pending tasks of the current function returning the parts
+    /// of its frame.
+    fn unwind_frame(
+        &mut self,
+        paren: &Token,
+    ) -> LoxResult<(Rc<LoxFunction>, EnvironmentRef, usize)> {
+        loop {
+            let Some(task) = self.tasks.pop() else {
+                return Err(LoxError::internal(
+                    paren,
+                    "Tail call isn't inside a function.",
+                ));
+            };
+            match task {
+                Task::FinishCall {
+                    function,
+                    caller_env,
+                    call_line,
+                } => return Ok((function, caller_env, call_line)),
+                Task::ExitStmt(exited) => {
+                    self.observe(|observer| observer.on_stmt_exit(exited.stmt()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Drops the pending tasks up to the target of the control flow signal and
+    /// continues the execution from there. Pending `finally` blocks on the way
+    /// run first, and the signal continues once they are done.
+    /// Statements which aren't inside their targets, like the ones running
+    /// without the resolver, are reported as internal errors.
+    fn unwind(&mut self, signal: ControlFlow, keyword: &Token) -> LoxResult<()> {
+        let returns = matches!(signal, ControlFlow::Return(_));
+        loop {
+            let Some(task) = self.tasks.pop() else {
+                // Returns of tail calls are reported at the parenthesis of the call.
+                let message = if returns {
+                    String::from("Return isn't inside a function.")
+                } else {
+                    format!("'{}' isn't inside a loop.", keyword.lexeme)
+                };
+                return Err(LoxError::internal(keyword, message));
+            };
+            match task {
+                Task::Finally {
+                    finally,
+                    environment,
+                    ..
+                } => {
+                    self.environment = environment;
+                    self.tasks
+                        .push(Task::ResumeUnwind(signal, keyword.to_owned()));
+                    self.tasks.push(Task::Execute(finally));
+                    return Ok(());
+                }
+                Task::FinishCall {
+                    function,
+                    caller_env,
+                    ..
+                } => {
+                    let ControlFlow::Return(value) = signal else {
+                        let message = format!("'{}' isn't inside a loop.", keyword.lexeme);
+                        return Err(LoxError::internal(keyword, message));
+                    };
+                    return self.finish_call(&function, caller_env, value);
+                }
+                // Leaving the blocks inside the loop body.
+                Task::RestoreEnvironment(env) => self.environment = env,
+                Task::WhileCondition { .. } | Task::ForIncrement(_) | Task::ForInNext { .. }
+                    if !returns =>
+                {
+                    if matches!(signal, ControlFlow::Continue) {
+                        self.tasks.push(task);
+                    }
+                    return Ok(());
+                }
+                Task::ExitStmt(exited) => {
+                    self.observe(|observer| observer.on_stmt_exit(exited.stmt()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn finish_call(
+        &mut self,
+        function: &LoxFunction,
+        caller_env: EnvironmentRef,
+        value: LoxValue,
+    ) -> LoxResult<()> {
+        self.environment = caller_env;
+        self.call_depth -= 1;
+        let value = function.return_value(value)?;
+        self.observe(|observer| observer.on_return(&value));
+        self.values.push(value);
+        Ok(())
     }
 
-    fn evaluate_unary(&mut self, operator: &Token, right: &Expr) -> LoxResult<LoxValue> {
-        let right = self.evaluate(right)?;
+    fn evaluate_unary(&mut self, operator: &Token, right: LoxValue) -> LoxResult<LoxValue> {
         let value = match (right, &operator.typ) {
             // Minus
             (LoxValue::Number(num), TT::Minus) => LoxValue::Number(-num),
-            (_, TT::Minus) => {
-                let err = LoxError::new(operator.to_owned(), "Operand must be number.");
+            (val, TT::Minus) => {
+                let err = LoxError::new(
+                    operator.to_owned(),
+                    codes::OPERAND_TYPE,
+                    Message::new(
+                        "Operand of '-' must be a number, but it was {}.",
+                        [val.describe_translated()],
+                    ),
+                );
                 return Err(err);
             }
 
             // Bang
             (val, TT::Bang) => LoxValue::Boolean(!val.is_truthy()),
 
-            // Unreachable
-            (val, oper) => {
-                panic!("Unreachable in Unary Expression. Value: {val:?}, Operator: {oper:?}")
+            // The parser creates unary expressions with these operators only.
+            (_, _) => {
+                let message = format!("'{}' isn't a unary operator.", operator.lexeme);
+                return Err(LoxError::internal(operator, message));
             }
         };
 
@@ -397,29 +2333,61 @@ impl Interpreter {
 
     fn evaluate_binary(
         &mut self,
-        left: &Expr,
+        left: LoxValue,
         operator: &Token,
-        right: &Expr,
+        right: LoxValue,
     ) -> LoxResult<LoxValue> {
         use LoxValue as V;
-        let left = self.evaluate(left)?;
-        let right = self.evaluate(right)?;
 
         let value = match (left, &operator.typ, right) {
             // Arithmetics
             (V::Number(left), TT::Minus, V::Number(right)) => V::Number(left - right),
+            (V::Number(_), TT::Slash | TT::TildeSlash | TT::Percent, V::Number(right))
+                if right == 0.0 && !self.options.ieee_division =>
+            {
+                let err = LoxError::new(
+                    operator.to_owned(),
+                    codes::DIVISION_BY_ZERO,
+                    "Division by zero.",
+                );
+                return Err(err);
+            }
             (V::Number(left), TT::Slash, V::Number(right)) => V::Number(left / right),
             (V::Number(left), TT::Star, V::Number(right)) => V::Number(left * right),
+            (V::Number(left), TT::TildeSlash, V::Number(right)) => {
+                V::Number(floor_div(left, right))
+            }
+            (V::Number(left), TT::Percent, V::Number(right)) => V::Number(floor_mod(left, right)),
 
             // Plus works on numbers and strings
             (V::Number(left), TT::Plus, V::Number(right)) => V::Number(left + right),
-            (V::String(left), TT::Plus, V::String(right)) => V::String(format!("{left}{right}")),
-            (_, TT::Plus, _) => {
-                let err = LoxError::new(
-                    operator.to_owned(),
-                    "Operands must be two numbers or two Strings",
-                );
-                return Err(err);
+            (V::String(left), TT::Plus, V::String(right)) => {
+                if let Some(limit) = self.options.max_string_length
+                    && left.len() + right.len() > limit
+                {
+                    return Err(LoxError::new(
+                        operator.to_owned(),
+                        codes::STRING_TOO_LONG,
+                        Message::new("String length exceeds the limit of {} bytes.", [limit]),
+                    ));
+                }
+                self.allocations += 1;
+                self.count_string(left.len() + right.len());
+                V::String(format!("{left}{right}").into())
+            }
+            (left, TT::Plus, right) => {
+                // Right is the wrong one when left is valid for addition.
+                let (template, wrong) = match (&left, &right) {
+                    (V::Number(_) | V::String(_), right) => (
+                        "Operands of '{}' must be two numbers or two strings, but the right operand was {}.",
+                        right,
+                    ),
+                    (left, _) => (
+                        "Operands of '{}' must be two numbers or two strings, but the left operand was {}.",
+                        left,
+                    ),
+                };
+                return Err(operands_error(operator, template, wrong));
             }
 
             // Comparison
@@ -430,58 +2398,157 @@ impl Interpreter {
 
             // Error where numeric values and demanded.
             (
-                _,
+                left,
                 TT::Minus
                 | TT::Slash
                 | TT::Star
+                | TT::TildeSlash
+                | TT::Percent
                 | TT::Greater
                 | TT::GreaterEqual
                 | TT::Less
                 | TT::LessEqual,
-                _,
+                right,
             ) => {
-                let err = LoxError::new(operator.to_owned(), "Operands must be numbers");
-
-                return Err(err);
+                let (template, wrong) = match &left {
+                    V::Number(_) => (
+                        "Operands of '{}' must be numbers, but the right operand was {}.",
+                        &right,
+                    ),
+                    left => (
+                        "Operands of '{}' must be numbers, but the left operand was {}.",
+                        left,
+                    ),
+                };
+                return Err(operands_error(operator, template, wrong));
             }
 
             // Equality
             (left, TT::EqualEqual, right) => V::Boolean(left == right),
             (left, TT::BangEqual, right) => V::Boolean(left != right),
 
-            // Unreachable
-            (l, op, r) => panic!(
-                "Unreachable in Binary Expression. Operator: {op:?}, Left: {l:?}, Right: {r:?}"
-            ),
+            // The parser creates binary expressions with these operators only.
+            (_, _, _) => {
+                let message = format!("'{}' isn't a binary operator.", operator.lexeme);
+                return Err(LoxError::internal(operator, message));
+            }
         };
 
         Ok(value)
     }
 
-    fn evaluate_logical(
+    /// Returns the method of the left operand which implements the operator for
+    /// instances: `equals(other)` for equality and `compareTo(other)` for comparison.
+    ///
+    /// Hooks are only called when both operands are instances, so instances
+    /// stay unequal to other values in both orders. Comparing an instance
+    /// implementing `compareTo` with another value fails instead.
+    fn operator_hook(
         &mut self,
-        left: &Expr,
+        left: &LoxValue,
         operator: &Token,
-        right: &Expr,
-    ) -> LoxResult<LoxValue> {
-        // Evaluate left first and only execute right if logical expand to it.
-        // This is necessary to avoid any side effect from executing right.
+        right: &LoxValue,
+    ) -> LoxResult<Option<Rc<LoxFunction>>> {
+        let name = match operator.typ {
+            TT::EqualEqual | TT::BangEqual => "equals",
+            TT::Greater | TT::GreaterEqual | TT::Less | TT::LessEqual => "compareTo",
+            _ => return Ok(None),
+        };
 
-        let left = self.evaluate(left)?;
-        match &operator.typ {
-            TT::Or => {
-                if left.is_truthy() {
-                    return Ok(left);
-                }
+        match (left, right) {
+            (LoxValue::Instance(instance), LoxValue::Instance(_)) => {
+                Ok(LoxInstance::bound_method(instance, name, self))
             }
-            TT::And => {
-                if !left.is_truthy() {
-                    return Ok(left);
-                }
+            (LoxValue::Instance(instance), other) | (other, LoxValue::Instance(instance))
+                if name == "compareTo" && instance.borrow().class().borrow().has_method(name) =>
+            {
+                let template = if std::ptr::eq(other, left) {
+                    "Operands of '{}' must both be instances to use 'compareTo', but the left operand was {}."
+                } else {
+                    "Operands of '{}' must both be instances to use 'compareTo', but the right operand was {}."
+                };
+                Err(operands_error(operator, template, other))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Checks if the logical expression evaluates to its left value without
+    /// evaluating the right one.
+    fn short_circuits(&self, left: &LoxValue, operator: &Token) -> LoxResult<bool> {
+        match &operator.typ {
+            TT::Or => Ok(left.is_truthy()),
+            TT::And => Ok(!left.is_truthy()),
+            _ => {
+                let message = format!("'{}' isn't a logical operator.", operator.lexeme);
+                Err(LoxError::internal(operator, message))
             }
-            invalid => panic!("Invalid logical operator: {invalid:?}"),
         }
+    }
+}
+
+/// Creates the error for operands with invalid types from the template naming
+/// the side of the wrong operand, filling in the operator and the operand.
+fn operands_error(operator: &Token, template: &'static str, operand: &LoxValue) -> LoxError {
+    LoxError::new(
+        operator.to_owned(),
+        codes::OPERAND_TYPE,
+        Message::new(
+            template,
+            [operator.lexeme.to_string(), operand.describe_translated()],
+        ),
+    )
+}
 
-        self.evaluate(right)
+/// Count of the values evaluated before the values of a multiple assignment
+/// for the target: the object of properties, and the object and index of
+/// subscripts.
+fn target_places(target: &Expr) -> usize {
+    match target {
+        Expr::Get { .. } => 1,
+        Expr::Index { .. } => 2,
+        _ => 0,
     }
 }
+
+/// Creates the error for accessing a property on a value which isn't an instance,
+/// filling the template with the object expression and what its value was.
+fn not_instance_error(
+    code: &'static str,
+    template: &'static str,
+    object: &Expr,
+    name: &Token,
+    value: &LoxValue,
+) -> LoxError {
+    LoxError::new(
+        name.to_owned(),
+        code,
+        Message::new(template, [object.to_source(), value.describe_translated()]),
+    )
+}
+
+/// Converts the result of an operator hook to the result of the operator.
+/// `compareTo` must return a number which is negative, zero or positive when the
+/// instance is less than, equal to or greater than the other value.
+fn operator_hook_result(operator: &Token, result: LoxValue) -> LoxResult<LoxValue> {
+    let value = match (&operator.typ, result) {
+        (TT::EqualEqual, result) => result.is_truthy(),
+        (TT::BangEqual, result) => !result.is_truthy(),
+        (TT::Greater, LoxValue::Number(order)) => order > 0.0,
+        (TT::GreaterEqual, LoxValue::Number(order)) => order >= 0.0,
+        (TT::Less, LoxValue::Number(order)) => order < 0.0,
+        (TT::LessEqual, LoxValue::Number(order)) => order <= 0.0,
+        (_, result) => {
+            return Err(LoxError::new(
+                operator.to_owned(),
+                codes::OPERAND_TYPE,
+                Message::new(
+                    "'compareTo' must return a number, but it returned {}.",
+                    [result.describe_translated()],
+                ),
+            ));
+        }
+    };
+
+    Ok(LoxValue::Boolean(value))
+}
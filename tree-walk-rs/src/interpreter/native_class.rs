@@ -0,0 +1,99 @@
+//! Classes defined from Rust, whose methods are Rust closures working on the
+//! state of the instances.
+
+use std::{collections::HashMap, fmt::Display, rc::Rc};
+
+use super::{Interpreter, LoxValue, instance::LoxInstanceRef};
+
+/// Signature of the methods of native classes, which get the instance they
+/// are called on and the arguments after checking their count. They return an
+/// error message on failures.
+pub type NativeMethodFn =
+    dyn Fn(&mut Interpreter, &LoxInstanceRef, &[LoxValue]) -> Result<LoxValue, String>;
+
+pub struct NativeMethod {
+    pub name: Rc<str>,
+    /// Name of the native class defining the method, which the sandbox policy
+    /// checks before calling it.
+    pub class: Rc<str>,
+    pub arity: usize,
+    pub function: Box<NativeMethodFn>,
+}
+
+impl std::fmt::Debug for NativeMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeMethod")
+            .field("name", &self.name)
+            .field("class", &self.class)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Class defined from Rust, which is registered with
+/// [`InterpreterBuilder::class()`](super::InterpreterBuilder::class).
+///
+/// Its `init` method sets up the instances, which can keep any Rust value as
+/// their state with [`LoxInstance::set_state()`](super::LoxInstance::set_state),
+/// or with [`LoxInstance::set_traced_state()`](super::LoxInstance::set_traced_state)
+/// when it holds Lox values.
+/// Lox classes can inherit from native classes, while native classes can't
+/// have superclasses themselves.
+#[derive(Debug)]
+pub struct NativeClass {
+    name: Rc<str>,
+    methods: HashMap<Rc<str>, Rc<NativeMethod>>,
+}
+
+impl NativeClass {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Adds the method with the given name and count of parameters, replacing
+    /// any method with the same name.
+    pub fn method(
+        mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Interpreter, &LoxInstanceRef, &[LoxValue]) -> Result<LoxValue, String>
+        + 'static,
+    ) -> Self {
+        let method = NativeMethod {
+            name: name.into(),
+            class: self.name.clone(),
+            arity,
+            function: Box::new(function),
+        };
+        self.methods.insert(name.into(), Rc::new(method));
+        self
+    }
+
+    pub fn name(&self) -> &Rc<str> {
+        &self.name
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<NativeMethod>> {
+        self.methods.get(name).cloned()
+    }
+
+    pub fn method_names(&self) -> impl Iterator<Item = &Rc<str>> {
+        self.methods.keys()
+    }
+}
+
+/// Method of a native class bound to the instance it's called on.
+#[derive(Debug, Clone)]
+pub struct BoundNativeMethod {
+    pub method: Rc<NativeMethod>,
+    pub this: LoxInstanceRef,
+}
+
+impl Display for BoundNativeMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.method.name)
+    }
+}
@@ -0,0 +1,744 @@
+//! Native functions defined in the global environment.
+
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+    time::SystemTime,
+};
+
+use lox_frontend::codes;
+
+use crate::resolver::is_private;
+
+use super::{
+    Capability, DEFAULT_PRETTY_DEPTH, Interpreter, LoxValue,
+    callables::{LoxCallable, LoxClassRef},
+    class::LoxClass,
+    coroutines,
+    function::LoxFunction,
+    hash_map,
+    instance::LoxInstance,
+    native_class::NativeClass,
+    number_format::{self, MAX_FIXED_DIGITS},
+    timers,
+};
+
+/// Signature of native functions, which get their arguments after checking
+/// their count and return an error message on failures.
+pub type NativeFn = fn(&mut Interpreter, &[LoxValue]) -> Result<LoxValue, String>;
+
+#[derive(Debug)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: NativeFn,
+    /// Capabilities the native needs, which are checked against the sandbox
+    /// policy before calling it.
+    pub capabilities: &'static [Capability],
+}
+
+impl NativeFunction {
+    pub const fn new(
+        name: &'static str,
+        arity: usize,
+        function: NativeFn,
+        capabilities: &'static [Capability],
+    ) -> Self {
+        Self {
+            name,
+            arity,
+            function,
+            capabilities,
+        }
+    }
+}
+
+/// Natives defined by default in the global environment.
+pub fn default_natives() -> Vec<NativeFunction> {
+    let natives = vec![
+        NativeFunction::new("at", 2, at, &[]),
+        NativeFunction::new("charAt", 2, char_at, &[]),
+        NativeFunction::new("classOf", 1, class_of, &[]),
+        NativeFunction::new("clearTimer", 1, timers::clear_timer, &[]),
+        NativeFunction::new("className", 1, class_name, &[]),
+        NativeFunction::new("clock", 0, clock, &[Capability::Time]),
+        NativeFunction::new("codePointAt", 2, code_point_at, &[]),
+        NativeFunction::new("coroutine", 1, coroutines::coroutine, &[]),
+        NativeFunction::new("docs", 1, docs, &[]),
+        NativeFunction::new("fields", 1, fields, &[]),
+        NativeFunction::new("filter", 2, filter, &[]),
+        NativeFunction::new("fromCodePoint", 1, from_code_point, &[]),
+        NativeFunction::new("gcStats", 0, gc_stats, &[]),
+        NativeFunction::new("hasMethod", 2, has_method, &[]),
+        NativeFunction::new("inspect", 1, inspect, &[]),
+        NativeFunction::new("join", 2, join, &[]),
+        NativeFunction::new("len", 1, len, &[]),
+        NativeFunction::new("list", 0, list, &[]),
+        NativeFunction::new("map", 2, map, &[]),
+        NativeFunction::new("methods", 1, methods, &[]),
+        NativeFunction::new("push", 2, push, &[]),
+        NativeFunction::new("reduce", 3, reduce, &[]),
+        NativeFunction::new("resume", 2, coroutines::resume, &[]),
+        NativeFunction::new(
+            "runEventLoop",
+            0,
+            timers::run_event_loop,
+            &[Capability::Time],
+        ),
+        NativeFunction::new("same", 2, same, &[]),
+        NativeFunction::new("setInterval", 2, timers::set_interval, &[]),
+        NativeFunction::new("setTimeout", 2, timers::set_timeout, &[]),
+        NativeFunction::new("sort", 2, sort, &[]),
+        NativeFunction::new("superclass", 1, superclass, &[]),
+        NativeFunction::new("toFixed", 2, to_fixed, &[]),
+        NativeFunction::new("weak", 1, weak, &[]),
+        NativeFunction::new("yield", 1, coroutines::yield_value, &[]),
+    ];
+    #[cfg(feature = "regex")]
+    let natives = natives
+        .into_iter()
+        .chain(super::patterns::natives())
+        .collect();
+    natives
+}
+
+/// Native classes defined by default in the global environment.
+pub fn default_classes() -> Vec<NativeClass> {
+    vec![hash_map::hash_map_class(), string_builder_class()]
+}
+
+/// Returns the global bindings of the native functions.
+pub fn bindings(natives: Vec<NativeFunction>) -> Vec<(Rc<str>, LoxValue)> {
+    natives
+        .into_iter()
+        .map(|native| {
+            let name = native.name.into();
+            let callable = LoxCallable::Native(Rc::new(native));
+            (name, LoxValue::Callable(callable))
+        })
+        .collect()
+}
+
+fn clock(interpreter: &mut Interpreter, _: &[LoxValue]) -> Result<LoxValue, String> {
+    if interpreter.options.deterministic {
+        let time = interpreter.fake_time;
+        interpreter.fake_time += 1.0;
+        return Ok(LoxValue::Number(time));
+    }
+
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|t| LoxValue::Number(t.as_secs() as f64))
+        .map_err(|err| format!("Error while calling system time: {err}"))
+}
+
+/// Returns the doc comment of the function or class, or `nil` when it has none.
+fn docs(_: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let doc = match &args[0] {
+        LoxValue::Callable(LoxCallable::LoxFunction(function)) => function.declaration.doc.clone(),
+        LoxValue::Callable(LoxCallable::Class(class)) => class.borrow().doc.clone(),
+        _ => None,
+    };
+
+    Ok(doc.map_or(LoxValue::Nil, |doc| LoxValue::String(doc.into())))
+}
+
+/// Returns the fields of the instance as a list of `[name, value]` pairs
+/// sorted by their names, which lets serializers walk any instance.
+/// Private fields are left out unless the class owning them calls it.
+fn fields(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let LoxValue::Instance(instance) = &args[0] else {
+        return Err(interpreter.fail_native(
+            codes::NOT_AN_INSTANCE,
+            format!(
+                "Only instances have fields, but got {}.",
+                args[0].describe()
+            ),
+        ));
+    };
+
+    let class_id = interpreter.current_class_id();
+    let instance = instance.borrow();
+    let pairs: Vec<_> = instance
+        .fields()
+        .into_iter()
+        .filter(|(name, _)| instance.is_visible(name, class_id))
+        .map(|(name, value)| {
+            LoxValue::list(vec![LoxValue::String(name.clone().into()), value.clone()])
+        })
+        .collect();
+    interpreter.allocations += pairs.len() as u64 + 1;
+    interpreter.count_elements(pairs.len() * 3);
+    Ok(LoxValue::list(pairs))
+}
+
+fn class_of(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    match &args[0] {
+        LoxValue::Instance(instance) => {
+            let class = instance.borrow().class().clone();
+            Ok(LoxValue::Callable(LoxCallable::Class(class)))
+        }
+        value => Err(interpreter.fail_native(
+            codes::NOT_AN_INSTANCE,
+            format!("Only instances have classes, but got {}.", value.describe()),
+        )),
+    }
+}
+
+fn class_name(_: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let class = expect_class(&args[0])?;
+    let name = class.borrow().name().clone();
+    Ok(LoxValue::String(name.into()))
+}
+
+/// Returns the names of the methods of the class, including the inherited
+/// ones, as a sorted list. Private methods are left out unless the class
+/// itself calls it.
+fn methods(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let class = expect_class(&args[0])?;
+    let inside = interpreter.current_class_id() == Some(class.borrow().id());
+    let mut names = class.borrow().method_names();
+    names.retain(|name| inside || !is_private(name));
+    interpreter.allocations += 1;
+    interpreter.count_elements(names.len());
+    Ok(LoxValue::list(
+        names
+            .into_iter()
+            .map(|name| LoxValue::String(name.into()))
+            .collect(),
+    ))
+}
+
+/// Returns the superclass of the class, or `nil` when it has none.
+fn superclass(_: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let class = expect_class(&args[0])?;
+    let super_class = class.borrow().super_class();
+    Ok(super_class.map_or(LoxValue::Nil, |class| {
+        LoxValue::Callable(LoxCallable::Class(class))
+    }))
+}
+
+/// Checks if the instance or class has a method with the given name, where
+/// private methods are only found by the class declaring them.
+fn has_method(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let class = match &args[0] {
+        LoxValue::Instance(instance) => instance.borrow().class().clone(),
+        value => expect_class(value)?,
+    };
+    let Some(name) = args[1].as_str() else {
+        return Err(format!(
+            "Method name must be a string, but got {}.",
+            args[1].describe()
+        ));
+    };
+
+    let found = if is_private(name) {
+        let class_id = interpreter.current_class_id();
+        class_id.is_some_and(|id| class.borrow().find_private_method(id, name).is_some())
+    } else {
+        class.borrow().has_method(name)
+    };
+
+    Ok(LoxValue::Boolean(found))
+}
+
+fn expect_class(value: &LoxValue) -> Result<LoxClassRef, String> {
+    match value {
+        LoxValue::Callable(LoxCallable::Class(class)) => Ok(class.clone()),
+        value => Err(format!("Expected a class, but got {}.", value.describe())),
+    }
+}
+
+/// Returns the counts of the live objects as the fields of an instance, like
+/// `gcStats().instances`.
+fn gc_stats(interpreter: &mut Interpreter, _: &[LoxValue]) -> Result<LoxValue, String> {
+    let stats = interpreter.heap_stats();
+    interpreter.allocations += 1;
+    let instance = LoxInstance::new(interpreter.heap_stats_class.clone());
+    let fields = [
+        ("environments", stats.environments),
+        ("unreachableEnvironments", stats.unreachable_environments),
+        ("instances", stats.instances),
+        ("functions", stats.functions),
+        ("lists", stats.lists),
+        ("strings", stats.strings),
+    ];
+    for (name, count) in fields {
+        instance
+            .borrow_mut()
+            .set_field(name, LoxValue::Number(count as f64));
+    }
+
+    Ok(LoxValue::Instance(instance))
+}
+
+/// Formats the number with the given count of digits after the decimal point,
+/// using the decimal separator of the number format of the interpreter.
+fn to_fixed(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let Some(num) = args[0].as_number() else {
+        return Err(format!(
+            "Can only format numbers, but got {}.",
+            args[0].describe()
+        ));
+    };
+    let digits = match args[1].as_number() {
+        Some(digits)
+            if digits.fract() == 0.0 && (0.0..=MAX_FIXED_DIGITS as f64).contains(&digits) =>
+        {
+            digits as usize
+        }
+        _ => {
+            return Err(format!(
+                "Digits must be a whole number between 0 and {MAX_FIXED_DIGITS}, but got {}.",
+                args[1].describe()
+            ));
+        }
+    };
+
+    let separator = interpreter
+        .options
+        .number_format
+        .as_ref()
+        .map_or('.', |format| format.decimal_separator);
+    let text = number_format::to_fixed(num, digits, separator);
+    interpreter.count_string(text.len());
+    Ok(LoxValue::String(text.into()))
+}
+
+/// Target of a weak handle, which doesn't keep the instance alive.
+struct WeakTarget(Weak<RefCell<LoxInstance>>);
+
+/// Class of the handles returned by `weak`, whose `get` method returns the
+/// instance or `nil` once it's freed.
+pub fn weak_class() -> NativeClass {
+    NativeClass::new("Weak").method("get", 0, |_, this, _| {
+        let target = this
+            .borrow()
+            .state::<WeakTarget>()
+            .and_then(|target| target.0.upgrade());
+        Ok(target.map_or(LoxValue::Nil, LoxValue::Instance))
+    })
+}
+
+/// Returns a weak handle to the instance, letting observers be referenced
+/// without keeping them alive.
+fn weak(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let LoxValue::Instance(instance) = &args[0] else {
+        return Err(format!(
+            "Only instances can be referenced weakly, but got {}.",
+            args[0].describe()
+        ));
+    };
+
+    interpreter.allocations += 1;
+    let handle = LoxInstance::new(interpreter.weak_class.clone());
+    handle
+        .borrow_mut()
+        .set_state(WeakTarget(Rc::downgrade(instance)));
+    Ok(LoxValue::Instance(handle))
+}
+
+/// Returns the count of elements of lists or characters of strings, where
+/// characters are Unicode scalar values.
+fn len(_: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let len = match &args[0] {
+        LoxValue::List(list) => list.borrow().len(),
+        LoxValue::String(text) => text.chars().count(),
+        value => {
+            return Err(format!(
+                "Can only get the length of lists and strings, but got {}.",
+                value.describe()
+            ));
+        }
+    };
+
+    Ok(LoxValue::Number(len as f64))
+}
+
+/// Returns the element of the list at the given index starting from zero.
+fn at(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let LoxValue::List(list) = &args[0] else {
+        return Err(interpreter.fail_native(
+            codes::INVALID_SUBSCRIPT,
+            format!("Can only index into lists, but got {}.", args[0].describe()),
+        ));
+    };
+
+    let list = list.borrow();
+    let index = check_index("List", &args[1], list.len())
+        .map_err(|message| interpreter.fail_native(codes::INVALID_SUBSCRIPT, message))?;
+    Ok(list[index].clone())
+}
+
+/// Returns a new empty list, which `push()` adds elements to.
+fn list(interpreter: &mut Interpreter, _: &[LoxValue]) -> Result<LoxValue, String> {
+    interpreter.allocations += 1;
+    Ok(LoxValue::list(Vec::new()))
+}
+
+/// Adds the value to the end of the list, returning the list so calls can be
+/// chained.
+fn push(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let LoxValue::List(list) = &args[0] else {
+        return Err(format!(
+            "Can only push to lists, but got {}.",
+            args[0].describe()
+        ));
+    };
+
+    interpreter.count_elements(1);
+    list.borrow_mut().push(args[1].clone());
+    Ok(args[0].clone())
+}
+
+/// Returns the elements of the list, which are copied so the functions called
+/// for them can't see the list changing.
+fn list_elements(value: &LoxValue, action: &str) -> Result<Vec<LoxValue>, String> {
+    match value {
+        LoxValue::List(list) => Ok(list.borrow().clone()),
+        value => Err(format!(
+            "Can only {action} lists, but got {}.",
+            value.describe()
+        )),
+    }
+}
+
+/// Returns a new list with the results of calling the function with each
+/// element.
+fn map(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let mapped = list_elements(&args[0], "map")?
+        .into_iter()
+        .map(|element| interpreter.call(&args[1], vec![element]))
+        .collect::<Result<Vec<_>, _>>()?;
+    interpreter.allocations += 1;
+    interpreter.count_elements(mapped.len());
+    Ok(LoxValue::list(mapped))
+}
+
+/// Returns a new list with the elements the function returns a truthy value
+/// for.
+fn filter(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let mut kept = Vec::new();
+    for element in list_elements(&args[0], "filter")? {
+        if interpreter
+            .call(&args[1], vec![element.clone()])?
+            .is_truthy()
+        {
+            kept.push(element);
+        }
+    }
+    interpreter.allocations += 1;
+    interpreter.count_elements(kept.len());
+    Ok(LoxValue::list(kept))
+}
+
+/// Combines the elements from the first to the last one by calling the
+/// function with the result so far and the element, starting with the
+/// initial value.
+fn reduce(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    list_elements(&args[0], "reduce")?
+        .into_iter()
+        .try_fold(args[2].clone(), |acc, element| {
+            interpreter.call(&args[1], vec![acc, element])
+        })
+}
+
+/// Returns a new list with the elements sorted by the comparator, which
+/// returns a negative number when its first argument comes first, a positive
+/// one when it comes last, and zero when their order doesn't matter.
+fn sort(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let elements = list_elements(&args[0], "sort")?;
+    let sorted = merge_sort(elements, &mut |a, b| {
+        let order = interpreter.call(&args[1], vec![a.clone(), b.clone()])?;
+        match order.as_number() {
+            Some(order) => Ok(order > 0.0),
+            None => Err(format!(
+                "Comparator must return a number, but got {}.",
+                order.describe()
+            )),
+        }
+    })?;
+    interpreter.allocations += 1;
+    interpreter.count_elements(sorted.len());
+    Ok(LoxValue::list(sorted))
+}
+
+/// Sorts the elements stably, where `comes_after` checks if its first argument
+/// must come after the second one.
+/// The sorts of the standard library can't stop on failed comparisons and
+/// may panic with comparators which aren't consistent, like most written in
+/// scripts.
+fn merge_sort(
+    mut elements: Vec<LoxValue>,
+    comes_after: &mut impl FnMut(&LoxValue, &LoxValue) -> Result<bool, String>,
+) -> Result<Vec<LoxValue>, String> {
+    if elements.len() <= 1 {
+        return Ok(elements);
+    }
+
+    let right = elements.split_off(elements.len() / 2);
+    let left = merge_sort(elements, comes_after)?;
+    let right = merge_sort(right, comes_after)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        // Equal elements keep their order by taking the left one first.
+        let next = if comes_after(l, r)? {
+            right.next()
+        } else {
+            left.next()
+        };
+        merged.extend(next);
+    }
+    merged.extend(left);
+    merged.extend(right);
+
+    Ok(merged)
+}
+
+/// Joins the elements of the list into a string with the separator between
+/// them, where elements which aren't strings are written like `print` does.
+fn join(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let elements = list_elements(&args[0], "join")?;
+    let Some(separator) = args[1].as_str() else {
+        return Err(format!(
+            "Separator must be a string, but got {}.",
+            args[1].describe()
+        ));
+    };
+
+    let mut joined = String::new();
+    for (index, element) in elements.iter().enumerate() {
+        if index > 0 {
+            joined.push_str(separator);
+        }
+        push_value(&mut joined, element);
+        check_string_length(interpreter, joined.len())?;
+    }
+    interpreter.allocations += 1;
+    interpreter.count_string(joined.len());
+    Ok(LoxValue::String(joined.into()))
+}
+
+/// Builds strings from many parts without copying the parts built so far on
+/// each append, unlike concatenating them with `+` in loops.
+fn string_builder_class() -> NativeClass {
+    /// Text of the builder, which is kept in the state of its instances.
+    struct Text(RefCell<String>);
+    const UNINITIALIZED: &str = "StringBuilder isn't initialized.";
+
+    NativeClass::new("StringBuilder")
+        .method("init", 0, |_, this, _| {
+            this.borrow_mut().set_state(Text(RefCell::default()));
+            Ok(LoxValue::Nil)
+        })
+        .method("append", 1, |interpreter, this, args| {
+            let instance = this.borrow();
+            let mut text = instance
+                .state::<Text>()
+                .ok_or(UNINITIALIZED)?
+                .0
+                .borrow_mut();
+            let len = text.len();
+            push_value(&mut text, &args[0]);
+            if let Err(err) = check_string_length(interpreter, text.len()) {
+                text.truncate(len);
+                return Err(err);
+            }
+            interpreter.count_string(text.len() - len);
+            // Returning the builder lets appends be chained.
+            Ok(LoxValue::Instance(this.clone()))
+        })
+        .method("toString", 0, |interpreter, this, _| {
+            let instance = this.borrow();
+            let text = instance.state::<Text>().ok_or(UNINITIALIZED)?.0.borrow();
+            interpreter.allocations += 1;
+            interpreter.count_string(text.len());
+            Ok(LoxValue::String(text.as_str().into()))
+        })
+}
+
+/// Appends the value to the string like `print` writes it.
+fn push_value(text: &mut String, value: &LoxValue) {
+    match value {
+        LoxValue::String(part) => text.push_str(part),
+        value => {
+            use std::fmt::Write;
+            write!(text, "{value}").expect("Writing to strings can't fail");
+        }
+    }
+}
+
+/// Checks the length of built strings against the limit of the options.
+fn check_string_length(interpreter: &mut Interpreter, len: usize) -> Result<(), String> {
+    match interpreter.options.max_string_length {
+        Some(limit) if len > limit => Err(interpreter.fail_native(
+            codes::STRING_TOO_LONG,
+            format!("String length exceeds the limit of {limit} bytes."),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the character at the given index, counting Unicode scalar values
+/// like `len` does.
+fn char_at(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let ch = nth_char(args)
+        .map_err(|message| interpreter.fail_native(codes::INVALID_SUBSCRIPT, message))?;
+    Ok(LoxValue::String(ch.to_string().into()))
+}
+
+/// Returns the code point of the character at the given index.
+fn code_point_at(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let ch = nth_char(args)
+        .map_err(|message| interpreter.fail_native(codes::INVALID_SUBSCRIPT, message))?;
+    Ok(LoxValue::Number(u32::from(ch) as f64))
+}
+
+/// Returns the string of the character with the given code point.
+fn from_code_point(_: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let ch = args[0]
+        .as_number()
+        .filter(|num| num.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(num))
+        .and_then(|num| char::from_u32(num as u32));
+    match ch {
+        Some(ch) => Ok(LoxValue::String(ch.to_string().into())),
+        None => Err(format!(
+            "Code point must be a Unicode scalar value, but got {}.",
+            args[0].describe()
+        )),
+    }
+}
+
+/// Returns the character of the string in the first argument at the index in
+/// the second one.
+fn nth_char(args: &[LoxValue]) -> Result<char, String> {
+    let Some(text) = args[0].as_str() else {
+        return Err(format!(
+            "Can only index into strings, but got {}.",
+            args[0].describe()
+        ));
+    };
+
+    let index = check_index("String", &args[1], text.chars().count())?;
+    Ok(text
+        .chars()
+        .nth(index)
+        .expect("Index is checked against the length"))
+}
+
+/// Checks that the value is a valid index into a list or string with the
+/// given length.
+pub(super) fn check_index(kind: &str, index: &LoxValue, len: usize) -> Result<usize, String> {
+    let Some(num) = index.as_number() else {
+        return Err(format!(
+            "{kind} index must be a number, but got {}.",
+            index.describe()
+        ));
+    };
+
+    if num.fract() != 0.0 || num < 0.0 || num >= len as f64 {
+        return Err(format!(
+            "{kind} index {index} is out of bounds for length {len}."
+        ));
+    }
+    Ok(num as usize)
+}
+
+/// Checks if both values are identical, ignoring the `equals` methods of instances.
+fn same(_: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    Ok(LoxValue::Boolean(args[0] == args[1]))
+}
+
+/// Describes the value in detail for debugging, listing the fields of instances
+/// and the methods and arity of classes and functions. Private members are left
+/// out like in `fields()` and `methods()`.
+fn inspect(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let class_id = interpreter.current_class_id();
+    let mut lines = Vec::new();
+    match &args[0] {
+        LoxValue::Callable(LoxCallable::Native(native)) => {
+            lines.push(format!("native fun {}", native.name));
+            lines.push(format!("  arity: {}", native.arity));
+        }
+        LoxValue::Callable(LoxCallable::LoxFunction(function)) => {
+            lines.push(format!("fun {}", signature(function)));
+            lines.push(format!("  arity: {}", function.arity()));
+        }
+        LoxValue::Callable(LoxCallable::Class(class)) => {
+            let class = class.borrow();
+            match class.super_class() {
+                Some(super_class) => {
+                    lines.push(format!("class {class} < {}", super_class.borrow()))
+                }
+                None => lines.push(format!("class {class}")),
+            }
+            lines.push(format!("  arity: {}", class.arity()));
+            push_methods(&mut lines, &visible_methods(&class, class_id));
+        }
+        LoxValue::Instance(instance) => {
+            let instance = instance.borrow();
+            lines.push(instance.to_string());
+            for (name, value) in instance.fields() {
+                if instance.is_visible(name, class_id) {
+                    lines.push(format!("  {name}: {}", inspect_value(value)));
+                }
+            }
+            push_methods(
+                &mut lines,
+                &visible_methods(&instance.class().borrow(), class_id),
+            );
+        }
+        LoxValue::Nil => lines.push(String::from("nil")),
+        LoxValue::Foreign(foreign) => lines.push(format!("foreign {}", foreign.type_name())),
+        value => lines.push(format!("{} {}", value.type_name(), inspect_value(value))),
+    }
+
+    let text = lines.join("\n");
+    interpreter.count_string(text.len());
+    Ok(LoxValue::String(text.into()))
+}
+
+/// Methods of the class without the private ones unless the class with the
+/// given ID is the class itself.
+fn visible_methods(class: &LoxClass, class_id: Option<u64>) -> Vec<(Rc<str>, LoxFunction)> {
+    let mut methods = class.methods();
+    if class_id != Some(class.id()) {
+        methods.retain(|(name, _)| !is_private(name));
+    }
+    methods
+}
+
+fn push_methods(lines: &mut Vec<String>, methods: &[(Rc<str>, LoxFunction)]) {
+    if methods.is_empty() {
+        return;
+    }
+
+    let methods: Vec<_> = methods
+        .iter()
+        .map(|(_, method)| signature(method))
+        .collect();
+    lines.push(format!("  methods: {}", methods.join(", ")));
+}
+
+/// Name of the function followed by its parameters, like `add(a, b)`.
+fn signature(function: &LoxFunction) -> String {
+    let declaration = &function.declaration;
+    let params: Vec<_> = declaration
+        .params
+        .iter()
+        .map(|param| param.lexeme.as_ref())
+        .collect();
+    format!("{}({})", declaration.name.lexeme, params.join(", "))
+}
+
+/// Renders the value with its state, quoting strings to tell them apart from
+/// other values.
+fn inspect_value(value: &LoxValue) -> String {
+    match value {
+        LoxValue::String(text) => format!("{text:?}"),
+        value => value.pretty(DEFAULT_PRETTY_DEPTH),
+    }
+}
@@ -0,0 +1,55 @@
+//! Formatting of the numbers printed by scripts, replacing the output of jlox
+//! with a fixed precision or the decimal separator of a locale.
+
+use super::LoxValue;
+
+/// Maximum count of digits after the decimal point accepted by `toFixed`.
+pub const MAX_FIXED_DIGITS: usize = 100;
+
+/// Configurations for printing numbers, set with
+/// [`InterpreterBuilder::number_format()`](super::InterpreterBuilder::number_format).
+#[derive(Debug, Clone)]
+pub struct NumberFormat {
+    /// Separator between the integral and the fractional part, like `,` for
+    /// German locales.
+    pub decimal_separator: char,
+    /// Count of digits printed after the separator. Numbers are printed like
+    /// jlox with all their digits when it's `None`.
+    pub precision: Option<usize>,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            decimal_separator: '.',
+            precision: None,
+        }
+    }
+}
+
+impl NumberFormat {
+    pub fn format(&self, num: f64) -> String {
+        match self.precision {
+            Some(digits) => to_fixed(num, digits, self.decimal_separator),
+            None => with_separator(LoxValue::Number(num).to_string(), self.decimal_separator),
+        }
+    }
+}
+
+/// Formats the number with the given count of digits after the separator,
+/// rounding ties to even. `NaN` and the infinities are written like jlox.
+pub fn to_fixed(num: f64, digits: usize, decimal_separator: char) -> String {
+    if !num.is_finite() {
+        return LoxValue::Number(num).to_string();
+    }
+
+    with_separator(format!("{num:.digits$}"), decimal_separator)
+}
+
+fn with_separator(text: String, decimal_separator: char) -> String {
+    if decimal_separator == '.' {
+        text
+    } else {
+        text.replace('.', decimal_separator.encode_utf8(&mut [0; 4]))
+    }
+}
@@ -0,0 +1,60 @@
+use std::rc::Rc;
+
+use lox_frontend::ast::Stmt;
+
+use super::LoxValue;
+use crate::errors::LoxError;
+
+/// Callbacks of the interpreter while executing programs, which tools like
+/// tracers, profilers, debuggers and coverage can be built on.
+///
+/// All callbacks do nothing by default, so observers only implement the ones
+/// they need.
+pub trait ExecutionObserver {
+    /// Called before executing the statement.
+    fn on_stmt_enter(&mut self, _stmt: &Stmt) {}
+
+    /// Called once the statement is done, including when it's left by `return`,
+    /// `break` or `continue`.
+    fn on_stmt_exit(&mut self, _stmt: &Stmt) {}
+
+    /// Called before calling the function, native or class on the given line.
+    fn on_call(&mut self, _callee: &LoxValue, _args: &[LoxValue], _line: usize) {}
+
+    /// Called with the value returned from the last call.
+    fn on_return(&mut self, _value: &LoxValue) {}
+
+    /// Called when an error stops the current top-level statement.
+    /// The statements and calls left by the error don't get exit and return
+    /// callbacks, except for the statements left to run `finally` blocks.
+    fn on_error(&mut self, _error: &LoxError) {}
+}
+
+/// Observer registered on the interpreter.
+pub(super) struct Observer(pub Box<dyn ExecutionObserver>);
+
+impl std::fmt::Debug for Observer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+/// Statement to pass to the observer once it's done.
+#[derive(Debug)]
+pub(super) enum ExitedStmt {
+    Shared(Rc<Stmt>),
+    /// Statement at the index of a block or function body.
+    InList {
+        stmts: Rc<[Stmt]>,
+        index: usize,
+    },
+}
+
+impl ExitedStmt {
+    pub fn stmt(&self) -> &Stmt {
+        match self {
+            ExitedStmt::Shared(stmt) => stmt,
+            ExitedStmt::InList { stmts, index } => &stmts[*index],
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use lox_frontend::ParserOptions;
+
+use super::{NumberFormat, PrettyOptions, SandboxPolicy};
+
+/// Default for the maximum count of nested calls.
+/// Call frames are kept on the heap, so the limit only guards against
+/// runaway recursion.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 100_000;
+
+/// Configurations for the behavior of the interpreter.
+#[derive(Debug, Clone)]
+pub struct InterpreterOptions {
+    /// Maximum count of nested function calls. Exceeding it fails with a
+    /// "Stack overflow." runtime error instead of crashing the host process.
+    pub max_call_depth: usize,
+    /// Maximum count of statements and expressions evaluated in one run.
+    /// Exceeding it aborts the execution, which protects against scripts that
+    /// would run forever. No limit is applied when it's `None`.
+    pub max_steps: Option<u64>,
+    /// Runs the constant folding pass over the parsed statements before
+    /// executing them.
+    pub fold_constants: bool,
+    /// Follows IEEE 754 on division by zero, resulting in infinity or `NaN`
+    /// instead of failing with a runtime error.
+    pub ieee_division: bool,
+    /// Requires the conditions of `if`, `while` and `for` to be booleans,
+    /// failing with a runtime error on other values instead of using their
+    /// truthiness. Useful for teaching, where `if (count)` is usually a bug.
+    pub strict_bool: bool,
+    /// Fails resolving with an error on variables which aren't declared in
+    /// any scope, instead of only warning about them.
+    pub deny_undefined_globals: bool,
+    /// Fails resolving with an error on local variables declared with `var`
+    /// which are never used.
+    pub deny_unused_variables: bool,
+    /// Fails resolving with an error on local variables and parameters with
+    /// the same name as a variable of an enclosing scope or a global declared
+    /// by the scripts.
+    pub deny_shadowing: bool,
+    /// Makes the natives depending on the environment reproducible, so runs
+    /// of the same program always print the same output. `clock()` returns a
+    /// fake time starting from zero and increasing by one second on each call.
+    pub deterministic: bool,
+    /// Restrictions of the natives and resources for running untrusted code.
+    pub sandbox: SandboxPolicy,
+    /// Limits of the parsed code, like the count of arguments in calls.
+    pub parser: ParserOptions,
+    /// Maximum length in bytes of the strings created by concatenation.
+    /// Exceeding it fails with a runtime error.
+    pub max_string_length: Option<usize>,
+    /// Maximum count of iterations of each run of a loop. Exceeding it aborts
+    /// the execution.
+    pub max_loop_iterations: Option<u64>,
+    /// Prints the values with the fields of the instances instead of the
+    /// output of jlox (`Foo instance`) when set.
+    pub pretty_print: Option<PrettyOptions>,
+    /// Prints numbers with the given precision and decimal separator instead
+    /// of the output of jlox when set.
+    pub number_format: Option<NumberFormat>,
+    /// Stops executing at the first runtime error returning it, instead of
+    /// reporting it and continuing with the next top level statement.
+    pub stop_on_error: bool,
+}
+
+impl InterpreterOptions {
+    /// Enables all the opt-in checks of the strict mode. Lox only converts
+    /// values implicitly when using their truthiness in conditions, which
+    /// `strict_bool` disallows.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict_bool = strict;
+        self.deny_undefined_globals = strict;
+        self.deny_unused_variables = strict;
+        self.deny_shadowing = strict;
+        self
+    }
+}
+
+impl Default for InterpreterOptions {
+    fn default() -> Self {
+        Self {
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_steps: None,
+            fold_constants: false,
+            ieee_division: false,
+            strict_bool: false,
+            deny_undefined_globals: false,
+            deny_unused_variables: false,
+            deny_shadowing: false,
+            deterministic: false,
+            sandbox: SandboxPolicy::default(),
+            parser: ParserOptions::default(),
+            max_string_length: None,
+            max_loop_iterations: None,
+            pretty_print: None,
+            number_format: None,
+            stop_on_error: false,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Natives matching regular expressions, enabled with the `regex` feature.
+//!
+//! Matches are returned as lists of their captures, starting with the whole
+//! match and followed by the groups in order, where groups which didn't
+//! participate in the match are `nil`.
+
+use regex::{Captures, Regex};
+
+use super::{Interpreter, LoxValue, natives::NativeFunction};
+
+/// Natives added to the defaults when the feature is enabled.
+pub fn natives() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("regexFindAll", 2, find_all, &[]),
+        NativeFunction::new("regexMatch", 2, find, &[]),
+        NativeFunction::new("regexReplace", 3, replace, &[]),
+    ]
+}
+
+/// Returns the captures of the first match in the string, or `nil` if the
+/// pattern doesn't match.
+fn find(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let regex = compile(&args[0])?;
+    let text = expect_str(&args[1])?;
+
+    Ok(regex
+        .captures(text)
+        .map_or(LoxValue::Nil, |caps| captures_list(interpreter, &caps)))
+}
+
+/// Returns the captures of all the matches in the string, which don't
+/// overlap.
+fn find_all(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let regex = compile(&args[0])?;
+    let text = expect_str(&args[1])?;
+
+    let matches: Vec<_> = regex
+        .captures_iter(text)
+        .map(|caps| captures_list(interpreter, &caps))
+        .collect();
+    interpreter.allocations += 1;
+    interpreter.count_elements(matches.len());
+    Ok(LoxValue::list(matches))
+}
+
+/// Replaces all the matches in the string, where the replacement can refer
+/// to the groups with `$1` or `${name}`.
+fn replace(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let regex = compile(&args[0])?;
+    let text = expect_str(&args[1])?;
+    let replacement = expect_str(&args[2])?;
+
+    let replaced = regex.replace_all(text, replacement);
+    interpreter.count_string(replaced.len());
+    Ok(LoxValue::String(replaced.into_owned().into()))
+}
+
+fn compile(pattern: &LoxValue) -> Result<Regex, String> {
+    let Some(pattern) = pattern.as_str() else {
+        return Err(format!(
+            "Regex pattern must be a string, but got {}.",
+            pattern.describe()
+        ));
+    };
+
+    Regex::new(pattern).map_err(|err| {
+        // Syntax errors point at the pattern over multiple lines before the
+        // actual message.
+        let err = err.to_string();
+        let message = err.lines().last().unwrap_or_default();
+        let message = message.strip_prefix("error: ").unwrap_or(message);
+        format!("Invalid regex pattern \"{pattern}\": {message}.")
+    })
+}
+
+fn expect_str(value: &LoxValue) -> Result<&str, String> {
+    value.as_str().ok_or_else(|| {
+        format!(
+            "Can only match regex patterns against strings, but got {}.",
+            value.describe()
+        )
+    })
+}
+
+fn captures_list(interpreter: &mut Interpreter, caps: &Captures) -> LoxValue {
+    let groups = caps
+        .iter()
+        .map(|group| group.map_or(LoxValue::Nil, |m| LoxValue::String(m.as_str().into())))
+        .collect::<Vec<_>>();
+    interpreter.allocations += 1;
+    interpreter.count_elements(groups.len());
+    LoxValue::list(groups)
+}
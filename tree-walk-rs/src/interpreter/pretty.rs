@@ -0,0 +1,155 @@
+//! Rendering of values together with their state, which is used where the
+//! values are shown to humans instead of matching the output of jlox.
+
+use std::rc::Rc;
+
+use super::LoxValue;
+
+/// Default for the count of nested levels rendered with their content.
+pub const DEFAULT_PRETTY_DEPTH: usize = 4;
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+
+/// Configurations for rendering values with [`LoxValue::pretty_with()`].
+#[derive(Debug, Clone)]
+pub struct PrettyOptions {
+    /// Count of nested levels rendered with their content. Deeper values are
+    /// shortened to `...`.
+    pub depth_limit: usize,
+    /// Colorizes the values with ANSI escape codes for terminals.
+    pub colors: bool,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> Self {
+        Self {
+            depth_limit: DEFAULT_PRETTY_DEPTH,
+            colors: false,
+        }
+    }
+}
+
+impl LoxValue {
+    /// Renders the value with the fields of the instances, like
+    /// `Point { x: 1, y: 2 }`, down to the given depth of nesting.
+    pub fn pretty(&self, depth_limit: usize) -> String {
+        self.pretty_with(&PrettyOptions {
+            depth_limit,
+            colors: false,
+        })
+    }
+
+    pub fn pretty_with(&self, options: &PrettyOptions) -> String {
+        let mut printer = PrettyPrinter {
+            options,
+            out: String::new(),
+            path: Vec::new(),
+        };
+        printer.value(self, 0);
+        printer.out
+    }
+}
+
+struct PrettyPrinter<'a> {
+    options: &'a PrettyOptions,
+    out: String,
+    /// Instances and lists enclosing the value being rendered, which are
+    /// cycles when they are reached again.
+    path: Vec<*const ()>,
+}
+
+impl PrettyPrinter<'_> {
+    fn value(&mut self, value: &LoxValue, depth: usize) {
+        match value {
+            LoxValue::Nil | LoxValue::Boolean(..) => self.colored(MAGENTA, &value.to_string()),
+            LoxValue::Number(..) => self.colored(YELLOW, &value.to_string()),
+            // Strings are only quoted inside other values, keeping the text
+            // printed on its own the same.
+            LoxValue::String(text) if depth == 0 => self.out.push_str(text),
+            LoxValue::String(text) => self.colored(GREEN, &format!("{text:?}")),
+            LoxValue::Callable(..) | LoxValue::Foreign(..) => {
+                self.colored(CYAN, &value.to_string())
+            }
+            LoxValue::Instance(instance) => {
+                let ptr = Rc::as_ptr(instance).cast();
+                let instance = instance.borrow();
+                if self.path.contains(&ptr) {
+                    self.colored(DIM, &format!("<cycle {instance}>"));
+                    return;
+                }
+
+                let fields = instance.fields();
+
+                self.colored(CYAN, &instance.class().borrow().to_string());
+                if fields.is_empty() {
+                    self.out.push_str(" {}");
+                    return;
+                }
+                if depth >= self.options.depth_limit {
+                    self.out.push_str(" { ");
+                    self.colored(DIM, "...");
+                    self.out.push_str(" }");
+                    return;
+                }
+
+                self.path.push(ptr);
+                self.out.push_str(" { ");
+                for (index, (name, value)) in fields.into_iter().enumerate() {
+                    if index > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str(name);
+                    self.out.push_str(": ");
+                    self.value(value, depth + 1);
+                }
+                self.out.push_str(" }");
+                self.path.pop();
+            }
+            LoxValue::List(list) => {
+                let ptr = Rc::as_ptr(list).cast();
+                if self.path.contains(&ptr) {
+                    self.colored(DIM, "<cycle list>");
+                    return;
+                }
+
+                let list = list.borrow();
+                if list.is_empty() {
+                    self.out.push_str("[]");
+                    return;
+                }
+                if depth >= self.options.depth_limit {
+                    self.out.push('[');
+                    self.colored(DIM, "...");
+                    self.out.push(']');
+                    return;
+                }
+
+                self.path.push(ptr);
+                self.out.push('[');
+                for (index, value) in list.iter().enumerate() {
+                    if index > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.value(value, depth + 1);
+                }
+                self.out.push(']');
+                self.path.pop();
+            }
+        }
+    }
+
+    fn colored(&mut self, color: &str, text: &str) {
+        if self.options.colors {
+            self.out.push_str(color);
+            self.out.push_str(text);
+            self.out.push_str(RESET);
+        } else {
+            self.out.push_str(text);
+        }
+    }
+}
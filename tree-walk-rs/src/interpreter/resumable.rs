@@ -0,0 +1,268 @@
+//! Resumable execution of scripts, which natives can suspend while they wait
+//! for the host. A game engine can run a script calling `wait(2.0)` over the
+//! frames of two seconds without blocking its main loop.
+//!
+//! Scripts can also run with a time budget for each slice, pausing once it's
+//! used up and continuing in the next slice, like the next frame of a game.
+//! Stepping scripts pause before each statement instead, which tools like the
+//! explorer use to show the state of the script between the statements.
+
+use std::{
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use lox_frontend::{ast::Stmt, codes};
+
+use crate::{
+    RunError,
+    errors::{LoxError, LoxResult},
+};
+
+use super::{Checkpoint, Interpreter, LoxValue, task::Task};
+
+/// State of a script running in resumable mode once it stops.
+#[derive(Debug)]
+pub enum RunState {
+    /// Script ran to its end, with the value of its last statement when it's
+    /// an expression statement.
+    Done(Option<LoxValue>),
+    /// Script waits for the host to handle the request of a native, and
+    /// continues with [`Interpreter::resume()`].
+    Suspended(LoxValue),
+    /// Script used up the time budget of its slice, or is stepping and
+    /// reached the next statement, and continues with
+    /// [`Interpreter::continue_run()`].
+    Paused,
+}
+
+/// Top level statements of a script running in resumable mode.
+#[derive(Debug)]
+pub(super) struct Script {
+    stmts: Rc<[Stmt]>,
+    /// Index of the running statement.
+    index: usize,
+    /// State before the running statement, which is restored when it fails.
+    checkpoint: Checkpoint,
+    last_value: Option<LoxValue>,
+    /// Time budget of each slice of the run.
+    budget: Option<Duration>,
+    /// Checks if the script pauses before each statement.
+    stepping: bool,
+    /// Checks if the statement at the index is started, which is false for
+    /// stepping scripts paused before it.
+    started: bool,
+    /// Checks if the script is suspended by a native, which gets the value
+    /// of the host once resumed.
+    waits_for_host: bool,
+}
+
+impl Interpreter {
+    /// Scans, parses, resolves and executes the source code like `eval()`,
+    /// where natives can suspend the execution with `suspend()`.
+    pub fn start(&mut self, source: &str) -> Result<RunState, RunError> {
+        let stmts = crate::prepare(self, source.to_owned())?;
+        self.start_script(stmts, None, false)
+    }
+
+    /// Scans, parses, resolves and executes the source code like `start()`,
+    /// pausing once the run took the given microseconds.
+    pub fn start_budgeted(&mut self, source: &str, max_micros: u64) -> Result<RunState, RunError> {
+        let stmts = crate::prepare(self, source.to_owned())?;
+        self.run_budgeted(stmts, max_micros)
+    }
+
+    /// Executes the resolved statements until they are done or the run took
+    /// the given microseconds, where they pause with [`RunState::Paused`].
+    /// Each call to `continue_run()` or `resume()` gets the same budget.
+    pub fn run_budgeted(
+        &mut self,
+        stmts: Vec<Stmt>,
+        max_micros: u64,
+    ) -> Result<RunState, RunError> {
+        self.start_script(stmts, Some(Duration::from_micros(max_micros)), false)
+    }
+
+    /// Scans, parses and resolves the source code like `start()`, then steps
+    /// through it like `step_through()`.
+    pub fn start_stepping(&mut self, source: &str) -> Result<RunState, RunError> {
+        let stmts = crate::prepare(self, source.to_owned())?;
+        self.step_through(stmts)
+    }
+
+    /// Executes the resolved statements pausing with [`RunState::Paused`]
+    /// before each statement, including the ones in blocks and functions,
+    /// where each call to `continue_run()` runs to the next statement.
+    /// Functions called back by natives run without pausing.
+    ///
+    /// While paused, [`Interpreter::next_stmt()`] returns the statement the
+    /// script continues with and [`Interpreter::environment_chain()`] returns
+    /// the variables it sees, including the names of its locals.
+    pub fn step_through(&mut self, stmts: Vec<Stmt>) -> Result<RunState, RunError> {
+        self.start_script(stmts, None, true)
+    }
+
+    fn start_script(
+        &mut self,
+        stmts: Vec<Stmt>,
+        budget: Option<Duration>,
+        stepping: bool,
+    ) -> Result<RunState, RunError> {
+        let script = Script {
+            stmts: stmts.into(),
+            index: 0,
+            checkpoint: self.checkpoint(),
+            last_value: None,
+            budget,
+            stepping,
+            started: false,
+            waits_for_host: false,
+        };
+
+        self.step_resumed = false;
+        self.start_run();
+        self.run_script(script, false).map_err(RunError::Runtime)
+    }
+
+    /// Continues the suspended script, where the given value is returned from
+    /// the call to the native which suspended it.
+    pub fn resume(&mut self, value: LoxValue) -> Result<RunState, RunError> {
+        let Some(script) = self.script.take_if(|script| script.waits_for_host) else {
+            return Err(anyhow!("There is no suspended script to resume").into());
+        };
+
+        self.start_run();
+        self.observe(|observer| observer.on_return(&value));
+        self.values.push(value);
+        self.run_script(script, true).map_err(RunError::Runtime)
+    }
+
+    /// Continues the paused script with a new slice of its time budget.
+    pub fn continue_run(&mut self) -> Result<RunState, RunError> {
+        let Some(script) = self.script.take_if(|script| !script.waits_for_host) else {
+            return Err(anyhow!("There is no paused script to continue").into());
+        };
+
+        // Stepping scripts run the statement they paused before.
+        self.step_resumed = true;
+        self.start_run();
+        self.run_script(script, true).map_err(RunError::Runtime)
+    }
+
+    /// Returns the statement the stepping script continues with while it's
+    /// paused.
+    pub fn next_stmt(&self) -> Option<&Stmt> {
+        let script = self.script.as_ref().filter(|script| script.stepping)?;
+        if script.started {
+            self.pending_stmt()
+        } else {
+            script.stmts.get(script.index)
+        }
+    }
+
+    /// Returns the statement the next pending task starts.
+    fn pending_stmt(&self) -> Option<&Stmt> {
+        match self.tasks.last()? {
+            Task::Execute(stmt) => Some(stmt),
+            Task::Statements { stmts, index } => stmts.get(*index),
+            _ => None,
+        }
+    }
+
+    /// Checks if the stepping script must pause before the next task, which
+    /// starts a statement it hasn't paused before.
+    pub(super) fn step_due(&mut self) -> bool {
+        if !self.stepping || self.pending_stmt().is_none() {
+            return false;
+        }
+
+        !std::mem::take(&mut self.step_resumed)
+    }
+
+    /// Checks if a script is suspended or paused, keeping its state until it
+    /// continues.
+    pub fn is_suspended(&self) -> bool {
+        self.script.is_some()
+    }
+
+    /// Suspends the running script until the host resumes it, where the
+    /// request tells the host what the script waits for.
+    /// Natives must return its result: `return interpreter.suspend(request);`,
+    /// which fails for scripts not running in resumable mode.
+    pub fn suspend(&mut self, request: LoxValue) -> Result<LoxValue, String> {
+        if !self.resumable {
+            return Err(self.fail_native(
+                codes::INVALID_SUSPENSION,
+                "Can't suspend scripts which aren't started as resumable.",
+            ));
+        }
+
+        self.suspended = Some(request);
+        Ok(LoxValue::Nil)
+    }
+
+    /// Runs the statements of the script until it's done, suspended or paused.
+    /// Resumed scripts continue with the pending tasks of their statement.
+    fn run_script(&mut self, mut script: Script, resumed: bool) -> LoxResult<RunState> {
+        self.resumable = true;
+        self.stepping = script.stepping;
+        self.deadline = script.budget.map(|budget| Instant::now() + budget);
+        self.slice_tasks = 0;
+        let stmts = script.stmts.clone();
+        let mut resumed = resumed && script.started;
+        let state = loop {
+            let Some(stmt) = stmts.get(script.index) else {
+                break Ok(RunState::Done(script.last_value));
+            };
+
+            let result = if resumed {
+                resumed = false;
+                self.run(script.checkpoint.tasks_len)
+            } else {
+                if self.stepping && !std::mem::take(&mut self.step_resumed) {
+                    script.started = false;
+                    self.script = Some(script);
+                    break Ok(RunState::Paused);
+                }
+                script.started = true;
+                script.checkpoint = self.checkpoint();
+                self.start_top_level(stmt)
+                    .and_then(|()| self.run(script.checkpoint.tasks_len))
+            };
+            match self.finish_top_level(stmt, &script.checkpoint, result) {
+                Ok(value) => script.last_value = value,
+                Err(LoxError::Suspended) => {
+                    let request = self
+                        .suspended
+                        .take()
+                        .expect("Natives suspending the script set their requests");
+                    script.waits_for_host = true;
+                    self.script = Some(script);
+                    break Ok(RunState::Suspended(request));
+                }
+                Err(LoxError::Paused) => {
+                    script.waits_for_host = false;
+                    self.script = Some(script);
+                    break Ok(RunState::Paused);
+                }
+                Err(err) => {
+                    // Requests of natives failing after suspending are dropped.
+                    self.suspended = None;
+                    if let Err(err) = self.handle_top_level_error(err) {
+                        break Err(err);
+                    }
+                    script.last_value = None;
+                }
+            }
+
+            self.collect_garbage_if_needed();
+            script.index += 1;
+        };
+        self.resumable = false;
+        self.stepping = false;
+        self.deadline = None;
+
+        state
+    }
+}
@@ -0,0 +1,92 @@
+use std::{collections::HashSet, fmt::Display, time::Duration};
+
+use super::natives::NativeFunction;
+
+/// Access to the host system which native functions can need.
+///
+/// Embedders tag their own natives with the capabilities they use through
+/// [`InterpreterBuilder::native_with_capabilities()`](super::InterpreterBuilder::native_with_capabilities),
+/// so the sandbox policy can disable them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Reading the current time of the system.
+    Time,
+    /// Reading and writing files, sockets and other I/O of the host.
+    Io,
+    /// Running other processes.
+    Exec,
+    /// Reading the environment variables of the host.
+    Env,
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capability::Time => f.write_str("time"),
+            Capability::Io => f.write_str("io"),
+            Capability::Exec => f.write_str("exec"),
+            Capability::Env => f.write_str("env"),
+        }
+    }
+}
+
+/// Restrictions for running untrusted code, which allows everything by
+/// default.
+#[derive(Debug, Clone, Default)]
+pub struct SandboxPolicy {
+    /// Names of the only natives and native classes which can be called. The
+    /// methods of native classes can only be called when their classes are
+    /// allowed, including the classes of the values returned by natives, like
+    /// `Coroutine`. All natives are allowed when it's `None`.
+    pub allowed_natives: Option<HashSet<String>>,
+    /// Capabilities which natives can't use.
+    pub disabled_capabilities: HashSet<Capability>,
+    /// Maximum duration of one run.
+    pub max_duration: Option<Duration>,
+    /// Maximum count of environments, instances, strings and lists created in
+    /// one run, regardless of their sizes.
+    pub max_allocations: Option<u64>,
+    /// Maximum count of bytes of the strings created and the elements added
+    /// to lists in one run, which caps the memory the run can use.
+    pub max_memory: Option<u64>,
+}
+
+impl SandboxPolicy {
+    /// Checks if the native can be called, returning the message of the
+    /// runtime error otherwise.
+    pub(super) fn check_native(&self, native: &NativeFunction) -> Result<(), String> {
+        self.check_allowed("native", native.name)?;
+
+        match native
+            .capabilities
+            .iter()
+            .find(|capability| self.disabled_capabilities.contains(capability))
+        {
+            Some(capability) => Err(format!(
+                "Capability disabled: '{}' needs the capability '{capability}'.",
+                native.name
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks if the native class with the given name can be instantiated and
+    /// its methods called, returning the message of the runtime error otherwise.
+    pub(super) fn check_native_class(&self, name: &str) -> Result<(), String> {
+        self.check_allowed("native class", name)
+    }
+
+    fn check_allowed(&self, kind: &str, name: &str) -> Result<(), String> {
+        match &self.allowed_natives {
+            Some(allowed) if !allowed.contains(name) => Err(format!(
+                "Capability disabled: {kind} '{name}' isn't allowed by the sandbox policy."
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks if the policy limits the resources of the runs.
+    pub fn has_limits(&self) -> bool {
+        self.max_duration.is_some() || self.max_allocations.is_some() || self.max_memory.is_some()
+    }
+}
@@ -0,0 +1,93 @@
+use std::{collections::BTreeMap, fmt::Display, rc::Rc};
+
+use super::LoxValue;
+
+/// Bindings of the global variables at one point of the execution.
+///
+/// Values are captured as they are, so instances and classes are still shared
+/// with the interpreter and changing their fields doesn't change the binding.
+#[derive(Debug, Clone, Default)]
+pub struct EnvSnapshot {
+    bindings: BTreeMap<Rc<str>, LoxValue>,
+}
+
+impl EnvSnapshot {
+    pub(super) fn new(bindings: impl IntoIterator<Item = (Rc<str>, LoxValue)>) -> Self {
+        Self {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LoxValue> {
+        self.bindings.get(name)
+    }
+
+    /// Returns the bindings sorted by their names.
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, &LoxValue)> {
+        self.bindings
+            .iter()
+            .map(|(name, value)| (name.as_ref(), value))
+    }
+
+    /// Returns the changes of the bindings from this snapshot to the other one.
+    pub fn diff(&self, other: &EnvSnapshot) -> EnvDiff {
+        let mut diff = EnvDiff::default();
+        for (name, new) in &other.bindings {
+            match self.bindings.get(name) {
+                None => diff.added.push((name.clone(), new.clone())),
+                Some(old) if !same_binding(old, new) => {
+                    diff.changed.push((name.clone(), old.clone(), new.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, old) in &self.bindings {
+            if !other.bindings.contains_key(name) {
+                diff.removed.push((name.clone(), old.clone()));
+            }
+        }
+
+        diff
+    }
+}
+
+/// Checks if both values are the same binding, treating `NaN` as equal to
+/// itself unlike the equality of Lox.
+fn same_binding(old: &LoxValue, new: &LoxValue) -> bool {
+    match (old, new) {
+        (LoxValue::Number(old), LoxValue::Number(new)) => old.to_bits() == new.to_bits(),
+        (old, new) => old == new,
+    }
+}
+
+/// Changes of the bindings between two snapshots, sorted by their names.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvDiff {
+    pub added: Vec<(Rc<str>, LoxValue)>,
+    /// Bindings with their old and new values.
+    pub changed: Vec<(Rc<str>, LoxValue, LoxValue)>,
+    pub removed: Vec<(Rc<str>, LoxValue)>,
+}
+
+impl EnvDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl Display for EnvDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut lines = Vec::new();
+        for (name, value) in &self.added {
+            lines.push(format!("+ {name} = {value}"));
+        }
+        for (name, old, new) in &self.changed {
+            lines.push(format!("~ {name} = {old} -> {new}"));
+        }
+        for (name, value) in &self.removed {
+            lines.push(format!("- {name} = {value}"));
+        }
+
+        f.write_str(&lines.join("\n"))
+    }
+}
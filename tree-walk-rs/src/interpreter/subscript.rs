@@ -0,0 +1,145 @@
+//! Subscripts like `xs[0]` and `m["key"] = v`.
+//!
+//! Lists and strings are indexed by the position of their elements, while
+//! instances are indexed through their `get(index)` and `set(index, value)`
+//! methods, which lets `HashMap` and classes written in Lox support
+//! subscripts too.
+
+use lox_frontend::{Token, ast::Expr, codes};
+
+use crate::errors::{LoxError, LoxResult};
+
+use super::{
+    Interpreter, LoxValue, instance::LoxInstance, natives::check_index, not_instance_error,
+    task::Task,
+};
+
+const NOT_INDEXABLE: &str =
+    "Only lists, strings and instances with a 'get' method can be indexed, but '{}' was {}.";
+const NOT_ASSIGNABLE: &str =
+    "Only lists and instances with a 'set' method can be assigned by index, but '{}' was {}.";
+
+impl Interpreter {
+    /// Pushes the element at the index, calling the `get` method of instances.
+    pub(super) fn index_value(
+        &mut self,
+        object_value: LoxValue,
+        index: LoxValue,
+        object: &Expr,
+        bracket: &Token,
+    ) -> LoxResult<()> {
+        let index_error =
+            |message| LoxError::new(bracket.to_owned(), codes::INVALID_SUBSCRIPT, message);
+        match &object_value {
+            LoxValue::List(list) => {
+                let list = list.borrow();
+                let index = check_index("List", &index, list.len()).map_err(index_error)?;
+                let element = list[index].clone();
+                self.values.push(element);
+            }
+            LoxValue::String(text) => {
+                let index =
+                    check_index("String", &index, text.chars().count()).map_err(index_error)?;
+                let ch = text
+                    .chars()
+                    .nth(index)
+                    .expect("Index is checked against the length");
+                self.values.push(LoxValue::String(ch.to_string().into()));
+            }
+            LoxValue::Instance(instance) => {
+                let Some(get) = LoxInstance::method(instance, "get", self) else {
+                    return Err(not_instance_error(
+                        codes::INVALID_SUBSCRIPT,
+                        NOT_INDEXABLE,
+                        object,
+                        bracket,
+                        &object_value,
+                    ));
+                };
+                self.call_value(get, bracket, vec![index], false)?;
+            }
+            _ => {
+                return Err(not_instance_error(
+                    codes::INVALID_SUBSCRIPT,
+                    NOT_INDEXABLE,
+                    object,
+                    bracket,
+                    &object_value,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that elements can be assigned to the object before evaluating
+    /// the assigned value, like fields are checked for set expressions.
+    pub(super) fn check_assignable(
+        &mut self,
+        object_value: &LoxValue,
+        object: &Expr,
+        bracket: &Token,
+    ) -> LoxResult<()> {
+        let assignable = match object_value {
+            LoxValue::List(_) => true,
+            LoxValue::Instance(instance) => LoxInstance::method(instance, "set", self).is_some(),
+            _ => false,
+        };
+        if !assignable {
+            return Err(not_instance_error(
+                codes::INVALID_SUBSCRIPT,
+                NOT_ASSIGNABLE,
+                object,
+                bracket,
+                object_value,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Assigns the element at the index, calling the `set` method of
+    /// instances, whose result is dropped.
+    pub(super) fn set_index(
+        &mut self,
+        object_value: LoxValue,
+        index: LoxValue,
+        value: LoxValue,
+        object: &Expr,
+        bracket: &Token,
+    ) -> LoxResult<()> {
+        match &object_value {
+            LoxValue::List(list) => {
+                let mut list = list.borrow_mut();
+                let index = check_index("List", &index, list.len()).map_err(|message| {
+                    LoxError::new(bracket.to_owned(), codes::INVALID_SUBSCRIPT, message)
+                })?;
+                list[index] = value;
+            }
+            LoxValue::Instance(instance) => {
+                let Some(set) = LoxInstance::method(instance, "set", self) else {
+                    return Err(not_instance_error(
+                        codes::INVALID_SUBSCRIPT,
+                        NOT_ASSIGNABLE,
+                        object,
+                        bracket,
+                        &object_value,
+                    ));
+                };
+                self.tasks.push(Task::Discard);
+                self.call_value(set, bracket, vec![index, value], false)?;
+            }
+            _ => {
+                return Err(not_instance_error(
+                    codes::INVALID_SUBSCRIPT,
+                    NOT_ASSIGNABLE,
+                    object,
+                    bracket,
+                    &object_value,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
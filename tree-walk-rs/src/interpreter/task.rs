@@ -0,0 +1,267 @@
+//! Units of work for the iterative execution of statements and expressions.
+//!
+//! Instead of recursing into the children of statements and expressions, the
+//! interpreter pushes tasks for them on its own stack, followed by the tasks
+//! continuing the work with their results. Results of expressions are passed
+//! through the value stack of the interpreter. This keeps the depth of Lox
+//! calls and of nested code limited by the heap instead of the native stack.
+
+use std::{cell::Cell, rc::Rc};
+
+use lox_frontend::{
+    NodeId, Token,
+    ast::{Expr, Stmt},
+};
+
+use crate::errors::LoxError;
+
+use super::{
+    LoxFunction, LoxListRef, LoxString, LoxValue,
+    environment::EnvironmentRef,
+    heap::{Trace, Tracer},
+    observer::ExitedStmt,
+    values::trace_list,
+};
+
+#[derive(Debug)]
+pub enum Task {
+    /// Executes the statement at the given index, then the ones following it.
+    Statements {
+        stmts: Rc<[Stmt]>,
+        index: usize,
+    },
+    Execute(Rc<Stmt>),
+    Evaluate(Rc<Expr>),
+    /// Notifies the observer that the statement is done.
+    ExitStmt(ExitedStmt),
+
+    // Continuations, which consume the values of the evaluated expressions.
+    /// Drops the value of an expression statement.
+    Discard,
+    Print,
+    DefineVariable(Token),
+    /// Moves the declared global into the namespace shared by all scripts.
+    Export(Token),
+    /// Restores the environment after executing a block.
+    RestoreEnvironment(EnvironmentRef),
+    Branch {
+        keyword: Token,
+        then_branch: Rc<Stmt>,
+        else_branch: Option<Rc<Stmt>>,
+    },
+    /// Evaluates the condition of a while loop for the next iteration.
+    WhileCondition {
+        keyword: Token,
+        condition: Rc<Expr>,
+        body: Rc<Stmt>,
+        /// Count of the iterations the loop ran.
+        iterations: u64,
+    },
+    /// Executes the body of a while loop if its condition is truthy.
+    WhileBody {
+        keyword: Token,
+        condition: Rc<Expr>,
+        body: Rc<Stmt>,
+        iterations: u64,
+    },
+    /// Evaluates the condition of a for loop for the next iteration.
+    ForCondition(Rc<ForLoop>),
+    /// Executes the body of a for loop if its condition is truthy.
+    ForBody(Rc<ForLoop>),
+    /// Evaluates the increment of a for loop after executing its body.
+    ForIncrement(Rc<ForLoop>),
+    /// Starts iterating over the evaluated value of a for-in loop.
+    ForInIterable(Rc<ForInLoop>),
+    /// Starts iterating over the iterator returned from the `iterator()`
+    /// method of the iterated instance.
+    ForInIterator(Rc<ForInLoop>),
+    /// Gets the value for the next iteration of a for-in loop.
+    ForInNext {
+        for_in: Rc<ForInLoop>,
+        iteration: Iteration,
+    },
+    /// Calls `next()` on the iterator once `hasNext()` returned a truthy value.
+    ForInHasNext {
+        for_in: Rc<ForInLoop>,
+        iterator: LoxValue,
+    },
+    /// Executes the body of a for-in loop with the value returned from `next()`.
+    ForInBody {
+        for_in: Rc<ForInLoop>,
+        iterator: LoxValue,
+    },
+    /// Executes the `finally` block once the try block before it is done.
+    /// Leaving the try block early runs the `finally` block first too.
+    Finally {
+        finally: Rc<Stmt>,
+        /// State of the interpreter when the try block started, which is
+        /// restored when a runtime error leaves the block.
+        environment: EnvironmentRef,
+        values_len: usize,
+        call_depth: usize,
+    },
+    /// Continues leaving the code with the signal once the `finally` block
+    /// running before it is done. The token is the one of the statement
+    /// leaving the code.
+    ResumeUnwind(ControlFlow, Token),
+    /// Reports the runtime error again once the `finally` block running before
+    /// it is done.
+    Rethrow(LoxError),
+    /// Returns from the current function with the value on the stack.
+    Return(Token),
+    Unary(Token),
+    /// Evaluates the right operand once the left one is evaluated.
+    BinaryRight {
+        operator: Token,
+        right: Rc<Expr>,
+    },
+    Binary(Token),
+    /// Converts the value returned from the `equals` or `compareTo` method of
+    /// an instance into the result of the binary operator.
+    OperatorHookResult(Token),
+    Logical {
+        operator: Token,
+        right: Rc<Expr>,
+    },
+    /// Assigns the evaluated value to the variable of the assignment with the
+    /// given ID.
+    Assign {
+        id: NodeId,
+        name: Token,
+    },
+    /// Evaluates the values of a multiple assignment, starting at the given
+    /// index.
+    AssignValues {
+        values: Rc<[Expr]>,
+        index: usize,
+    },
+    /// Moves the evaluated values of a multiple assignment next to the objects
+    /// and indices of their targets once all of them are evaluated.
+    AssignTargets(Rc<[Expr]>),
+    /// Assigns the target at the given index of a multiple assignment.
+    AssignTarget {
+        targets: Rc<[Expr]>,
+        index: usize,
+    },
+    /// Evaluates the argument at the given index, then the ones following it.
+    /// The call is made once all arguments are evaluated.
+    /// Calls in tail position replace the frame of the current function.
+    Arguments {
+        paren: Token,
+        arguments: Rc<[Expr]>,
+        index: usize,
+        tail: bool,
+    },
+    /// Frame of a called function, which is done once this task is reached or
+    /// when the function returns before it.
+    FinishCall {
+        function: Rc<LoxFunction>,
+        caller_env: EnvironmentRef,
+        /// Line of the call, which is shown in stack traces.
+        call_line: usize,
+    },
+    /// Gets the property of the evaluated object, keeping the object expression
+    /// for error messages.
+    Get {
+        object: Rc<Expr>,
+        name: Token,
+    },
+    /// Evaluates the value of a set expression once its object is evaluated.
+    SetValue {
+        object: Rc<Expr>,
+        name: Token,
+        value: Rc<Expr>,
+    },
+    Set(Token),
+    /// Gets the element at the evaluated index of the evaluated object.
+    Index {
+        object: Rc<Expr>,
+        bracket: Token,
+    },
+    /// Evaluates the value of a subscript assignment once its object and
+    /// index are evaluated.
+    SetIndexValue {
+        object: Rc<Expr>,
+        bracket: Token,
+        value: Rc<Expr>,
+    },
+    SetIndex {
+        object: Rc<Expr>,
+        bracket: Token,
+    },
+}
+
+/// Pending tasks keep the environments and functions of the running calls
+/// alive.
+impl Trace for Task {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            Task::RestoreEnvironment(env)
+            | Task::Finally {
+                environment: env, ..
+            } => tracer.mark_env(env),
+            Task::ResumeUnwind(ControlFlow::Return(value), _) => value.trace(tracer),
+            Task::FinishCall {
+                function,
+                caller_env,
+                ..
+            } => {
+                tracer.visit_rc(function, |tracer| function.trace(tracer));
+                tracer.mark_env(caller_env);
+            }
+            Task::ForInNext { iteration, .. } => match iteration {
+                Iteration::List { list, .. } => trace_list(list, tracer),
+                Iteration::Chars { .. } => {}
+                Iteration::Iterator(iterator) => iterator.trace(tracer),
+            },
+            Task::ForInHasNext { iterator, .. } | Task::ForInBody { iterator, .. } => {
+                iterator.trace(tracer);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Clauses of a running for loop, shared between the tasks executing it.
+#[derive(Debug)]
+pub struct ForLoop {
+    pub keyword: Token,
+    pub condition: Option<Rc<Expr>>,
+    pub increment: Option<Rc<Expr>>,
+    pub body: Rc<Stmt>,
+    /// Count of the iterations the loop ran.
+    pub iterations: Cell<u64>,
+}
+
+/// Parts of a running for-in loop, shared between the tasks executing it.
+#[derive(Debug)]
+pub struct ForInLoop {
+    pub name: Token,
+    pub keyword: Token,
+    pub body: Rc<Stmt>,
+    /// Count of the iterations the loop ran.
+    pub iterations: Cell<u64>,
+}
+
+/// Position of a running for-in loop in the value it iterates over.
+#[derive(Debug)]
+pub enum Iteration {
+    /// Elements of the list starting at the index.
+    List { list: LoxListRef, index: usize },
+    /// Characters of the string starting at the byte offset.
+    Chars { text: LoxString, offset: usize },
+    /// Iterator providing the values with its `hasNext()` and `next()` methods.
+    Iterator(LoxValue),
+}
+
+/// Signals of the statements leaving the code they are in, which drop the
+/// pending tasks up to their targets instead of being reported as errors.
+#[derive(Debug)]
+pub enum ControlFlow {
+    /// Returns from the current function with the value.
+    Return(LoxValue),
+    /// Leaves the innermost loop.
+    Break,
+    /// Continues with the next iteration of the innermost loop.
+    Continue,
+}
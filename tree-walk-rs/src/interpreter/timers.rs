@@ -0,0 +1,254 @@
+//! Natives scheduling callbacks on a single-threaded event loop.
+//!
+//! `setTimeout` and `setInterval` only queue their callbacks, which run once
+//! the script calls `runEventLoop()`. The loop waits for the earliest timer,
+//! calls it and continues until no timers are left. In deterministic mode the
+//! loop jumps to the time of the next timer instead of waiting for it.
+
+use std::time::{Duration, Instant};
+
+use lox_frontend::codes;
+
+use crate::errors::LoxError;
+
+use super::{
+    Interpreter, LoxValue,
+    heap::{Trace, Tracer},
+};
+
+/// Longest sleep of the loop before it checks again if the run was cancelled
+/// or exceeded its time limit.
+const SLEEP_SLICE: Duration = Duration::from_millis(10);
+
+/// Timers waiting for the event loop.
+#[derive(Debug)]
+pub struct Timers {
+    pending: Vec<Timer>,
+    next_id: u64,
+    /// Count of the scheduled timers, ordering the timers due at the same time
+    /// by their scheduling.
+    scheduled: u64,
+    /// Start of the time of the timers in milliseconds.
+    started: Instant,
+    /// Time of the last timer that ran in deterministic mode.
+    fake_now: f64,
+    running: bool,
+}
+
+#[derive(Debug)]
+struct Timer {
+    id: u64,
+    /// Time in milliseconds when the timer is due.
+    due: f64,
+    order: u64,
+    /// Period of intervals, which are scheduled again after each run.
+    interval: Option<f64>,
+    callback: LoxValue,
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+            next_id: 1,
+            scheduled: 0,
+            started: Instant::now(),
+            fake_now: 0.0,
+            running: false,
+        }
+    }
+}
+
+impl Timers {
+    fn now(&self, deterministic: bool) -> f64 {
+        if deterministic {
+            self.fake_now
+        } else {
+            self.started.elapsed().as_secs_f64() * 1000.0
+        }
+    }
+
+    fn schedule(&mut self, id: u64, due: f64, interval: Option<f64>, callback: LoxValue) {
+        self.scheduled += 1;
+        self.pending.push(Timer {
+            id,
+            due,
+            order: self.scheduled,
+            interval,
+            callback,
+        });
+    }
+
+    /// Removes the timer which is due first.
+    fn pop_next(&mut self) -> Option<Timer> {
+        let index = (0..self.pending.len()).min_by(|&a, &b| {
+            let (a, b) = (&self.pending[a], &self.pending[b]);
+            a.due.total_cmp(&b.due).then(a.order.cmp(&b.order))
+        })?;
+        Some(self.pending.swap_remove(index))
+    }
+}
+
+/// Callbacks of the pending timers are kept alive until they run.
+impl Trace for Timers {
+    fn trace(&self, tracer: &mut Tracer) {
+        for timer in &self.pending {
+            timer.callback.trace(tracer);
+        }
+    }
+}
+
+/// Schedules the function to be called once after the delay in milliseconds,
+/// returning the ID of the timer.
+pub fn set_timeout(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    schedule(interpreter, args, false)
+}
+
+/// Schedules the function to be called repeatedly with the period in
+/// milliseconds, returning the ID of the timer.
+pub fn set_interval(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    schedule(interpreter, args, true)
+}
+
+fn schedule(
+    interpreter: &mut Interpreter,
+    args: &[LoxValue],
+    repeats: bool,
+) -> Result<LoxValue, String> {
+    let callback = &args[0];
+    match callback {
+        LoxValue::Callable(callable) if callable.arity() == 0 => {}
+        LoxValue::Callable(callable) => {
+            return Err(format!(
+                "Timers call their callbacks without arguments, but {} takes {}.",
+                callback.describe(),
+                callable.arity()
+            ));
+        }
+        _ => {
+            return Err(format!(
+                "Timers can only call functions and classes, but got {}.",
+                callback.describe()
+            ));
+        }
+    }
+    let Some(delay) = args[1].as_number() else {
+        return Err(format!(
+            "Timer delay must be a number of milliseconds, but got {}.",
+            args[1].describe()
+        ));
+    };
+    if !delay.is_finite() || delay < 0.0 {
+        return Err(format!(
+            "Timer delay must be a finite number of milliseconds that isn't negative, but got {}.",
+            args[1]
+        ));
+    }
+    // Intervals wait at least one millisecond so the loop can't get stuck on
+    // them, like in JavaScript.
+    let delay = if repeats {
+        delay.max(1.0)
+    } else {
+        delay.max(0.0)
+    };
+
+    let deterministic = interpreter.options.deterministic;
+    let timers = &mut interpreter.timers;
+    let id = timers.next_id;
+    timers.next_id += 1;
+    let due = timers.now(deterministic) + delay;
+    timers.schedule(id, due, repeats.then_some(delay), callback.clone());
+
+    Ok(LoxValue::Number(id as f64))
+}
+
+/// Cancels the timer with the given ID, returning if it was still pending.
+pub fn clear_timer(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let Some(id) = args[0].as_number() else {
+        return Err(format!(
+            "Timer ID must be a number, but got {}.",
+            args[0].describe()
+        ));
+    };
+
+    let pending = &mut interpreter.timers.pending;
+    let count = pending.len();
+    pending.retain(|timer| timer.id as f64 != id);
+    Ok(LoxValue::Boolean(pending.len() < count))
+}
+
+/// Runs the timers in the order they are due until none are left, including
+/// the ones scheduled by the callbacks.
+pub fn run_event_loop(interpreter: &mut Interpreter, _: &[LoxValue]) -> Result<LoxValue, String> {
+    if interpreter.timers.running {
+        return Err(
+            interpreter.fail_native(codes::INVALID_SUSPENSION, "Event loop is already running.")
+        );
+    }
+
+    interpreter.timers.running = true;
+    let result = run_timers(interpreter);
+    interpreter.timers.running = false;
+
+    result.map(|()| LoxValue::Nil)
+}
+
+fn run_timers(interpreter: &mut Interpreter) -> Result<(), String> {
+    let deterministic = interpreter.options.deterministic;
+    while let Some(timer) = interpreter.timers.pop_next() {
+        let timers = &mut interpreter.timers;
+        if deterministic {
+            timers.fake_now = timers.fake_now.max(timer.due);
+        } else {
+            wait_until(interpreter, timer.due)?;
+        }
+        let timers = &mut interpreter.timers;
+
+        // Intervals are scheduled again before their calls, so they can
+        // clear themselves.
+        if let Some(interval) = timer.interval {
+            timers.schedule(
+                timer.id,
+                timer.due + interval,
+                timer.interval,
+                timer.callback.clone(),
+            );
+        }
+        interpreter.call(&timer.callback, Vec::new())?;
+    }
+
+    Ok(())
+}
+
+/// Sleeps until the time of the timer in slices, so cancelling the run or
+/// reaching its time limit stops the loop while waiting.
+fn wait_until(interpreter: &mut Interpreter, due: f64) -> Result<(), String> {
+    loop {
+        if interpreter.cancel.is_cancelled() {
+            return Err(abort(interpreter, LoxError::Cancelled));
+        }
+        if let Some(limit) = interpreter.options.sandbox.max_duration
+            && interpreter.run_started.elapsed() > limit
+        {
+            return Err(abort(interpreter, LoxError::TimeLimitExceeded { limit }));
+        }
+
+        let wait = (due - interpreter.timers.now(false)) / 1000.0;
+        let Ok(wait) = Duration::try_from_secs_f64(wait) else {
+            // The time is already past the timer.
+            return Ok(());
+        };
+        if wait.is_zero() {
+            return Ok(());
+        }
+        std::thread::sleep(wait.min(SLEEP_SLICE));
+    }
+}
+
+/// Fails the native with the error, which is rethrown as it is instead of
+/// reporting its message as a runtime error.
+fn abort(interpreter: &mut Interpreter, err: LoxError) -> String {
+    let message = err.to_string();
+    interpreter.native_error = Some(err);
+    message
+}
@@ -1,17 +1,32 @@
-use std::fmt::Display;
+use std::{any::Any, cell::RefCell, fmt::Display, rc::Rc};
 
-use crate::ast::LiteralValue;
+use lox_frontend::{
+    LoxString,
+    ast::LiteralValue,
+    locale::{self, Lang},
+};
 
-use super::{callables::LoxCallable, instance::LoxInstanceRef};
+use super::{
+    callables::LoxCallable,
+    foreign::Foreign,
+    heap::{Trace, Tracer},
+    instance::LoxInstanceRef,
+};
 
-#[derive(Debug, Clone, PartialEq)]
+/// Elements of a list, which is shared by all values referring to it.
+pub type LoxListRef = Rc<RefCell<Vec<LoxValue>>>;
+
+#[derive(Debug, Clone)]
 pub enum LoxValue {
     Nil,
     Boolean(bool),
     Number(f64),
-    String(String),
+    String(LoxString),
     Callable(LoxCallable),
     Instance(LoxInstanceRef),
+    List(LoxListRef),
+    /// Data of the host passed through the scripts.
+    Foreign(Foreign),
 }
 
 impl From<&LiteralValue> for LoxValue {
@@ -19,26 +34,219 @@ impl From<&LiteralValue> for LoxValue {
         match value {
             LiteralValue::Nil => LoxValue::Nil,
             LiteralValue::Boolean(val) => LoxValue::Boolean(*val),
-            LiteralValue::Text(val) => LoxValue::String(val.into()),
+            LiteralValue::Text(val) => LoxValue::String(val.clone()),
             LiteralValue::Number(val) => LoxValue::Number(*val),
         }
     }
 }
 
+// Values are copied all the time while evaluating the code, so they are kept
+// two words wide with all the data behind thin pointers.
+#[cfg(target_pointer_width = "64")]
+const _: () = assert!(std::mem::size_of::<LoxValue>() == 16);
+
+/// Values are compared by their content, except for callables, instances and
+/// lists which are only equal to themselves.
+impl PartialEq for LoxValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LoxValue::Nil, LoxValue::Nil) => true,
+            (LoxValue::Boolean(a), LoxValue::Boolean(b)) => a == b,
+            (LoxValue::Number(a), LoxValue::Number(b)) => a == b,
+            (LoxValue::String(a), LoxValue::String(b)) => a == b,
+            (LoxValue::Callable(a), LoxValue::Callable(b)) => a == b,
+            (LoxValue::Instance(a), LoxValue::Instance(b)) => Rc::ptr_eq(a, b),
+            (LoxValue::List(a), LoxValue::List(b)) => Rc::ptr_eq(a, b),
+            (LoxValue::Foreign(a), LoxValue::Foreign(b)) => a.ptr_eq(b),
+            _ => false,
+        }
+    }
+}
+
 impl Display for LoxValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             LoxValue::Nil => f.write_str("Nil"),
             LoxValue::Boolean(val) => write!(f, "{val}"),
-            LoxValue::Number(val) => write!(f, "{val}"),
+            LoxValue::Number(val) => write_number(f, *val),
             LoxValue::String(val) => write!(f, "{val}"),
             LoxValue::Callable(lox_callable) => write!(f, "{lox_callable}"),
             LoxValue::Instance(instance) => write!(f, "{}", instance.borrow()),
+            LoxValue::List(list) => write_list(f, list, &mut Vec::new()),
+            LoxValue::Foreign(foreign) => write!(f, "{foreign}"),
         }
     }
 }
 
+/// Writes the elements of the list quoting strings, like `[1, "a"]`.
+/// Lists containing themselves are shortened to `[...]` where they repeat.
+fn write_list(
+    f: &mut std::fmt::Formatter<'_>,
+    list: &LoxListRef,
+    path: &mut Vec<*const RefCell<Vec<LoxValue>>>,
+) -> std::fmt::Result {
+    let ptr = Rc::as_ptr(list);
+    if path.contains(&ptr) {
+        return f.write_str("[...]");
+    }
+
+    path.push(ptr);
+    f.write_str("[")?;
+    for (index, value) in list.borrow().iter().enumerate() {
+        if index > 0 {
+            f.write_str(", ")?;
+        }
+        match value {
+            LoxValue::String(text) => write!(f, "{text:?}")?,
+            LoxValue::List(inner) => write_list(f, inner, path)?,
+            value => write!(f, "{value}")?,
+        }
+    }
+    path.pop();
+    f.write_str("]")
+}
+
+/// Writes the number the same way jlox does, which uses the output of Java's
+/// `Double.toString()` after stripping the trailing `.0` from it.
+fn write_number(f: &mut std::fmt::Formatter<'_>, num: f64) -> std::fmt::Result {
+    if num.is_nan() {
+        return f.write_str("NaN");
+    }
+    if num.is_infinite() {
+        let text = if num > 0.0 { "Infinity" } else { "-Infinity" };
+        return f.write_str(text);
+    }
+
+    let abs = num.abs();
+    if abs == 0.0 || (1e-3..1e7).contains(&abs) {
+        // Rust already writes integral values without fraction and keeps the
+        // sign of negative zero like jlox.
+        return write!(f, "{num}");
+    }
+
+    // Java switches to scientific notation out of this range, always keeping
+    // a fraction in the mantissa (`1.0E10`) which isn't stripped by jlox.
+    let text = format!("{num:e}");
+    let (mantissa, exponent) = text
+        .split_once('e')
+        .expect("Scientific notation always contains an exponent");
+    if mantissa.contains('.') {
+        write!(f, "{mantissa}E{exponent}")
+    } else {
+        write!(f, "{mantissa}.0E{exponent}")
+    }
+}
+
+/// Integer division rounding the quotient down, like Python's `//`.
+pub fn floor_div(left: f64, right: f64) -> f64 {
+    (left / right).floor()
+}
+
+/// Remainder of the floored division, which takes the sign of the divisor:
+/// `left == floor_div(left, right) * right + floor_mod(left, right)`.
+pub fn floor_mod(left: f64, right: f64) -> f64 {
+    let rem = left % right;
+    if rem != 0.0 && (rem < 0.0) != (right < 0.0) {
+        rem + right
+    } else {
+        rem
+    }
+}
+
 impl LoxValue {
+    /// Name of the type of the value as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LoxValue::Nil => "nil",
+            LoxValue::Boolean(..) => "boolean",
+            LoxValue::Number(..) => "number",
+            LoxValue::String(..) => "string",
+            LoxValue::Callable(LoxCallable::Class(..)) => "class",
+            LoxValue::Callable(..) => "function",
+            LoxValue::Instance(..) => "instance",
+            LoxValue::List(..) => "list",
+            LoxValue::Foreign(..) => "foreign",
+        }
+    }
+
+    /// Describes the value with its type for error messages, like `the string "abc"`.
+    pub fn describe(&self) -> String {
+        match self {
+            LoxValue::Nil => String::from("nil"),
+            LoxValue::String(text) => format!("the string \"{text}\""),
+            // Instances are already displayed with their type.
+            LoxValue::Instance(..) => format!("the {self}"),
+            LoxValue::Foreign(foreign) => format!("the foreign {}", foreign.type_name()),
+            _ => format!("the {} {self}", self.type_name()),
+        }
+    }
+
+    /// Describes the value like [`Self::describe`] in the language of the
+    /// diagnostics, for the messages translated by the locale catalog.
+    pub fn describe_translated(&self) -> String {
+        if locale::lang() == Lang::English {
+            return self.describe();
+        }
+
+        match self {
+            LoxValue::Nil => String::from("nil"),
+            LoxValue::Boolean(..) => format!("der Wahrheitswert {self}"),
+            LoxValue::Number(..) => format!("die Zahl {self}"),
+            LoxValue::String(text) => format!("die Zeichenkette \"{text}\""),
+            LoxValue::Callable(LoxCallable::Class(..)) => format!("die Klasse {self}"),
+            LoxValue::Callable(..) => format!("die Funktion {self}"),
+            LoxValue::Instance(instance) => {
+                format!("eine Instanz von {}", instance.borrow().class().borrow())
+            }
+            LoxValue::List(..) => format!("die Liste {self}"),
+            LoxValue::Foreign(foreign) => {
+                format!("der fremde Wert vom Typ {}", foreign.type_name())
+            }
+        }
+    }
+
+    /// Creates a new list with the given elements.
+    pub fn list(elements: Vec<LoxValue>) -> Self {
+        LoxValue::List(Rc::new(RefCell::new(elements)))
+    }
+
+    /// Wraps the value of the host, which is shown to Lox code with the
+    /// given name of its type.
+    pub fn foreign(type_name: &'static str, value: impl Any) -> Self {
+        LoxValue::Foreign(Foreign::new(type_name, value))
+    }
+
+    /// Returns the value of the host if it's a foreign value of the given type.
+    pub fn as_foreign<T: Any>(&self) -> Option<&T> {
+        match self {
+            LoxValue::Foreign(foreign) => foreign.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            LoxValue::Number(num) => Some(*num),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of booleans only, unlike [`LoxValue::is_truthy()`]
+    /// which converts all values.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            LoxValue::Boolean(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            LoxValue::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
     pub fn is_truthy(&self) -> bool {
         // We follow Ruby approach in Lox
         match self {
@@ -47,7 +255,37 @@ impl LoxValue {
             LoxValue::Number(..)
             | LoxValue::String(..)
             | LoxValue::Callable(..)
-            | LoxValue::Instance(..) => true,
+            | LoxValue::Instance(..)
+            | LoxValue::List(..)
+            | LoxValue::Foreign(..) => true,
+        }
+    }
+}
+
+impl Trace for LoxValue {
+    fn trace(&self, tracer: &mut Tracer) {
+        match self {
+            LoxValue::Nil
+            | LoxValue::Boolean(..)
+            | LoxValue::Number(..)
+            | LoxValue::Foreign(..) => {}
+            LoxValue::String(text) => {
+                if tracer.visit(text.as_ptr()) {
+                    tracer.stats.strings += 1;
+                }
+            }
+            LoxValue::Callable(lox_callable) => lox_callable.trace(tracer),
+            LoxValue::Instance(instance) => {
+                tracer.visit_rc(instance, |tracer| instance.borrow().trace(tracer));
+            }
+            LoxValue::List(list) => trace_list(list, tracer),
         }
     }
 }
+
+pub(crate) fn trace_list(list: &LoxListRef, tracer: &mut Tracer) {
+    tracer.visit_rc(list, |tracer| {
+        tracer.stats.lists += 1;
+        list.borrow().iter().for_each(|value| value.trace(tracer));
+    });
+}
@@ -1,91 +1,219 @@
 use anyhow::Context;
-use errors::RunError;
-use interpreter::Interpreter;
-use parser::Parser;
+use lox_frontend::{
+    Parser, ScanError, Scanner, Token,
+    ast::Stmt,
+    engine::Engine,
+    locale::{self, Message},
+};
 use resolver::Resolver;
-use scanner::Scanner;
-use std::{io::Write, path::Path};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
 
-mod ast;
+mod engine;
 mod errors;
+mod explorer;
 mod interpreter;
-mod parser;
+pub mod optimizer;
+mod repl;
 mod resolver;
-mod scanner;
+mod transpiler;
+#[cfg(feature = "worker")]
+mod worker;
+
+pub use engine::TreeWalkEngine;
+pub use errors::{LoxError, LoxWarning, RunError};
+pub use explorer::explore_file;
+pub use interpreter::{
+    CancelHandle, Capability, DEFAULT_PRETTY_DEPTH, EnvDiff, EnvSnapshot, ExecutionObserver,
+    Foreign, HeapStats, Interpreter, InterpreterBuilder, InterpreterOptions, LoxClass, LoxInstance,
+    LoxInstanceRef, LoxListRef, LoxString, LoxValue, NativeClass, NativeFn, NumberFormat,
+    PrettyOptions, RunState, SandboxPolicy, Trace, Tracer,
+};
+pub use repl::{ReplOutput, ReplSession, run_prompt, run_repl_script};
+pub use transpiler::Target;
+#[cfg(feature = "worker")]
+pub use worker::{JobResult, Worker};
+
+/// Runs the file on the given interpreter, which keeps the definitions of the
+/// file for later runs.
+pub fn run_file(interpreter: &mut Interpreter, path: &Path) -> anyhow::Result<()> {
+    let file_content = std::fs::read_to_string(path)
+        .with_context(|| translated("Error while reading input file. Path: {}", path.display()))?;
 
-pub use interpreter::LoxValue;
-pub use scanner::{Token, TokenType};
+    run(interpreter, file_content).map_err(RunError::into_anyhow)?;
 
-pub fn run_file(path: &Path) -> anyhow::Result<()> {
-    let file_content = std::fs::read_to_string(path)
-        .with_context(|| format!("Error while reading input file. Path: {}", path.display()))?;
+    Ok(())
+}
 
-    run(file_content).map_err(|err| {
-        // NOTE:
-        // Anyhow errors must be Send, while the current implementation
-        // misusing errors adding `Rc<RefCell<>>` to them.
-        anyhow::anyhow!("{err:?}")
-    })?;
+/// Runs the prelude file in the global environment, so its definitions are
+/// available to the scripts and the REPL running after it.
+pub fn run_prelude(interpreter: &mut Interpreter, path: &Path) -> anyhow::Result<()> {
+    run_file(interpreter, path)
+        .with_context(|| translated("Error while running prelude. Path: {}", path.display()))
+}
+
+/// Runs the files in order on the given interpreter, where each file has its
+/// own namespace for its globals. Files share only the declarations they
+/// export, which are visible in the files running after them.
+pub fn run_files(interpreter: &mut Interpreter, paths: &[PathBuf]) -> anyhow::Result<()> {
+    for path in paths {
+        let file_content = std::fs::read_to_string(path).with_context(|| {
+            translated("Error while reading input file. Path: {}", path.display())
+        })?;
+
+        interpreter
+            .run_isolated(&file_content)
+            .map_err(RunError::into_anyhow)
+            .with_context(|| translated("Error while running file. Path: {}", path.display()))?;
+    }
 
     Ok(())
 }
 
-pub fn run_prompt() -> anyhow::Result<()> {
-    println!("Welcome to rlox interpreter!");
-    println!("To exit press <C-d> or <C-c>");
-    let mut content = String::new();
-    loop {
-        content.clear();
-        print!(">>> ");
-
-        std::io::stdout()
-            .flush()
-            .context("Error while flushing stdout")?;
-
-        let read = std::io::stdin()
-            .read_line(&mut content)
-            .context("Error while reading from stdin")?;
-
-        if read == 0 {
-            println!("Bye Bye!");
-            return Ok(());
-        }
+/// Creates the engine with the given name as selected in the command line.
+/// The options only apply to the tree-walk interpreter.
+pub fn engine(name: &str, options: InterpreterOptions) -> Option<Box<dyn Engine>> {
+    match name {
+        "tree" => Some(Box::new(TreeWalkEngine::new(Interpreter::with_options(
+            options,
+        )))),
+        "vm" => Some(Box::new(bytecode_rs::VmEngine::default())),
+        _ => None,
+    }
+}
 
-        match run(content.clone()) {
-            Ok(()) => {}
-            Err(RunError::Unrecoverable(err)) => return Err(err),
-            // Don't stop on other errors
-            Err(err @ RunError::Scan(_)) => eprintln!("Scan Error:\n{err}"),
-            Err(RunError::LoxError(err)) => eprintln!("Lox Error:\n{err}"),
+/// Runs the file on the given engine, printing its output once it's done.
+pub fn run_file_on(engine: &mut dyn Engine, path: &Path) -> anyhow::Result<()> {
+    let file_content = std::fs::read_to_string(path)
+        .with_context(|| translated("Error while reading input file. Path: {}", path.display()))?;
+
+    match engine.run(&file_content) {
+        Ok(output) => {
+            print!("{}", output.stdout);
+            Ok(())
+        }
+        Err(diagnostics) => {
+            print!("{}", diagnostics.output.stdout);
+            Err(diagnostics.into())
         }
     }
 }
 
-fn run(content: String) -> Result<(), RunError> {
-    let scanner = Scanner::new(content);
-    let scan_res = scanner.scan_tokens();
+/// Scans, parses, resolves and then executes the given source code using
+/// the provided interpreter, returning the value of the last statement when
+/// it's an expression statement.
+pub fn run(interpreter: &mut Interpreter, content: String) -> Result<Option<LoxValue>, RunError> {
+    let stmts = prepare(interpreter, content)?;
+    let value = interpreter.interpret(&stmts).map_err(RunError::Runtime)?;
 
-    let errors_count = scan_res.errors.len();
+    Ok(value)
+}
 
-    if errors_count > 0 {
-        println!("Errors: ");
-        for err in scan_res.errors {
-            eprintln!("  {err}");
-        }
-        println!("-------------------------------------------");
-        return Err(RunError::Scan(errors_count));
+/// Scans, parses and resolves the given source code, returning the statements
+/// ready for executing them with the provided interpreter.
+pub(crate) fn prepare(
+    interpreter: &mut Interpreter,
+    content: String,
+) -> Result<Vec<Stmt>, RunError> {
+    // Suspended scripts keep the state of the interpreter in their functions.
+    if interpreter.is_suspended() {
+        return Err(
+            anyhow::anyhow!("Suspended script must be resumed before running other code").into(),
+        );
     }
 
-    let mut parser = Parser::new(scan_res.tokens);
+    let tokens = interpreter.scan(content).map_err(|errors| {
+        let count = errors.len();
+        errors
+            .into_iter()
+            .for_each(|err| interpreter.report_error(err));
+        RunError::Scan(count)
+    })?;
 
-    let stmts = parser.parse()?;
+    let parse_res = interpreter.parse(tokens);
+    // Statements without errors are still executed.
+    for err in parse_res.errors {
+        interpreter.report_error(err);
+    }
 
-    let mut interpreter = Interpreter::new();
+    let mut stmts = parse_res.stmts;
+
+    if interpreter.options().fold_constants {
+        let folder = optimizer::ConstantFolder {
+            max_string_length: interpreter.options().max_string_length,
+        };
+        stmts = folder.fold_constants(stmts);
+    }
 
-    let mut resolver = Resolver::new(&mut interpreter);
-    resolver.resolve_stmts(&stmts)?;
+    let mut resolver = Resolver::new(interpreter).with_source_map(&parse_res.source_map);
+    resolver.resolve(&stmts)?;
 
-    interpreter.interpret(&stmts);
+    Ok(stmts)
+}
+
+/// Scans the source code keeping the comments, so the parser can attach the
+/// doc comments to the declarations. IDs of the tokens start from the given
+/// one.
+fn scan(content: String, first_id: u64) -> Result<Vec<Token>, Vec<ScanError>> {
+    let scanner = Scanner::new(content).with_trivia().with_first_id(first_id);
+    let scan_res = scanner.scan_tokens();
+
+    if scan_res.errors.is_empty() {
+        Ok(scan_res.tokens)
+    } else {
+        Err(scan_res.errors)
+    }
+}
+
+fn print_scan_errors(errors: Vec<ScanError>) -> RunError {
+    let errors_count = errors.len();
+    println!("{}: ", locale::translate_word("Errors"));
+    for err in errors {
+        eprintln!("  {err}");
+    }
+    println!("-------------------------------------------");
+    RunError::Scan(errors_count)
+}
+
+/// Fills the message of the command line in the language of the diagnostics.
+fn translated(template: &'static str, arg: impl Display) -> String {
+    Message::new(locale::translate_word(template), [arg]).to_string()
+}
+
+/// Prints the code generated from the file in the given target language.
+pub fn transpile_file(path: &Path, target: Target) -> anyhow::Result<()> {
+    let file_content = std::fs::read_to_string(path)
+        .with_context(|| translated("Error while reading input file. Path: {}", path.display()))?;
+
+    let output = transpile(file_content, target).map_err(|err| anyhow::anyhow!("{err}"))?;
+    print!("{output}");
 
     Ok(())
 }
+
+/// Scans, parses and resolves the given source code then generates the code
+/// of the given target language from it.
+/// Unlike running the code, any errors in the source code fail the whole
+/// transpiling.
+pub fn transpile(content: String, target: Target) -> Result<String, RunError> {
+    let tokens = scan(content, 0).map_err(print_scan_errors)?;
+
+    let parse_res = Parser::new(tokens).parse();
+    let errors_count = parse_res.errors.len();
+    if errors_count > 0 {
+        for err in parse_res.errors {
+            eprintln!("{err}");
+        }
+        return Err(RunError::Parse(errors_count));
+    }
+
+    // Resolving reports the static errors the same way as running the code.
+    let mut interpreter = Interpreter::new();
+    Resolver::new(&mut interpreter)
+        .with_source_map(&parse_res.source_map)
+        .resolve(&parse_res.stmts)?;
+
+    Ok(transpiler::transpile(&parse_res.stmts, target))
+}
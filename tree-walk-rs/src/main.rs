@@ -1,17 +1,165 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::ExitCode};
 
-use anyhow::bail;
-use tree_walk_rs::{run_file, run_prompt};
+use anyhow::{anyhow, bail};
+use lox_frontend::{
+    codes,
+    locale::{self, Lang},
+};
+use tree_walk_rs::{
+    Interpreter, InterpreterOptions, Target, engine, explore_file, run_file, run_file_on,
+    run_files, run_prelude, run_prompt, run_repl_script, transpile_file,
+};
 
-fn main() -> anyhow::Result<()> {
-    let args: Vec<_> = std::env::args().collect();
-    match args.len() {
-        0 => panic!("Environment arguments must starts with the path of the binary file"),
+const USAGE: &str =
+    "Usage: rlox [--deterministic] [--strict] [--strict-bool] [--debug-heap] [--lang=en|de] [--prelude <file>] [script...]
+       rlox [--deterministic] --engine=tree|vm <script>
+       rlox [--prelude <file>] repl --script <commands>
+       rlox explore <script>
+       rlox explain [code]
+       rlox transpile --target=js <script>";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            report(&err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints the error with its causes like the errors returned from `main`,
+/// in the language of the diagnostics.
+fn report(err: &anyhow::Error) {
+    eprintln!("{}: {err}", locale::translate_word("Error"));
+    let causes: Vec<_> = err.chain().skip(1).collect();
+    if causes.is_empty() {
+        return;
+    }
+    eprintln!("\n{}:", locale::translate_word("Caused by"));
+    match causes.as_slice() {
+        [cause] => eprintln!("    {cause}"),
+        causes => {
+            for (index, cause) in causes.iter().enumerate() {
+                eprintln!("    {index}: {cause}");
+            }
+        }
+    }
+}
+
+fn run() -> anyhow::Result<()> {
+    // Flags for the interpreter can be combined with running the scripts
+    // and the REPL.
+    // Scripts stop at their first runtime error, which the REPL recovers from.
+    let mut options = InterpreterOptions {
+        stop_on_error: true,
+        ..Default::default()
+    };
+    // Prelude runs before the scripts and the REPL only.
+    let mut prelude = std::env::var_os("RLOX_PRELUDE")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from);
+    // Language of the diagnostics, which the flag overrides.
+    if let Some(lang) = std::env::var_os("LOX_LANG").filter(|lang| !lang.is_empty()) {
+        let lang = lang
+            .to_string_lossy()
+            .parse::<Lang>()
+            .map_err(|err| anyhow!(err))?;
+        locale::set_lang(lang);
+    }
+    // Counts of the live objects are printed once the scripts are done.
+    let mut debug_heap = false;
+    let mut args = Vec::new();
+    let mut env_args = std::env::args();
+    while let Some(arg) = env_args.next() {
+        match arg.as_str() {
+            "--deterministic" => options.deterministic = true,
+            "--strict" => options = options.strict(true),
+            "--strict-bool" => options.strict_bool = true,
+            "--debug-heap" => debug_heap = true,
+            lang if lang.starts_with("--lang=") => {
+                let lang = lang.trim_start_matches("--lang=");
+                locale::set_lang(lang.parse::<Lang>().map_err(|err| anyhow!(err))?);
+            }
+            "--prelude" => {
+                let Some(path) = env_args.next() else {
+                    bail!("Missing path of the prelude\n{USAGE}");
+                };
+                prelude = Some(PathBuf::from(path));
+            }
+            _ => args.push(arg),
+        }
+    }
+
+    match args.as_slice() {
+        [] => panic!("Environment arguments must starts with the path of the binary file"),
         // No args => Run interactive REPL session.
-        1 => run_prompt(),
+        [_] => run_prompt(options, prelude.as_deref()),
+        // Feed the lines of the file to the REPL instead of reading stdin.
+        [_, cmd, flag, path] if cmd == "repl" && flag == "--script" => {
+            run_repl_script(options, prelude.as_deref(), &PathBuf::from(path))
+        }
+        // Print the code generated in the target language instead of running it.
+        [_, cmd, target, path] if cmd == "transpile" => {
+            let Some(target) = target.strip_prefix("--target=") else {
+                bail!("Usage: rlox transpile --target=js <script>");
+            };
+            let target = target.parse::<Target>().map_err(|err| anyhow!(err))?;
+            transpile_file(&PathBuf::from(path), target)
+        }
+        // Describe the error code in detail, or list all codes without one.
+        [_, cmd] if cmd == "explain" => {
+            for code in codes::all() {
+                println!("{}: {}", code.code, code.title);
+            }
+            Ok(())
+        }
+        [_, cmd, code] if cmd == "explain" => {
+            let Some(code) = codes::find(code) else {
+                bail!("Unknown error code '{code}'. Run `rlox explain` to list all codes.");
+            };
+            println!("{}: {}\n\n{}", code.code, code.title, code.explanation);
+            Ok(())
+        }
+        // Step through the statements of the file interactively.
+        [_, cmd, path] if cmd == "explore" => explore_file(&PathBuf::from(path)),
+        // Run the file on the selected engine, capturing its output.
+        [_, flag, path] if flag.starts_with("--engine=") => {
+            let name = flag.trim_start_matches("--engine=");
+            let Some(mut engine) = engine(name, options) else {
+                bail!("Unknown engine '{name}'\n{USAGE}");
+            };
+            run_file_on(engine.as_mut(), &PathBuf::from(path))
+        }
         // File provided => Use it
-        2 => run_file(&PathBuf::from(&args[1])),
-        // We don't support more handling more than one file.
-        _ => bail!("Usage: rlox [script]"),
+        [_, path] => {
+            let mut interpreter = Interpreter::with_options(options);
+            if let Some(prelude) = &prelude {
+                run_prelude(&mut interpreter, prelude)?;
+            }
+            let result = run_file(&mut interpreter, &PathBuf::from(path));
+            print_heap_stats(&interpreter, debug_heap);
+            result
+        }
+        // Files run in order, each one in its own namespace.
+        [_, paths @ ..] => {
+            if paths.iter().any(|path| path.starts_with("--")) {
+                bail!(USAGE);
+            }
+            let paths: Vec<_> = paths.iter().map(PathBuf::from).collect();
+            let mut interpreter = Interpreter::with_options(options);
+            if let Some(prelude) = &prelude {
+                run_prelude(&mut interpreter, prelude)?;
+            }
+            let result = run_files(&mut interpreter, &paths);
+            print_heap_stats(&interpreter, debug_heap);
+            result
+        }
+    }
+}
+
+fn print_heap_stats(interpreter: &Interpreter, debug_heap: bool) {
+    if debug_heap {
+        eprintln!("[heap] {}", interpreter.heap_stats());
     }
 }
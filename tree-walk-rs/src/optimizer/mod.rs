@@ -0,0 +1,459 @@
+//! Optimization passes transforming the AST before interpretation.
+
+use std::rc::Rc;
+
+use lox_frontend::{
+    TokenType as TT,
+    ast::{Expr, FuncDeclaration, LiteralValue, Stmt},
+};
+
+use crate::interpreter::{floor_div, floor_mod};
+
+/// Folds the expressions which only consist of literals into a single literal,
+/// and simplifies statements with constant conditions.
+///
+/// Expressions which would fail on runtime (like `-"text"`) are kept as they are,
+/// so their errors are still reported while executing them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConstantFolder {
+    /// Maximum length in bytes of the strings created by concatenation. Longer
+    /// strings aren't folded, leaving their error to the runtime.
+    pub max_string_length: Option<usize>,
+}
+
+impl ConstantFolder {
+    /// Folds the given statements and the statements inside them.
+    pub fn fold_constants(&self, stmts: Vec<Stmt>) -> Vec<Stmt> {
+        stmts
+            .into_iter()
+            .map(|stmt| self.fold_stmt(stmt))
+            // Empty blocks are left from removed statements and have no effect.
+            .filter(|stmt| !matches!(stmt, Stmt::Block { statements, .. } if statements.is_empty()))
+            .collect()
+    }
+
+    fn fold_stmt(&self, stmt: Stmt) -> Stmt {
+        match stmt {
+            Stmt::Expression(expr) => Stmt::Expression(self.fold_expr(expr)),
+            Stmt::Function(declaration) => Stmt::Function(self.fold_function(declaration)),
+            Stmt::If {
+                id,
+                keyword,
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.fold_expr(condition);
+                if let Some(value) = literal_condition(&condition) {
+                    return if value {
+                        self.fold_stmt(Rc::unwrap_or_clone(then_branch))
+                    } else {
+                        else_branch
+                            .map(|stmt| self.fold_stmt(Rc::unwrap_or_clone(stmt)))
+                            .unwrap_or(Stmt::Block {
+                                id,
+                                statements: Rc::new([]),
+                            })
+                    };
+                }
+
+                Stmt::If {
+                    id,
+                    keyword,
+                    condition,
+                    then_branch: Rc::new(self.fold_stmt(Rc::unwrap_or_clone(then_branch))),
+                    else_branch: else_branch
+                        .map(|stmt| Rc::new(self.fold_stmt(Rc::unwrap_or_clone(stmt)))),
+                }
+            }
+            Stmt::Print { id, expr } => Stmt::Print {
+                id,
+                expr: self.fold_expr(expr),
+            },
+            Stmt::Return {
+                id,
+                keyword,
+                value_expr,
+            } => Stmt::Return {
+                id,
+                keyword,
+                value_expr: value_expr.map(|expr| self.fold_expr(expr)),
+            },
+            Stmt::Var {
+                id,
+                name,
+                initializer,
+            } => Stmt::Var {
+                id,
+                name,
+                initializer: initializer.map(|expr| self.fold_expr(expr)),
+            },
+            Stmt::MultiAssign {
+                id,
+                targets,
+                values,
+            } => Stmt::MultiAssign {
+                id,
+                targets: targets
+                    .iter()
+                    .cloned()
+                    .map(|expr| self.fold_expr(expr))
+                    .collect(),
+                values: values
+                    .iter()
+                    .cloned()
+                    .map(|expr| self.fold_expr(expr))
+                    .collect(),
+            },
+            Stmt::While {
+                id,
+                keyword,
+                condition,
+                body,
+            } => {
+                let condition = self.fold_expr(Rc::unwrap_or_clone(condition));
+                if literal_condition(&condition) == Some(false) {
+                    return Stmt::Block {
+                        id,
+                        statements: Rc::new([]),
+                    };
+                }
+
+                Stmt::While {
+                    id,
+                    keyword,
+                    condition: Rc::new(condition),
+                    body: Rc::new(self.fold_stmt(Rc::unwrap_or_clone(body))),
+                }
+            }
+            Stmt::For {
+                id,
+                keyword,
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                let initializer = initializer.map(|stmt| Box::new(self.fold_stmt(*stmt)));
+                let condition = condition.map(|expr| self.fold_expr(Rc::unwrap_or_clone(expr)));
+                let literal = condition.as_ref().and_then(literal_condition);
+                if literal == Some(false) {
+                    // Only the initializer is executed when the loop never runs.
+                    let statements: Vec<_> = initializer.map(|stmt| *stmt).into_iter().collect();
+                    return Stmt::Block {
+                        id,
+                        statements: statements.into(),
+                    };
+                }
+
+                // True conditions are the same as loops without conditions.
+                let condition = condition.filter(|_| literal.is_none());
+
+                Stmt::For {
+                    id,
+                    keyword,
+                    initializer,
+                    condition: condition.map(Rc::new),
+                    increment: increment
+                        .map(|expr| Rc::new(self.fold_expr(Rc::unwrap_or_clone(expr)))),
+                    body: Rc::new(self.fold_stmt(Rc::unwrap_or_clone(body))),
+                }
+            }
+            Stmt::ForIn {
+                id,
+                name,
+                keyword,
+                iterable,
+                body,
+            } => Stmt::ForIn {
+                id,
+                name,
+                keyword,
+                iterable: self.fold_expr(iterable),
+                body: Rc::new(self.fold_stmt(Rc::unwrap_or_clone(body))),
+            },
+            Stmt::Export {
+                id,
+                keyword,
+                declaration,
+            } => Stmt::Export {
+                id,
+                keyword,
+                declaration: Rc::new(self.fold_stmt(Rc::unwrap_or_clone(declaration))),
+            },
+            Stmt::Try {
+                id,
+                keyword,
+                body,
+                finally,
+            } => Stmt::Try {
+                id,
+                keyword,
+                body: Rc::new(self.fold_stmt(Rc::unwrap_or_clone(body))),
+                finally: Rc::new(self.fold_stmt(Rc::unwrap_or_clone(finally))),
+            },
+            stmt @ (Stmt::Break { .. } | Stmt::Continue { .. }) => stmt,
+            Stmt::Block { id, statements } => Stmt::Block {
+                id,
+                statements: self.fold_constants(statements.to_vec()).into(),
+            },
+            Stmt::Class {
+                id,
+                name,
+                super_class,
+                methods,
+                doc,
+            } => Stmt::Class {
+                id,
+                name,
+                super_class,
+                methods: methods
+                    .into_iter()
+                    .map(|declaration| self.fold_function(declaration))
+                    .collect(),
+                doc,
+            },
+            Stmt::Extend {
+                id,
+                keyword,
+                class,
+                methods,
+            } => Stmt::Extend {
+                id,
+                keyword,
+                class,
+                methods: methods
+                    .into_iter()
+                    .map(|declaration| self.fold_function(declaration))
+                    .collect(),
+            },
+        }
+    }
+
+    fn fold_function(&self, declaration: Rc<FuncDeclaration>) -> Rc<FuncDeclaration> {
+        let FuncDeclaration {
+            id,
+            name,
+            params,
+            body,
+            doc,
+        } = Rc::unwrap_or_clone(declaration);
+        let mut declaration =
+            FuncDeclaration::new(id, name, params, self.fold_constants(body.to_vec()));
+        declaration.doc = doc;
+        Rc::new(declaration)
+    }
+
+    /// Folds the given expression and its sub expressions if they consist of literals only.
+    ///
+    /// Folded literals keep the ID of the expression they replace, which keeps
+    /// them mapped to the code of the whole expression.
+    pub fn fold_expr(&self, expr: Expr) -> Expr {
+        match expr {
+            Expr::Binary {
+                id,
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.fold_expr(Rc::unwrap_or_clone(left));
+                let right = self.fold_expr(Rc::unwrap_or_clone(right));
+                if let (Expr::Literal { value: l, .. }, Expr::Literal { value: r, .. }) =
+                    (&left, &right)
+                    && let Some(value) = self.fold_binary(l, &operator.typ, r)
+                {
+                    return Expr::Literal { id, value };
+                }
+
+                Expr::Binary {
+                    id,
+                    left: Rc::new(left),
+                    operator,
+                    right: Rc::new(right),
+                }
+            }
+            Expr::Grouping { id, expression } => {
+                match self.fold_expr(Rc::unwrap_or_clone(expression)) {
+                    Expr::Literal { value, .. } => Expr::Literal { id, value },
+                    expression => Expr::Grouping {
+                        id,
+                        expression: Rc::new(expression),
+                    },
+                }
+            }
+            Expr::Unary {
+                id,
+                operator,
+                right,
+            } => {
+                let right = self.fold_expr(Rc::unwrap_or_clone(right));
+                let folded = match (&operator.typ, &right) {
+                    (
+                        TT::Minus,
+                        Expr::Literal {
+                            value: LiteralValue::Number(num),
+                            ..
+                        },
+                    ) => Some(LiteralValue::Number(-num)),
+                    (TT::Bang, Expr::Literal { value, .. }) => {
+                        Some(LiteralValue::Boolean(!value.is_truthy()))
+                    }
+                    _ => None,
+                };
+
+                match folded {
+                    Some(value) => Expr::Literal { id, value },
+                    None => Expr::Unary {
+                        id,
+                        operator,
+                        right: Rc::new(right),
+                    },
+                }
+            }
+            Expr::Logical {
+                id,
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.fold_expr(Rc::unwrap_or_clone(left));
+                let right = self.fold_expr(Rc::unwrap_or_clone(right));
+                if let Expr::Literal { value, .. } = &left {
+                    // Mirrors the short circuit evaluation of logical operators.
+                    let returns_left = match operator.typ {
+                        TT::Or => value.is_truthy(),
+                        TT::And => !value.is_truthy(),
+                        _ => unreachable!("Logical expressions have `and` and `or` operators only"),
+                    };
+
+                    return if returns_left { left } else { right };
+                }
+
+                Expr::Logical {
+                    id,
+                    left: Rc::new(left),
+                    operator,
+                    right: Rc::new(right),
+                }
+            }
+            Expr::Call {
+                id,
+                callee,
+                paren,
+                arguments,
+            } => Expr::Call {
+                id,
+                callee: Rc::new(self.fold_expr(Rc::unwrap_or_clone(callee))),
+                paren,
+                arguments: arguments
+                    .iter()
+                    .cloned()
+                    .map(|expr| self.fold_expr(expr))
+                    .collect(),
+            },
+            Expr::Get { id, object, name } => Expr::Get {
+                id,
+                object: Rc::new(self.fold_expr(Rc::unwrap_or_clone(object))),
+                name,
+            },
+            Expr::Set {
+                id,
+                object,
+                name,
+                value,
+            } => Expr::Set {
+                id,
+                object: Rc::new(self.fold_expr(Rc::unwrap_or_clone(object))),
+                name,
+                value: Rc::new(self.fold_expr(Rc::unwrap_or_clone(value))),
+            },
+            Expr::Index {
+                id,
+                object,
+                bracket,
+                index,
+            } => Expr::Index {
+                id,
+                object: Rc::new(self.fold_expr(Rc::unwrap_or_clone(object))),
+                bracket,
+                index: Rc::new(self.fold_expr(Rc::unwrap_or_clone(index))),
+            },
+            Expr::SetIndex {
+                id,
+                object,
+                bracket,
+                index,
+                value,
+            } => Expr::SetIndex {
+                id,
+                object: Rc::new(self.fold_expr(Rc::unwrap_or_clone(object))),
+                bracket,
+                index: Rc::new(self.fold_expr(Rc::unwrap_or_clone(index))),
+                value: Rc::new(self.fold_expr(Rc::unwrap_or_clone(value))),
+            },
+            Expr::Assign { id, name, value } => Expr::Assign {
+                id,
+                name,
+                value: Rc::new(self.fold_expr(Rc::unwrap_or_clone(value))),
+            },
+            expr @ (Expr::Literal { .. }
+            | Expr::Super { .. }
+            | Expr::This { .. }
+            | Expr::Variable { .. }) => expr,
+        }
+    }
+
+    /// Computes binary operation on literals, returning `None` for the operations
+    /// which would fail on runtime.
+    fn fold_binary(
+        &self,
+        left: &LiteralValue,
+        operator: &TT,
+        right: &LiteralValue,
+    ) -> Option<LiteralValue> {
+        use LiteralValue as L;
+
+        let value = match (left, operator, right) {
+            (L::Number(l), TT::Minus, L::Number(r)) => L::Number(l - r),
+            // Division by zero is left for the runtime, which decides whether it's an error.
+            (L::Number(l), TT::Slash, L::Number(r)) if *r != 0.0 => L::Number(l / r),
+            (L::Number(l), TT::Star, L::Number(r)) => L::Number(l * r),
+            (L::Number(l), TT::TildeSlash, L::Number(r)) if *r != 0.0 => {
+                L::Number(floor_div(*l, *r))
+            }
+            (L::Number(l), TT::Percent, L::Number(r)) if *r != 0.0 => L::Number(floor_mod(*l, *r)),
+            (L::Number(l), TT::Plus, L::Number(r)) => L::Number(l + r),
+            (L::Text(l), TT::Plus, L::Text(r))
+                if self
+                    .max_string_length
+                    .is_none_or(|limit| l.len() + r.len() <= limit) =>
+            {
+                L::Text(format!("{l}{r}").into())
+            }
+
+            (L::Number(l), TT::Greater, L::Number(r)) => L::Boolean(l > r),
+            (L::Number(l), TT::GreaterEqual, L::Number(r)) => L::Boolean(l >= r),
+            (L::Number(l), TT::Less, L::Number(r)) => L::Boolean(l < r),
+            (L::Number(l), TT::LessEqual, L::Number(r)) => L::Boolean(l <= r),
+
+            (l, TT::EqualEqual, r) => L::Boolean(l == r),
+            (l, TT::BangEqual, r) => L::Boolean(l != r),
+
+            _ => return None,
+        };
+
+        Some(value)
+    }
+}
+
+/// Returns the value of conditions which are boolean literals. Other literals
+/// are kept, since strict mode fails on conditions which aren't booleans.
+fn literal_condition(condition: &Expr) -> Option<bool> {
+    match condition {
+        Expr::Literal {
+            value: LiteralValue::Boolean(value),
+            ..
+        } => Some(*value),
+        _ => None,
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::Context;
+
+use crate::{Interpreter, InterpreterOptions, PrettyOptions, RunError, run, run_prelude};
+
+const HELP: &str = "Commands:
+  :save <path>  Save the lines executed without errors into a script
+  :load <path>  Run the script in a fresh interpreter continuing its session
+                after running the prelude again
+  :type <expr>  Print the type of the value of the expression
+  :help         Print this help";
+
+/// Text written while handling one line fed to a [`ReplSession`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplOutput {
+    /// Printed values and the messages of the commands.
+    pub output: String,
+    /// Reported errors and warnings.
+    pub error_output: String,
+}
+
+/// Buffer shared between the outputs of the interpreter and the session.
+#[derive(Debug, Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Buffer {
+    fn take(&self) -> String {
+        String::from_utf8_lossy(&self.0.take()).into_owned()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Destinations of the text written by the session and its interpreter.
+#[derive(Debug, Clone)]
+enum Outputs {
+    /// Written to stdout and stderr right away.
+    Std,
+    /// Kept until the line is handled, then returned from [`ReplSession::feed()`].
+    Captured {
+        output: Buffer,
+        error_output: Buffer,
+    },
+}
+
+impl Outputs {
+    fn interpreter(&self, options: InterpreterOptions) -> Interpreter {
+        match self {
+            Outputs::Std => Interpreter::with_options(options),
+            Outputs::Captured {
+                output,
+                error_output,
+            } => Interpreter::builder()
+                .options(options)
+                .output(output.clone())
+                .error_output(error_output.clone())
+                .build(),
+        }
+    }
+
+    fn print(&self, text: impl Display) {
+        match self {
+            Outputs::Std => println!("{text}"),
+            Outputs::Captured { output, .. } => {
+                // Writing into the buffer can't fail.
+                let _ = writeln!(output.clone(), "{text}");
+            }
+        }
+    }
+
+    fn eprint(&self, text: impl Display) {
+        match self {
+            Outputs::Std => eprintln!("{text}"),
+            Outputs::Captured { error_output, .. } => {
+                let _ = writeln!(error_output.clone(), "{text}");
+            }
+        }
+    }
+}
+
+/// REPL session keeping the definitions of the previous lines.
+///
+/// Lines are fed one by one with [`ReplSession::feed()`], which returns what
+/// they printed and reported instead of writing it to stdout and stderr. This
+/// drives the REPL without a terminal, like in tests or `rlox repl --script`.
+#[derive(Debug)]
+pub struct ReplSession {
+    interpreter: Interpreter,
+    /// Lines executed without errors, which can be replayed as a script.
+    /// Definitions of the prelude aren't part of it.
+    history: Vec<String>,
+    prelude: Option<PathBuf>,
+    outputs: Outputs,
+}
+
+impl ReplSession {
+    /// Creates a session capturing the output of each line, running the
+    /// prelude file first when provided. Output of the prelude is discarded.
+    ///
+    /// Values are printed with their state without colors, and runtime errors
+    /// don't end the session like in the interactive REPL.
+    pub fn new(options: InterpreterOptions, prelude: Option<&Path>) -> anyhow::Result<Self> {
+        let outputs = Outputs::Captured {
+            output: Buffer::default(),
+            error_output: Buffer::default(),
+        };
+        let session = Self::with_outputs(
+            repl_options(options, false),
+            prelude.map(Path::to_path_buf),
+            outputs,
+        )?;
+        session.take_output();
+
+        Ok(session)
+    }
+
+    fn with_outputs(
+        options: InterpreterOptions,
+        prelude: Option<PathBuf>,
+        outputs: Outputs,
+    ) -> anyhow::Result<Self> {
+        let mut interpreter = outputs.interpreter(options);
+        if let Some(prelude) = &prelude {
+            run_prelude(&mut interpreter, prelude)?;
+        }
+
+        Ok(Self {
+            interpreter,
+            history: Vec::new(),
+            prelude,
+            outputs,
+        })
+    }
+
+    /// Handles the line like the interactive REPL, running it as code or as a
+    /// command when it starts with a colon, and returns the text written
+    /// meanwhile.
+    ///
+    /// Errors of the code and failing commands are part of the returned error
+    /// output, where only unrecoverable errors are returned as errors.
+    pub fn feed(&mut self, line: &str) -> anyhow::Result<ReplOutput> {
+        self.handle(line)?;
+
+        Ok(self.take_output())
+    }
+
+    /// Lines executed without errors so far, which `:save` writes.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    fn handle(&mut self, line: &str) -> anyhow::Result<()> {
+        match line.trim().strip_prefix(':') {
+            // Failing commands don't end the session.
+            Some(command) => {
+                if let Err(err) = self.command(command) {
+                    self.outputs.eprint(format_args!("{err:#}"));
+                }
+                Ok(())
+            }
+            None => self.run(line.to_owned()),
+        }
+    }
+
+    fn take_output(&self) -> ReplOutput {
+        match &self.outputs {
+            Outputs::Std => ReplOutput::default(),
+            Outputs::Captured {
+                output,
+                error_output,
+            } => ReplOutput {
+                output: output.take(),
+                error_output: error_output.take(),
+            },
+        }
+    }
+
+    /// Runs the given code recording it in the history if it didn't fail.
+    fn run(&mut self, content: String) -> anyhow::Result<()> {
+        let reported = self.interpreter.reported_errors();
+        match run(&mut self.interpreter, content.clone()) {
+            Ok(_) if self.interpreter.reported_errors() == reported => {
+                self.history.push(content);
+            }
+            Ok(_) => {}
+            Err(RunError::Unrecoverable(err)) => return Err(err),
+            // Don't stop on other errors
+            Err(err @ RunError::Scan(_)) => self.outputs.eprint(format_args!("Scan Error:\n{err}")),
+            Err(err @ RunError::Parse(_)) => {
+                self.outputs.eprint(format_args!("Parse Error:\n{err}"))
+            }
+            Err(RunError::LoxError(err) | RunError::Runtime(err)) => {
+                self.outputs.eprint(format_args!("Lox Error:\n{err}"))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut script = String::new();
+        for line in &self.history {
+            script.push_str(line);
+            if !line.ends_with('\n') {
+                script.push('\n');
+            }
+        }
+
+        std::fs::write(path, script)
+            .with_context(|| format!("Error while writing session. Path: {}", path.display()))
+    }
+
+    /// Replaces the session with a fresh one running the given script.
+    fn load(&mut self, path: &Path) -> anyhow::Result<()> {
+        let script = std::fs::read_to_string(path)
+            .with_context(|| format!("Error while reading session. Path: {}", path.display()))?;
+
+        *self = ReplSession::with_outputs(
+            self.interpreter.options().clone(),
+            self.prelude.clone(),
+            self.outputs.clone(),
+        )?;
+        self.run(script)
+    }
+
+    /// Runs the REPL command without the leading colon.
+    fn command(&mut self, command: &str) -> anyhow::Result<()> {
+        match command.split_once(char::is_whitespace) {
+            Some(("save", path)) => {
+                self.save(Path::new(path.trim()))?;
+                self.outputs
+                    .print(format_args!("Session saved to {}", path.trim()));
+            }
+            Some(("load", path)) => self.load(Path::new(path.trim()))?,
+            Some(("type", expr)) => match self.interpreter.eval_expr_in_current_scope(expr) {
+                Ok(value) => self.outputs.print(value.type_name()),
+                Err(RunError::Unrecoverable(err)) => return Err(err),
+                // Errors of the expression are already reported.
+                Err(RunError::Scan(_) | RunError::Parse(_)) => {}
+                Err(RunError::LoxError(err) | RunError::Runtime(err)) => self.outputs.eprint(err),
+            },
+            None if command == "help" => self.outputs.print(HELP),
+            _ => self
+                .outputs
+                .eprint(format_args!("Unknown command ':{command}'\n{HELP}")),
+        }
+
+        Ok(())
+    }
+}
+
+/// Adjusts the options of the interpreter to the REPL.
+fn repl_options(mut options: InterpreterOptions, colors: bool) -> InterpreterOptions {
+    // Values are printed with their state in the REPL.
+    options.pretty_print.get_or_insert_with(|| PrettyOptions {
+        colors,
+        ..Default::default()
+    });
+    // Errors of a line don't end the session.
+    options.stop_on_error = false;
+
+    options
+}
+
+/// Runs the REPL session, running the prelude file first when provided.
+pub fn run_prompt(options: InterpreterOptions, prelude: Option<&Path>) -> anyhow::Result<()> {
+    let options = repl_options(options, std::io::stdout().is_terminal());
+    let mut session =
+        ReplSession::with_outputs(options, prelude.map(Path::to_path_buf), Outputs::Std)?;
+
+    println!("Welcome to rlox interpreter!");
+    println!("To exit press <C-d> or <C-c>. Type :help for commands");
+
+    let mut content = String::new();
+    loop {
+        content.clear();
+        print!(">>> ");
+
+        std::io::stdout()
+            .flush()
+            .context("Error while flushing stdout")?;
+
+        let read = std::io::stdin()
+            .read_line(&mut content)
+            .context("Error while reading from stdin")?;
+
+        if read == 0 {
+            println!("Bye Bye!");
+            return Ok(());
+        }
+
+        session.handle(&content)?;
+    }
+}
+
+/// Feeds the lines of the file to a REPL session one by one, printing each
+/// line after the prompt followed by its output like an interactive session.
+pub fn run_repl_script(
+    options: InterpreterOptions,
+    prelude: Option<&Path>,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let script = std::fs::read_to_string(path)
+        .with_context(|| format!("Error while reading REPL script. Path: {}", path.display()))?;
+
+    let mut session = ReplSession::new(options, prelude)?;
+    for line in script.lines() {
+        println!(">>> {line}");
+        let output = session.feed(line)?;
+        print!("{}", output.output);
+        eprint!("{}", output.error_output);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,168 @@
+//! Infos needed for checking the count of arguments in calls while resolving.
+//!
+//! Arities can only be checked when the callee is a variable which refers to the
+//! function or class it's declared with. Variables which are assigned anywhere in
+//! the program are considered unknown without going through their resolution,
+//! which keeps the check on the safe side.
+
+use std::{collections::HashSet, rc::Rc};
+
+use lox_frontend::{
+    Token,
+    ast::{Expr, FuncDeclaration, Stmt},
+};
+
+/// Returns the arity of the callable declared by the statement if it's known.
+pub fn declared_arity(stmt: &Stmt) -> Option<usize> {
+    match stmt {
+        Stmt::Function(declaration) => Some(declaration.params.len()),
+        Stmt::Class {
+            super_class,
+            methods,
+            ..
+        } => class_arity(super_class.as_ref(), methods),
+        _ => None,
+    }
+}
+
+/// Returns the arity of the class with the given methods if it's known.
+pub fn class_arity(super_class: Option<&Token>, methods: &[Rc<FuncDeclaration>]) -> Option<usize> {
+    let init = methods.iter().find(|method| &*method.name.lexeme == "init");
+    match (init, super_class) {
+        (Some(init), _) => Some(init.params.len()),
+        (None, None) => Some(0),
+        // The initializer can be inherited from the super class.
+        (None, Some(_)) => None,
+    }
+}
+
+/// Collects the names of all variables being assigned in the statements.
+pub fn collect_assigned(stmts: &[Stmt], names: &mut HashSet<Rc<str>>) {
+    for stmt in stmts {
+        collect_in_stmt(stmt, names);
+    }
+}
+
+fn collect_in_stmt(stmt: &Stmt, names: &mut HashSet<Rc<str>>) {
+    match stmt {
+        Stmt::Expression(expr) | Stmt::Print { expr, .. } => collect_in_expr(expr, names),
+        Stmt::Function(declaration) => collect_assigned(&declaration.body, names),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            collect_in_expr(condition, names);
+            collect_in_stmt(then_branch, names);
+            if let Some(else_branch) = else_branch {
+                collect_in_stmt(else_branch, names);
+            }
+        }
+        Stmt::Return { value_expr, .. } => {
+            if let Some(value) = value_expr {
+                collect_in_expr(value, names);
+            }
+        }
+        Stmt::Var { initializer, .. } => {
+            if let Some(initializer) = initializer {
+                collect_in_expr(initializer, names);
+            }
+        }
+        Stmt::MultiAssign {
+            targets, values, ..
+        } => {
+            for target in targets.iter() {
+                match target {
+                    Expr::Variable { name, .. } => {
+                        names.insert(name.lexeme.clone());
+                    }
+                    target => collect_in_expr(target, names),
+                }
+            }
+            for value in values.iter() {
+                collect_in_expr(value, names);
+            }
+        }
+        Stmt::While {
+            condition, body, ..
+        } => {
+            collect_in_expr(condition, names);
+            collect_in_stmt(body, names);
+        }
+        Stmt::For {
+            initializer,
+            condition,
+            increment,
+            body,
+            ..
+        } => {
+            if let Some(initializer) = initializer {
+                collect_in_stmt(initializer, names);
+            }
+            for expr in condition.iter().chain(increment) {
+                collect_in_expr(expr, names);
+            }
+            collect_in_stmt(body, names);
+        }
+        Stmt::ForIn { iterable, body, .. } => {
+            collect_in_expr(iterable, names);
+            collect_in_stmt(body, names);
+        }
+        Stmt::Block { statements, .. } => collect_assigned(statements, names),
+        Stmt::Try { body, finally, .. } => {
+            collect_in_stmt(body, names);
+            collect_in_stmt(finally, names);
+        }
+        Stmt::Class { methods, .. } | Stmt::Extend { methods, .. } => {
+            for method in methods {
+                collect_assigned(&method.body, names);
+            }
+        }
+        Stmt::Export { declaration, .. } => collect_in_stmt(declaration, names),
+        Stmt::Break { .. } | Stmt::Continue { .. } => {}
+    }
+}
+
+fn collect_in_expr(expr: &Expr, names: &mut HashSet<Rc<str>>) {
+    match expr {
+        Expr::Assign { name, value, .. } => {
+            names.insert(name.lexeme.clone());
+            collect_in_expr(value, names);
+        }
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            collect_in_expr(left, names);
+            collect_in_expr(right, names);
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            collect_in_expr(callee, names);
+            for arg in arguments.iter() {
+                collect_in_expr(arg, names);
+            }
+        }
+        Expr::Get { object, .. } => collect_in_expr(object, names),
+        Expr::Set { object, value, .. } => {
+            collect_in_expr(object, names);
+            collect_in_expr(value, names);
+        }
+        Expr::Index { object, index, .. } => {
+            collect_in_expr(object, names);
+            collect_in_expr(index, names);
+        }
+        Expr::SetIndex {
+            object,
+            index,
+            value,
+            ..
+        } => {
+            collect_in_expr(object, names);
+            collect_in_expr(index, names);
+            collect_in_expr(value, names);
+        }
+        Expr::Grouping { expression, .. } => collect_in_expr(expression, names),
+        Expr::Unary { right, .. } => collect_in_expr(right, names),
+        Expr::Literal { .. } | Expr::Super { .. } | Expr::This { .. } | Expr::Variable { .. } => {}
+    }
+}
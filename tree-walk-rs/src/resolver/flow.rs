@@ -0,0 +1,77 @@
+//! Control flow infos needed for finding unreachable code while resolving.
+//!
+//! The analysis only follows the structure of the statements without
+//! evaluating any conditions, except for loops with the literal `true` as
+//! condition, which can only be left with `break` or `return`.
+
+use lox_frontend::ast::{Expr, LiteralValue, Stmt};
+
+/// Checks if the statement never completes normally, so the statements after
+/// it in the same block can't be reached.
+pub fn exits(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return { .. } | Stmt::Break { .. } | Stmt::Continue { .. } => true,
+        Stmt::Block { statements, .. } => statements.iter().any(exits),
+        Stmt::If {
+            then_branch,
+            else_branch,
+            ..
+        } => else_branch
+            .as_ref()
+            .is_some_and(|else_branch| exits(then_branch) && exits(else_branch)),
+        Stmt::While {
+            condition, body, ..
+        } => is_true(condition) && !breaks(body),
+        Stmt::For {
+            condition, body, ..
+        } => condition.as_deref().is_none_or(is_true) && !breaks(body),
+        Stmt::Try { body, finally, .. } => exits(body) || exits(finally),
+        Stmt::Expression(_)
+        | Stmt::Function(_)
+        | Stmt::Print { .. }
+        | Stmt::Var { .. }
+        | Stmt::MultiAssign { .. }
+        | Stmt::ForIn { .. }
+        | Stmt::Export { .. }
+        | Stmt::Class { .. }
+        | Stmt::Extend { .. } => false,
+    }
+}
+
+/// Checks if the loop body contains a `break` leaving the loop itself, which
+/// excludes the ones of nested loops and functions.
+fn breaks(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Break { .. } => true,
+        Stmt::Block { statements, .. } => statements.iter().any(breaks),
+        Stmt::If {
+            then_branch,
+            else_branch,
+            ..
+        } => breaks(then_branch) || else_branch.as_deref().is_some_and(breaks),
+        Stmt::Try { body, finally, .. } => breaks(body) || breaks(finally),
+        Stmt::Expression(_)
+        | Stmt::Function(_)
+        | Stmt::Print { .. }
+        | Stmt::Return { .. }
+        | Stmt::Continue { .. }
+        | Stmt::Var { .. }
+        | Stmt::MultiAssign { .. }
+        | Stmt::While { .. }
+        | Stmt::For { .. }
+        | Stmt::ForIn { .. }
+        | Stmt::Export { .. }
+        | Stmt::Class { .. }
+        | Stmt::Extend { .. } => false,
+    }
+}
+
+fn is_true(condition: &Expr) -> bool {
+    matches!(
+        condition,
+        Expr::Literal {
+            value: LiteralValue::Boolean(true),
+            ..
+        }
+    )
+}
@@ -1,12 +1,23 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
-use crate::{
-    Token,
+use lox_frontend::{
+    NodeId, SourceMap, Token,
     ast::{Expr, FuncDeclaration, Stmt},
-    errors::{LoxError, LoxResult},
+    codes,
+    locale::Message,
+};
+
+use crate::{
+    errors::{LoxError, LoxResult, LoxWarning},
     interpreter::Interpreter,
 };
 
+mod arity;
+mod flow;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum FunctionType {
     None,
@@ -20,18 +31,55 @@ enum ClassType {
     None,
     Class,
     SubClass,
+    /// Methods added to an existing class with `extend`.
+    Extension,
+}
+
+/// Infos about a variable declared in a local scope.
+#[derive(Debug, Clone)]
+struct Variable {
+    /// State of the variable with:
+    /// - False: Variable declared but not defined (Not initialized with a value)
+    /// - True: Variable defined with the initialized value (Which can be nil as well)
+    defined: bool,
+    /// Index of the variable in the environment of its scope, following
+    /// the order of declarations.
+    slot: usize,
+    /// Count of the arguments the variable must be called with when it's known
+    /// to always refer to the function or class it's declared with.
+    arity: Option<usize>,
+    /// Name in the `var` declaration of the variable, which is reported in
+    /// strict mode when the variable is never used.
+    var_name: Option<Token>,
+    used: bool,
 }
 
 #[derive(Debug)]
 pub struct Resolver<'a> {
     interpreter: &'a mut Interpreter,
-    /// The scope contains the variables in the current scope and
-    /// their state with:
-    /// - False: Variable declared but not defined (Not initialized with a value)
-    /// - True: Variable defined with the initialized value (Which can be nil as well)
-    scopes: Vec<HashMap<String, bool>>,
+    /// The scope contains the variables in the current scope and their infos.
+    scopes: Vec<HashMap<Rc<str>, Variable>>,
     current_function: FunctionType,
     current_class: ClassType,
+    /// Count of the loops enclosing the current statement in the current function.
+    loop_depth: usize,
+    /// Names of the variables which can't be checked for arity since they are
+    /// assigned or declared more than once on top level.
+    reassigned: HashSet<Rc<str>>,
+    /// Arities of the functions and classes declared on top level.
+    global_arities: HashMap<Rc<str>, usize>,
+    /// Spans of the resolved nodes, which the warnings point at.
+    source_map: Option<&'a SourceMap>,
+    /// Names declared on top level so far, which are the globals together
+    /// with the ones defined by the previous runs of the interpreter.
+    globals: HashSet<Rc<str>>,
+    /// Names of all declarations on top level collected before resolving,
+    /// which functions can refer to before they are declared since they are
+    /// called later.
+    top_level: HashSet<Rc<str>>,
+    /// First error found on leaving a scope, like unused variables in strict
+    /// mode, which is returned once resolving is done.
+    scope_error: Option<LoxError>,
 }
 
 impl<'a> Resolver<'a> {
@@ -41,17 +89,89 @@ impl<'a> Resolver<'a> {
             scopes: Vec::new(),
             current_function: FunctionType::None,
             current_class: ClassType::None,
+            loop_depth: 0,
+            reassigned: HashSet::new(),
+            global_arities: HashMap::new(),
+            source_map: None,
+            globals: HashSet::new(),
+            top_level: HashSet::new(),
+            scope_error: None,
+        }
+    }
+
+    /// Sets the source map of the statements, which is needed for reporting
+    /// warnings with the spans of their code.
+    pub fn with_source_map(mut self, source_map: &'a SourceMap) -> Self {
+        self.source_map = Some(source_map);
+        self
+    }
+
+    /// Resolves the statements of a whole program.
+    pub fn resolve(&mut self, stmts: &[Stmt]) -> LoxResult<()> {
+        arity::collect_assigned(stmts, &mut self.reassigned);
+
+        // Top-level declarations are collected before resolving any function
+        // bodies, which can refer to them in any order. Globals can be
+        // declared again, changing the values they refer to.
+        for stmt in stmts {
+            let stmt = match stmt {
+                Stmt::Export { declaration, .. } => declaration,
+                stmt => stmt,
+            };
+            let name = match stmt {
+                Stmt::Function(declaration) => &declaration.name,
+                Stmt::Class { name, .. } | Stmt::Var { name, .. } => name,
+                _ => continue,
+            };
+            if !self.top_level.insert(name.lexeme.clone()) {
+                self.reassigned.insert(name.lexeme.clone());
+            }
+            if let Some(arity) = arity::declared_arity(stmt) {
+                self.global_arities.insert(name.lexeme.clone(), arity);
+            }
+        }
+
+        self.resolve_stmts(stmts)?;
+
+        match self.scope_error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
         }
     }
 
-    pub fn resolve_stmts(&mut self, stmts: &[Stmt]) -> LoxResult<()> {
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) -> LoxResult<()> {
         for stmt in stmts {
             self.resolve_stmt(stmt)?;
         }
 
+        // Only the first unreachable statement is reported for each block.
+        if let Some(exit) = stmts.iter().position(flow::exits)
+            && let Some(unreachable) = stmts.get(exit + 1)
+        {
+            self.warn(
+                unreachable.id(),
+                codes::UNREACHABLE_CODE,
+                "Unreachable code.",
+            );
+        }
+
         Ok(())
     }
 
+    /// Reports the warning about the node, which is skipped without a source
+    /// map to point at its code.
+    fn warn(&mut self, id: NodeId, code: &'static str, message: impl Into<Message>) {
+        if let Some(span) = self.source_map.and_then(|map| map.span(id)) {
+            self.interpreter
+                .report_warning(&LoxWarning::new(span, code, message));
+        }
+    }
+
+    fn warn_token(&mut self, token: &Token, code: &'static str, message: impl Into<Message>) {
+        self.interpreter
+            .report_warning(&LoxWarning::new(token.span, code, message));
+    }
+
     fn resolve_stmt(&mut self, stmt: &Stmt) -> LoxResult<()> {
         match stmt {
             Stmt::Expression(expr) => self.resolve_expr(expr),
@@ -60,6 +180,7 @@ impl<'a> Resolver<'a> {
                 condition,
                 then_branch,
                 else_branch,
+                ..
             } => {
                 self.resolve_expr(condition)?;
                 // Static analyzing resolve both branches, as opposite to interpretation
@@ -70,22 +191,73 @@ impl<'a> Resolver<'a> {
                 }
                 Ok(())
             }
-            Stmt::Print(expr) => self.resolve_expr(expr),
+            Stmt::Print { expr, .. } => self.resolve_expr(expr),
             Stmt::Return {
                 keyword,
                 value_expr,
+                ..
             } => self.resolve_return(keyword, value_expr.as_ref()),
-            Stmt::Var { name, initializer } => self.resolve_var(name, initializer.as_ref()),
-            Stmt::While { condition, body } => {
+            Stmt::Var {
+                name, initializer, ..
+            } => self.resolve_var(name, initializer.as_ref()),
+            Stmt::MultiAssign {
+                targets, values, ..
+            } => self.resolve_multi_assign(targets, values),
+            Stmt::While {
+                condition, body, ..
+            } => {
                 self.resolve_expr(condition)?;
-                self.resolve_stmt(body)
+                self.resolve_loop_body(body)
+            }
+            Stmt::Break { keyword, .. } => self.resolve_loop_control(keyword, "break"),
+            Stmt::Continue { keyword, .. } => self.resolve_loop_control(keyword, "continue"),
+            Stmt::Export {
+                keyword,
+                declaration,
+                ..
+            } => {
+                if !self.scopes.is_empty() {
+                    return Err(LoxError::new(
+                        keyword.to_owned(),
+                        codes::NESTED_EXPORT,
+                        "Can only export declarations on top level.",
+                    ));
+                }
+                self.resolve_stmt(declaration)
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => self.resolve_for(
+                initializer.as_deref(),
+                condition.as_deref(),
+                increment.as_deref(),
+                body,
+            ),
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+                ..
+            } => self.resolve_for_in(name, iterable, body),
+            Stmt::Block { statements, .. } => self.resolve_block(statements),
+            Stmt::Try { body, finally, .. } => {
+                self.resolve_stmt(body)?;
+                self.resolve_stmt(finally)
             }
-            Stmt::Block { statements } => self.resolve_block(statements),
             Stmt::Class {
+                id,
                 name,
                 super_class,
                 methods,
-            } => self.resolve_stmt_class(name, super_class.as_ref(), methods),
+                doc: _,
+            } => self.resolve_stmt_class(*id, name, super_class.as_ref(), methods),
+            Stmt::Extend {
+                id, class, methods, ..
+            } => self.resolve_stmt_extend(*id, class, methods),
         }
     }
 
@@ -93,6 +265,7 @@ impl<'a> Resolver<'a> {
         if self.current_function == FunctionType::None {
             return Err(LoxError::new(
                 keyword.to_owned(),
+                codes::TOP_LEVEL_RETURN,
                 "Can't return from top level code",
             ));
         }
@@ -100,6 +273,7 @@ impl<'a> Resolver<'a> {
             if self.current_function == FunctionType::Initializer {
                 return Err(LoxError::new(
                     keyword.to_owned(),
+                    codes::INITIALIZER_RETURN_VALUE,
                     "Can't return a value fron an initializer.",
                 ));
             }
@@ -110,9 +284,10 @@ impl<'a> Resolver<'a> {
 
     fn resolve_stmt_class(
         &mut self,
+        id: NodeId,
         name: &Token,
         super_class: Option<&Token>,
-        methods: &[FuncDeclaration],
+        methods: &[Rc<FuncDeclaration>],
     ) -> LoxResult<()> {
         let enclusing_class = self.current_class;
         self.current_class = ClassType::Class;
@@ -123,12 +298,16 @@ impl<'a> Resolver<'a> {
 
         s.declare(name)?;
         s.define(name);
+        if let Some(arity) = arity::class_arity(super_class, methods) {
+            s.set_arity(name, arity);
+        }
 
         if let Some(super_class) = super_class {
             // class Foo < Foo {...}
             if name.lexeme == super_class.lexeme {
                 return Err(LoxError::new(
                     super_class.to_owned(),
+                    codes::SELF_INHERITANCE,
                     "A class can't inherit from itself.",
                 ));
             }
@@ -136,13 +315,11 @@ impl<'a> Resolver<'a> {
             s.current_class = ClassType::SubClass;
 
             // Resolve
-            s.resolve_expr(&Expr::Variable {
-                name: super_class.to_owned(),
-            })?;
+            s.expr_var(id, super_class)?;
 
             // Set scope for super
             s.begin_scope();
-            s.scopes.last_mut().unwrap().insert("super".into(), true);
+            s.declare_defined("super");
         }
 
         s.begin_scope();
@@ -153,15 +330,68 @@ impl<'a> Resolver<'a> {
             }
         });
 
-        s.scopes.last_mut().unwrap().insert("this".into(), true);
+        s.declare_defined("this");
+        s.resolve_methods(methods)
+    }
+
+    fn resolve_stmt_extend(
+        &mut self,
+        id: NodeId,
+        class: &Token,
+        methods: &[Rc<FuncDeclaration>],
+    ) -> LoxResult<()> {
+        self.expr_var(id, class)?;
+
+        for method in methods {
+            if &*method.name.lexeme == "init" {
+                return Err(LoxError::new(
+                    method.name.to_owned(),
+                    codes::INVALID_EXTENSION,
+                    "Can't add an initializer to an existing class.",
+                ));
+            }
+            if is_private(&method.name.lexeme) {
+                return Err(LoxError::new(
+                    method.name.to_owned(),
+                    codes::INVALID_EXTENSION,
+                    "Can't add private methods to an existing class.",
+                ));
+            }
+        }
+
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Extension;
+        self.begin_scope();
+
+        let mut s = scopeguard::guard(self, |s| {
+            s.end_scope();
+            s.current_class = enclosing_class;
+        });
+
+        s.declare_defined("this");
+        s.resolve_methods(methods)
+    }
 
+    fn resolve_methods(&mut self, methods: &[Rc<FuncDeclaration>]) -> LoxResult<()> {
+        let mut method_names = HashSet::new();
         for method in methods {
-            let declaration = if method.name.lexeme == "init" {
+            if !method_names.insert(&method.name.lexeme) {
+                return Err(LoxError::new(
+                    method.name.to_owned(),
+                    codes::DUPLICATE_DECLARATION,
+                    Message::new(
+                        "Already a method named '{}' in this class.",
+                        [&method.name.lexeme],
+                    ),
+                ));
+            }
+
+            let declaration = if &*method.name.lexeme == "init" {
                 FunctionType::Initializer
             } else {
                 FunctionType::Method
             };
-            s.resolve_function(method, declaration)?;
+            self.resolve_function(method, declaration)?;
         }
 
         Ok(())
@@ -170,6 +400,7 @@ impl<'a> Resolver<'a> {
     fn visit_stmt_function(&mut self, func_declaration: &FuncDeclaration) -> LoxResult<()> {
         self.declare(&func_declaration.name)?;
         self.define(&func_declaration.name);
+        self.set_arity(&func_declaration.name, func_declaration.params.len());
 
         self.resolve_function(func_declaration, FunctionType::Function)
     }
@@ -182,15 +413,28 @@ impl<'a> Resolver<'a> {
     ) -> LoxResult<()> {
         let enclosing_fun = self.current_function;
         self.current_function = typ;
+        // Loops around the declaration can't be controlled from the function body.
+        let enclosing_loop_depth = std::mem::take(&mut self.loop_depth);
 
         self.begin_scope();
 
         let mut sel = scopeguard::guard(self, |s| {
             s.end_scope();
             s.current_function = enclosing_fun;
+            s.loop_depth = enclosing_loop_depth;
         });
 
-        for param in &func_declaration.params {
+        for (idx, param) in func_declaration.params.iter().enumerate() {
+            if func_declaration.params[..idx]
+                .iter()
+                .any(|prev| prev.lexeme == param.lexeme)
+            {
+                return Err(LoxError::new(
+                    param.to_owned(),
+                    codes::DUPLICATE_DECLARATION,
+                    Message::new("Duplicate parameter name '{}'.", [&param.lexeme]),
+                ));
+            }
             sel.declare(param)?;
             sel.define(param);
         }
@@ -209,6 +453,13 @@ impl<'a> Resolver<'a> {
         // ```
         // In such case we need to return an error.
         self.declare(name)?;
+        if let Some(var) = self
+            .scopes
+            .last_mut()
+            .and_then(|map| map.get_mut(&name.lexeme))
+        {
+            var.var_name = Some(name.to_owned());
+        }
         if let Some(init) = initializer {
             self.resolve_expr(init)?;
         }
@@ -217,11 +468,94 @@ impl<'a> Resolver<'a> {
         Ok(())
     }
 
+    fn resolve_for(
+        &mut self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> LoxResult<()> {
+        // The initializer gets its own scope like a block around the loop.
+        let Some(initializer) = initializer else {
+            return self.resolve_loop_clauses(condition, increment, body);
+        };
+
+        self.begin_scope();
+        let res = self
+            .resolve_stmt(initializer)
+            .and_then(|()| self.resolve_loop_clauses(condition, increment, body));
+        self.end_scope();
+
+        res
+    }
+
+    /// The loop variable is declared in its own scope around the body, which
+    /// the interpreter creates for each iteration.
+    fn resolve_for_in(&mut self, name: &Token, iterable: &Expr, body: &Stmt) -> LoxResult<()> {
+        self.resolve_expr(iterable)?;
+
+        self.begin_scope();
+        let res = self.declare(name).and_then(|()| {
+            self.define(name);
+            self.resolve_loop_body(body)
+        });
+        self.end_scope();
+
+        res
+    }
+
+    fn resolve_loop_clauses(
+        &mut self,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) -> LoxResult<()> {
+        if let Some(condition) = condition {
+            self.resolve_expr(condition)?;
+        }
+        if let Some(increment) = increment {
+            self.resolve_expr(increment)?;
+        }
+        self.resolve_loop_body(body)
+    }
+
+    fn resolve_loop_body(&mut self, body: &Stmt) -> LoxResult<()> {
+        self.loop_depth += 1;
+        let res = self.resolve_stmt(body);
+        self.loop_depth -= 1;
+
+        res
+    }
+
+    fn resolve_loop_control(&mut self, keyword: &Token, name: &str) -> LoxResult<()> {
+        if self.loop_depth == 0 {
+            return Err(LoxError::new(
+                keyword.to_owned(),
+                codes::LOOP_CONTROL_OUTSIDE_LOOP,
+                Message::new("Can't use '{}' outside of a loop.", [name]),
+            ));
+        }
+
+        Ok(())
+    }
+
     fn declare(&mut self, name: &Token) -> LoxResult<()> {
+        if self.interpreter.options().deny_shadowing {
+            self.check_shadowing(name)?;
+        }
+
         if let Some(map) = self.scopes.last_mut() {
-            if map.insert(name.lexeme.to_owned(), false).is_some() {
+            let variable = Variable {
+                defined: false,
+                slot: map.len(),
+                arity: None,
+                var_name: None,
+                used: false,
+            };
+            if map.insert(name.lexeme.to_owned(), variable).is_some() {
                 return Err(LoxError::new(
                     name.to_owned(),
+                    codes::DUPLICATE_DECLARATION,
                     "Already a variable with the same name in this scope",
                 ));
             }
@@ -230,15 +564,112 @@ impl<'a> Resolver<'a> {
         Ok(())
     }
 
+    /// Declares and defines the variable for the implicit names like `this` and `super`.
+    fn declare_defined(&mut self, name: &str) {
+        let map = self
+            .scopes
+            .last_mut()
+            .expect("Implicit variables are always declared in local scopes");
+        let variable = Variable {
+            defined: true,
+            slot: map.len(),
+            arity: None,
+            var_name: None,
+            used: false,
+        };
+        map.insert(name.into(), variable);
+    }
+
+    /// Checks that the local variable doesn't hide a variable of the enclosing
+    /// scopes or a global declared by the scripts.
+    fn check_shadowing(&self, name: &Token) -> LoxResult<()> {
+        let Some((_, enclosing)) = self.scopes.split_last() else {
+            return Ok(());
+        };
+        let shadows = enclosing.iter().any(|map| map.contains_key(&name.lexeme))
+            || self.top_level.contains(&name.lexeme)
+            || self.globals.contains(&name.lexeme);
+        if shadows {
+            return Err(LoxError::new(
+                name.to_owned(),
+                codes::SHADOWED_VARIABLE,
+                Message::new(
+                    "Variable '{}' shadows a variable of an enclosing scope.",
+                    [&name.lexeme],
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
     fn define(&mut self, name: &Token) {
-        if let Some(map) = self.scopes.last_mut() {
-            let entry = map
-                .get_mut(&name.lexeme)
-                .expect("Variable must be declared before defining it");
-            *entry = true;
+        match self.scopes.last_mut() {
+            Some(map) => {
+                let entry = map
+                    .get_mut(&name.lexeme)
+                    .expect("Variable must be declared before defining it");
+                entry.defined = true;
+            }
+            None => {
+                self.globals.insert(name.lexeme.clone());
+            }
+        }
+    }
+
+    /// Sets the arity of the function or class declared in the current local scope.
+    fn set_arity(&mut self, name: &Token, arity: usize) {
+        if self.reassigned.contains(&name.lexeme) {
+            return;
+        }
+        if let Some(var) = self
+            .scopes
+            .last_mut()
+            .and_then(|map| map.get_mut(&name.lexeme))
+        {
+            var.arity = Some(arity);
         }
     }
 
+    /// Returns the arity of the variable with the given name if it's known.
+    fn known_arity(&self, name: &Token) -> Option<usize> {
+        if self.reassigned.contains(&name.lexeme) {
+            return None;
+        }
+        match self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|map| map.get(&name.lexeme))
+        {
+            Some(var) => var.arity,
+            None => self.global_arities.get(&name.lexeme).copied(),
+        }
+    }
+
+    fn resolve_call(&mut self, callee: &Expr, paren: &Token, arguments: &[Expr]) -> LoxResult<()> {
+        self.resolve_expr(callee)?;
+        for arg in arguments {
+            self.resolve_expr(arg)?;
+        }
+
+        if let Expr::Variable { name, .. } = callee
+            && let Some(arity) = self.known_arity(name)
+            && arity != arguments.len()
+        {
+            return Err(LoxError::new(
+                paren.to_owned(),
+                codes::WRONG_ARGUMENT_COUNT,
+                Message::new(
+                    "Expected {} arguments but got {}.",
+                    [arity, arguments.len()],
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
     fn resolve_block(&mut self, stmts: &[Stmt]) -> LoxResult<()> {
         self.begin_scope();
         let res = self.resolve_stmts(stmts);
@@ -253,59 +684,91 @@ impl<'a> Resolver<'a> {
                 left,
                 operator: _,
                 right,
+                ..
             } => {
                 self.resolve_expr(left)?;
                 self.resolve_expr(right)
             }
             Expr::Call {
                 callee,
-                paren: _,
+                paren,
                 arguments,
+                ..
+            } => self.resolve_call(callee, paren, arguments),
+            Expr::Grouping { expression, .. } => self.resolve_expr(expression),
+            Expr::Index { object, index, .. } => {
+                self.resolve_expr(object)?;
+                self.resolve_expr(index)
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
             } => {
-                self.resolve_expr(callee)?;
-                for arg in arguments {
-                    self.resolve_expr(arg)?;
-                }
-
-                Ok(())
+                self.resolve_expr(object)?;
+                self.resolve_expr(index)?;
+                self.resolve_expr(value)
             }
-            Expr::Grouping { expression } => self.resolve_expr(expression),
-            Expr::Literal { value: _ } => Ok(()),
+            Expr::Literal { value: _, .. } => Ok(()),
             Expr::Logical {
                 left,
                 operator: _,
                 right,
+                ..
             } => {
                 self.resolve_expr(left)?;
                 self.resolve_expr(right)
             }
-            Expr::Unary { operator: _, right } => self.resolve_expr(right),
-            expr @ Expr::Variable { name } => self.expr_var(expr, name),
-            expr @ Expr::Assign { name, value } => self.expr_assign(expr, name, value.as_ref()),
-            Expr::Get { object, name: _ } => self.resolve_expr(object),
+            Expr::Unary {
+                operator: _, right, ..
+            } => self.resolve_expr(right),
+            Expr::Variable { id, name } => self.expr_var(*id, name),
+            Expr::Assign { id, name, value } => self.expr_assign(*id, name, value.as_ref()),
+            Expr::Get { object, name, .. } => {
+                self.resolve_member(object, name)?;
+                self.resolve_expr(object)
+            }
             Expr::Set {
                 object,
-                name: _,
+                name,
                 value,
+                ..
             } => {
+                self.resolve_member(object, name)?;
                 self.resolve_expr(object)?;
                 self.resolve_expr(value)
             }
-            expr @ Expr::This { keyword } => {
+            Expr::This { id, keyword } => {
                 if self.current_class == ClassType::None {
                     return Err(LoxError::new(
                         keyword.to_owned(),
+                        codes::THIS_OUTSIDE_CLASS,
                         "Can't use 'this' outside of a class.",
                     ));
                 }
-                self.resolve_local(expr, keyword);
-                Ok(())
+                self.resolve_local(*id, keyword)
             }
-            expr @ Expr::Super { keyword, method: _ } => {
+            Expr::Super {
+                id,
+                keyword,
+                method,
+            } => {
+                if is_private(&method.lexeme) {
+                    return Err(LoxError::new(
+                        method.to_owned(),
+                        codes::PRIVATE_ACCESS,
+                        Message::new(
+                            "Can't access private method '{}' through 'super'.",
+                            [&method.lexeme],
+                        ),
+                    ));
+                }
                 match self.current_class {
                     ClassType::None => {
                         return Err(LoxError::new(
                             keyword.to_owned(),
+                            codes::INVALID_SUPER,
                             "Can't use 'super' outside of a class",
                         ));
                     }
@@ -313,51 +776,149 @@ impl<'a> Resolver<'a> {
                     ClassType::Class => {
                         return Err(LoxError::new(
                             keyword.to_owned(),
+                            codes::INVALID_SUPER,
                             "Can't use 'super' in a class with no superclass",
                         ));
                     }
+                    ClassType::Extension => {
+                        return Err(LoxError::new(
+                            keyword.to_owned(),
+                            codes::INVALID_SUPER,
+                            "Can't use 'super' in methods added with 'extend'.",
+                        ));
+                    }
                 }
-                self.resolve_local(expr, keyword);
-                Ok(())
+                self.resolve_local(*id, keyword)
             }
         }
     }
 
-    fn expr_var(&mut self, expr: &Expr, name: &Token) -> LoxResult<()> {
+    /// Checks that private members are only accessed through `this`, leaving
+    /// the checks of the classes owning them to the runtime since methods added
+    /// with `extend` belong to the extended class too.
+    fn resolve_member(&mut self, object: &Expr, name: &Token) -> LoxResult<()> {
+        if !is_private(&name.lexeme) || matches!(object, Expr::This { .. }) {
+            return Ok(());
+        }
+
+        Err(LoxError::new(
+            name.to_owned(),
+            codes::PRIVATE_ACCESS,
+            Message::new(
+                "Can't access private member '{}' except through 'this' inside its class.",
+                [&name.lexeme],
+            ),
+        ))
+    }
+
+    fn expr_var(&mut self, id: NodeId, name: &Token) -> LoxResult<()> {
         if let Some(map) = self.scopes.last()
-            && map.get(&name.lexeme).is_some_and(|val| !val)
+            && map.get(&name.lexeme).is_some_and(|var| !var.defined)
         {
             return Err(LoxError::new(
                 name.to_owned(),
+                codes::READ_IN_OWN_INITIALIZER,
                 "Can't read local variable in its own initializer.",
             ));
         }
 
-        self.resolve_local(expr, name);
+        self.resolve_local(id, name)
+    }
 
+    fn resolve_multi_assign(&mut self, targets: &[Expr], values: &[Expr]) -> LoxResult<()> {
+        // Property and subscript targets are resolved like reading them, since
+        // their objects and indices are evaluated the same way.
+        for target in targets {
+            if let Expr::Get { .. } | Expr::Index { .. } = target {
+                self.resolve_expr(target)?;
+            }
+        }
+        for value in values {
+            self.resolve_expr(value)?;
+        }
+        for target in targets {
+            if let Expr::Variable { id, name } = target {
+                self.resolve_local(*id, name)?;
+            }
+        }
         Ok(())
     }
 
-    fn expr_assign(&mut self, assign_expr: &Expr, name: &Token, value: &Expr) -> LoxResult<()> {
+    fn expr_assign(&mut self, id: NodeId, name: &Token, value: &Expr) -> LoxResult<()> {
         self.resolve_expr(value)?;
-        self.resolve_local(assign_expr, name);
-        Ok(())
+        self.resolve_local(id, name)
     }
 
-    fn resolve_local(&mut self, expr: &Expr, name: &Token) {
-        for (idx, map) in self.scopes.iter().enumerate().rev() {
-            if map.contains_key(&name.lexeme) {
-                self.interpreter.resolve(expr, self.scopes.len() - 1 - idx);
-                return;
+    /// Resolves the variable with the given name for the node with the given
+    /// ID referring to it.
+    fn resolve_local(&mut self, id: NodeId, name: &Token) -> LoxResult<()> {
+        let depth = self.scopes.len();
+        for (idx, map) in self.scopes.iter_mut().enumerate().rev() {
+            if let Some(var) = map.get_mut(&name.lexeme) {
+                var.used = true;
+                self.interpreter.resolve(id, depth - 1 - idx, var.slot);
+                return Ok(());
             }
         }
+
+        self.check_global(name)
+    }
+
+    /// Warns about the global variable if it isn't declared so far, neither
+    /// by the resolved code nor by the previous runs, failing instead in
+    /// strict mode. Functions can refer to the globals declared after them as
+    /// well, like mutually recursive ones.
+    fn check_global(&mut self, name: &Token) -> LoxResult<()> {
+        let declared_later =
+            self.current_function != FunctionType::None && self.top_level.contains(&name.lexeme);
+        if declared_later
+            || self.globals.contains(&name.lexeme)
+            || self.interpreter.get_global(&name.lexeme).is_some()
+        {
+            return Ok(());
+        }
+
+        let message = Message::new("Undefined variable '{}'.", [&name.lexeme]);
+        if self.interpreter.options().deny_undefined_globals {
+            return Err(LoxError::new(
+                name.to_owned(),
+                codes::UNDEFINED_VARIABLE,
+                message,
+            ));
+        }
+        self.warn_token(name, codes::UNDEFINED_VARIABLE, message);
+
+        Ok(())
     }
 
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
     }
 
+    /// Leaves the current scope, keeping the error about its first unused
+    /// variable in strict mode.
     fn end_scope(&mut self) {
-        self.scopes.pop();
+        let scope = self.scopes.pop().expect("Scopes are balanced");
+        if !self.interpreter.options().deny_unused_variables || self.scope_error.is_some() {
+            return;
+        }
+
+        let unused = scope
+            .into_values()
+            .filter(|var| !var.used)
+            .filter_map(|var| var.var_name.map(|name| (var.slot, name)))
+            .min_by_key(|(slot, _)| *slot);
+        if let Some((_, name)) = unused {
+            self.scope_error = Some(LoxError::new(
+                name.to_owned(),
+                codes::UNUSED_VARIABLE,
+                Message::new("Local variable '{}' is never used.", [&name.lexeme]),
+            ));
+        }
     }
 }
+
+/// Members of classes with names starting with an underscore are private.
+pub(crate) fn is_private(name: &str) -> bool {
+    name.starts_with('_')
+}
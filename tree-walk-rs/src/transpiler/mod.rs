@@ -0,0 +1,815 @@
+//! Backends generating code in other languages from the AST.
+//!
+//! The generated JavaScript keeps the structure of the Lox program with its
+//! functions, classes and closures, while the operations with semantics
+//! different from JavaScript ones are delegated to the small runtime included
+//! at the start of the output.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    rc::Rc,
+    str::FromStr,
+};
+
+use lox_frontend::{
+    Token, TokenType as TT,
+    ast::{Expr, FuncDeclaration, LiteralValue, Stmt},
+};
+
+const JS_RUNTIME: &str = include_str!("runtime.js");
+
+/// Names which can't be used as identifiers or properties in JavaScript, or
+/// which would shadow the built-ins used by the runtime.
+const JS_RESERVED: &[&str] = &[
+    "Array",
+    "Date",
+    "Error",
+    "Function",
+    "Infinity",
+    "Map",
+    "Math",
+    "NaN",
+    "Number",
+    "Object",
+    "RangeError",
+    "ReferenceError",
+    "String",
+    "Symbol",
+    "WeakMap",
+    "__proto__",
+    "arguments",
+    "await",
+    "case",
+    "catch",
+    "console",
+    "const",
+    "constructor",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "finally",
+    "function",
+    "globalThis",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "process",
+    "protected",
+    "public",
+    "static",
+    "switch",
+    "throw",
+    "try",
+    "typeof",
+    "undefined",
+    "void",
+    "with",
+    "yield",
+];
+
+/// Natives already defined as globals in the runtime.
+const NATIVES: &[&str] = &[
+    "HashMap",
+    "StringBuilder",
+    "at",
+    "charAt",
+    "classOf",
+    "clearTimer",
+    "className",
+    "clock",
+    "codePointAt",
+    "coroutine",
+    "docs",
+    "fields",
+    "filter",
+    "fromCodePoint",
+    "hasMethod",
+    "inspect",
+    "join",
+    "len",
+    "list",
+    "map",
+    "methods",
+    "push",
+    "reduce",
+    "resume",
+    "regexFindAll",
+    "regexMatch",
+    "regexReplace",
+    "runEventLoop",
+    "same",
+    "setInterval",
+    "setTimeout",
+    "sort",
+    "superclass",
+    "toFixed",
+    "weak",
+    "yield",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    JavaScript,
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "js" => Ok(Target::JavaScript),
+            _ => Err(format!("Unsupported transpile target '{s}'. Supported: js")),
+        }
+    }
+}
+
+/// Generates the code of the given target from the resolved statements.
+pub fn transpile(stmts: &[Stmt], target: Target) -> String {
+    match target {
+        Target::JavaScript => JsGenerator::default().program(stmts),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    Function,
+    Method,
+    Initializer,
+}
+
+/// Local scope of a block or a function.
+#[derive(Debug, Default)]
+struct Scope {
+    /// Identifiers of the variables declared in the scope so far.
+    variables: HashMap<Rc<str>, String>,
+    /// Names used in the scope before declaring them in it.
+    referenced: HashSet<Rc<str>>,
+}
+
+#[derive(Debug, Default)]
+struct JsGenerator {
+    output: String,
+    indent: usize,
+    /// Scopes enclosing the current statement, which is empty on top level.
+    scopes: Vec<Scope>,
+    /// Names declared on top level, since Lox allows redeclaring them.
+    globals: HashSet<Rc<str>>,
+    /// Names declared anywhere on top level, which are the only globals which
+    /// can be defined while running the program.
+    defined: HashSet<Rc<str>>,
+    /// Count of the variables renamed so far, keeping their names unique.
+    renamed: usize,
+    /// Names of the classes enclosing the current method.
+    classes: Vec<String>,
+    in_initializer: bool,
+}
+
+impl JsGenerator {
+    fn program(mut self, stmts: &[Stmt]) -> String {
+        self.globals = NATIVES.iter().map(|&name| name.into()).collect();
+        self.defined = self.globals.clone();
+        self.defined.extend(stmts.iter().filter_map(declared_name));
+
+        self.output.push_str(JS_RUNTIME);
+        self.output.push_str("\n$main(() => {\n");
+        self.indent += 1;
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        self.indent -= 1;
+        self.output.push_str("});\n");
+
+        self.output
+    }
+
+    fn line(&mut self, line: impl AsRef<str>) {
+        for _ in 0..self.indent {
+            self.output.push_str("  ");
+        }
+        self.output.push_str(line.as_ref());
+        self.output.push('\n');
+    }
+
+    /// Writes the statements indented in their own scope.
+    fn nested(&mut self, stmts: &[Stmt]) {
+        self.indent += 1;
+        self.scopes.push(Scope::default());
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        self.scopes.pop();
+        self.indent -= 1;
+    }
+
+    /// Writes the statement as the body of a control flow statement, which is
+    /// always wrapped in braces.
+    fn body(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block { statements, .. } => self.nested(statements),
+            stmt => self.nested(std::slice::from_ref(stmt)),
+        }
+    }
+
+    /// Checks if the name is declared again on top level, which must assign
+    /// the existing variable since JavaScript doesn't allow redeclaring it.
+    fn redeclares(&mut self, name: &Token) -> bool {
+        self.scopes.is_empty() && !self.globals.insert(name.lexeme.clone())
+    }
+
+    /// Returns the beginning of declaring the variable with the given name.
+    fn declaration(&mut self, name: &Token) -> String {
+        if self.redeclares(name) {
+            format!("{} = ", ident(&name.lexeme))
+        } else {
+            format!("let {} = ", self.declare(name))
+        }
+    }
+
+    /// Declares the variable in the current scope returning its identifier.
+    ///
+    /// Lox variables are only visible after their declaration, while the ones
+    /// of JavaScript are visible in the whole block. Variables are renamed
+    /// when their name is used in the scope before, which refers to another
+    /// variable in Lox.
+    fn declare(&mut self, name: &Token) -> String {
+        let Some(scope) = self.scopes.last_mut() else {
+            return ident(&name.lexeme);
+        };
+
+        let ident = if scope.referenced.contains(&name.lexeme) {
+            self.renamed += 1;
+            format!("{}${}", name.lexeme, self.renamed)
+        } else {
+            ident(&name.lexeme)
+        };
+        scope.variables.insert(name.lexeme.clone(), ident.clone());
+
+        ident
+    }
+
+    /// Returns the identifier of the variable the name refers to, or `None`
+    /// for globals which are never defined.
+    fn resolve(&mut self, name: &Token) -> Option<String> {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(ident) = scope.variables.get(&name.lexeme) {
+                return Some(ident.clone());
+            }
+            scope.referenced.insert(name.lexeme.clone());
+        }
+
+        self.defined
+            .contains(&name.lexeme)
+            .then(|| ident(&name.lexeme))
+    }
+
+    /// Returns the expression reading the variable.
+    fn variable(&mut self, name: &Token) -> String {
+        self.resolve(name).unwrap_or_else(|| {
+            format!(
+                "$undefinedVariable({}, {})",
+                string_literal(&name.lexeme),
+                name.line
+            )
+        })
+    }
+
+    /// Evaluates the objects and indices of the targets and the values into
+    /// arrays before assigning them, keeping the evaluation order of Lox.
+    fn multi_assign(&mut self, targets: &[Expr], values: &[Expr]) {
+        self.line("{");
+        self.indent += 1;
+        let mut objects = Vec::new();
+        for target in targets {
+            match target {
+                Expr::Get { object, .. } => objects.push(self.expr(object)),
+                Expr::Index { object, index, .. } => {
+                    objects.push(self.expr(object));
+                    objects.push(self.expr(index));
+                }
+                _ => {}
+            }
+        }
+        if !objects.is_empty() {
+            self.line(format!("const $objects = [{}];", objects.join(", ")));
+        }
+        let values: Vec<_> = values.iter().map(|value| self.expr(value)).collect();
+        self.line(format!("const $values = [{}];", values.join(", ")));
+
+        let mut objects = 0;
+        for (index, target) in targets.iter().enumerate() {
+            match target {
+                Expr::Variable { name, .. } => match self.resolve(name) {
+                    Some(ident) => self.line(format!("{ident} = $values[{index}];")),
+                    None => {
+                        let variable = self.variable(name);
+                        self.line(format!("{variable};"));
+                    }
+                },
+                Expr::Get { object, name, .. } => {
+                    self.line(format!(
+                        "$set($objects[{objects}], \"{}\", $values[{index}], {}, {});",
+                        property(&name.lexeme),
+                        string_literal(&object.to_source()),
+                        name.line
+                    ));
+                    objects += 1;
+                }
+                Expr::Index {
+                    object, bracket, ..
+                } => {
+                    self.line(format!(
+                        "$setIndex($objects[{objects}], $objects[{}], $values[{index}], {}, {});",
+                        objects + 1,
+                        string_literal(&object.to_source()),
+                        bracket.line
+                    ));
+                    objects += 2;
+                }
+                target => unreachable!("Invalid assignment target {target:?}"),
+            }
+        }
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression(expr) => {
+                let expr = self.expr(expr);
+                self.line(format!("{expr};"));
+            }
+            Stmt::Function(declaration) => {
+                let declare = self.declaration(&declaration.name);
+                self.function(&declare, declaration, FunctionKind::Function);
+                if let Some(doc) = &declaration.doc {
+                    let function = self.variable(&declaration.name);
+                    self.line(format!("$doc({function}, {});", string_literal(doc)));
+                }
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.expr(condition);
+                self.line(format!("if ($truthy({condition})) {{"));
+                self.body(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.line("} else {");
+                    self.body(else_branch);
+                }
+                self.line("}");
+            }
+            Stmt::Print { expr, .. } => {
+                let expr = self.expr(expr);
+                self.line(format!("$print({expr});"));
+            }
+            Stmt::Return { value_expr, .. } => {
+                let value = match value_expr {
+                    // Initializers always return the instance they are bound to.
+                    _ if self.in_initializer => String::from("this"),
+                    Some(expr) => self.expr(expr),
+                    None => String::from("null"),
+                };
+                self.line(format!("return {value};"));
+            }
+            Stmt::Break { .. } => self.line("break;"),
+            Stmt::Continue { .. } => self.line("continue;"),
+            // Programs are transpiled from a single script, which shares all
+            // its declarations.
+            Stmt::Export { declaration, .. } => self.stmt(declaration),
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                let value = match initializer {
+                    Some(expr) => self.expr(expr),
+                    None => String::from("null"),
+                };
+                let declare = self.declaration(name);
+                self.line(format!("{declare}{value};"));
+            }
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let condition = self.expr(condition);
+                self.line(format!("while ($truthy({condition})) {{"));
+                self.body(body);
+                self.line("}");
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => self.for_loop(
+                initializer.as_deref(),
+                condition.as_deref(),
+                increment.as_deref(),
+                body,
+            ),
+            Stmt::ForIn {
+                name,
+                keyword,
+                iterable,
+                body,
+                ..
+            } => {
+                // The iterable is in the scope of the variable for JavaScript,
+                // which renames the variable if the iterable refers to another
+                // one with the same name.
+                self.scopes.push(Scope::default());
+                let iterable = self.expr(iterable);
+                let variable = self.declare(name);
+                self.line(format!(
+                    "for (let {variable} of $iterate({iterable}, {})) {{",
+                    keyword.line
+                ));
+                self.body(body);
+                self.line("}");
+                self.scopes.pop();
+            }
+            Stmt::Block { statements, .. } => {
+                self.line("{");
+                self.nested(statements);
+                self.line("}");
+            }
+            Stmt::MultiAssign {
+                targets, values, ..
+            } => self.multi_assign(targets, values),
+            Stmt::Try { body, finally, .. } => {
+                self.line("try {");
+                self.body(body);
+                self.line("} finally {");
+                self.body(finally);
+                self.line("}");
+            }
+            Stmt::Class {
+                name,
+                super_class,
+                methods,
+                doc,
+                ..
+            } => self.class(name, super_class.as_ref(), methods, doc.as_deref()),
+            Stmt::Extend { class, methods, .. } => self.extend(class, methods),
+        }
+    }
+
+    /// Variables declared in the initializer of Lox loops are shared between
+    /// all iterations, unlike the ones of JavaScript loops. They are declared
+    /// in a block around the loop instead.
+    fn for_loop(
+        &mut self,
+        initializer: Option<&Stmt>,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &Stmt,
+    ) {
+        if let Some(initializer) = initializer {
+            self.line("{");
+            self.indent += 1;
+            self.scopes.push(Scope::default());
+            self.stmt(initializer);
+        }
+
+        let condition = condition
+            .map(|expr| format!("$truthy({})", self.expr(expr)))
+            .unwrap_or_default();
+        let increment = increment.map(|expr| self.expr(expr)).unwrap_or_default();
+        self.line(format!("for (; {condition}; {increment}) {{"));
+        self.body(body);
+        self.line("}");
+
+        if initializer.is_some() {
+            self.scopes.pop();
+            self.indent -= 1;
+            self.line("}");
+        }
+    }
+
+    fn class(
+        &mut self,
+        name: &Token,
+        super_class: Option<&Token>,
+        methods: &[Rc<FuncDeclaration>],
+        doc: Option<&str>,
+    ) {
+        let parent = match super_class {
+            Some(super_class) => {
+                format!(
+                    "$superclass({}, {})",
+                    self.variable(super_class),
+                    super_class.line
+                )
+            }
+            None => String::from("$Instance"),
+        };
+
+        // Class declarations can't be redeclared, unlike class expressions.
+        let redeclares = self.redeclares(name);
+        let (declare, class_name) = if redeclares {
+            let class_name = ident(&name.lexeme);
+            (format!("{class_name} = "), class_name)
+        } else {
+            (String::new(), self.declare(name))
+        };
+        self.line(format!("{declare}class {class_name} extends {parent} {{"));
+        self.indent += 1;
+        self.classes.push(class_name);
+
+        for (idx, method) in methods.iter().enumerate() {
+            if idx > 0 {
+                self.output.push('\n');
+            }
+            let kind = if &*method.name.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.function(&property(&method.name.lexeme), method, kind);
+        }
+
+        let class_name = self.classes.pop().expect("Class is pushed above");
+        self.indent -= 1;
+        self.line(if redeclares { "};" } else { "}" });
+
+        if let Some(doc) = doc {
+            self.line(format!("$doc({class_name}, {});", string_literal(doc)));
+        }
+        for method in methods {
+            if let Some(doc) = &method.doc {
+                self.line(format!(
+                    "$doc({class_name}.prototype.{}, {});",
+                    property(&method.name.lexeme),
+                    string_literal(doc)
+                ));
+            }
+        }
+    }
+
+    /// Copies the methods of an anonymous class over to the prototype of the
+    /// extended class.
+    fn extend(&mut self, class: &Token, methods: &[Rc<FuncDeclaration>]) {
+        let class_name = self.variable(class);
+        self.line(format!("$extend({class_name}, class extends $Instance {{"));
+        self.indent += 1;
+        for (idx, method) in methods.iter().enumerate() {
+            if idx > 0 {
+                self.output.push('\n');
+            }
+            self.function(&property(&method.name.lexeme), method, FunctionKind::Method);
+        }
+        self.indent -= 1;
+        self.line(format!("}}, {});", class.line));
+
+        for method in methods {
+            if let Some(doc) = &method.doc {
+                self.line(format!(
+                    "$doc({class_name}.prototype.{}, {});",
+                    property(&method.name.lexeme),
+                    string_literal(doc)
+                ));
+            }
+        }
+    }
+
+    /// Writes the function starting with the declaration of functions or the
+    /// name of methods.
+    fn function(&mut self, start: &str, declaration: &FuncDeclaration, kind: FunctionKind) {
+        let enclosing =
+            std::mem::replace(&mut self.in_initializer, kind == FunctionKind::Initializer);
+
+        self.scopes.push(Scope::default());
+        let params: Vec<_> = declaration
+            .params
+            .iter()
+            .map(|param| self.declare(param))
+            .collect();
+        let params = params.join(", ");
+        match kind {
+            // Functions are arrow functions, so they keep `this` of enclosing methods.
+            FunctionKind::Function => self.line(format!("{start}({params}) => {{")),
+            FunctionKind::Method | FunctionKind::Initializer => {
+                self.line(format!("{start}({params}) {{"));
+            }
+        }
+
+        self.indent += 1;
+        for stmt in declaration.body.iter() {
+            self.stmt(stmt);
+        }
+        let returns = matches!(declaration.body.last(), Some(Stmt::Return { .. }));
+        if kind == FunctionKind::Initializer && !returns {
+            self.line("return this;");
+        }
+        self.indent -= 1;
+        self.scopes.pop();
+
+        match kind {
+            FunctionKind::Function => self.line("};"),
+            FunctionKind::Method | FunctionKind::Initializer => self.line("}"),
+        }
+
+        self.in_initializer = enclosing;
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        match expr {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left = self.expr(left);
+                let right = self.expr(right);
+                let line = operator.line;
+                match operator.typ {
+                    TT::Plus => format!("$add({left}, {right}, {line})"),
+                    TT::Minus => format!("$sub({left}, {right}, {line})"),
+                    TT::Star => format!("$mul({left}, {right}, {line})"),
+                    TT::Slash => format!("$div({left}, {right}, {line})"),
+                    TT::TildeSlash => format!("$floorDiv({left}, {right}, {line})"),
+                    TT::Percent => format!("$mod({left}, {right}, {line})"),
+                    TT::Greater | TT::GreaterEqual | TT::Less | TT::LessEqual => {
+                        format!("$compare({left}, \"{}\", {right}, {line})", operator.lexeme)
+                    }
+                    TT::EqualEqual => format!("$eq({left}, {right}, {line})"),
+                    TT::BangEqual => format!("!$eq({left}, {right}, {line})"),
+                    ref typ => unreachable!("Invalid binary operator {typ:?}"),
+                }
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+                ..
+            } => {
+                let mut call = format!("$call({}, {}", self.expr(callee), paren.line);
+                for argument in arguments.iter() {
+                    let _ = write!(call, ", {}", self.expr(argument));
+                }
+                call.push(')');
+                call
+            }
+            Expr::Get { object, name, .. } => {
+                let source = object.to_source();
+                let object = self.expr(object);
+                let key = property(&name.lexeme);
+                match self.classes.last() {
+                    Some(class) if name.lexeme.starts_with('_') => {
+                        format!("$getPrivate({object}, \"{key}\", {class}, {})", name.line)
+                    }
+                    _ => format!(
+                        "$get({object}, \"{key}\", {}, {})",
+                        string_literal(&source),
+                        name.line
+                    ),
+                }
+            }
+            Expr::Grouping { expression, .. } => format!("({})", self.expr(expression)),
+            Expr::Literal { value, .. } => match value {
+                LiteralValue::Nil => String::from("null"),
+                LiteralValue::Boolean(val) => val.to_string(),
+                LiteralValue::Text(text) => string_literal(text),
+                LiteralValue::Number(num) => num.to_string(),
+            },
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left = self.expr(left);
+                let right = self.expr(right);
+                match operator.typ {
+                    TT::Or => format!("$or({left}, () => {right})"),
+                    TT::And => format!("$and({left}, () => {right})"),
+                    ref typ => unreachable!("Invalid logical operator {typ:?}"),
+                }
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+                ..
+            } => {
+                let source = object.to_source();
+                format!(
+                    "$set({}, \"{}\", {}, {}, {})",
+                    self.expr(object),
+                    property(&name.lexeme),
+                    self.expr(value),
+                    string_literal(&source),
+                    name.line
+                )
+            }
+            Expr::Index {
+                object,
+                bracket,
+                index,
+                ..
+            } => {
+                let source = object.to_source();
+                format!(
+                    "$index({}, {}, {}, {})",
+                    self.expr(object),
+                    self.expr(index),
+                    string_literal(&source),
+                    bracket.line
+                )
+            }
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+                ..
+            } => {
+                let source = object.to_source();
+                format!(
+                    "$setIndex({}, {}, {}, {}, {})",
+                    self.expr(object),
+                    self.expr(index),
+                    self.expr(value),
+                    string_literal(&source),
+                    bracket.line
+                )
+            }
+            Expr::Super { method, .. } => {
+                let key = property(&method.lexeme);
+                format!("$super(this, super.{key}, \"{key}\", {})", method.line)
+            }
+            Expr::This { .. } => String::from("this"),
+            Expr::Unary {
+                operator, right, ..
+            } => {
+                let right = self.expr(right);
+                match operator.typ {
+                    TT::Minus => format!("$negate({right}, {})", operator.line),
+                    TT::Bang => format!("!$truthy({right})"),
+                    ref typ => unreachable!("Invalid unary operator {typ:?}"),
+                }
+            }
+            Expr::Variable { name, .. } => self.variable(name),
+            Expr::Assign { name, value, .. } => {
+                let value = self.expr(value);
+                match self.resolve(name) {
+                    Some(ident) => format!("{ident} = {value}"),
+                    // The value is still evaluated before failing.
+                    None => format!("({value}, {})", self.variable(name)),
+                }
+            }
+        }
+    }
+}
+
+/// Renames the Lox identifiers clashing with JavaScript names by adding a
+/// suffix, which can't be part of Lox identifiers.
+fn ident(name: &str) -> String {
+    if JS_RESERVED.contains(&name) {
+        format!("{name}$")
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Property names are only renamed when they have special meaning on
+/// JavaScript objects.
+fn property(name: &str) -> String {
+    match name {
+        "constructor" | "__proto__" => format!("{name}$"),
+        _ => name.to_owned(),
+    }
+}
+
+/// Escapes the text as a JavaScript string literal.
+fn string_literal(text: &str) -> String {
+    // Debug formatting of Rust escapes the text with sequences which are
+    // valid in JavaScript too.
+    format!("{text:?}")
+}
+
+/// Returns the name of the global declared by the top-level statement.
+fn declared_name(stmt: &Stmt) -> Option<Rc<str>> {
+    match stmt {
+        Stmt::Var { name, .. } | Stmt::Class { name, .. } => Some(name.lexeme.clone()),
+        Stmt::Function(declaration) => Some(declaration.name.lexeme.clone()),
+        Stmt::Export { declaration, .. } => declared_name(declaration),
+        _ => None,
+    }
+}
@@ -0,0 +1,1117 @@
+// Runtime for Lox programs transpiled to JavaScript, implementing the parts of
+// Lox semantics which differ from JavaScript.
+"use strict";
+
+class $LoxError extends Error {
+  constructor(message, line) {
+    super(message);
+    this.line = line;
+  }
+}
+
+/** Base class of all Lox classes. */
+class $Instance {}
+
+/** Methods already bound to each instance, which are dropped once `extend` changes any class. */
+let $boundMethods = new WeakMap();
+
+/** Doc comments of the functions, methods and classes. */
+const $docs = new WeakMap();
+
+let clock = $native(function clock() {
+  return Math.floor(Date.now() / 1000);
+});
+
+let same = $native(function same(a, b) {
+  return a === b;
+});
+
+let docs = $native(function docs(value) {
+  return typeof value === "function" ? ($docs.get(value) ?? null) : null;
+});
+
+let fields = $native(function fields(value) {
+  if (!(value instanceof $Instance)) {
+    throw new $LoxError(`Only instances have fields, but got ${$describe(value)}.`);
+  }
+  return Object.keys(value)
+    .sort()
+    .map((name) => [$demangle(name), value[name]]);
+});
+
+let len = $native(function len(value) {
+  if (Array.isArray(value)) {
+    return value.length;
+  }
+  if (typeof value === "string") {
+    return [...value].length;
+  }
+  throw new $LoxError(`Can only get the length of lists and strings, but got ${$describe(value)}.`);
+});
+
+let list = $native(function list() {
+  return [];
+});
+
+let push = $native(function push(list, value) {
+  if (!Array.isArray(list)) {
+    throw new $LoxError(`Can only push to lists, but got ${$describe(list)}.`);
+  }
+  list.push(value);
+  return list;
+});
+
+function $checkIndex(kind, index, length, line) {
+  if (typeof index !== "number") {
+    throw new $LoxError(`${kind} index must be a number, but got ${$describe(index)}.`, line);
+  }
+  if (!Number.isInteger(index) || index < 0 || index >= length) {
+    throw new $LoxError(`${kind} index ${$number(index)} is out of bounds for length ${length}.`, line);
+  }
+}
+
+let at = $native(function at(list, index) {
+  if (!Array.isArray(list)) {
+    throw new $LoxError(`Can only index into lists, but got ${$describe(list)}.`);
+  }
+  $checkIndex("List", index, list.length);
+  return list[index];
+});
+
+function $nthChar(text, index) {
+  if (typeof text !== "string") {
+    throw new $LoxError(`Can only index into strings, but got ${$describe(text)}.`);
+  }
+  let chars = [...text];
+  $checkIndex("String", index, chars.length);
+  return chars[index];
+}
+
+let charAt = $native(function charAt(text, index) {
+  return $nthChar(text, index);
+});
+
+let codePointAt = $native(function codePointAt(text, index) {
+  return $nthChar(text, index).codePointAt(0);
+});
+
+let fromCodePoint = $native(function fromCodePoint(code) {
+  let valid = typeof code === "number" && Number.isInteger(code) && code >= 0 && code <= 0x10ffff
+    && !(code >= 0xd800 && code <= 0xdfff);
+  if (!valid) {
+    throw new $LoxError(`Code point must be a Unicode scalar value, but got ${$describe(code)}.`);
+  }
+  return String.fromCodePoint(code);
+});
+
+let classOf = $native(function classOf(value) {
+  if (!(value instanceof $Instance)) {
+    throw new $LoxError(`Only instances have classes, but got ${$describe(value)}.`);
+  }
+  return value.constructor;
+});
+
+let className = $native(function className(cls) {
+  return $demangle($expectClass(cls).name);
+});
+
+let methods = $native(function methods(cls) {
+  return [...$methods($expectClass(cls).prototype).keys()].map($demangle).sort();
+});
+
+let superclass = $native(function superclass(cls) {
+  const parent = Object.getPrototypeOf($expectClass(cls));
+  return parent === $Instance ? null : parent;
+});
+
+let hasMethod = $native(function hasMethod(value, name) {
+  const cls = value instanceof $Instance ? value.constructor : $expectClass(value);
+  if (typeof name !== "string") {
+    throw new $LoxError(`Method name must be a string, but got ${$describe(name)}.`);
+  }
+  for (let proto = cls.prototype; proto !== $Instance.prototype; proto = Object.getPrototypeOf(proto)) {
+    if (Object.getOwnPropertyNames(proto).some((method) => method !== "constructor" && $demangle(method) === name)) {
+      return true;
+    }
+  }
+  return false;
+});
+
+/** Handle returned by `weak`, which doesn't keep the instance alive. */
+class Weak extends $Instance {
+  #target;
+
+  constructor(target) {
+    super();
+    this.#target = target === undefined ? undefined : new WeakRef(target);
+  }
+
+  get() {
+    return this.#target?.deref() ?? null;
+  }
+}
+
+let weak = $native(function weak(value) {
+  if (!(value instanceof $Instance)) {
+    throw new $LoxError(`Only instances can be referenced weakly, but got ${$describe(value)}.`);
+  }
+  return new Weak(value);
+});
+
+let toFixed = $native(function toFixed(num, digits) {
+  if (typeof num !== "number") {
+    throw new $LoxError(`Can only format numbers, but got ${$describe(num)}.`);
+  }
+  if (typeof digits !== "number" || !Number.isInteger(digits) || digits < 0 || digits > 100) {
+    throw new $LoxError(`Digits must be a whole number between 0 and 100, but got ${$describe(digits)}.`);
+  }
+  return Number.isFinite(num) ? num.toFixed(digits) : $number(num);
+});
+
+function $listElements(value, action) {
+  if (!Array.isArray(value)) {
+    throw new $LoxError(`Can only ${action} lists, but got ${$describe(value)}.`);
+  }
+  return [...value];
+}
+
+let map = $native(function map(list, fn) {
+  return $listElements(list, "map").map((element) => $call(fn, undefined, element));
+});
+
+let filter = $native(function filter(list, fn) {
+  return $listElements(list, "filter").filter((element) => $truthy($call(fn, undefined, element)));
+});
+
+let reduce = $native(function reduce(list, fn, init) {
+  return $listElements(list, "reduce").reduce((acc, element) => $call(fn, undefined, acc, element), init);
+});
+
+let sort = $native(function sort(list, comparator) {
+  // Array.prototype.sort is stable like the merge sort of the interpreter,
+  // but may compare the elements in another order.
+  return $listElements(list, "sort").sort((a, b) => {
+    const order = $call(comparator, undefined, a, b);
+    if (typeof order !== "number") {
+      throw new $LoxError(`Comparator must return a number, but got ${$describe(order)}.`);
+    }
+    return order;
+  });
+});
+
+let join = $native(function join(list, separator) {
+  const elements = $listElements(list, "join");
+  if (typeof separator !== "string") {
+    throw new $LoxError(`Separator must be a string, but got ${$describe(separator)}.`);
+  }
+  return elements.map($str).join(separator);
+});
+
+/** Native class building strings from many parts without copying them on each append. */
+class StringBuilder extends $Instance {
+  #parts;
+
+  init() {
+    this.#parts = [];
+  }
+
+  append(value) {
+    this.#uninitialized();
+    this.#parts.push($str(value));
+    return this;
+  }
+
+  toString() {
+    this.#uninitialized();
+    return this.#parts.join("");
+  }
+
+  #uninitialized() {
+    if (this.#parts === undefined) {
+      throw new $LoxError("StringBuilder isn't initialized.");
+    }
+  }
+}
+
+/** Identities of the keys of `HashMap` compared by identity. */
+const $keyIds = new WeakMap();
+let $nextKeyId = 0;
+
+class HashMap extends $Instance {
+  #entries;
+  #buckets;
+  #revision;
+
+  init() {
+    this.#entries = [];
+    this.#buckets = new Map();
+    this.#revision = 0;
+  }
+
+  get(key) {
+    const index = this.#find(key).index;
+    return index === undefined ? null : this.#entries[index].value;
+  }
+
+  set(key, value) {
+    const { bucket, index } = this.#find(key);
+    this.#revision++;
+    if (index !== undefined) {
+      this.#entries[index].value = value;
+    } else {
+      this.#entries.push({ bucket, key, value });
+      this.#indices(bucket).push(this.#entries.length - 1);
+    }
+  }
+
+  has(key) {
+    return this.#find(key).index !== undefined;
+  }
+
+  remove(key) {
+    const index = this.#find(key).index;
+    if (index === undefined) {
+      return false;
+    }
+    this.#revision++;
+    this.#entries.splice(index, 1);
+    this.#buckets.clear();
+    this.#entries.forEach((entry, index) => this.#indices(entry.bucket).push(index));
+    return true;
+  }
+
+  size() {
+    this.#uninitialized();
+    return this.#entries.length;
+  }
+
+  keys() {
+    this.#uninitialized();
+    return this.#entries.map((entry) => entry.key);
+  }
+
+  values() {
+    this.#uninitialized();
+    return this.#entries.map((entry) => entry.value);
+  }
+
+  iterator() {
+    return this.keys();
+  }
+
+  #indices(bucket) {
+    if (!this.#buckets.has(bucket)) {
+      this.#buckets.set(bucket, []);
+    }
+    return this.#buckets.get(bucket);
+  }
+
+  #find(key) {
+    this.#uninitialized();
+    const bucket = $bucket(key);
+    const revision = this.#revision;
+    const candidates = (this.#buckets.get(bucket) ?? []).map((index) => [index, this.#entries[index].key]);
+
+    let found;
+    for (const [index, candidate] of candidates) {
+      if ($keysEqual(key, candidate)) {
+        found = index;
+        break;
+      }
+    }
+    if (this.#revision !== revision) {
+      throw new $LoxError("HashMap was changed while comparing its keys.");
+    }
+    return { bucket, index: found };
+  }
+
+  #uninitialized() {
+    if (this.#entries === undefined) {
+      throw new $LoxError("HashMap isn't initialized.");
+    }
+  }
+}
+
+/** The `hash()` and `equals()` methods of instances compared by their values. */
+function $valueMethods(key) {
+  if (!(key instanceof $Instance)) {
+    return undefined;
+  }
+  const hash = $method(key, "hash");
+  const equals = $method(key, "equals");
+  return hash === undefined || equals === undefined ? undefined : { hash, equals };
+}
+
+/** Keys of the buckets in `HashMap`, which are equal for equal keys. */
+function $bucket(key) {
+  if (key === null) {
+    return "nil";
+  }
+  switch (typeof key) {
+    case "boolean":
+      return `b${key}`;
+    case "number":
+      return `n${key}`;
+    case "string":
+      return `s${key}`;
+  }
+
+  const methods = $valueMethods(key);
+  if (methods !== undefined) {
+    const hash = $call(methods.hash, undefined);
+    if (typeof hash !== "number") {
+      throw new $LoxError(`Method 'hash' must return a number, but got ${$describe(hash)}.`);
+    }
+    return `h${hash}`;
+  }
+  if (!$keyIds.has(key)) {
+    $keyIds.set(key, $nextKeyId++);
+  }
+  return `o${$keyIds.get(key)}`;
+}
+
+function $keysEqual(key, other) {
+  const methods = $valueMethods(key);
+  if (methods !== undefined && other instanceof $Instance) {
+    return $truthy($call(methods.equals, undefined, other));
+  }
+  return key === other;
+}
+
+/** Timers waiting for `runEventLoop()`, which runs them synchronously like the interpreter. */
+const $timers = { pending: [], nextId: 1, scheduled: 0, running: false };
+
+function $schedule(callback, delay, repeats) {
+  if (typeof callback !== "function") {
+    throw new $LoxError(`Timers can only call functions and classes, but got ${$describe(callback)}.`);
+  }
+  const arity = $isClass(callback) ? $classArity(callback) : callback.length;
+  if (arity !== 0) {
+    throw new $LoxError(
+      `Timers call their callbacks without arguments, but ${$describe(callback)} takes ${arity}.`,
+    );
+  }
+  if (typeof delay !== "number" || Number.isNaN(delay)) {
+    throw new $LoxError(`Timer delay must be a number of milliseconds, but got ${$describe(delay)}.`);
+  }
+  if (!Number.isFinite(delay) || delay < 0) {
+    throw new $LoxError(
+      `Timer delay must be a finite number of milliseconds that isn't negative, but got ${$str(delay)}.`,
+    );
+  }
+  // Intervals wait at least one millisecond so the loop can't get stuck on them.
+  delay = repeats ? Math.max(delay, 1) : Math.max(delay, 0);
+
+  const id = $timers.nextId++;
+  $pushTimer(id, performance.now() + delay, repeats ? delay : undefined, callback);
+  return id;
+}
+
+function $pushTimer(id, due, interval, callback) {
+  $timers.pending.push({ id, due, order: $timers.scheduled++, interval, callback });
+}
+
+let setTimeout = $native(function setTimeout(callback, delay) {
+  return $schedule(callback, delay, false);
+});
+
+let setInterval = $native(function setInterval(callback, delay) {
+  return $schedule(callback, delay, true);
+});
+
+let clearTimer = $native(function clearTimer(id) {
+  if (typeof id !== "number") {
+    throw new $LoxError(`Timer ID must be a number, but got ${$describe(id)}.`);
+  }
+  const count = $timers.pending.length;
+  $timers.pending = $timers.pending.filter((timer) => timer.id !== id);
+  return $timers.pending.length < count;
+});
+
+let runEventLoop = $native(function runEventLoop() {
+  if ($timers.running) {
+    throw new $LoxError("Event loop is already running.");
+  }
+  $timers.running = true;
+  try {
+    while ($timers.pending.length > 0) {
+      const timer = $timers.pending.reduce((next, timer) =>
+        timer.due < next.due || (timer.due === next.due && timer.order < next.order) ? timer : next,
+      );
+      $timers.pending.splice($timers.pending.indexOf(timer), 1);
+
+      const wait = timer.due - performance.now();
+      if (wait > 0) {
+        // Blocks the thread like the interpreter instead of yielding to the event loop of Node.js.
+        Atomics.wait(new Int32Array(new SharedArrayBuffer(4)), 0, 0, wait);
+      }
+      if (timer.interval !== undefined) {
+        $pushTimer(timer.id, timer.due + timer.interval, timer.interval, timer.callback);
+      }
+      $call(timer.callback, undefined);
+    }
+  } finally {
+    $timers.running = false;
+  }
+  return null;
+});
+
+// Plain JavaScript calls can't be suspended in the middle, which coroutines need.
+function $noCoroutines() {
+  throw new $LoxError("Coroutines aren't supported in transpiled programs.");
+}
+
+let coroutine = $native(function coroutine(fn) {
+  $noCoroutines();
+});
+
+let resume = $native(function resume(co, value) {
+  $noCoroutines();
+});
+
+let yield$ = $native(function yield$(value) {
+  $noCoroutines();
+});
+
+function $regex(pattern, flags) {
+  if (typeof pattern !== "string") {
+    throw new $LoxError(`Regex pattern must be a string, but got ${$describe(pattern)}.`);
+  }
+  try {
+    return new RegExp(pattern, flags);
+  } catch (error) {
+    throw new $LoxError(`Invalid regex pattern "${pattern}": ${error.message}.`);
+  }
+}
+
+function $regexText(text) {
+  if (typeof text !== "string") {
+    throw new $LoxError(`Can only match regex patterns against strings, but got ${$describe(text)}.`);
+  }
+  return text;
+}
+
+function $captures(match) {
+  return Array.from(match, (group) => group ?? null);
+}
+
+let regexFindAll = $native(function regexFindAll(pattern, text) {
+  let regex = $regex(pattern, "gu");
+  return Array.from($regexText(text).matchAll(regex), $captures);
+});
+
+let regexMatch = $native(function regexMatch(pattern, text) {
+  let match = $regex(pattern, "u").exec($regexText(text));
+  return match === null ? null : $captures(match);
+});
+
+let regexReplace = $native(function regexReplace(pattern, text, replacement) {
+  let regex = $regex(pattern, "gu");
+  // Named groups are written like `${name}` in Lox and `$<name>` in JavaScript.
+  let jsReplacement = $regexText(replacement).replace(/\$\{(\w+)\}/g, "$$<$1>");
+  return $regexText(text).replace(regex, jsReplacement);
+});
+
+let inspect = $native(function inspect(value) {
+  const lines = [];
+  if ($isClass(value)) {
+    const parent = Object.getPrototypeOf(value);
+    const name = $demangle(value.name);
+    lines.push(parent === $Instance ? `class ${name}` : `class ${name} < ${$demangle(parent.name)}`);
+    lines.push(`  arity: ${$classArity(value)}`);
+    $pushMethods(lines, value.prototype);
+  } else if (typeof value === "function") {
+    lines.push(value.$native ? `native fun ${value.name}` : `fun ${$signature(value.$method ?? value)}`);
+    lines.push(`  arity: ${value.length}`);
+  } else if (value instanceof $Instance) {
+    lines.push($str(value));
+    for (const name of Object.keys(value).sort()) {
+      lines.push(`  ${name}: ${$inspectValue(value[name])}`);
+    }
+    $pushMethods(lines, Object.getPrototypeOf(value));
+  } else if (value === null) {
+    lines.push("nil");
+  } else {
+    lines.push(`${$typeName(value)} ${$inspectValue(value)}`);
+  }
+  return lines.join("\n");
+});
+
+function $expectClass(value) {
+  if (!$isClass(value)) {
+    throw new $LoxError(`Expected a class, but got ${$describe(value)}.`);
+  }
+  return value;
+}
+
+function $native(fn) {
+  fn.$native = true;
+  return fn;
+}
+
+function $doc(value, text) {
+  $docs.set(value, text);
+}
+
+function $isClass(value) {
+  return typeof value === "function" && value.prototype instanceof $Instance;
+}
+
+/** Removes the suffix added to Lox names which have been renamed. */
+function $demangle(name) {
+  return name.replace(/\$\d*$/, "");
+}
+
+function $truthy(value) {
+  return value !== null && value !== false;
+}
+
+function $print(value) {
+  console.log($str(value));
+}
+
+function $str(value) {
+  if (value === null) {
+    return "Nil";
+  }
+  switch (typeof value) {
+    case "number":
+      return $number(value);
+    case "function":
+      if ($isClass(value)) {
+        return $demangle(value.name);
+      }
+      if (value.$native) {
+        return "<native fn>";
+      }
+      return `<fn ${$demangle(value.name)}>`;
+    case "object":
+      if (Array.isArray(value)) {
+        return $list(value);
+      }
+      return `${$demangle(value.constructor.name)} instance`;
+    default:
+      return String(value);
+  }
+}
+
+/** Writes the elements of the list quoting strings, like `[1, "a"]`. */
+function $list(list, path = []) {
+  if (path.includes(list)) {
+    return "[...]";
+  }
+
+  path.push(list);
+  const text = list.map((value) => {
+    if (typeof value === "string") {
+      return JSON.stringify(value);
+    }
+    return Array.isArray(value) ? $list(value, path) : $str(value);
+  });
+  path.pop();
+  return `[${text.join(", ")}]`;
+}
+
+/** Formats numbers the same way jlox does. */
+function $number(num) {
+  if (Number.isNaN(num)) {
+    return "NaN";
+  }
+  if (!Number.isFinite(num)) {
+    return num > 0 ? "Infinity" : "-Infinity";
+  }
+
+  const abs = Math.abs(num);
+  if (abs === 0) {
+    return Object.is(num, -0) ? "-0" : "0";
+  }
+  if (abs >= 1e-3 && abs < 1e7) {
+    return String(num);
+  }
+
+  const [mantissa, exponent] = num.toExponential().split("e");
+  const fraction = mantissa.includes(".") ? mantissa : `${mantissa}.0`;
+  return `${fraction}E${Number(exponent)}`;
+}
+
+function $typeName(value) {
+  if (value === null) {
+    return "nil";
+  }
+  switch (typeof value) {
+    case "function":
+      return $isClass(value) ? "class" : "function";
+    case "object":
+      return Array.isArray(value) ? "list" : "instance";
+    default:
+      return typeof value;
+  }
+}
+
+/** Renders the value with the fields of the instances like `LoxValue::pretty()`. */
+function $pretty(value, depthLimit, depth = 0, path = []) {
+  if (typeof value === "string") {
+    return depth === 0 ? value : JSON.stringify(value);
+  }
+  if (Array.isArray(value)) {
+    return $prettyList(value, depthLimit, depth, path);
+  }
+  if (!(value instanceof $Instance)) {
+    return $str(value);
+  }
+
+  const name = $demangle(value.constructor.name);
+  if (path.includes(value)) {
+    return `<cycle ${name} instance>`;
+  }
+  const fields = Object.keys(value).sort();
+  if (fields.length === 0) {
+    return `${name} {}`;
+  }
+  if (depth >= depthLimit) {
+    return `${name} { ... }`;
+  }
+
+  path.push(value);
+  const text = fields.map((field) => `${field}: ${$pretty(value[field], depthLimit, depth + 1, path)}`);
+  path.pop();
+  return `${name} { ${text.join(", ")} }`;
+}
+
+function $prettyList(list, depthLimit, depth, path) {
+  if (path.includes(list)) {
+    return "<cycle list>";
+  }
+  if (list.length === 0) {
+    return "[]";
+  }
+  if (depth >= depthLimit) {
+    return "[...]";
+  }
+
+  path.push(list);
+  const text = list.map((value) => $pretty(value, depthLimit, depth + 1, path));
+  path.pop();
+  return `[${text.join(", ")}]`;
+}
+
+/** Renders the value for `inspect`, quoting strings to tell them apart from other values. */
+function $inspectValue(value) {
+  return typeof value === "string" ? JSON.stringify(value) : $pretty(value, 4);
+}
+
+/** Lists the methods of the class with the public ones it inherits for `inspect`. */
+function $pushMethods(lines, proto) {
+  const methods = $methods(proto);
+  if (methods.size > 0) {
+    const names = [...methods.keys()].sort();
+    lines.push(`  methods: ${names.map((name) => $signature(methods.get(name))).join(", ")}`);
+  }
+}
+
+/** Methods of the class with the public ones it inherits by their names. */
+function $methods(proto) {
+  const methods = new Map();
+  for (let own = true; proto !== $Instance.prototype; proto = Object.getPrototypeOf(proto), own = false) {
+    for (const name of Object.getOwnPropertyNames(proto)) {
+      if (name !== "constructor" && !methods.has(name) && (own || !name.startsWith("_"))) {
+        methods.set(name, proto[name]);
+      }
+    }
+  }
+  return methods;
+}
+
+/** Name of the function followed by its parameters, like `add(a, b)`. */
+function $signature(fn) {
+  const params = fn.toString().match(/^[^(]*\(([^)]*)\)/)?.[1] ?? "";
+  const names = params.split(",").map((param) => $demangle(param.trim()));
+  return `${$demangle(fn.name)}(${names.filter((name) => name !== "").join(", ")})`;
+}
+
+/** Describes the value with its type for error messages. */
+function $describe(value) {
+  if (value === null) {
+    return "nil";
+  }
+  if (typeof value === "string") {
+    return `the string "${value}"`;
+  }
+  if (value instanceof $Instance) {
+    return `the ${$str(value)}`;
+  }
+  return `the ${$typeName(value)} ${$str(value)}`;
+}
+
+function $operandsError(operator, expected, side, operand, line) {
+  return new $LoxError(
+    `Operands of '${operator}' must be ${expected}, but the ${side} operand was ${$describe(operand)}.`,
+    line,
+  );
+}
+
+function $checkNumbers(left, operator, right, line) {
+  if (typeof left !== "number") {
+    throw $operandsError(operator, "numbers", "left", left, line);
+  }
+  if (typeof right !== "number") {
+    throw $operandsError(operator, "numbers", "right", right, line);
+  }
+}
+
+function $negate(value, line) {
+  if (typeof value !== "number") {
+    throw new $LoxError(`Operand of '-' must be a number, but it was ${$describe(value)}.`, line);
+  }
+  return -value;
+}
+
+function $add(left, right, line) {
+  if (typeof left === "number" && typeof right === "number") {
+    return left + right;
+  }
+  if (typeof left === "string" && typeof right === "string") {
+    return left + right;
+  }
+
+  // Right is the wrong one when left is valid for addition.
+  const leftValid = typeof left === "number" || typeof left === "string";
+  const [side, operand] = leftValid ? ["right", right] : ["left", left];
+  throw $operandsError("+", "two numbers or two strings", side, operand, line);
+}
+
+function $sub(left, right, line) {
+  $checkNumbers(left, "-", right, line);
+  return left - right;
+}
+
+function $mul(left, right, line) {
+  $checkNumbers(left, "*", right, line);
+  return left * right;
+}
+
+function $div(left, right, line) {
+  $checkNumbers(left, "/", right, line);
+  if (right === 0) {
+    throw new $LoxError("Division by zero.", line);
+  }
+  return left / right;
+}
+
+/** Integer division rounding the quotient down, written `~/` in Lox. */
+function $floorDiv(left, right, line) {
+  $checkNumbers(left, "~/", right, line);
+  if (right === 0) {
+    throw new $LoxError("Division by zero.", line);
+  }
+  return Math.floor(left / right);
+}
+
+/** Remainder of the floored division, which takes the sign of the divisor. */
+function $mod(left, right, line) {
+  $checkNumbers(left, "%", right, line);
+  if (right === 0) {
+    throw new $LoxError("Division by zero.", line);
+  }
+  const rem = left % right;
+  return rem !== 0 && rem < 0 !== right < 0 ? rem + right : rem;
+}
+
+/** Compares the values calling the `compareTo` method of instances. */
+function $compare(left, operator, right, line) {
+  const compareTo = $operatorHook(left, "compareTo");
+  if (compareTo !== undefined) {
+    const order = $call(compareTo, line, right);
+    if (typeof order !== "number") {
+      throw new $LoxError(
+        `'compareTo' must return a number, but it returned ${$describe(order)}.`,
+        line,
+      );
+    }
+    return $order(order, operator, 0);
+  }
+
+  $checkNumbers(left, operator, right, line);
+  return $order(left, operator, right);
+}
+
+function $order(left, operator, right) {
+  switch (operator) {
+    case "<":
+      return left < right;
+    case "<=":
+      return left <= right;
+    case ">":
+      return left > right;
+    case ">=":
+      return left >= right;
+  }
+}
+
+/** Checks the equality of the values calling the `equals` method of instances. */
+function $eq(left, right, line) {
+  const equals = $operatorHook(left, "equals");
+  if (equals !== undefined) {
+    return $truthy($call(equals, line, right));
+  }
+  return left === right;
+}
+
+function $operatorHook(value, name) {
+  return value instanceof $Instance ? $method(value, name) : undefined;
+}
+
+/** Evaluates to the left value when it's truthy without evaluating the right one. */
+function $or(left, right) {
+  return $truthy(left) ? left : right();
+}
+
+/** Evaluates to the left value when it's falsy without evaluating the right one. */
+function $and(left, right) {
+  return $truthy(left) ? right() : left;
+}
+
+function $call(callee, line, ...args) {
+  if (typeof callee !== "function") {
+    throw new $LoxError("Can only call functions and classes.", line);
+  }
+
+  const isClass = $isClass(callee);
+  const arity = isClass ? $classArity(callee) : callee.length;
+  if (arity !== args.length) {
+    throw new $LoxError(`Expected ${arity} arguments but got ${args.length}.`, line);
+  }
+
+  if (isClass) {
+    const instance = new callee();
+    const init = $method(instance, "init");
+    if (init !== undefined) {
+      init(...args);
+    }
+    return instance;
+  }
+
+  let result;
+  try {
+    result = callee(...args);
+  } catch (error) {
+    // Natives throw their errors without knowing the line of the call.
+    if (error instanceof $LoxError && error.line === undefined) {
+      error.line = line;
+    }
+    throw error;
+  }
+  return result === undefined ? null : result;
+}
+
+function $classArity(cls) {
+  const init = $findMethod(cls.prototype, "init");
+  return init === undefined ? 0 : init.length;
+}
+
+/** Finds the method in the class hierarchy, ignoring JavaScript built-ins. */
+function $findMethod(proto, name) {
+  for (; proto !== $Instance.prototype; proto = Object.getPrototypeOf(proto)) {
+    if (Object.hasOwn(proto, name)) {
+      return proto[name];
+    }
+  }
+  return undefined;
+}
+
+/** Returns the method of the class with the given name bound to the instance. */
+function $method(instance, name) {
+  let bound = $boundMethods.get(instance);
+  if (bound === undefined) {
+    bound = new Map();
+    $boundMethods.set(instance, bound);
+  }
+
+  if (!bound.has(name)) {
+    const method = $findMethod(Object.getPrototypeOf(instance), name);
+    if (method === undefined) {
+      return undefined;
+    }
+    bound.set(name, $bind(instance, method));
+  }
+
+  return bound.get(name);
+}
+
+/** Values of for-in loops over lists, strings and instances implementing the iterator protocol. */
+function* $iterate(iterable, line) {
+  if (Array.isArray(iterable)) {
+    for (let index = 0; index < iterable.length; index++) {
+      yield iterable[index];
+    }
+    return;
+  }
+  if (typeof iterable === "string") {
+    yield* iterable;
+    return;
+  }
+
+  const iteratorMethod = iterable instanceof $Instance ? $method(iterable, "iterator") : undefined;
+  if (iteratorMethod === undefined) {
+    throw new $LoxError(
+      `Can only iterate over lists, strings and instances with an 'iterator' method, but got ${$describe(iterable)}.`,
+      line,
+    );
+  }
+  const iterator = $call(iteratorMethod, line);
+  // Iterators can be lists and strings too, like the keys of maps.
+  if (Array.isArray(iterator) || typeof iterator === "string") {
+    yield* $iterate(iterator, line);
+    return;
+  }
+  const protocolMethod = (name) => {
+    const method = iterator instanceof $Instance ? $method(iterator, name) : undefined;
+    if (method === undefined) {
+      throw new $LoxError(`Iterators must have a '${name}' method, but got ${$describe(iterator)}.`, line);
+    }
+    return method;
+  };
+  while ($truthy($call(protocolMethod("hasNext"), line))) {
+    yield $call(protocolMethod("next"), line);
+  }
+}
+
+function $bind(instance, method) {
+  const bound = method.bind(instance);
+  Object.defineProperty(bound, "name", { value: method.name });
+  bound.$method = method;
+  if ($docs.has(method)) {
+    $docs.set(bound, $docs.get(method));
+  }
+  return bound;
+}
+
+function $notInstanceError(message, source, value, line) {
+  return new $LoxError(`${message}, but '${source}' was ${$describe(value)}.`, line);
+}
+
+function $get(object, name, source, line) {
+  if (!(object instanceof $Instance)) {
+    throw $notInstanceError("Only instances have properties", source, object, line);
+  }
+  if (Object.hasOwn(object, name)) {
+    return object[name];
+  }
+
+  const method = $method(object, name);
+  if (method === undefined) {
+    throw new $LoxError(`Undefined property '${$demangle(name)}'.`, line);
+  }
+  return method;
+}
+
+/**
+ * Gets the private member accessed from the methods of the given class.
+ * Private methods aren't inherited, so only the methods of the class itself
+ * are considered.
+ */
+function $getPrivate(object, name, cls, line) {
+  if (Object.hasOwn(object, name)) {
+    return object[name];
+  }
+  if (Object.hasOwn(cls.prototype, name)) {
+    return $bind(object, cls.prototype[name]);
+  }
+  throw new $LoxError(`Undefined property '${$demangle(name)}'.`, line);
+}
+
+function $set(object, name, value, source, line) {
+  if (!(object instanceof $Instance)) {
+    throw $notInstanceError("Only instances have fields", source, object, line);
+  }
+  object[name] = value;
+  return value;
+}
+
+/** Gets the element at the index of lists and strings, or calls the `get` method of instances. */
+function $index(object, index, source, line) {
+  if (Array.isArray(object)) {
+    $checkIndex("List", index, object.length, line);
+    return object[index];
+  }
+  if (typeof object === "string") {
+    const chars = [...object];
+    $checkIndex("String", index, chars.length, line);
+    return chars[index];
+  }
+  const get = object instanceof $Instance ? $method(object, "get") : undefined;
+  if (get === undefined) {
+    throw $notInstanceError(
+      "Only lists, strings and instances with a 'get' method can be indexed", source, object, line,
+    );
+  }
+  return $call(get, line, index);
+}
+
+/** Sets the element at the index of lists, or calls the `set` method of instances. */
+function $setIndex(object, index, value, source, line) {
+  if (Array.isArray(object)) {
+    $checkIndex("List", index, object.length, line);
+    object[index] = value;
+    return value;
+  }
+  const set = object instanceof $Instance ? $method(object, "set") : undefined;
+  if (set === undefined) {
+    throw $notInstanceError(
+      "Only lists and instances with a 'set' method can be assigned by index", source, object, line,
+    );
+  }
+  $call(set, line, index, value);
+  return value;
+}
+
+/** Binds the method of the superclass found via `super`. */
+function $super(instance, method, name, line) {
+  if (typeof method !== "function" || method === Object.prototype[name]) {
+    throw new $LoxError(`Undefined property '${$demangle(name)}'.`, line);
+  }
+  return $bind(instance, method);
+}
+
+/** Adds the methods of the extension to the class, replacing the ones with the same names. */
+function $extend(cls, extension, line) {
+  if (!$isClass(cls)) {
+    throw new $LoxError("Can only extend classes.", line);
+  }
+  for (const name of Object.getOwnPropertyNames(extension.prototype)) {
+    if (name !== "constructor") {
+      Object.defineProperty(cls.prototype, name, Object.getOwnPropertyDescriptor(extension.prototype, name));
+    }
+  }
+  $boundMethods = new WeakMap();
+}
+
+function $undefinedVariable(name, line) {
+  throw new $LoxError(`Undefined variable '${name}'.`, line);
+}
+
+function $superclass(value, line) {
+  if (!$isClass(value)) {
+    throw new $LoxError("Superclass must be a class.", line);
+  }
+  return value;
+}
+
+/** Runs the program reporting runtime errors like the interpreter. */
+function $main(program) {
+  try {
+    program();
+  } catch (error) {
+    if (error instanceof $LoxError) {
+      console.error(`${error.message}\n[line ${error.line}]`);
+    } else if (error instanceof RangeError) {
+      console.error("Stack overflow.");
+    } else if (error instanceof ReferenceError) {
+      const name = error.message.match(/'?(\w+)'? is not defined|'(\w+)' before/);
+      console.error(`Undefined variable '${$demangle(name?.[1] ?? name?.[2] ?? "")}'.`);
+    } else {
+      throw error;
+    }
+    process.exitCode = 70;
+  }
+}
@@ -0,0 +1,117 @@
+//! Interpreter confined to its own thread, which can be shared between the
+//! threads of a server.
+//!
+//! The values of the interpreter use `Rc<RefCell<...>>` and can't leave the
+//! thread they are created on, so the worker only exchanges the sources and
+//! their captured outputs with it.
+
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread::JoinHandle,
+};
+
+use lox_frontend::engine::{Diagnostics, Engine, Output};
+
+use crate::{CancelHandle, Interpreter, InterpreterOptions, TreeWalkEngine};
+
+/// Result of one job as returned by the engines.
+pub type JobResult = Result<Output, Diagnostics>;
+
+struct Job {
+    source: String,
+    reply: Sender<JobResult>,
+}
+
+/// Handle to an interpreter running the submitted jobs one after another on a
+/// worker thread.
+///
+/// Jobs share the globals of the interpreter, like the runs of an engine.
+/// Dropping the worker waits for the submitted jobs to finish.
+#[derive(Debug)]
+pub struct Worker {
+    jobs: Option<Sender<Job>>,
+    cancel: CancelHandle,
+    thread: Option<JoinHandle<()>>,
+}
+
+// Workers are meant to be shared between threads.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Worker>();
+};
+
+impl Worker {
+    /// Spawns the thread of the worker creating its interpreter with the given
+    /// options.
+    pub fn spawn(options: InterpreterOptions) -> Self {
+        let (jobs, jobs_rx) = mpsc::channel::<Job>();
+        let (cancel_tx, cancel_rx) = mpsc::channel();
+
+        let thread = std::thread::spawn(move || {
+            let interpreter = Interpreter::with_options(options);
+            // Worker can't be created if the thread is gone already.
+            let _ = cancel_tx.send(interpreter.cancel_handle());
+
+            let mut engine = TreeWalkEngine::new(interpreter);
+            for job in jobs_rx {
+                // Callers can drop the receiver if they aren't interested in the result.
+                let _ = job.reply.send(engine.run(&job.source));
+            }
+        });
+
+        let cancel = cancel_rx
+            .recv()
+            .expect("Worker thread sends the cancel handle on start");
+
+        Self {
+            jobs: Some(jobs),
+            cancel,
+            thread: Some(thread),
+        }
+    }
+
+    /// Queues the source to run after the previously submitted jobs returning
+    /// the receiver of its result.
+    pub fn submit(&self, source: impl Into<String>) -> Receiver<JobResult> {
+        let (reply, result) = mpsc::channel();
+        let job = Job {
+            source: source.into(),
+            reply,
+        };
+
+        // The thread only stops when the interpreter panics, which drops the
+        // reply sender as well and is reported by `run()`.
+        let _ = self
+            .jobs
+            .as_ref()
+            .expect("Jobs sender is only taken on drop")
+            .send(job);
+
+        result
+    }
+
+    /// Runs the source waiting for its result.
+    pub fn run(&self, source: impl Into<String>) -> JobResult {
+        self.submit(source).recv().unwrap_or_else(|_| {
+            Err(Diagnostics {
+                errors: vec![String::from("Worker thread has stopped.")],
+                ..Default::default()
+            })
+        })
+    }
+
+    /// Returns a handle to cancel the running job.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // Closing the channel ends the loop of the thread.
+        drop(self.jobs.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
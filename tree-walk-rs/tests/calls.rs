@@ -0,0 +1,127 @@
+//! Tests of the depth of nested calls, which overflow the stack past the
+//! limit, and of calls in tail position reusing the frame of their caller.
+
+use tree_walk_rs::{Interpreter, LoxValue};
+
+/// Creates an interpreter allowing only a few nested calls, returning the
+/// runtime errors.
+fn interpreter() -> Interpreter {
+    Interpreter::builder()
+        .max_call_depth(20)
+        .stop_on_error(true)
+        .output(std::io::sink())
+        .error_output(std::io::sink())
+        .build()
+}
+
+fn number(interpreter: &mut Interpreter, code: &str) -> f64 {
+    match interpreter.eval(code) {
+        Ok(Some(LoxValue::Number(number))) => number,
+        result => panic!("Expected a number, but got {result:?}"),
+    }
+}
+
+#[test]
+fn nesting_more_calls_than_allowed_overflows_the_stack() {
+    let mut interpreter = interpreter();
+    interpreter
+        .eval(
+            "fun depth(n) {
+  if (n == 0) return 0;
+  return 1 + depth(n - 1);
+}",
+        )
+        .unwrap();
+
+    // The call of `depth(19)` nests exactly the 20 allowed frames.
+    assert_eq!(number(&mut interpreter, "depth(19);"), 19.0);
+    let err = interpreter.eval("depth(20);").unwrap_err();
+    assert!(
+        err.to_string().starts_with("[E0307] Stack overflow."),
+        "{err}"
+    );
+    // The frames of the failed run are released for the later ones.
+    assert_eq!(number(&mut interpreter, "depth(19);"), 19.0);
+}
+
+#[test]
+fn deep_recursion_fails_without_crashing_the_host() {
+    let mut interpreter = Interpreter::builder()
+        .stop_on_error(true)
+        .output(std::io::sink())
+        .error_output(std::io::sink())
+        .build();
+    interpreter
+        .eval(
+            "fun depth(n) {
+  return 1 + depth(n + 1);
+}",
+        )
+        .unwrap();
+
+    let err = interpreter.eval("depth(0);").unwrap_err();
+    assert!(
+        err.to_string().starts_with("[E0307] Stack overflow."),
+        "{err}"
+    );
+}
+
+#[test]
+fn tail_calls_dont_nest_frames() {
+    let mut interpreter = interpreter();
+    interpreter
+        .eval(
+            "fun count(n, total) {
+  if (n == 0) return total;
+  return count(n - 1, total + 1);
+}
+fun isEven(n) {
+  if (n == 0) return true;
+  return isOdd(n - 1);
+}
+fun isOdd(n) {
+  if (n == 0) return false;
+  return isEven(n - 1);
+}
+class Loop {
+  run(n) {
+    if (n == 0) return \"done\";
+    return this.run(n - 1);
+  }
+}",
+        )
+        .unwrap();
+
+    assert_eq!(number(&mut interpreter, "count(10000, 0);"), 10000.0);
+    assert!(matches!(
+        interpreter.eval("isEven(1001);"),
+        Ok(Some(LoxValue::Boolean(false)))
+    ));
+    assert!(matches!(
+        interpreter.eval("Loop().run(1000);"),
+        Ok(Some(LoxValue::String(ref text))) if &**text == "done"
+    ));
+}
+
+#[test]
+fn calls_in_try_blocks_keep_their_frames() {
+    let mut interpreter = interpreter();
+    interpreter
+        .eval(
+            "fun count(n) {
+  try {
+    if (n == 0) return 0;
+    return count(n - 1);
+  } finally {}
+}",
+        )
+        .unwrap();
+
+    assert_eq!(number(&mut interpreter, "count(10);"), 0.0);
+    // The frames are kept until the `finally` blocks run.
+    let err = interpreter.eval("count(100);").unwrap_err();
+    assert!(
+        err.to_string().starts_with("[E0307] Stack overflow."),
+        "{err}"
+    );
+}
@@ -0,0 +1,47 @@
+//! Tests of the deterministic mode, where the natives depending on the
+//! environment give the same results on every run.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::SharedBuffer;
+use tree_walk_rs::{Interpreter, run};
+
+const PROGRAM: &str = "print clock();
+print clock();
+fun late() { print \"late\"; print clock(); }
+fun soon() { print \"soon\"; print clock(); }
+setTimeout(late, 60000);
+setTimeout(soon, 1000);
+runEventLoop();
+print clock();
+";
+
+/// Runs the program in deterministic mode, returning its output.
+fn run_deterministic(code: &str) -> String {
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder()
+        .deterministic(true)
+        .stop_on_error(true)
+        .output(output.clone())
+        .build();
+    run(&mut interpreter, code.to_owned()).unwrap();
+
+    output.take()
+}
+
+#[test]
+fn clock_counts_seconds_from_zero() {
+    let started = Instant::now();
+    let output = run_deterministic(PROGRAM);
+
+    assert_eq!(output, "0\n1\nsoon\n2\nlate\n3\n4\n");
+    // The event loop jumps to the timers instead of waiting for them.
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn runs_of_the_same_program_print_the_same_output() {
+    assert_eq!(run_deterministic(PROGRAM), run_deterministic(PROGRAM));
+}
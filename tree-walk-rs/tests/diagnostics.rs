@@ -0,0 +1,350 @@
+//! Snapshot tests of the rendered diagnostics of each stage, so changes to
+//! their formatting show up in the reviewed snapshots. Diagnostics are
+//! followed by the line of the code they are reported at, checking their
+//! lines and columns.
+//!
+//! Snapshots are in `tests/snapshots`, and `INSTA_UPDATE=always cargo test
+//! --test diagnostics` rewrites them after deliberate changes.
+
+mod common;
+
+use std::{collections::HashSet, fmt::Display, io::Write, time::Duration};
+
+use common::SharedBuffer;
+use insta::assert_snapshot;
+use lox_frontend::{Parser, Scanner, Token, render_snippet};
+use tree_walk_rs::{
+    Capability, Interpreter, InterpreterBuilder, LoxError, LoxValue, NativeClass, RunError,
+    SandboxPolicy, run,
+};
+
+/// Renders the diagnostic followed by the line of the code at its position.
+fn with_snippet(
+    diagnostic: impl Display,
+    code: &str,
+    line: usize,
+    column: usize,
+    width: usize,
+) -> String {
+    let snippet = render_snippet(code, line, column, width);
+    if snippet.is_empty() {
+        diagnostic.to_string()
+    } else {
+        format!("{diagnostic}\n{snippet}")
+    }
+}
+
+/// Renders the diagnostic followed by the line of the code with the token.
+fn with_token_snippet(diagnostic: impl Display, code: &str, token: &Token) -> String {
+    let span = token.span;
+    with_snippet(
+        diagnostic,
+        code,
+        span.line,
+        span.column,
+        token.lexeme.chars().count(),
+    )
+}
+
+fn scan_errors(code: &str) -> String {
+    let scan_res = Scanner::new(code.to_owned()).scan_tokens();
+    let errors: Vec<_> = scan_res
+        .errors
+        .iter()
+        .map(|err| with_snippet(err, code, err.line(), err.column(), 1))
+        .collect();
+
+    errors.join("\n")
+}
+
+fn parse_errors(code: &str) -> String {
+    let scan_res = Scanner::new(code.to_owned()).scan_tokens();
+    let parse_res = Parser::new(scan_res.tokens).parse();
+    let errors: Vec<_> = parse_res
+        .errors
+        .iter()
+        .map(|err| with_token_snippet(err, code, &err.token))
+        .collect();
+
+    errors.join("\n")
+}
+
+/// Runs the code like `rlox` runs scripts, returning the reported errors and
+/// warnings followed by the error stopping it if any, rendered through
+/// `RunError::into_anyhow()` like `run_file()` does with the line of the code
+/// it's reported at. Printed values are left out.
+fn run_errors(builder: InterpreterBuilder, code: &str) -> String {
+    let mut buffer = SharedBuffer::default();
+    let mut interpreter = builder.error_output(buffer.clone()).build();
+
+    if let Err(err) = run(&mut interpreter, code.to_owned()) {
+        let token = match &err {
+            RunError::LoxError(LoxError::Error { token, .. })
+            | RunError::Runtime(LoxError::Error { token, .. }) => Some(token.as_ref().clone()),
+            _ => None,
+        };
+        let err = err.into_anyhow();
+        let rendered = match token {
+            Some(token) => with_token_snippet(err, code, &token),
+            None => err.to_string(),
+        };
+        write!(buffer, "{rendered}").unwrap();
+    }
+
+    buffer.take()
+}
+
+/// Runs each line of the code without resolving it first, like embedders
+/// misusing `Interpreter::interpret()` do, returning the rendered errors of
+/// each one. The resolved code runs before them, leaving the infos of its
+/// resolver in the interpreter.
+fn unresolved_errors(resolved: &str, code: &str) -> String {
+    let mut interpreter = interpreter().build();
+    run(&mut interpreter, resolved.to_owned()).unwrap();
+
+    let errors: Vec<_> = code
+        .lines()
+        .map(|line| {
+            let scan_res = Scanner::new(line.to_owned()).scan_tokens();
+            let parse_res = Parser::new(scan_res.tokens).parse();
+            assert!(parse_res.errors.is_empty());
+
+            match interpreter.interpret(&parse_res.stmts) {
+                Ok(_) => String::from("no error"),
+                Err(err) => match &err {
+                    LoxError::Error { token, .. } => with_token_snippet(&err, line, token),
+                    _ => err.to_string(),
+                },
+            }
+        })
+        .collect();
+
+    errors.join("\n")
+}
+
+fn interpreter() -> InterpreterBuilder {
+    Interpreter::builder()
+        .stop_on_error(true)
+        .output(std::io::sink())
+}
+
+#[test]
+fn scan_unexpected_character() {
+    assert_snapshot!(scan_errors("var price = 5$;"));
+}
+
+#[test]
+fn scan_unterminated_string() {
+    assert_snapshot!(scan_errors("var a = 1;\nprint \"unfinished;\nprint a;"));
+}
+
+#[test]
+fn parse_missing_semicolon() {
+    assert_snapshot!(parse_errors("var a = 1\nprint a;"));
+}
+
+#[test]
+fn parse_reserved_word() {
+    assert_snapshot!(parse_errors("var class = 1;"));
+}
+
+#[test]
+fn parse_chained_comparison() {
+    assert_snapshot!(parse_errors("print 1 < x < 10;"));
+    assert_snapshot!(parse_errors("print 1 < mid() < 3;"));
+}
+
+#[test]
+fn parse_long_operator_chains() {
+    // Chains within the limit still run.
+    let sum = vec!["1"; 200].join(" + ");
+    assert_eq!(run_errors(interpreter(), &format!("print {sum};")), "");
+
+    for operator in ["+", "*", "==", "and", "or"] {
+        let chain = vec!["1"; 300].join(&format!(" {operator} "));
+        assert_snapshot!(run_errors(interpreter(), &format!("print {chain};")));
+    }
+}
+
+#[test]
+fn parse_errors_in_lists_and_statements() {
+    assert_snapshot!(parse_errors(
+        "fun f(a, 1, b) { print a; }\nf(1, , 2);\nprint (1 + 2;"
+    ));
+}
+
+#[test]
+fn parse_malformed_parameter_keeps_the_arity() {
+    assert_snapshot!(run_errors(
+        interpreter(),
+        "fun add(a, 1, c) {\n  return a + c;\n}\nprint add(1, 2, 3);"
+    ));
+}
+
+#[test]
+fn resolve_own_initializer() {
+    assert_snapshot!(run_errors(interpreter(), "{ var a = 1; { var a = a; } }"));
+}
+
+#[test]
+fn resolve_top_level_return() {
+    assert_snapshot!(run_errors(interpreter(), "return 1;"));
+}
+
+#[test]
+fn resolve_warnings() {
+    assert_snapshot!(run_errors(
+        interpreter(),
+        "fun f() {\n  return 1;\n  print \"never\";\n}\nprint f();\nprint totl;"
+    ));
+}
+
+#[test]
+fn resolve_strict_unused_variable() {
+    assert_snapshot!(run_errors(
+        interpreter().strict(true),
+        "fun f() {\n  var unused = 1;\n}"
+    ));
+}
+
+#[test]
+fn runtime_operand_type() {
+    assert_snapshot!(run_errors(interpreter(), "print -\"x\";"));
+}
+
+#[test]
+fn runtime_compare_instance_with_other_value() {
+    let class = "class P { compareTo(other) { return 0; } }\n";
+    assert_snapshot!(run_errors(interpreter(), &format!("{class}print P() < 1;")));
+    assert_snapshot!(run_errors(
+        interpreter(),
+        &format!("{class}print 1 >= P();")
+    ));
+}
+
+#[test]
+fn runtime_native_arity() {
+    assert_snapshot!(run_errors(interpreter(), "print clock(1);"));
+}
+
+#[test]
+fn runtime_error_with_trace() {
+    assert_snapshot!(run_errors(
+        interpreter(),
+        "fun inner(n) {\n  return n / 0;\n}\nfun outer() {\n  var half = inner(1);\n  return half;\n}\nouter();"
+    ));
+}
+
+#[test]
+fn runtime_step_limit() {
+    assert_snapshot!(run_errors(interpreter().max_steps(100), "while (true) {}"));
+}
+
+#[test]
+fn runtime_infinite_timer_delay() {
+    assert_snapshot!(run_errors(
+        interpreter().ieee_division(true),
+        "fun f() {}\nsetTimeout(f, 1 / 0);"
+    ));
+}
+
+#[test]
+fn runtime_negative_timer_delay() {
+    assert_snapshot!(run_errors(interpreter(), "fun f() {}\nsetInterval(f, -5);"));
+}
+
+#[test]
+fn runtime_time_limit_while_waiting_for_timer() {
+    let sandbox = SandboxPolicy {
+        max_duration: Some(Duration::from_millis(50)),
+        ..SandboxPolicy::default()
+    };
+    assert_snapshot!(run_errors(
+        interpreter().sandbox(sandbox),
+        "fun f() {}\nsetTimeout(f, 60000);\nrunEventLoop();"
+    ));
+}
+
+#[test]
+fn runtime_memory_limit_of_strings() {
+    let sandbox = SandboxPolicy {
+        max_allocations: Some(1000),
+        max_memory: Some(1 << 20),
+        ..SandboxPolicy::default()
+    };
+    assert_snapshot!(run_errors(
+        interpreter().sandbox(sandbox),
+        "var s = \"ab\";\nwhile (true) s = s + s;"
+    ));
+}
+
+#[test]
+fn runtime_memory_limit_of_lists() {
+    let sandbox = SandboxPolicy {
+        max_memory: Some(1 << 20),
+        ..SandboxPolicy::default()
+    };
+    assert_snapshot!(run_errors(
+        interpreter().sandbox(sandbox),
+        "var l = list();\nwhile (true) push(l, 1);"
+    ));
+}
+
+#[test]
+fn runtime_capability_of_embedder_native_disabled() {
+    let sandbox = SandboxPolicy {
+        disabled_capabilities: HashSet::from([Capability::Io]),
+        ..SandboxPolicy::default()
+    };
+    assert_snapshot!(run_errors(
+        interpreter().sandbox(sandbox).native_with_capabilities(
+            "readFile",
+            1,
+            |_, _| Ok(LoxValue::Nil),
+            &[Capability::Io]
+        ),
+        "readFile(\"secret.txt\");"
+    ));
+}
+
+#[test]
+fn runtime_native_class_denied_by_sandbox() {
+    let sandbox = SandboxPolicy {
+        allowed_natives: Some(HashSet::from([
+            String::from("len"),
+            String::from("coroutine"),
+        ])),
+        ..SandboxPolicy::default()
+    };
+    assert_snapshot!(run_errors(
+        interpreter()
+            .stop_on_error(false)
+            .sandbox(sandbox)
+            .class(NativeClass::new("Counter")),
+        "Counter();\nvar map = HashMap();\nclass Map < HashMap {}\nMap();\nfun f() {}\ncoroutine(f).isDone();"
+    ));
+}
+
+#[test]
+fn runtime_string_limit_of_folded_constants() {
+    assert_snapshot!(run_errors(
+        interpreter().fold_constants(true).max_string_length(5),
+        "print \"abc\" + \"def\";"
+    ));
+}
+
+#[test]
+fn runtime_unresolved_control_flow() {
+    assert_snapshot!(unresolved_errors(
+        "",
+        "return 1;\nreturn clock();\nfun h() {}\nreturn h();\nbreak;\ncontinue;\nfun f() { break; }\nf();"
+    ));
+}
+
+#[test]
+fn runtime_locals_resolved_for_other_code() {
+    assert_snapshot!(unresolved_errors(
+        "{ var a = 1; { print a; } }",
+        "{ { a = 2; } }\nclass A { init() {} } A();"
+    ));
+}
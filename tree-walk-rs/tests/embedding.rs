@@ -0,0 +1,309 @@
+//! Tests of the API hosts use to embed the interpreter, running the code
+//! through it instead of the command line.
+
+use std::{cell::RefCell, rc::Rc, thread, time::Duration};
+
+use lox_frontend::ast::Stmt;
+use tree_walk_rs::{ExecutionObserver, Interpreter, LoxError, LoxValue, RunError, RunState};
+
+/// Cancels the run calling it through the handle of the interpreter.
+fn cancel(interpreter: &mut Interpreter, _args: &[LoxValue]) -> Result<LoxValue, String> {
+    interpreter.cancel_handle().cancel();
+    Ok(LoxValue::Nil)
+}
+
+/// Suspends the script until the host resumes it with the result.
+fn wait(interpreter: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    interpreter.suspend(args[0].clone())
+}
+
+/// File of the host handed to the scripts as a foreign value.
+struct File {
+    name: String,
+}
+
+fn open(_: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    let name = args[0].to_string();
+    Ok(LoxValue::foreign("File", File { name }))
+}
+
+fn file_name(_: &mut Interpreter, args: &[LoxValue]) -> Result<LoxValue, String> {
+    match args[0].as_foreign::<File>() {
+        Some(file) => Ok(LoxValue::String(file.name.as_str().into())),
+        None => Err(format!("Expected a file, but got {}.", args[0].describe())),
+    }
+}
+
+/// Observer recording the events of the execution in order.
+#[derive(Default)]
+struct Recorder(Rc<RefCell<Vec<String>>>);
+
+impl ExecutionObserver for Recorder {
+    fn on_stmt_enter(&mut self, stmt: &Stmt) {
+        self.0
+            .borrow_mut()
+            .push(format!("enter {}", stmt.to_source()));
+    }
+
+    fn on_stmt_exit(&mut self, stmt: &Stmt) {
+        self.0
+            .borrow_mut()
+            .push(format!("exit {}", stmt.to_source()));
+    }
+
+    fn on_call(&mut self, callee: &LoxValue, args: &[LoxValue], line: usize) {
+        let args: Vec<_> = args.iter().map(ToString::to_string).collect();
+        self.0
+            .borrow_mut()
+            .push(format!("call {callee}({}) on line {line}", args.join(", ")));
+    }
+
+    fn on_return(&mut self, value: &LoxValue) {
+        self.0.borrow_mut().push(format!("return {value}"));
+    }
+}
+
+#[test]
+fn observer_sees_calls_returns_and_resumed_natives_in_order() {
+    let events = Rc::default();
+    let mut interpreter = Interpreter::builder()
+        .native("wait", 1, wait)
+        .observer(Recorder(Rc::clone(&events)))
+        .output(std::io::sink())
+        .build();
+
+    let code =
+        "fun add(a, b) {\n  return a + b;\n}\nprint add(1, 2);\nvar answer = wait(\"answer\");";
+    let state = interpreter.start(code).unwrap();
+    assert!(
+        matches!(state, RunState::Suspended(LoxValue::String(ref request)) if &**request == "answer")
+    );
+    let state = interpreter.resume(LoxValue::Number(42.0)).unwrap();
+    assert!(matches!(state, RunState::Done(None)));
+
+    assert_eq!(
+        *events.borrow(),
+        [
+            "enter fun add(a, b) {\n    return a + b;\n}",
+            "exit fun add(a, b) {\n    return a + b;\n}",
+            "enter print add(1, 2);",
+            "call <fn add>(1, 2) on line 4",
+            "enter return a + b;",
+            "exit return a + b;",
+            "return 3",
+            "exit print add(1, 2);",
+            "enter var answer = wait(\"answer\");",
+            "call <native fn>(answer) on line 5",
+            // Suspended until the host resumes the native with its result.
+            "return 42",
+            "exit var answer = wait(\"answer\");",
+        ]
+    );
+}
+
+/// Creates an interpreter with the `wait` native returning the runtime errors
+/// and leaving out the output.
+fn interpreter() -> Interpreter {
+    Interpreter::builder()
+        .native("wait", 1, wait)
+        .stop_on_error(true)
+        .output(std::io::sink())
+        .error_output(std::io::sink())
+        .build()
+}
+
+#[test]
+fn reset_clears_the_globals_keeping_the_natives() {
+    let mut interpreter = interpreter();
+    interpreter
+        .eval("var a = 1;\nfun f() { return a; }")
+        .unwrap();
+    assert!(matches!(
+        interpreter.start("wait(nil);"),
+        Ok(RunState::Suspended(LoxValue::Nil))
+    ));
+
+    interpreter.reset();
+
+    assert!(!interpreter.is_suspended());
+    assert!(interpreter.eval("a;").is_err());
+    assert!(interpreter.eval("f;").is_err());
+    assert!(matches!(
+        interpreter.eval("len(\"abc\");"),
+        Ok(Some(LoxValue::Number(3.0)))
+    ));
+    // Natives of the host survive the reset as well.
+    assert!(matches!(
+        interpreter.start("wait(1);"),
+        Ok(RunState::Suspended(LoxValue::Number(1.0)))
+    ));
+}
+
+#[test]
+fn isolated_runs_keep_their_definitions_to_themselves() {
+    let mut interpreter = interpreter();
+    interpreter.eval("var shared = 1;").unwrap();
+
+    interpreter
+        .run_isolated("var local = 2;\nshared = shared + local;")
+        .unwrap();
+    assert!(matches!(
+        interpreter.eval("shared;"),
+        Ok(Some(LoxValue::Number(3.0)))
+    ));
+    assert!(interpreter.eval("local;").is_err());
+
+    // Failing runs leave the globals as they were too.
+    assert!(
+        interpreter
+            .run_isolated("var other = 1;\nprint nil + 1;")
+            .is_err()
+    );
+    assert!(interpreter.eval("other;").is_err());
+    assert!(matches!(
+        interpreter.eval("shared;"),
+        Ok(Some(LoxValue::Number(3.0)))
+    ));
+}
+
+#[test]
+fn expressions_evaluate_in_the_scope_of_the_previous_runs() {
+    let mut interpreter = interpreter();
+    interpreter
+        .eval(
+            "var count = 1;
+fun makeCounter() {
+  var n = 0;
+  fun next() { n = n + 1; return n; }
+  return next;
+}
+var next = makeCounter();",
+        )
+        .unwrap();
+
+    assert!(matches!(
+        interpreter.eval_expr_in_current_scope("count + next()"),
+        Ok(LoxValue::Number(2.0))
+    ));
+    // Assignments change the globals of the later runs.
+    assert!(matches!(
+        interpreter.eval_expr_in_current_scope("count = count * 10"),
+        Ok(LoxValue::Number(10.0))
+    ));
+    // Closures of the previous runs keep their resolved variables.
+    assert!(matches!(
+        interpreter.eval("count + next();"),
+        Ok(Some(LoxValue::Number(12.0)))
+    ));
+}
+
+#[test]
+fn only_single_expressions_evaluate_in_the_current_scope() {
+    let mut interpreter = interpreter();
+    assert!(matches!(
+        interpreter.eval_expr_in_current_scope("1 + 2; var a = 1;"),
+        Err(RunError::Parse(1))
+    ));
+    assert!(matches!(
+        interpreter.eval_expr_in_current_scope("var a = 1"),
+        Err(RunError::Parse(1))
+    ));
+    assert!(matches!(
+        interpreter.eval_expr_in_current_scope("\"open"),
+        Err(RunError::Scan(1))
+    ));
+    assert!(matches!(
+        interpreter.eval_expr_in_current_scope("missing"),
+        Err(RunError::LoxError(_))
+    ));
+    assert!(interpreter.eval("a;").is_err());
+}
+
+#[test]
+fn scripts_pass_foreign_values_back_to_the_natives() {
+    let mut interpreter = Interpreter::builder()
+        .native("open", 1, open)
+        .native("fileName", 1, file_name)
+        .stop_on_error(true)
+        .output(std::io::sink())
+        .error_output(std::io::sink())
+        .build();
+    interpreter
+        .eval("var file = open(\"notes.txt\");\nvar other = open(\"notes.txt\");")
+        .unwrap();
+
+    assert!(matches!(
+        interpreter.eval("fileName(file);"),
+        Ok(Some(LoxValue::String(ref name))) if &**name == "notes.txt"
+    ));
+    let Ok(Some(file)) = interpreter.eval("file;") else {
+        panic!("Expected the foreign value");
+    };
+    assert_eq!(file.to_string(), "<foreign File>");
+    assert_eq!(file.as_foreign::<File>().unwrap().name, "notes.txt");
+    assert!(file.as_foreign::<String>().is_none());
+
+    // Foreign values are only equal to themselves.
+    assert!(matches!(
+        interpreter.eval("file == file;"),
+        Ok(Some(LoxValue::Boolean(true)))
+    ));
+    assert!(matches!(
+        interpreter.eval("file == other;"),
+        Ok(Some(LoxValue::Boolean(false)))
+    ));
+    // Lox code can't look inside of them.
+    assert!(interpreter.eval("file.name;").is_err());
+    assert!(interpreter.eval("file + 1;").is_err());
+    assert!(interpreter.eval("fileName(\"notes.txt\");").is_err());
+}
+
+#[test]
+fn cancelled_runs_dont_abort_later_ones() {
+    let mut interpreter = Interpreter::builder()
+        .output(std::io::sink())
+        .error_output(std::io::sink())
+        .build();
+
+    let handle = interpreter.cancel_handle();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.cancel();
+    });
+    let result = interpreter.eval("while (true) {}");
+    canceller.join().unwrap();
+    assert!(matches!(
+        result,
+        Err(RunError::Runtime(LoxError::Cancelled))
+    ));
+    assert!(matches!(
+        interpreter.eval("1 + 2;"),
+        Ok(Some(LoxValue::Number(3.0)))
+    ));
+}
+
+#[test]
+fn cancelled_expressions_dont_abort_later_runs() {
+    let mut interpreter = Interpreter::builder()
+        .native("cancel", 0, cancel)
+        .output(std::io::sink())
+        .error_output(std::io::sink())
+        .build();
+    interpreter
+        .eval("fun spin() { cancel(); while (true) {} }")
+        .unwrap();
+
+    let result = interpreter.eval_expr_in_current_scope("spin()");
+    assert!(matches!(
+        result,
+        Err(RunError::LoxError(LoxError::Cancelled))
+    ));
+    assert!(matches!(
+        interpreter.eval_expr_in_current_scope("1 + 2"),
+        Ok(LoxValue::Number(3.0))
+    ));
+    assert!(matches!(
+        interpreter.eval("1 + 2;"),
+        Ok(Some(LoxValue::Number(3.0)))
+    ));
+}
@@ -0,0 +1,145 @@
+//! Golden tests running the scripts in `tests/scripts` on each engine and
+//! comparing their output with the `.expected` file next to each script.
+//!
+//! The scripts run through the `Engine` trait, so the printed values come
+//! first, followed by the reported errors. Warnings are left out by engines,
+//! which the snapshots of the diagnostics cover instead. Scripts using features
+//! only the tree-walk interpreter has are listed in `TREE_ONLY`. Error codes
+//! and stack traces are only compared on the tree-walk interpreter, the other
+//! engines must report the same messages with their own traces. Running the
+//! tests with `UPDATE_EXPECT=1` writes the output of the tree-walk interpreter
+//! to the expected files instead, which are then reviewed in the diff.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tree_walk_rs::{InterpreterOptions, engine};
+
+/// Engines running the scripts, where the first one writes the expected files.
+const ENGINES: &[&str] = &["tree", "vm"];
+
+/// Scripts running on the tree-walk interpreter only.
+const TREE_ONLY: &[&str] = &[
+    // Doc comments of the declarations read with the `docs` native.
+    "docs",
+    // Integer division, modulo, loop control, for-in loops and multiple
+    // assignments.
+    "extensions",
+    // Lists built with the list natives and processed by functions.
+    "lists",
+    // Equality and comparison of instances with `equals` and `compareTo`.
+    "operator_hooks",
+    // Codes and help of the diagnostics.
+    "parse_errors",
+    // Private members of classes, which are public on the virtual machine.
+    "private_members",
+    "private_methods",
+    // Reflection natives hiding the private members.
+    "reflection",
+    // Natives inspecting the values.
+    "strings",
+    // Code of the undefined variable error.
+    "warnings",
+];
+
+/// Runs the script on a new instance of the engine, returning its output
+/// followed by the errors stopping it if any. Scripts stop at their first
+/// runtime error like when `rlox` runs them.
+///
+/// With `messages_only` the errors are reduced to their messages, leaving out
+/// the codes and stack traces which differ between the engines.
+fn run_script(name: &str, code: &str, messages_only: bool) -> String {
+    let options = InterpreterOptions {
+        stop_on_error: true,
+        ..Default::default()
+    };
+    let mut engine = engine(name, options).expect("Engine exists");
+
+    match engine.run(code) {
+        Ok(output) => output.stdout,
+        Err(diagnostics) if messages_only => {
+            let messages: String = diagnostics
+                .errors
+                .iter()
+                .map(|error| format!("{}\n", message(error)))
+                .collect();
+            format!("{}{messages}", diagnostics.output.stdout)
+        }
+        Err(diagnostics) => format!("{}{diagnostics}\n", diagnostics.output.stdout),
+    }
+}
+
+/// Returns the message of the reported error without its code and trace.
+fn message(error: &str) -> &str {
+    let line = error.lines().next().unwrap_or_default();
+    match line.strip_prefix("[E") {
+        Some(rest) => rest.split_once("] ").map_or(line, |(_, message)| message),
+        None => line,
+    }
+}
+
+fn scripts() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts");
+    let mut scripts: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    scripts.sort();
+
+    scripts
+}
+
+#[test]
+fn scripts_match_expected_output() {
+    let update = std::env::var("UPDATE_EXPECT").is_ok_and(|value| value == "1");
+
+    let scripts = scripts();
+    assert!(!scripts.is_empty(), "No scripts found");
+
+    let mut failures = Vec::new();
+    for script in scripts {
+        let code = fs::read_to_string(&script).unwrap();
+        let expected_path = script.with_extension("expected");
+        let tree_only = script
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| TREE_ONLY.contains(&stem));
+        let engines = if tree_only { &ENGINES[..1] } else { ENGINES };
+
+        if update {
+            fs::write(&expected_path, run_script(ENGINES[0], &code, false)).unwrap();
+            continue;
+        }
+
+        let Ok(expected) = fs::read_to_string(&expected_path) else {
+            failures.push(format!(
+                "{}: Missing expected file. Run with UPDATE_EXPECT=1 to create it.",
+                script.display()
+            ));
+            continue;
+        };
+        let expected_messages = run_script(ENGINES[0], &code, true);
+        for (index, name) in engines.iter().enumerate() {
+            let (expected, actual) = if index == 0 {
+                (&expected, run_script(name, &code, false))
+            } else {
+                (&expected_messages, run_script(name, &code, true))
+            };
+            if actual != *expected {
+                failures.push(format!(
+                    "{} on {name}:\n--- expected\n{expected}--- actual\n{actual}",
+                    script.display()
+                ));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "Scripts with unexpected output:\n\n{}",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,193 @@
+//! Tests of the cycle collector with values the host keeps outside of the
+//! interpreter, of weak handles and of the counts of the live objects.
+
+use std::cell::RefCell;
+
+use tree_walk_rs::{Interpreter, LoxValue, NativeClass, Trace, Tracer};
+
+thread_local! {
+    /// Closure the test holds while the script drops its own references.
+    static HELD: RefCell<Option<LoxValue>> = const { RefCell::new(None) };
+}
+
+fn call_held(interpreter: &mut Interpreter, _args: &[LoxValue]) -> Result<LoxValue, String> {
+    let held = HELD.with_borrow(Clone::clone).unwrap();
+    interpreter.call(&held, Vec::new())
+}
+
+/// Creates enough environments to run a collection.
+fn churn(interpreter: &mut Interpreter) {
+    interpreter
+        .eval("for (var i = 0; i < 2000; i = i + 1) { var x = i; }")
+        .unwrap();
+    interpreter.eval("nil;").unwrap();
+}
+
+#[test]
+fn closures_held_by_the_host_survive_collections() {
+    let mut interpreter = Interpreter::builder()
+        .native("callHeld", 0, call_held)
+        .build();
+    interpreter
+        .eval(
+            "fun counter() { var k = 0; fun inc() { k = k + 1; return k; } return inc; }
+             var cb = counter();",
+        )
+        .unwrap();
+    HELD.set(interpreter.get_global("cb"));
+    interpreter.eval("cb = nil;").unwrap();
+
+    churn(&mut interpreter);
+    let value = interpreter.eval("callHeld();").unwrap();
+    assert_eq!(value.and_then(|value| value.as_number()), Some(1.0));
+
+    churn(&mut interpreter);
+    let value = interpreter.eval("callHeld();").unwrap();
+    assert_eq!(value.and_then(|value| value.as_number()), Some(2.0));
+
+    // Once the host drops the closure its environment is collected.
+    HELD.set(None);
+    churn(&mut interpreter);
+    assert_eq!(interpreter.heap_stats().unreachable_environments, 0);
+}
+
+/// State of the emitters, holding the callbacks registered from Lox.
+#[derive(Default)]
+struct Listeners(RefCell<Vec<LoxValue>>);
+
+impl Trace for Listeners {
+    fn trace(&self, tracer: &mut Tracer) {
+        for listener in self.0.borrow().iter() {
+            listener.trace(tracer);
+        }
+    }
+}
+
+fn emitter_class() -> NativeClass {
+    NativeClass::new("Emitter")
+        .method("init", 0, |_, this, _| {
+            this.borrow_mut().set_traced_state(Listeners::default());
+            Ok(LoxValue::Nil)
+        })
+        .method("on", 1, |_, this, args| {
+            let this = this.borrow();
+            let listeners = this
+                .state::<Listeners>()
+                .ok_or("Emitter isn't initialized.")?;
+            listeners.0.borrow_mut().push(args[0].clone());
+            Ok(LoxValue::Nil)
+        })
+        .method("fire", 0, |interpreter, this, _| {
+            let listeners = this.borrow().state_rc::<Listeners>();
+            let listeners = listeners.ok_or("Emitter isn't initialized.")?;
+            let callbacks = listeners.0.borrow().clone();
+            let mut last = LoxValue::Nil;
+            for callback in &callbacks {
+                last = interpreter.call(callback, Vec::new())?;
+            }
+            Ok(last)
+        })
+}
+
+#[test]
+fn callbacks_stored_in_native_classes_survive_collections() {
+    let mut interpreter = Interpreter::builder().class(emitter_class()).build();
+    interpreter
+        .eval(
+            "var emitter = Emitter();
+             fun listen() { var count = 0; fun on() { count = count + 1; return count; } emitter.on(on); }
+             listen();",
+        )
+        .unwrap();
+
+    for expected in [1.0, 2.0] {
+        churn(&mut interpreter);
+        let value = interpreter.eval("emitter.fire();").unwrap();
+        assert_eq!(value.and_then(|value| value.as_number()), Some(expected));
+    }
+}
+
+#[test]
+fn weak_handles_dont_keep_their_instances_alive() {
+    let mut interpreter = Interpreter::builder()
+        .stop_on_error(true)
+        .error_output(std::io::sink())
+        .build();
+    interpreter
+        .eval(
+            "class Node {}
+             var node = Node();
+             var handle = weak(node);
+             fun captured() {
+               var node = Node();
+               fun get() { return node; }
+               return weak(node);
+             }
+             var capturedHandle = captured();",
+        )
+        .unwrap();
+
+    let value = interpreter.eval("handle.get() == node;").unwrap();
+    assert!(matches!(value, Some(LoxValue::Boolean(true))));
+
+    interpreter.eval("node = nil;").unwrap();
+    let value = interpreter.eval("handle.get();").unwrap();
+    assert!(matches!(value, Some(LoxValue::Nil)));
+
+    // Closures keep their environments alive in cycles until they are
+    // collected, freeing the instances captured in them.
+    let value = interpreter.eval("capturedHandle.get() == nil;").unwrap();
+    assert!(matches!(value, Some(LoxValue::Boolean(false))));
+    churn(&mut interpreter);
+    let value = interpreter.eval("capturedHandle.get();").unwrap();
+    assert!(matches!(value, Some(LoxValue::Nil)));
+
+    assert!(interpreter.eval("weak(1);").is_err());
+}
+
+#[test]
+fn gc_stats_count_the_live_objects() {
+    let mut interpreter = Interpreter::new();
+    interpreter.eval("class Node {}").unwrap();
+    let before = interpreter.heap_stats();
+
+    interpreter
+        .eval(
+            "var nodes = list();
+             for (var i = 0; i < 10; i = i + 1) push(nodes, Node());
+             fun make() { var k = 0; fun inc() { k = k + 1; return k; } return inc; }
+             make();",
+        )
+        .unwrap();
+    let after = interpreter.heap_stats();
+    assert_eq!(after.instances, before.instances + 10);
+    assert_eq!(after.lists, before.lists + 1);
+    // The dropped closure keeps its environment alive until the collection.
+    assert!(after.unreachable_environments > 0);
+
+    // Scripts get the same counts, which are taken before creating the
+    // instance returned to them.
+    let value = interpreter
+        .eval("var stats = gcStats(); stats.instances;")
+        .unwrap();
+    let stats = interpreter.heap_stats();
+    assert_eq!(
+        value.and_then(|value| value.as_number()),
+        Some((stats.instances - 1) as f64)
+    );
+    for (field, count) in [
+        ("environments", stats.environments),
+        ("unreachableEnvironments", stats.unreachable_environments),
+        ("functions", stats.functions),
+        ("lists", stats.lists),
+    ] {
+        let value = interpreter.eval(&format!("stats.{field};")).unwrap();
+        assert_eq!(
+            value.and_then(|value| value.as_number()),
+            Some(count as f64)
+        );
+    }
+
+    churn(&mut interpreter);
+    assert_eq!(interpreter.heap_stats().unreachable_environments, 0);
+}
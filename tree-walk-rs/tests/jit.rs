@@ -0,0 +1,82 @@
+//! Tests of the JIT compiler, checking that compiled functions return the same
+//! values as the interpreter and fall back to it for the cases the native code
+//! can't handle.
+#![cfg(feature = "jit")]
+
+mod common;
+
+use common::SharedBuffer;
+use lox_frontend::codes;
+use tree_walk_rs::{Interpreter, RunState};
+
+fn eval_number(interpreter: &mut Interpreter, source: &str) -> f64 {
+    interpreter
+        .eval(source)
+        .unwrap()
+        .and_then(|value| value.as_number())
+        .unwrap()
+}
+
+#[test]
+fn compiled_functions_match_the_interpreter() {
+    let mut interpreter = Interpreter::new();
+    let code = "
+        fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+        fun sum(n) {
+            var total = 0;
+            for (var i = 0; i < n; i = i + 1) {
+                if (i == 3) continue;
+                if (i > 8) break;
+                var odd = i % 2 == 1;
+                if (odd and !(i == 5) or false) total = total + i;
+            }
+            return total;
+        }
+        fun floors(a, b) { return a ~/ b * 10 + a % b; }";
+    interpreter.eval(code).unwrap();
+
+    assert_eq!(eval_number(&mut interpreter, "fib(20);"), 6765.0);
+    assert_eq!(eval_number(&mut interpreter, "sum(100);"), 8.0);
+    assert_eq!(eval_number(&mut interpreter, "floors(-7, 2);"), -39.0);
+}
+
+#[test]
+fn compiled_code_calls_redefined_callees() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .eval("fun g(x) { return x + 1; } fun f(x) { return g(x); }")
+        .unwrap();
+    assert_eq!(eval_number(&mut interpreter, "f(1);"), 2.0);
+
+    interpreter.eval("fun g(x) { return x * 10; }").unwrap();
+    assert_eq!(eval_number(&mut interpreter, "f(1);"), 10.0);
+}
+
+#[test]
+fn runtime_errors_are_reported_by_the_interpreter() {
+    let errors = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder()
+        .error_output(errors.clone())
+        .max_call_depth(2000)
+        .build();
+    interpreter
+        .eval("fun div(a, b) { return a / b; } fun deep(n) { return 1 + deep(n + 1); }")
+        .unwrap();
+
+    assert_eq!(eval_number(&mut interpreter, "div(1, 4);"), 0.25);
+    interpreter.eval("div(1, 0);").unwrap();
+    assert!(errors.take().contains(codes::DIVISION_BY_ZERO));
+    interpreter.eval("deep(0);").unwrap();
+    assert!(errors.take().contains(codes::STACK_OVERFLOW));
+}
+
+#[test]
+fn budgeted_runs_pause_inside_of_compiled_functions() {
+    let mut interpreter = Interpreter::new();
+    let code = "
+        fun spin(n) { var i = 0; while (i < n) i = i + 1; return i; }
+        spin(100000000);";
+
+    let state = interpreter.start_budgeted(code, 1000).unwrap();
+    assert!(matches!(state, RunState::Paused));
+}
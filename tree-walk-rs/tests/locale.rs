@@ -0,0 +1,52 @@
+//! Tests of the translated diagnostics, which are in their own binary since
+//! the language is selected for the whole process.
+
+mod common;
+
+use std::io::Write;
+
+use common::SharedBuffer;
+use lox_frontend::locale::{self, Lang};
+use tree_walk_rs::{Interpreter, run};
+
+fn run_errors(code: &str) -> String {
+    let mut buffer = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder()
+        .stop_on_error(true)
+        .output(std::io::sink())
+        .error_output(buffer.clone())
+        .build();
+
+    if let Err(err) = run(&mut interpreter, code.to_owned()) {
+        write!(buffer, "{}", err.into_anyhow()).unwrap();
+    }
+
+    buffer.take()
+}
+
+#[test]
+fn german_messages_by_code() {
+    locale::set_lang(Lang::German);
+
+    // The same English message is translated for the resolver and the runtime.
+    assert_eq!(
+        run_errors("fun f() {}\nf(1);"),
+        "[E0215] 0 Argumente erwartet, aber 1 erhalten.\n[line 2]"
+    );
+    assert_eq!(
+        run_errors("print clock(1);"),
+        "[E0317] 0 Argumente erwartet, aber 1 erhalten.\n[line 1]"
+    );
+    // Runtime errors describe the values in German too.
+    insta::assert_snapshot!(
+        "german_runtime_error",
+        run_errors("fun add(a) {\n  return a + 1;\n}\nprint add(nil);")
+    );
+    // Messages missing in the catalog are shown in English.
+    assert_eq!(
+        run_errors("print len(1);"),
+        "[E0315] Can only get the length of lists and strings, but got the number 1.\n[line 1]"
+    );
+
+    locale::set_lang(Lang::English);
+}
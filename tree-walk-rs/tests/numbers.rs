@@ -0,0 +1,94 @@
+//! Tests of formatting numbers with `toFixed` and the number format of the
+//! interpreter.
+
+mod common;
+
+use common::SharedBuffer;
+use tree_walk_rs::{Interpreter, InterpreterBuilder, NumberFormat, RunError, run};
+
+/// Runs the code on the interpreter of the builder, returning its printed
+/// output.
+fn output(builder: InterpreterBuilder, code: &str) -> Result<String, RunError> {
+    let output = SharedBuffer::default();
+    let mut interpreter = builder
+        .stop_on_error(true)
+        .output(output.clone())
+        .error_output(std::io::sink())
+        .build();
+    run(&mut interpreter, code.to_owned())?;
+
+    Ok(output.take())
+}
+
+fn german(precision: Option<usize>) -> InterpreterBuilder {
+    Interpreter::builder().number_format(NumberFormat {
+        decimal_separator: ',',
+        precision,
+    })
+}
+
+#[test]
+fn to_fixed_rounds_to_the_digits() {
+    // Infinities and `NaN` come from dividing by zero.
+    let printed = output(
+        Interpreter::builder().ieee_division(true),
+        "print toFixed(0.1 + 0.2, 2);
+print toFixed(2.5, 0);
+print toFixed(1.005, 2);
+print toFixed(12, 3);
+print toFixed(-1.25, 1);
+print toFixed(0 / 0, 2);
+print toFixed(-1 / 0, 2);",
+    )
+    .unwrap();
+
+    assert_eq!(printed, "0.30\n2\n1.00\n12.000\n-1.2\nNaN\n-Infinity\n");
+}
+
+#[test]
+fn to_fixed_uses_the_decimal_separator_of_the_format() {
+    let printed = output(german(None), "print toFixed(12.5, 2);").unwrap();
+    assert_eq!(printed, "12,50\n");
+}
+
+#[test]
+fn to_fixed_rejects_invalid_arguments() {
+    for code in [
+        "toFixed(\"1\", 2);",
+        "toFixed(1, -1);",
+        "toFixed(1, 1.5);",
+        "toFixed(1, 101);",
+        "toFixed(1, nil);",
+    ] {
+        assert!(
+            matches!(
+                output(Interpreter::builder(), code),
+                Err(RunError::Runtime(_))
+            ),
+            "{code}"
+        );
+    }
+    assert_eq!(
+        output(Interpreter::builder(), "print toFixed(1, 100);")
+            .unwrap()
+            .len(),
+        "1.\n".len() + 100
+    );
+}
+
+#[test]
+fn printed_numbers_follow_the_number_format() {
+    let code = "print 0.1 + 0.2;\nprint 12.5;\nprint 3;";
+    assert_eq!(
+        output(Interpreter::builder(), code).unwrap(),
+        "0.30000000000000004\n12.5\n3\n"
+    );
+    assert_eq!(
+        output(german(None), code).unwrap(),
+        "0,30000000000000004\n12,5\n3\n"
+    );
+    assert_eq!(
+        output(german(Some(2)), code).unwrap(),
+        "0,30\n12,50\n3,00\n"
+    );
+}
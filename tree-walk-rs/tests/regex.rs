@@ -0,0 +1,71 @@
+//! Tests of the natives matching regular expressions.
+#![cfg(feature = "regex")]
+
+mod common;
+
+use common::SharedBuffer;
+use tree_walk_rs::{Interpreter, RunError, run};
+
+/// Runs the code returning its printed output.
+fn output(code: &str) -> Result<String, RunError> {
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder()
+        .stop_on_error(true)
+        .output(output.clone())
+        .build();
+    run(&mut interpreter, code.to_owned())?;
+
+    Ok(output.take())
+}
+
+#[test]
+fn matches_return_their_captures() {
+    let printed = output(
+        r#"print regexMatch("(\w+)@(\w+)", "mail me@host or you@other");
+print regexMatch("(a)|(b)", "b");
+print regexMatch("\d", "none");
+print regexFindAll("(\d)(\d)?", "1 23 456");
+print regexFindAll("\d", "none");"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        printed,
+        r#"["me@host", "me", "host"]
+["b", Nil, "b"]
+Nil
+[["1", "1", Nil], ["23", "2", "3"], ["45", "4", "5"], ["6", "6", Nil]]
+[]
+"#
+    );
+}
+
+#[test]
+fn replacements_insert_the_groups() {
+    let printed = output(
+        r#"print regexReplace("(\d+)", "a1b22", "<$1>");
+print regexReplace("(?P<user>\w+)@(?P<host>\w+)", "me@host", "${host} of ${user}");
+print regexReplace("x", "none", "y");"#,
+    )
+    .unwrap();
+
+    assert_eq!(printed, "a<1>b<22>\nhost of me\nnone\n");
+}
+
+#[test]
+fn invalid_patterns_and_arguments_fail() {
+    for code in [
+        r#"regexMatch("(", "text");"#,
+        r#"regexMatch(1, "text");"#,
+        r#"regexFindAll("\d", 1);"#,
+        r#"regexReplace("\d", "a1", nil);"#,
+    ] {
+        assert!(matches!(output(code), Err(RunError::Runtime(_))), "{code}");
+    }
+
+    let err = output(r#"regexMatch("(", "text");"#).unwrap_err();
+    assert!(
+        err.to_string().contains("Invalid regex pattern \"(\""),
+        "{err}"
+    );
+}
@@ -0,0 +1,135 @@
+//! Tests of the REPL driven through `ReplSession`, feeding it lines like a
+//! user would type them.
+
+use tree_walk_rs::{InterpreterOptions, ReplSession};
+
+fn session() -> ReplSession {
+    ReplSession::new(InterpreterOptions::default(), None).unwrap()
+}
+
+#[test]
+fn definitions_persist_across_lines() {
+    let mut session = session();
+
+    assert_eq!(session.feed("var a = 1;").unwrap().output, "");
+    session.feed("fun add(x) { return a + x; }").unwrap();
+    assert_eq!(session.feed("print add(2);").unwrap().output, "3\n");
+
+    // Redefining a function replaces it for the existing callers.
+    session.feed("fun inc() { return add(10); }").unwrap();
+    session.feed("fun add(x) { return x * 2; }").unwrap();
+    assert_eq!(session.feed("print inc();").unwrap().output, "20\n");
+}
+
+#[test]
+fn redefinitions_replace_functions_and_classes() {
+    let mut session = session();
+
+    session
+        .feed("class Greeter { greet() { return \"hi\"; } }")
+        .unwrap();
+    session.feed("var old = Greeter();").unwrap();
+    session
+        .feed("fun greet() { return Greeter().greet(); }")
+        .unwrap();
+    assert_eq!(session.feed("print greet();").unwrap().output, "hi\n");
+
+    let redefined = session
+        .feed("class Greeter { greet() { return \"hello\"; } }")
+        .unwrap();
+    assert_eq!(redefined.error_output, "");
+
+    // Existing callers see the new class, while instances keep the old one.
+    assert_eq!(session.feed("print greet();").unwrap().output, "hello\n");
+    assert_eq!(session.feed("print old.greet();").unwrap().output, "hi\n");
+    assert_eq!(
+        session
+            .feed("print classOf(old) == Greeter;")
+            .unwrap()
+            .output,
+        "false\n"
+    );
+}
+
+#[test]
+fn locals_of_earlier_lines_stay_resolved() {
+    let mut session = session();
+
+    // Both functions read `y` from the same position in their line, but from
+    // another slot, so resolving the second must not change the first.
+    session.feed("fun first(x, y) { return y; }").unwrap();
+    session.feed("fun second(y, x) { return y; }").unwrap();
+
+    assert_eq!(session.feed("print first(1, 2);").unwrap().output, "2\n");
+    assert_eq!(session.feed("print second(1, 2);").unwrap().output, "1\n");
+
+    // Expressions of commands are resolved after the code of the lines too.
+    session.feed("var y = \"text\";").unwrap();
+    assert_eq!(session.feed(":type y").unwrap().output, "string\n");
+}
+
+#[test]
+fn values_are_printed_with_their_state() {
+    let mut session = session();
+
+    session
+        .feed("class Point { init(x, y) { this.x = x; this.y = y; } }")
+        .unwrap();
+    let output = session.feed("print Point(1, 2);").unwrap();
+
+    assert_eq!(output.output, "Point { x: 1, y: 2 }\n");
+    assert_eq!(output.error_output, "");
+
+    // Strings inside other values are quoted.
+    let output = session
+        .feed("print Point(\"one\", methods(Point));")
+        .unwrap();
+    assert_eq!(output.output, "Point { x: \"one\", y: [\"init\"] }\n");
+}
+
+#[test]
+fn errors_are_recorded_without_ending_the_session() {
+    let mut session = session();
+
+    session.feed("var a = 1;").unwrap();
+    let parse = session.feed("print 1 +;").unwrap();
+    assert!(parse.error_output.contains("[E0102]"), "{parse:?}");
+    assert_eq!(parse.output, "");
+
+    let runtime = session.feed("print -\"x\";").unwrap();
+    assert!(runtime.error_output.contains("[E0301]"), "{runtime:?}");
+
+    assert_eq!(session.feed("print a;").unwrap().output, "1\n");
+    assert_eq!(session.history(), ["var a = 1;", "print a;"]);
+}
+
+#[test]
+fn commands_write_to_the_outputs() {
+    let mut session = session();
+
+    session.feed("fun f() {}").unwrap();
+    assert_eq!(session.feed(":type f").unwrap().output, "function\n");
+    assert!(session.feed(":help").unwrap().output.contains(":save"));
+
+    let unknown = session.feed(":bogus").unwrap();
+    assert!(unknown.error_output.starts_with("Unknown command ':bogus'"));
+}
+
+#[test]
+fn saved_session_loads_into_fresh_one() {
+    let path = std::env::temp_dir().join(format!("rlox-repl-{}.lox", std::process::id()));
+    let mut session = session();
+
+    session.feed("var a = 1;").unwrap();
+    session.feed("var b = a + 1;").unwrap();
+    let saved = session.feed(&format!(":save {}", path.display())).unwrap();
+    assert!(saved.output.starts_with("Session saved"), "{saved:?}");
+
+    let mut fresh = self::session();
+    fresh.feed("var c = 3;").unwrap();
+    fresh.feed(&format!(":load {}", path.display())).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(fresh.feed("print b;").unwrap().output, "2\n");
+    assert!(fresh.feed("print c;").unwrap().error_output.contains("'c'"));
+}
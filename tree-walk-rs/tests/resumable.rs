@@ -0,0 +1,80 @@
+//! Tests of scripts running in slices of a time budget, and of continuing
+//! scripts which aren't paused or suspended.
+
+use lox_frontend::{Parser, Scanner};
+use tree_walk_rs::{Interpreter, LoxValue, RunState};
+
+const LOOP: &str = "var total = 0;
+var i = 0;
+while (i < 10000) {
+  total = total + i;
+  i = i + 1;
+}
+total;";
+
+/// Continues the paused script until it's done, returning its value and the
+/// count of the slices it took.
+fn finish(interpreter: &mut Interpreter, mut state: RunState) -> (Option<LoxValue>, usize) {
+    let mut slices = 1;
+    loop {
+        match state {
+            RunState::Paused => state = interpreter.continue_run().unwrap(),
+            RunState::Done(value) => return (value, slices),
+            RunState::Suspended(request) => panic!("Script isn't suspended, but got {request}"),
+        }
+        slices += 1;
+    }
+}
+
+#[test]
+fn used_up_budgets_pause_the_script_until_it_continues() {
+    for max_micros in [0, 1] {
+        let mut interpreter = Interpreter::new();
+        let state = interpreter.start_budgeted(LOOP, max_micros).unwrap();
+        assert!(matches!(state, RunState::Paused));
+        assert!(interpreter.is_suspended());
+
+        let (value, slices) = finish(&mut interpreter, state);
+        assert!(matches!(value, Some(LoxValue::Number(total)) if total == 49995000.0));
+        assert!(slices > 1);
+        assert!(!interpreter.is_suspended());
+    }
+}
+
+#[test]
+fn budgeted_statements_pause_until_they_continue() {
+    let stmts = Parser::new(Scanner::new(LOOP.to_owned()).scan_tokens().tokens)
+        .parse()
+        .stmts;
+
+    let mut interpreter = Interpreter::new();
+    let state = interpreter.run_budgeted(stmts, 0).unwrap();
+    assert!(matches!(state, RunState::Paused));
+
+    let (value, _) = finish(&mut interpreter, state);
+    assert!(matches!(value, Some(LoxValue::Number(total)) if total == 49995000.0));
+}
+
+#[test]
+fn only_stopped_scripts_continue() {
+    let mut interpreter = Interpreter::new();
+    let err = interpreter.resume(LoxValue::Nil).unwrap_err();
+    assert!(
+        err.to_string()
+            .ends_with("There is no suspended script to resume"),
+        "{err}"
+    );
+    let err = interpreter.continue_run().unwrap_err();
+    assert!(
+        err.to_string()
+            .ends_with("There is no paused script to continue"),
+        "{err}"
+    );
+
+    // Paused scripts continue with `continue_run()` instead.
+    let state = interpreter.start_budgeted(LOOP, 0).unwrap();
+    assert!(matches!(state, RunState::Paused));
+    assert!(interpreter.resume(LoxValue::Nil).is_err());
+    let (value, _) = finish(&mut interpreter, RunState::Paused);
+    assert!(value.is_some());
+}
@@ -0,0 +1,74 @@
+//! Tests of stepping through scripts statement by statement, checking the
+//! statements they pause before and the environments they see.
+
+use tree_walk_rs::{Interpreter, RunState};
+
+/// Returns the source of the statement the stepping script continues with.
+fn next_stmt(interpreter: &Interpreter) -> Option<String> {
+    interpreter.next_stmt().map(|stmt| stmt.to_source())
+}
+
+/// Returns the bindings of each environment in the chain as `name = value`.
+fn environments(interpreter: &Interpreter) -> Vec<Vec<String>> {
+    interpreter
+        .environment_chain()
+        .into_iter()
+        .map(|bindings| {
+            bindings
+                .into_iter()
+                .map(|(name, value)| format!("{name} = {value}"))
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn pauses_before_each_statement_including_function_bodies() {
+    let mut interpreter = Interpreter::new();
+    let code = "var a = 1;\nfun f(x) {\n  var y = x + a;\n  print y;\n}\nf(2);";
+
+    let mut stmts = Vec::new();
+    let mut state = interpreter.start_stepping(code).unwrap();
+    while matches!(state, RunState::Paused) {
+        stmts.push(next_stmt(&interpreter).unwrap());
+        state = interpreter.continue_run().unwrap();
+    }
+
+    assert!(matches!(state, RunState::Done(_)));
+    assert_eq!(next_stmt(&interpreter), None);
+    assert_eq!(
+        stmts,
+        [
+            "var a = 1;",
+            "fun f(x) {\n    var y = x + a;\n    print y;\n}",
+            "f(2);",
+            "var y = x + a;",
+            "print y;",
+        ]
+    );
+}
+
+#[test]
+fn environment_chain_names_the_locals_of_paused_functions() {
+    let mut interpreter = Interpreter::new();
+    let code = "var a = 1;\nclass A {\n  get(x) {\n    { var y = x + a;\n    return y; }\n  }\n}\nA().get(2);";
+
+    interpreter.start_stepping(code).unwrap();
+    while next_stmt(&interpreter).as_deref() != Some("return y;") {
+        interpreter.continue_run().unwrap();
+    }
+
+    assert_eq!(
+        environments(&interpreter),
+        [
+            vec!["y = 3"],
+            vec!["x = 2"],
+            vec!["this = A instance"],
+            vec!["A = A", "a = 1"],
+        ]
+    );
+
+    // Once done, only the globals are left.
+    while !matches!(interpreter.continue_run().unwrap(), RunState::Done(_)) {}
+    assert_eq!(environments(&interpreter), [vec!["A = A", "a = 1"]]);
+}
@@ -0,0 +1,63 @@
+//! Tests of transpiling Lox programs to JavaScript, checking the generated
+//! code and, when Node.js is installed, that it prints the same output as
+//! the interpreter.
+
+mod common;
+
+use std::process::Command;
+
+use common::SharedBuffer;
+use insta::assert_snapshot;
+use tree_walk_rs::{Interpreter, Target, run, transpile};
+
+const PROGRAM: &str = r#"class Counter {
+  init(start) { this.count = start; }
+  add(n) { this.count = this.count + n; return this; }
+}
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+var counter = Counter(1).add(2);
+print counter.count;
+for (var i = 0; i < 5; i = i + 1) print fib(i) + 1;
+print "done";
+"#;
+
+/// Returns the code generated for the program without the runtime before it.
+fn generated_program(code: &str) -> String {
+    let output = transpile(code.to_owned(), Target::JavaScript).unwrap();
+    let start = output.rfind("$main(").expect("Program runs in `$main()`");
+
+    output[start..].to_owned()
+}
+
+#[test]
+fn transpiles_programs_into_calls_of_the_runtime() {
+    assert_snapshot!(generated_program(PROGRAM));
+}
+
+#[test]
+fn transpiled_programs_print_like_the_interpreter() {
+    let js = transpile(PROGRAM.to_owned(), Target::JavaScript).unwrap();
+    // Node.js is optional for running the tests.
+    let Ok(node) = Command::new("node").arg("-e").arg(&js).output() else {
+        return;
+    };
+    assert!(
+        node.status.success(),
+        "{}",
+        String::from_utf8_lossy(&node.stderr)
+    );
+
+    let output = SharedBuffer::default();
+    let mut interpreter = Interpreter::builder().output(output.clone()).build();
+    run(&mut interpreter, PROGRAM.to_owned()).unwrap();
+
+    assert_eq!(String::from_utf8(node.stdout).unwrap(), output.take());
+}
+
+#[test]
+fn errors_in_the_code_fail_the_transpiling() {
+    assert!(transpile(String::from("print 1 +;"), Target::JavaScript).is_err());
+}
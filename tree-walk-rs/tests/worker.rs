@@ -0,0 +1,43 @@
+//! Tests of the worker running the jobs of several threads on its own
+//! interpreter.
+#![cfg(feature = "worker")]
+
+use std::{sync::Arc, thread};
+
+use tree_walk_rs::{InterpreterOptions, Worker};
+
+#[test]
+fn jobs_from_other_threads_share_the_globals() {
+    let worker = Arc::new(Worker::spawn(InterpreterOptions::default()));
+    worker.run("var total = 40;").unwrap();
+
+    let handles: Vec<_> = (0..2)
+        .map(|_| {
+            let worker = Arc::clone(&worker);
+            thread::spawn(move || worker.run("total = total + 1;").unwrap())
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let output = worker.run("print total;").unwrap();
+    assert_eq!(output.stdout, "42\n");
+}
+
+#[test]
+fn failed_jobs_return_their_errors_and_keep_the_worker_running() {
+    let worker = Worker::spawn(InterpreterOptions::default());
+
+    let diagnostics = worker.run("print \"before\";\nprint nil + 1;").unwrap_err();
+    assert_eq!(diagnostics.output.stdout, "before\n");
+    assert_eq!(
+        diagnostics.errors,
+        [
+            "[E0301] Operands of '+' must be two numbers or two strings, but the left operand was nil.\n[line 2]"
+        ]
+    );
+
+    let output = worker.run("print 1 + 1;").unwrap();
+    assert_eq!(output.stdout, "2\n");
+}